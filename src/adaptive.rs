@@ -0,0 +1,234 @@
+/// Adaptive routing - score providers by recent latency, error rate and price
+///
+/// Latency is tracked here as an exponentially weighted moving average per
+/// provider and model, kept apart for streaming (time to first chunk) and
+/// non-streaming (full response) requests; the error rate comes from the
+/// circuit breaker and prices from models.dev (or the fallback table).
+use crate::config::AdaptiveConfig;
+use rand::Rng;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Weight of the newest latency sample in the moving average
+const LATENCY_ALPHA: f64 = 0.3;
+
+/// What a latency sample measures
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LatencyKind {
+    /// Time to the first chunk of a streamed response
+    FirstChunk,
+    /// Time to a complete non-streaming response
+    Complete,
+}
+
+impl LatencyKind {
+    pub fn of(stream: bool) -> Self {
+        if stream { LatencyKind::FirstChunk } else { LatencyKind::Complete }
+    }
+}
+
+type LatencyKey = (String, String, LatencyKind);
+
+/// Recent latency per provider, model and kind
+pub struct LatencyTracker {
+    samples: Mutex<HashMap<LatencyKey, LatencySample>>,
+}
+
+struct LatencySample {
+    seconds: f64,
+    updated: Instant,
+}
+
+impl LatencyTracker {
+    pub fn new() -> Self {
+        Self {
+            samples: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Fold a measured duration into the moving average for its provider,
+    /// model and kind
+    pub fn record(&self, provider: &str, model: &str, kind: LatencyKind, seconds: f64) {
+        let mut samples = self.samples.lock().unwrap();
+        let now = Instant::now();
+
+        samples
+            .entry((provider.to_string(), model.to_string(), kind))
+            .and_modify(|sample| {
+                sample.seconds = sample.seconds * (1.0 - LATENCY_ALPHA) + seconds * LATENCY_ALPHA;
+                sample.updated = now;
+            })
+            .or_insert(LatencySample { seconds, updated: now });
+    }
+
+    /// Average latency in seconds, if measured within `max_age`
+    pub fn get(&self, provider: &str, model: &str, kind: LatencyKind, max_age: Duration) -> Option<f64> {
+        let samples = self.samples.lock().unwrap();
+        samples
+            .get(&(provider.to_string(), model.to_string(), kind))
+            .filter(|sample| sample.updated.elapsed() <= max_age)
+            .map(|sample| sample.seconds)
+    }
+}
+
+impl Default for LatencyTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What we know about one provider when ranking
+#[derive(Debug, Clone, Default)]
+pub struct Candidate {
+    /// Seconds; `None` when never measured or stale
+    pub latency: Option<f64>,
+    /// 0.0 - 1.0
+    pub error_rate: f64,
+    /// USD per 1M tokens (input + output); `None` when the price is unknown
+    pub cost: Option<f64>,
+}
+
+/// Score every candidate (lower is better)
+///
+/// Latency and cost are divided by the largest value among the candidates.
+/// Unknown latency scores 0 so unmeasured providers get a chance to be
+/// measured; unknown cost scores as the mean known cost, neither cheap nor
+/// expensive.
+pub fn scores(config: &AdaptiveConfig, candidates: &[Candidate]) -> Vec<f64> {
+    let max_latency = candidates.iter().filter_map(|c| c.latency).fold(0.0, f64::max);
+    let max_cost = candidates.iter().filter_map(|c| c.cost).fold(0.0, f64::max);
+    let known_costs: Vec<f64> = candidates.iter().filter_map(|c| c.cost).collect();
+    let mean_cost = match known_costs.len() {
+        0 => None,
+        known => Some(known_costs.iter().sum::<f64>() / known as f64),
+    };
+
+    let normalize = |value: Option<f64>, max: f64| match value {
+        Some(value) if max > 0.0 => value / max,
+        _ => 0.0,
+    };
+
+    candidates
+        .iter()
+        .map(|c| {
+            config.latency_weight * normalize(c.latency, max_latency)
+                + config.error_weight * c.error_rate.clamp(0.0, 1.0)
+                + config.cost_weight * normalize(c.cost.or(mean_cost), max_cost)
+        })
+        .collect()
+}
+
+/// Candidate indices in the order they should be tried
+///
+/// With probability `exploration` a random candidate is moved to the front so
+/// providers that lost on score are still measured from time to time.
+pub fn rank(config: &AdaptiveConfig, candidates: &[Candidate]) -> Vec<usize> {
+    let scores = scores(config, candidates);
+    let mut order: Vec<usize> = (0..candidates.len()).collect();
+    // Stable sort keeps configured order on ties
+    order.sort_by(|&a, &b| scores[a].total_cmp(&scores[b]));
+
+    let mut rng = rand::thread_rng();
+    if order.len() > 1 && rng.r#gen::<f64>() < config.exploration {
+        let pick = rng.gen_range(1..order.len());
+        let explored = order.remove(pick);
+        order.insert(0, explored);
+    }
+
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> AdaptiveConfig {
+        AdaptiveConfig {
+            exploration: 0.0,
+            ..Default::default()
+        }
+    }
+
+    fn candidate(latency: Option<f64>, error_rate: f64, cost: Option<f64>) -> Candidate {
+        Candidate { latency, error_rate, cost }
+    }
+
+    #[test]
+    fn test_prefers_fast_reliable_cheap() {
+        let candidates = vec![
+            candidate(Some(4.0), 0.0, Some(20.0)),
+            candidate(Some(1.0), 0.0, Some(5.0)),
+            candidate(Some(1.0), 0.2, Some(5.0)),
+        ];
+
+        assert_eq!(rank(&config(), &candidates), vec![1, 2, 0]);
+    }
+
+    #[test]
+    fn test_weights_shift_the_winner() {
+        // Fast but expensive vs slow but cheap
+        let candidates = vec![
+            candidate(Some(1.0), 0.0, Some(30.0)),
+            candidate(Some(3.0), 0.0, Some(1.0)),
+        ];
+
+        let latency_first = AdaptiveConfig { latency_weight: 1.0, cost_weight: 0.1, ..config() };
+        assert_eq!(rank(&latency_first, &candidates)[0], 0);
+
+        let cost_first = AdaptiveConfig { latency_weight: 0.1, cost_weight: 1.0, ..config() };
+        assert_eq!(rank(&cost_first, &candidates)[0], 1);
+    }
+
+    #[test]
+    fn test_unmeasured_provider_is_tried() {
+        let candidates = vec![
+            candidate(Some(2.0), 0.0, None),
+            candidate(None, 0.0, None),
+        ];
+
+        assert_eq!(rank(&config(), &candidates)[0], 1);
+    }
+
+    #[test]
+    fn test_unknown_cost_scores_as_mean() {
+        let candidates = vec![
+            candidate(Some(1.0), 0.0, Some(30.0)),
+            candidate(Some(1.0), 0.0, None),
+            candidate(Some(1.0), 0.0, Some(10.0)),
+        ];
+
+        assert_eq!(rank(&config(), &candidates), vec![2, 1, 0]);
+    }
+
+    #[test]
+    fn test_exploration_reorders() {
+        let candidates = vec![
+            candidate(Some(0.1), 0.0, None),
+            candidate(Some(9.0), 0.0, None),
+        ];
+        let always = AdaptiveConfig { exploration: 1.0, ..config() };
+
+        assert_eq!(rank(&always, &candidates), vec![1, 0]);
+    }
+
+    #[test]
+    fn test_latency_tracker() {
+        let tracker = LatencyTracker::new();
+        let minute = Duration::from_secs(60);
+        assert!(tracker.get("openai", "gpt-4o", LatencyKind::Complete, minute).is_none());
+
+        tracker.record("openai", "gpt-4o", LatencyKind::Complete, 1.0);
+        tracker.record("openai", "gpt-4o", LatencyKind::Complete, 2.0);
+        let average = tracker.get("openai", "gpt-4o", LatencyKind::Complete, minute).unwrap();
+        assert!((average - 1.3).abs() < 1e-9);
+
+        // First-chunk times and other models are averaged apart
+        tracker.record("openai", "gpt-4o", LatencyKind::FirstChunk, 0.2);
+        assert_eq!(tracker.get("openai", "gpt-4o", LatencyKind::FirstChunk, minute), Some(0.2));
+        assert!(tracker.get("openai", "gpt-4o-mini", LatencyKind::Complete, minute).is_none());
+
+        // Stale samples are ignored
+        assert!(tracker.get("openai", "gpt-4o", LatencyKind::Complete, Duration::ZERO).is_none());
+    }
+}
@@ -1,5 +1,6 @@
 /// Anthropic Claude Max OAuth (PKCE Flow - like OpenCode)
 /// Use your $20/month Claude Max subscription instead of API billing
+use crate::secret::Secret;
 use anyhow::Result;
 use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use serde::{Deserialize, Serialize};
@@ -24,9 +25,9 @@ struct TokenRequest {
 #[derive(Deserialize)]
 pub struct TokenResponse {
     pub token_type: String,
-    pub access_token: String,
+    pub access_token: Secret,
     pub expires_in: u64, // 28800 seconds (8 hours)
-    pub refresh_token: String,
+    pub refresh_token: Secret,
     pub scope: String,
     pub organization: Option<Organization>,
     pub account: Option<Account>,
@@ -46,6 +47,7 @@ pub struct Account {
 
 pub struct AnthropicOAuth {
     client: reqwest::Client,
+    token_url: String,
 }
 
 impl Default for AnthropicOAuth {
@@ -56,8 +58,14 @@ impl Default for AnthropicOAuth {
 
 impl AnthropicOAuth {
     pub fn new() -> Self {
+        Self::with_token_url(TOKEN_URL)
+    }
+
+    /// Exchange and refresh codes at another token endpoint (tests, proxies)
+    pub fn with_token_url(token_url: impl Into<String>) -> Self {
         Self {
             client: reqwest::Client::new(),
+            token_url: token_url.into(),
         }
     }
 
@@ -124,7 +132,7 @@ impl AnthropicOAuth {
 
         let res = self
             .client
-            .post(TOKEN_URL)
+            .post(&self.token_url)
             .json(&token_request)
             .send()
             .await?;
@@ -139,23 +147,23 @@ impl AnthropicOAuth {
     }
 
     /// Refresh access token using refresh token
-    pub async fn refresh_token(&self, refresh_token: &str) -> Result<TokenResponse> {
+    pub async fn refresh_token(&self, refresh_token: &Secret) -> Result<TokenResponse> {
         #[derive(Serialize)]
-        struct RefreshRequest {
+        struct RefreshRequest<'a> {
             grant_type: String,
-            refresh_token: String,
+            refresh_token: &'a str,
             client_id: String,
         }
 
         let refresh_request = RefreshRequest {
             grant_type: "refresh_token".to_string(),
-            refresh_token: refresh_token.to_string(),
+            refresh_token: refresh_token.expose(),
             client_id: CLIENT_ID.to_string(),
         };
 
         let res = self
             .client
-            .post(TOKEN_URL)
+            .post(&self.token_url)
             .json(&refresh_request)
             .send()
             .await?;
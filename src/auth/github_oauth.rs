@@ -1,13 +1,13 @@
 /// GitHub OAuth Device Flow (like VS Code / OpenCode)
 /// Used for GitHub Copilot access
+use crate::secret::Secret;
 use anyhow::Result;
 use serde::Deserialize;
 use std::time::Duration;
 
 const CLIENT_ID: &str = "Iv1.b507a08c87ecfe98"; // VS Code's public client
-const DEVICE_CODE_URL: &str = "https://github.com/login/device/code";
-const TOKEN_URL: &str = "https://github.com/login/oauth/access_token";
-const COPILOT_TOKEN_URL: &str = "https://api.github.com/copilot_internal/v2/token";
+const GITHUB_URL: &str = "https://github.com";
+const GITHUB_API_URL: &str = "https://api.github.com";
 const SCOPES: &str = "read:user";
 
 #[derive(Deserialize)]
@@ -22,7 +22,7 @@ struct DeviceCodeResponse {
 
 #[derive(Deserialize)]
 struct TokenResponse {
-    access_token: String,
+    access_token: Secret,
     #[allow(dead_code)]
     token_type: String,
     #[allow(dead_code)]
@@ -31,12 +31,14 @@ struct TokenResponse {
 
 #[derive(Deserialize)]
 struct CopilotTokenResponse {
-    token: String,
+    token: Secret,
     expires_at: i64,
 }
 
 pub struct GitHubOAuth {
     client: reqwest::Client,
+    github_url: String,
+    api_url: String,
 }
 
 impl Default for GitHubOAuth {
@@ -47,8 +49,16 @@ impl Default for GitHubOAuth {
 
 impl GitHubOAuth {
     pub fn new() -> Self {
+        Self::with_base_urls(GITHUB_URL, GITHUB_API_URL)
+    }
+
+    /// Talk to another GitHub (tests, proxies): the device flow at
+    /// `github_url`, Copilot tokens at `api_url`
+    pub fn with_base_urls(github_url: impl Into<String>, api_url: impl Into<String>) -> Self {
         Self {
             client: reqwest::Client::new(),
+            github_url: github_url.into(),
+            api_url: api_url.into(),
         }
     }
 
@@ -61,7 +71,7 @@ impl GitHubOAuth {
 
         let res = self
             .client
-            .post(DEVICE_CODE_URL)
+            .post(format!("{}/login/device/code", self.github_url))
             .form(&params)
             .header("Accept", "application/json")
             .send()
@@ -82,7 +92,7 @@ impl GitHubOAuth {
     }
 
     /// Poll for access token (call this in a loop)
-    pub async fn poll_for_token(&self, device_code: &str) -> Result<Option<String>> {
+    pub async fn poll_for_token(&self, device_code: &str) -> Result<Option<Secret>> {
         let params = [
             ("client_id", CLIENT_ID),
             ("device_code", device_code),
@@ -91,7 +101,7 @@ impl GitHubOAuth {
 
         let res = self
             .client
-            .post(TOKEN_URL)
+            .post(format!("{}/login/oauth/access_token", self.github_url))
             .form(&params)
             .header("Accept", "application/json")
             .send()
@@ -125,11 +135,11 @@ impl GitHubOAuth {
     }
 
     /// Exchange GitHub token for Copilot token
-    pub async fn get_copilot_token(&self, github_token: &str) -> Result<(String, i64)> {
+    pub async fn get_copilot_token(&self, github_token: &Secret) -> Result<(Secret, i64)> {
         let res = self
             .client
-            .get(COPILOT_TOKEN_URL)
-            .header("Authorization", format!("Bearer {}", github_token))
+            .get(format!("{}/copilot_internal/v2/token", self.api_url))
+            .header("Authorization", format!("Bearer {}", github_token.expose()))
             .header("Accept", "application/json")
             .header("User-Agent", "Thanos-AI-Gateway/0.1.0")
             .send()
@@ -145,7 +155,7 @@ impl GitHubOAuth {
     }
 
     /// Complete OAuth flow (blocking until user authorizes)
-    pub async fn authorize(&self) -> Result<(String, String, i64)> {
+    pub async fn authorize(&self) -> Result<(Secret, Secret, i64)> {
         println!("\n🔐 GitHub Copilot OAuth");
         println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
 
@@ -1,13 +1,17 @@
-/// System keyring integration for secure OAuth token storage
+/// System keyring integration for secure OAuth token and API key storage
 /// Uses native keyring: Secret Service (Linux), Keychain (macOS), Credential Manager (Windows)
+use crate::secret::Secret;
 use anyhow::Result;
 use keyring::Entry;
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize)]
+/// Tokens as stored in the keyring; they are only written out in full there
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OAuthTokens {
-    pub access_token: String,
-    pub refresh_token: Option<String>,
+    #[serde(serialize_with = "crate::secret::expose")]
+    pub access_token: Secret,
+    #[serde(serialize_with = "crate::secret::expose_option")]
+    pub refresh_token: Option<Secret>,
     pub expires_at: Option<i64>, // Unix timestamp
 }
 
@@ -25,7 +29,7 @@ impl KeyringStore {
     /// Store OAuth tokens in system keyring
     pub fn store_oauth_tokens(&self, provider: &str, tokens: &OAuthTokens) -> Result<()> {
         let entry = Entry::new(&self.service, provider)?;
-        let json = serde_json::to_string(tokens)?;
+        let json = zeroize::Zeroizing::new(serde_json::to_string(tokens)?);
         entry.set_password(&json)?;
         Ok(())
     }
@@ -35,6 +39,7 @@ impl KeyringStore {
         let entry = Entry::new(&self.service, provider)?;
         match entry.get_password() {
             Ok(json) => {
+                let json = zeroize::Zeroizing::new(json);
                 let tokens: OAuthTokens = serde_json::from_str(&json)?;
                 Ok(Some(tokens))
             }
@@ -53,6 +58,21 @@ impl KeyringStore {
         }
     }
 
+    /// Store a secret such as an API key (read back with `keyring:<name>`)
+    pub fn store_secret(&self, name: &str, secret: &Secret) -> Result<()> {
+        Entry::new(&self.service, name)?.set_password(secret.expose())?;
+        Ok(())
+    }
+
+    /// Retrieve a secret stored with `store_secret`
+    pub fn get_secret(&self, name: &str) -> Result<Option<Secret>> {
+        match Entry::new(&self.service, name)?.get_password() {
+            Ok(secret) => Ok(Some(Secret::new(secret))),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
     /// Check if tokens exist for provider
     pub fn has_tokens(&self, provider: &str) -> bool {
         self.get_oauth_tokens(provider).ok().flatten().is_some()
@@ -68,8 +88,8 @@ mod tests {
     fn test_keyring_roundtrip() {
         let store = KeyringStore::new("thanos-test");
         let tokens = OAuthTokens {
-            access_token: "test-access-token".to_string(),
-            refresh_token: Some("test-refresh-token".to_string()),
+            access_token: "test-access-token".into(),
+            refresh_token: Some("test-refresh-token".into()),
             expires_at: Some(1234567890),
         };
 
@@ -0,0 +1,379 @@
+/// OAuth logins driven over the admin API
+///
+/// `thanos auth claude|copilot` need a terminal on the gateway host. These are
+/// the same flows for a remote client such as an editor plugin: `start`
+/// returns the URL to open (and for GitHub, the code to enter there), then the
+/// client either pastes Anthropic's `code#state` back with `submit_code`, or
+/// polls `status` while the gateway polls GitHub. Tokens are saved to the
+/// configured token store.
+use super::{AnthropicOAuth, GitHubOAuth, OAuthTokens, TokenStore};
+use crate::secret::Secret;
+use anyhow::Result;
+use chrono::Utc;
+use once_cell::sync::Lazy;
+use rand::RngCore;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How long a started login waits for the user (GitHub device codes last as long)
+const LOGIN_TTL: Duration = Duration::from_secs(15 * 60);
+
+/// The token store name for a provider, also accepting the `thanos auth` names
+pub fn provider_id(name: &str) -> Option<&'static str> {
+    match name {
+        "anthropic_max" | "claude" | "anthropic" => Some("anthropic_max"),
+        "github_copilot" | "copilot" | "github" => Some("github_copilot"),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LoginState {
+    Pending,
+    Complete,
+    Failed,
+}
+
+impl LoginState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Complete => "complete",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+/// What a client is shown about a login
+#[derive(Debug, Clone, Serialize)]
+pub struct LoginStatus {
+    pub id: String,
+    pub provider: &'static str,
+    /// "pkce" (paste the code shown after authorizing) or "device" (enter
+    /// `user_code` at `url`; the gateway waits for it)
+    pub flow: &'static str,
+    pub url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_code: Option<String>,
+    pub state: LoginState,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Seconds until the login is abandoned
+    pub expires_in: u64,
+    /// When the stored access token expires, once complete
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_expires_at: Option<i64>,
+}
+
+struct Login {
+    status: LoginStatus,
+    expires: Instant,
+    /// PKCE verifier and state, for the pasted code
+    pkce: Option<(Secret, String)>,
+}
+
+impl Login {
+    fn status(&self) -> LoginStatus {
+        LoginStatus {
+            expires_in: self.expires.saturating_duration_since(Instant::now()).as_secs(),
+            ..self.status.clone()
+        }
+    }
+}
+
+/// Logins in progress
+pub struct Logins {
+    /// `None` saves to the store configured in `[oauth]`
+    store: Option<Arc<dyn TokenStore>>,
+    anthropic: AnthropicOAuth,
+    github: GitHubOAuth,
+    logins: Mutex<HashMap<String, Login>>,
+}
+
+pub static LOGINS: Lazy<Arc<Logins>> = Lazy::new(|| Arc::new(Logins::new()));
+
+impl Default for Logins {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Logins {
+    pub fn new() -> Self {
+        Self {
+            store: None,
+            anthropic: AnthropicOAuth::new(),
+            github: GitHubOAuth::new(),
+            logins: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Save tokens to `store`, logging in through other OAuth clients (tests, proxies)
+    pub fn with(store: Arc<dyn TokenStore>, anthropic: AnthropicOAuth, github: GitHubOAuth) -> Self {
+        Self { store: Some(store), anthropic, github, logins: Mutex::new(HashMap::new()) }
+    }
+
+    fn store(&self) -> Arc<dyn TokenStore> {
+        self.store.clone().unwrap_or_else(super::token_store)
+    }
+
+    /// Start logging in to a provider (see `provider_id`)
+    pub async fn start(self: &Arc<Self>, provider: &str) -> Result<LoginStatus> {
+        let provider = provider_id(provider).ok_or_else(|| anyhow::anyhow!("unknown OAuth provider '{}'", provider))?;
+        let id = new_id();
+        let expires = Instant::now() + LOGIN_TTL;
+        self.logins.lock().unwrap().retain(|_, login| login.expires > Instant::now());
+
+        let (login, device) = match provider {
+            "anthropic_max" => {
+                let (url, verifier, state) = self.anthropic.build_auth_url();
+                let login = Login {
+                    status: status(&id, provider, "pkce", url, None),
+                    expires,
+                    pkce: Some((Secret::new(verifier), state)),
+                };
+                (login, None)
+            }
+            _ => {
+                let (user_code, url, device_code, interval) = self.github.initiate_device_flow().await?;
+                let login = Login {
+                    status: status(&id, provider, "device", url, Some(user_code)),
+                    expires,
+                    pkce: None,
+                };
+                (login, Some((device_code, Duration::from_secs(interval))))
+            }
+        };
+
+        tracing::info!("🔐 Remote {} login started", provider);
+        let status = login.status();
+        self.logins.lock().unwrap().insert(id.clone(), login);
+
+        if let Some((device_code, interval)) = device {
+            let logins = self.clone();
+            tokio::spawn(async move { logins.wait_for_device(&id, &device_code, interval).await });
+        }
+        Ok(status)
+    }
+
+    /// A login's progress, if it exists and hasn't expired
+    pub fn status(&self, id: &str) -> Option<LoginStatus> {
+        let logins = self.logins.lock().unwrap();
+        logins.get(id).filter(|login| login.expires > Instant::now()).map(Login::status)
+    }
+
+    /// Abandon a login; `false` if there was none
+    pub fn cancel(&self, id: &str) -> bool {
+        self.logins.lock().unwrap().remove(id).is_some()
+    }
+
+    /// Finish a PKCE login with the `code#state` shown after authorizing;
+    /// `None` if there is no such login
+    ///
+    /// A rejected code leaves the login pending, so the user can try again.
+    pub async fn submit_code(&self, id: &str, input: &str) -> Option<Result<LoginStatus>> {
+        let (verifier, expected_state) = {
+            let logins = self.logins.lock().unwrap();
+            let login = logins.get(id).filter(|login| login.expires > Instant::now())?;
+            match (&login.pkce, login.status.state) {
+                (_, LoginState::Complete | LoginState::Failed) => {
+                    return Some(Err(anyhow::anyhow!("this login is already {}", login.status.state.as_str())));
+                }
+                (Some(pkce), LoginState::Pending) => pkce.clone(),
+                (None, LoginState::Pending) => {
+                    return Some(Err(anyhow::anyhow!("this login doesn't take a code; poll its status instead")));
+                }
+            }
+        };
+
+        Some(self.exchange(id, input.trim(), &verifier, &expected_state).await)
+    }
+
+    async fn exchange(&self, id: &str, input: &str, verifier: &Secret, expected_state: &str) -> Result<LoginStatus> {
+        let (code, state) = input
+            .split_once('#')
+            .ok_or_else(|| anyhow::anyhow!("invalid code, expected the code#state shown after authorizing"))?;
+        // CSRF protection, as in `thanos auth claude`
+        anyhow::ensure!(state == expected_state, "state mismatch, start a new login");
+
+        let response = self.anthropic.exchange_code(code, state, verifier.expose()).await?;
+        let tokens = OAuthTokens {
+            access_token: response.access_token,
+            refresh_token: Some(response.refresh_token),
+            expires_at: Some(Utc::now().timestamp() + response.expires_in as i64),
+        };
+        self.save(id, "anthropic_max", &tokens)?;
+        self.status(id).ok_or_else(|| anyhow::anyhow!("login was cancelled"))
+    }
+
+    /// Poll GitHub until the user enters the code, then fetch a Copilot token
+    async fn wait_for_device(&self, id: &str, device_code: &str, interval: Duration) {
+        let result = async {
+            loop {
+                tokio::time::sleep(interval).await;
+                if self.status(id).is_none() {
+                    return Ok(None); // Cancelled or expired
+                }
+                if let Some(github_token) = self.github.poll_for_token(device_code).await? {
+                    let (copilot_token, expires_at) = self.github.get_copilot_token(&github_token).await?;
+                    return Ok(Some(OAuthTokens {
+                        access_token: copilot_token,
+                        refresh_token: Some(github_token),
+                        expires_at: Some(expires_at),
+                    }));
+                }
+            }
+        }
+        .await;
+
+        let result = match result {
+            Ok(Some(tokens)) => self.save(id, "github_copilot", &tokens),
+            Ok(None) => return,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            tracing::warn!("⚠️  Remote github_copilot login failed: {:#}", e);
+            if let Some(login) = self.logins.lock().unwrap().get_mut(id) {
+                login.status.state = LoginState::Failed;
+                login.status.error = Some(format!("{:#}", e));
+            }
+        }
+    }
+
+    /// Store the tokens and mark the login complete
+    fn save(&self, id: &str, provider: &str, tokens: &OAuthTokens) -> Result<()> {
+        self.store().store_oauth_tokens(provider, tokens)?;
+        super::TOKENS.clear_cache();
+        tracing::info!("✅ Remote {} login complete", provider);
+
+        if let Some(login) = self.logins.lock().unwrap().get_mut(id) {
+            login.status.state = LoginState::Complete;
+            login.status.token_expires_at = tokens.expires_at;
+            login.pkce = None;
+        }
+        Ok(())
+    }
+}
+
+fn status(id: &str, provider: &'static str, flow: &'static str, url: String, user_code: Option<String>) -> LoginStatus {
+    LoginStatus {
+        id: id.to_string(),
+        provider,
+        flow,
+        url,
+        user_code,
+        state: LoginState::Pending,
+        error: None,
+        expires_in: LOGIN_TTL.as_secs(),
+        token_expires_at: None,
+    }
+}
+
+/// Unguessable, since the id is all it takes to finish a login
+fn new_id() -> String {
+    let mut bytes = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::token_store::MemoryStore;
+
+    fn logins(store: Arc<MemoryStore>, server: &mockito::Server) -> Arc<Logins> {
+        Arc::new(Logins::with(
+            store,
+            AnthropicOAuth::with_token_url(format!("{}/v1/oauth/token", server.url())),
+            GitHubOAuth::with_base_urls(server.url(), server.url()),
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_pkce_login_with_pasted_code() {
+        let mut server = mockito::Server::new_async().await;
+        let store = Arc::new(MemoryStore::default());
+        let logins = logins(store.clone(), &server);
+
+        let started = logins.start("claude").await.unwrap();
+        assert_eq!((started.provider, started.flow, started.state), ("anthropic_max", "pkce", LoginState::Pending));
+        let state = started.url.split("state=").nth(1).unwrap().to_string();
+
+        let error = logins.submit_code(&started.id, "no-separator").await.unwrap().unwrap_err();
+        assert!(error.to_string().contains("code#state"), "{}", error);
+        let error = logins.submit_code(&started.id, "abc#forged").await.unwrap().unwrap_err();
+        assert!(error.to_string().contains("state mismatch"), "{}", error);
+        assert!(logins.submit_code("nope", "abc#def").await.is_none());
+
+        let exchange = server
+            .mock("POST", "/v1/oauth/token")
+            .match_body(mockito::Matcher::PartialJsonString(format!(r#"{{"code":"abc","state":"{}"}}"#, state)))
+            .with_body(
+                r#"{"token_type":"bearer","access_token":"remote-access","expires_in":28800,
+                    "refresh_token":"remote-refresh","scope":"user:inference"}"#,
+            )
+            .create_async()
+            .await;
+        let done = logins.submit_code(&started.id, &format!(" abc#{}\n", state)).await.unwrap().unwrap();
+        exchange.assert_async().await;
+        assert_eq!(done.state, LoginState::Complete);
+        assert!(done.token_expires_at.is_some());
+
+        let stored = store.tokens.lock().unwrap()["anthropic_max"].clone();
+        assert_eq!(stored.access_token.expose(), "remote-access");
+        assert!(logins.submit_code(&started.id, "abc#def").await.unwrap().is_err(), "already complete");
+    }
+
+    #[tokio::test]
+    async fn test_device_login_completes_in_background() {
+        let mut server = mockito::Server::new_async().await;
+        let store = Arc::new(MemoryStore::default());
+        let logins = logins(store.clone(), &server);
+
+        server
+            .mock("POST", "/login/device/code")
+            .with_body(
+                r#"{"device_code":"dev-123","user_code":"ABCD-1234",
+                    "verification_uri":"https://github.com/login/device","expires_in":900,"interval":0}"#,
+            )
+            .create_async()
+            .await;
+        server
+            .mock("POST", "/login/oauth/access_token")
+            .with_body(r#"{"access_token":"gho-token","token_type":"bearer","scope":"read:user"}"#)
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/copilot_internal/v2/token")
+            .match_header("authorization", "Bearer gho-token")
+            .with_body(r#"{"token":"copilot-token","expires_at":1900000000}"#)
+            .create_async()
+            .await;
+
+        let started = logins.start("copilot").await.unwrap();
+        assert_eq!(started.flow, "device");
+        assert_eq!(started.user_code.as_deref(), Some("ABCD-1234"));
+        assert!(logins.submit_code(&started.id, "abc#def").await.unwrap().is_err());
+
+        let mut status = started;
+        for _ in 0..200 {
+            status = logins.status(&status.id).unwrap();
+            if status.state != LoginState::Pending {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(status.state, LoginState::Complete, "{:?}", status.error);
+        assert_eq!(status.token_expires_at, Some(1_900_000_000));
+
+        let stored = store.tokens.lock().unwrap()["github_copilot"].clone();
+        assert_eq!(stored.access_token.expose(), "copilot-token");
+        assert_eq!(stored.refresh_token.unwrap().expose(), "gho-token");
+
+        assert!(logins.cancel(&status.id));
+        assert!(logins.status(&status.id).is_none());
+    }
+}
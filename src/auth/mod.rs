@@ -3,9 +3,15 @@
 pub mod anthropic_oauth;
 pub mod github_oauth;
 pub mod keyring;
+pub mod login;
+pub mod secrets;
 pub mod token_manager;
+pub mod token_store;
 
 pub use anthropic_oauth::AnthropicOAuth;
 pub use github_oauth::GitHubOAuth;
 pub use keyring::{KeyringStore, OAuthTokens};
-pub use token_manager::TokenManager;
+pub use login::{LoginState, LoginStatus, LOGINS};
+pub use secrets::{SecretSource, SECRETS};
+pub use token_manager::{TokenManager, TOKENS};
+pub use token_store::{token_store, FileTokenStore, TokenStore};
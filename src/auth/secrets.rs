@@ -0,0 +1,235 @@
+/// Secret references for provider credentials
+///
+/// An `api_key` of `keyring:openai`, `file:/run/secrets/openai` or
+/// `cmd:pass show openai` is looked up when the config is loaded or reloaded.
+/// Anything whose prefix isn't a registered source is used as written. More
+/// sources can be added with `SECRETS.register`.
+use crate::auth::KeyringStore;
+use crate::config::Config;
+use crate::secret::Secret;
+use anyhow::{Context, Result};
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::io::Read;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use zeroize::Zeroizing;
+
+/// Somewhere secrets can be read from
+pub trait SecretSource: Send + Sync {
+    /// Prefix of references to this source, e.g. `keyring` for `keyring:openai`
+    fn scheme(&self) -> &'static str;
+
+    /// The secret `locator` (the part after the colon) points at
+    fn fetch(&self, locator: &str, config: &Config) -> Result<Secret>;
+}
+
+/// `keyring:<name>`: an entry in the system keyring (see `thanos auth set`)
+pub struct KeyringSource;
+
+impl SecretSource for KeyringSource {
+    fn scheme(&self) -> &'static str {
+        "keyring"
+    }
+
+    fn fetch(&self, locator: &str, config: &Config) -> Result<Secret> {
+        KeyringStore::new(&config.oauth.keyring_service)
+            .get_secret(locator)?
+            .with_context(|| format!("no '{}' in the keyring (run `thanos auth set {}`)", locator, locator))
+    }
+}
+
+/// `file:<path>`: the contents of a file, e.g. a Docker or systemd secret
+pub struct FileSource;
+
+impl SecretSource for FileSource {
+    fn scheme(&self) -> &'static str {
+        "file"
+    }
+
+    fn fetch(&self, locator: &str, _config: &Config) -> Result<Secret> {
+        std::fs::read_to_string(locator)
+            .map(Secret::new)
+            .with_context(|| format!("failed to read {}", locator))
+    }
+}
+
+/// `cmd:<command line>`: what a command prints, e.g. `pass show openai`
+///
+/// Blocks until the command exits, so configs are loaded off the async
+/// workers. Not run for a config file found in the working directory.
+pub struct CommandSource;
+
+impl SecretSource for CommandSource {
+    fn scheme(&self) -> &'static str {
+        "cmd"
+    }
+
+    fn fetch(&self, locator: &str, config: &Config) -> Result<Secret> {
+        let timeout = Duration::from_secs(config.secrets.command_timeout_secs);
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(locator)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("failed to run `{}`", locator))?;
+
+        let started = Instant::now();
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if started.elapsed() > timeout {
+                let _ = child.kill();
+                let _ = child.wait();
+                anyhow::bail!("`{}` did not finish within {}s", locator, timeout.as_secs());
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        };
+
+        let mut stdout = Zeroizing::new(String::new());
+        let mut stderr = String::new();
+        if let Some(mut out) = child.stdout.take() {
+            out.read_to_string(&mut stdout)?;
+        }
+        if let Some(mut err) = child.stderr.take() {
+            err.read_to_string(&mut stderr)?;
+        }
+        if !status.success() {
+            anyhow::bail!("`{}` failed ({}): {}", locator, status, stderr.trim());
+        }
+        Ok(Secret::new(stdout.trim()))
+    }
+}
+
+/// Registered sources, and secrets already looked up
+pub struct Secrets {
+    sources: RwLock<Vec<Arc<dyn SecretSource>>>,
+    cache: Mutex<HashMap<String, (Secret, Instant)>>,
+}
+
+pub static SECRETS: Lazy<Secrets> = Lazy::new(Secrets::new);
+
+impl Secrets {
+    fn new() -> Self {
+        Self {
+            sources: RwLock::new(vec![Arc::new(KeyringSource), Arc::new(FileSource), Arc::new(CommandSource)]),
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Add a source, replacing any with the same scheme
+    pub fn register(&self, source: Arc<dyn SecretSource>) {
+        let mut sources = self.sources.write().unwrap();
+        sources.retain(|existing| existing.scheme() != source.scheme());
+        sources.push(source);
+    }
+
+    /// Source a value refers to, if it is a reference
+    fn source(&self, value: &str) -> Option<(Arc<dyn SecretSource>, String)> {
+        let (scheme, locator) = value.split_once(':')?;
+        let sources = self.sources.read().unwrap();
+        let source = sources.iter().find(|source| source.scheme() == scheme)?;
+        Some((source.clone(), locator.trim().to_string()))
+    }
+
+    /// Whether a value is a reference rather than the secret itself
+    pub fn is_reference(&self, value: &str) -> bool {
+        self.source(value).is_some()
+    }
+
+    /// The secret a reference points at, or `None` if the value isn't one
+    ///
+    /// Secrets are reused for `secrets.cache_ttl_secs`; surrounding whitespace
+    /// (such as a file's trailing newline) is trimmed.
+    pub fn resolve(&self, value: &str, config: &Config) -> Option<Result<Secret>> {
+        let (source, locator) = self.source(value)?;
+
+        let ttl = Duration::from_secs(config.secrets.cache_ttl_secs);
+        if let Some((secret, fetched)) = self.cache.lock().unwrap().get(value)
+            && fetched.elapsed() < ttl
+        {
+            return Some(Ok(secret.clone()));
+        }
+
+        let result = source.fetch(&locator, config).and_then(|fetched| {
+            let trimmed = fetched.expose().trim();
+            anyhow::ensure!(!trimmed.is_empty(), "secret {} is empty", value);
+            Ok(if trimmed.len() == fetched.expose().len() { fetched } else { Secret::new(trimmed) })
+        });
+        if let Ok(secret) = &result
+            && !ttl.is_zero()
+        {
+            self.cache.lock().unwrap().insert(value.to_string(), (secret.clone(), Instant::now()));
+        }
+        Some(result.with_context(|| format!("secret {}", value)))
+    }
+
+    /// Forget every cached secret, so the next load reads them again
+    pub fn clear_cache(&self) {
+        self.cache.lock().unwrap().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn config(cache_ttl_secs: u64) -> Config {
+        let mut config: Config = toml::from_str("[server]\n[routing]\n[providers]\n").unwrap();
+        config.secrets.cache_ttl_secs = cache_ttl_secs;
+        config
+    }
+
+    #[test]
+    fn test_file_and_command_sources() {
+        let path = std::env::temp_dir().join(format!("thanos-secret-{}", std::process::id()));
+        std::fs::write(&path, "sk-from-file\n").unwrap();
+        let config = config(0);
+
+        let secret = SECRETS.resolve(&format!("file:{}", path.display()), &config).unwrap().unwrap();
+        assert_eq!(secret.expose(), "sk-from-file");
+        let secret = SECRETS.resolve("cmd: echo sk-from-cmd", &config).unwrap().unwrap();
+        assert_eq!(secret.expose(), "sk-from-cmd");
+
+        let error = SECRETS.resolve("cmd:echo oops >&2; exit 3", &config).unwrap().unwrap_err();
+        assert!(format!("{:#}", error).contains("oops"), "{:#}", error);
+        assert!(SECRETS.resolve("file:/nonexistent/thanos-secret", &config).unwrap().is_err());
+
+        // Not a reference: used as written
+        assert!(SECRETS.resolve("sk-plain:with-colon", &config).is_none());
+    }
+
+    struct Counting(AtomicUsize);
+
+    impl SecretSource for Counting {
+        fn scheme(&self) -> &'static str {
+            "counting"
+        }
+
+        fn fetch(&self, locator: &str, _config: &Config) -> Result<Secret> {
+            let n = self.0.fetch_add(1, Ordering::SeqCst) + 1;
+            Ok(Secret::new(format!("{}-{}", locator, n)))
+        }
+    }
+
+    #[test]
+    fn test_registered_source_and_cache() {
+        SECRETS.register(Arc::new(Counting(AtomicUsize::new(0))));
+        assert!(SECRETS.is_reference("counting:key"));
+
+        // Without a TTL every load looks the secret up again
+        assert_eq!(SECRETS.resolve("counting:key", &config(0)).unwrap().unwrap().expose(), "key-1");
+        assert_eq!(SECRETS.resolve("counting:key", &config(0)).unwrap().unwrap().expose(), "key-2");
+
+        // With one, rotation needs the cache cleared
+        assert_eq!(SECRETS.resolve("counting:key", &config(60)).unwrap().unwrap().expose(), "key-3");
+        assert_eq!(SECRETS.resolve("counting:key", &config(60)).unwrap().unwrap().expose(), "key-3");
+        SECRETS.clear_cache();
+        assert_eq!(SECRETS.resolve("counting:key", &config(60)).unwrap().unwrap().expose(), "key-4");
+    }
+}
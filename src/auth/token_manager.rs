@@ -1,11 +1,50 @@
 /// Token manager with auto-refresh capabilities
+///
+/// Requests share one manager, `TOKENS`: tokens are kept in memory after the
+/// first read from the token store, and each provider's refresh runs once at
+/// a time, so concurrent requests never spend the same refresh token twice
+/// (Anthropic rotates them, and a reused one ends the session). With
+/// `oauth.auto_refresh`, `spawn_refresher` refreshes tokens before they
+/// expire, so requests don't wait for it.
+use crate::secret::Secret;
 use anyhow::Result;
 use chrono::Utc;
+use once_cell::sync::Lazy;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
 
-use super::{AnthropicOAuth, GitHubOAuth, KeyringStore, OAuthTokens};
+use super::{AnthropicOAuth, GitHubOAuth, OAuthTokens, TokenStore};
+use crate::config::OAuthConfig;
+
+/// Providers signed in with OAuth (`thanos auth claude|copilot`)
+pub const OAUTH_PROVIDERS: [&str; 2] = ["anthropic_max", "github_copilot"];
+
+/// Requests refresh tokens that expire within this many seconds
+const EXPIRY_BUFFER_SECS: i64 = 300;
+
+/// The refresher refreshes tokens that expire within this many seconds
+const REFRESH_AHEAD_SECS: i64 = 600;
+
+/// How often the refresher looks at the tokens
+const REFRESH_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The manager requests use
+pub static TOKENS: Lazy<TokenManager> = Lazy::new(TokenManager::new);
 
 pub struct TokenManager {
-    keyring: KeyringStore,
+    /// `None` follows the store configured in `[oauth]`
+    store: Option<Arc<dyn TokenStore>>,
+    anthropic: AnthropicOAuth,
+    github: GitHubOAuth,
+    cache: Mutex<HashMap<String, OAuthTokens>>,
+    refreshing: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+    /// Providers already warned about, so the refresher doesn't repeat itself
+    warned: Mutex<HashSet<String>>,
+    /// Providers whose refreshed tokens failed to save; the cache has the
+    /// only copy of the rotated refresh token until a save succeeds
+    unsaved: Mutex<HashSet<String>>,
 }
 
 impl Default for TokenManager {
@@ -15,37 +54,127 @@ impl Default for TokenManager {
 }
 
 impl TokenManager {
+    /// Manager for the store configured in `[oauth]`
     pub fn new() -> Self {
         Self {
-            keyring: KeyringStore::new("thanos"),
+            store: None,
+            anthropic: AnthropicOAuth::new(),
+            github: GitHubOAuth::new(),
+            cache: Mutex::new(HashMap::new()),
+            refreshing: Mutex::new(HashMap::new()),
+            warned: Mutex::new(HashSet::new()),
+            unsaved: Mutex::new(HashSet::new()),
         }
     }
 
-    /// Get valid access token, automatically refreshing if expired
-    pub async fn get_access_token(&self, provider: &str) -> Result<String> {
+    pub fn with_store(store: Arc<dyn TokenStore>) -> Self {
+        Self { store: Some(store), ..Self::new() }
+    }
+
+    /// Refresh through other OAuth clients (tests, proxies)
+    pub fn with_clients(self, anthropic: AnthropicOAuth, github: GitHubOAuth) -> Self {
+        Self { anthropic, github, ..self }
+    }
+
+    fn store(&self) -> Arc<dyn TokenStore> {
+        self.store.clone().unwrap_or_else(super::token_store)
+    }
+
+    /// Forget cached tokens, e.g. when the token store changes
+    pub fn clear_cache(&self) {
+        self.cache.lock().unwrap().clear();
+        self.unsaved.lock().unwrap().clear();
+    }
+
+    /// Tokens from the cache, or the store the first time
+    fn tokens(&self, provider: &str) -> Result<OAuthTokens> {
+        if let Some(tokens) = self.cache.lock().unwrap().get(provider) {
+            return Ok(tokens.clone());
+        }
+        self.load(provider)
+    }
+
+    /// Tokens from the store, which another process (`thanos auth`) may have changed
+    ///
+    /// Refreshed tokens that failed to save are newer than the store's; they
+    /// are kept, and saved again.
+    fn load(&self, provider: &str) -> Result<OAuthTokens> {
+        if self.unsaved.lock().unwrap().contains(provider) {
+            let cached = self.cache.lock().unwrap().get(provider).cloned();
+            if let Some(tokens) = cached {
+                self.save(provider, &tokens);
+                return Ok(tokens);
+            }
+        }
+
         let tokens = self
-            .keyring
+            .store()
             .get_oauth_tokens(provider)?
             .ok_or_else(|| anyhow::anyhow!("No tokens found for provider: {}", provider))?;
+        self.cache.lock().unwrap().insert(provider.to_string(), tokens.clone());
+        Ok(tokens)
+    }
 
-        // Check if token is expired (with 5 minute buffer)
-        let now = Utc::now().timestamp();
-        let is_expired = tokens
-            .expires_at
-            .map(|exp| exp - 300 < now) // 5 minute buffer
-            .unwrap_or(false);
-
-        if is_expired {
-            // Token is expired, attempt to refresh
-            self.refresh_token(provider, &tokens).await
-        } else {
-            // Token is still valid
-            Ok(tokens.access_token)
+    /// Get valid access token, automatically refreshing if expired
+    pub async fn get_access_token(&self, provider: &str) -> Result<Secret> {
+        let tokens = self.tokens(provider)?;
+        if !expires_within(&tokens, EXPIRY_BUFFER_SECS) {
+            return Ok(tokens.access_token);
+        }
+        self.refresh(provider, EXPIRY_BUFFER_SECS).await
+    }
+
+    /// Refresh a provider's tokens if they expire within `within` seconds,
+    /// one refresh at a time
+    async fn refresh(&self, provider: &str, within: i64) -> Result<Secret> {
+        let lock = self.refreshing.lock().unwrap().entry(provider.to_string()).or_default().clone();
+        let _refreshing = lock.lock().await;
+
+        // Whoever held the lock may have refreshed already
+        let tokens = self.load(provider)?;
+        if !expires_within(&tokens, within) {
+            return Ok(tokens.access_token);
+        }
+
+        let metrics = &crate::metrics::METRICS;
+        match self.refresh_token(provider, &tokens).await {
+            Ok(refreshed) => {
+                metrics.oauth_refreshes_total.with_label_values(&[provider, "success"]).inc();
+                // The old refresh token is spent: keep the new one even if saving fails
+                self.cache.lock().unwrap().insert(provider.to_string(), refreshed.clone());
+                self.save(provider, &refreshed);
+                tracing::info!("🔄 Refreshed {} OAuth token", provider);
+                Ok(refreshed.access_token)
+            }
+            Err(e) => {
+                metrics.oauth_refreshes_total.with_label_values(&[provider, "failure"]).inc();
+                Err(e.context(format!("Failed to refresh {} OAuth token", provider)))
+            }
+        }
+    }
+
+    /// Write refreshed tokens to the store; on failure they stay in memory
+    /// only, which is flagged with `oauth_token_warning`
+    fn save(&self, provider: &str, tokens: &OAuthTokens) {
+        match self.store().store_oauth_tokens(provider, tokens) {
+            Ok(()) => {
+                self.unsaved.lock().unwrap().remove(provider);
+            }
+            Err(e) => {
+                if self.unsaved.lock().unwrap().insert(provider.to_string()) {
+                    tracing::warn!(
+                        "⚠️  Refreshed {} OAuth token could not be saved, keeping it in memory: {:#}",
+                        provider,
+                        e
+                    );
+                }
+                crate::metrics::METRICS.oauth_token_warning.with_label_values(&[provider]).set(1.0);
+            }
         }
     }
 
     /// Refresh token for a provider
-    async fn refresh_token(&self, provider: &str, tokens: &OAuthTokens) -> Result<String> {
+    async fn refresh_token(&self, provider: &str, tokens: &OAuthTokens) -> Result<OAuthTokens> {
         let refresh_token = tokens
             .refresh_token
             .as_ref()
@@ -53,35 +182,23 @@ impl TokenManager {
 
         match provider {
             "anthropic_max" => {
-                let oauth = AnthropicOAuth::new();
-                let token_response = oauth.refresh_token(refresh_token).await?;
-
-                let expires_at = Utc::now().timestamp() + token_response.expires_in as i64;
+                let token_response = self.anthropic.refresh_token(refresh_token).await?;
 
-                let new_tokens = OAuthTokens {
-                    access_token: token_response.access_token.clone(),
+                Ok(OAuthTokens {
+                    access_token: token_response.access_token,
                     refresh_token: Some(token_response.refresh_token),
-                    expires_at: Some(expires_at),
-                };
-
-                self.keyring.store_oauth_tokens(provider, &new_tokens)?;
-
-                Ok(token_response.access_token)
+                    expires_at: Some(Utc::now().timestamp() + token_response.expires_in as i64),
+                })
             }
             "github_copilot" => {
                 // For GitHub Copilot, the "refresh_token" is actually the GitHub access token
-                let oauth = GitHubOAuth::new();
-                let (copilot_token, expires_at) = oauth.get_copilot_token(refresh_token).await?;
+                let (copilot_token, expires_at) = self.github.get_copilot_token(refresh_token).await?;
 
-                let new_tokens = OAuthTokens {
-                    access_token: copilot_token.clone(),
-                    refresh_token: Some(refresh_token.to_string()),
+                Ok(OAuthTokens {
+                    access_token: copilot_token,
+                    refresh_token: Some(refresh_token.clone()),
                     expires_at: Some(expires_at),
-                };
-
-                self.keyring.store_oauth_tokens(provider, &new_tokens)?;
-
-                Ok(copilot_token)
+                })
             }
             _ => Err(anyhow::anyhow!(
                 "Auto-refresh not supported for provider: {}",
@@ -90,38 +207,209 @@ impl TokenManager {
         }
     }
 
+    /// Refresh a provider's tokens ahead of expiry, and warn when they will
+    /// run out: a refresh failed, or expiry is within `refresh_warning_hours`
+    /// with no way to refresh
+    pub async fn check(&self, provider: &str, oauth: &OAuthConfig) {
+        let metrics = &crate::metrics::METRICS;
+        let Ok(tokens) = self.load(provider) else { return };
+        let Some(expires_at) = tokens.expires_at else { return };
+
+        let refreshable = oauth.auto_refresh && tokens.refresh_token.is_some();
+        let mut warning = None;
+        let mut expires_in = expires_at - Utc::now().timestamp();
+
+        if refreshable && expires_in < REFRESH_AHEAD_SECS {
+            match self.refresh(provider, REFRESH_AHEAD_SECS).await {
+                Ok(_) => {
+                    if let Some(expires_at) = self.tokens(provider).ok().and_then(|tokens| tokens.expires_at) {
+                        expires_in = expires_at - Utc::now().timestamp();
+                    }
+                }
+                Err(e) => warning = Some(format!("{:#}", e)),
+            }
+        } else if !refreshable && expires_in < oauth.refresh_warning_hours as i64 * 3600 {
+            warning = Some(if expires_in > 0 {
+                format!("{} OAuth token expires in {} minutes and can't be refreshed automatically", provider, expires_in / 60)
+            } else {
+                format!("{} OAuth token has expired and can't be refreshed automatically", provider)
+            });
+        }
+        if warning.is_none() && self.unsaved.lock().unwrap().contains(provider) {
+            warning = Some(format!("{} OAuth token could not be saved to {}", provider, self.store().describe()));
+        }
+
+        metrics.oauth_token_expires_in_seconds.with_label_values(&[provider]).set(expires_in as f64);
+        metrics.oauth_token_warning.with_label_values(&[provider]).set(if warning.is_some() { 1.0 } else { 0.0 });
+
+        let mut warned = self.warned.lock().unwrap();
+        match warning {
+            Some(message) => {
+                if warned.insert(provider.to_string()) {
+                    tracing::warn!("⚠️  {} (sign in again with `thanos auth`)", message);
+                }
+            }
+            None => {
+                warned.remove(provider);
+            }
+        }
+    }
+
     /// Check if token is expired or about to expire
     pub fn is_token_expired(&self, provider: &str) -> Result<bool> {
-        let tokens = self
-            .keyring
-            .get_oauth_tokens(provider)?
-            .ok_or_else(|| anyhow::anyhow!("No tokens found for provider: {}", provider))?;
-
-        let now = Utc::now().timestamp();
-        Ok(tokens
-            .expires_at
-            .map(|exp| exp < now)
-            .unwrap_or(false))
+        let tokens = self.tokens(provider)?;
+        Ok(expires_within(&tokens, 0))
     }
 
     /// Get time until expiration in seconds
     pub fn time_until_expiration(&self, provider: &str) -> Result<Option<i64>> {
-        let tokens = self
-            .keyring
-            .get_oauth_tokens(provider)?
-            .ok_or_else(|| anyhow::anyhow!("No tokens found for provider: {}", provider))?;
+        let tokens = self.tokens(provider)?;
 
         let now = Utc::now().timestamp();
         Ok(tokens.expires_at.map(|exp| exp - now))
     }
 }
 
+/// Whether tokens expire within `secs` seconds; tokens without an expiry don't
+fn expires_within(tokens: &OAuthTokens, secs: i64) -> bool {
+    tokens
+        .expires_at
+        .is_some_and(|exp| exp - secs < Utc::now().timestamp())
+}
+
+/// Check the OAuth tokens every minute in the background
+///
+/// `[oauth]` is re-read each time, so reloads can change `auto_refresh` and
+/// `refresh_warning_hours`.
+pub fn spawn_refresher(live: Arc<crate::reload::LiveRouter>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            let oauth = live.config().oauth.clone();
+            for provider in OAUTH_PROVIDERS {
+                TOKENS.check(provider, &oauth).await;
+            }
+            tokio::time::sleep(REFRESH_CHECK_INTERVAL).await;
+        }
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::auth::token_store::MemoryStore;
+    use std::sync::atomic::Ordering;
+
+    /// Held by tests that check the (global) `anthropic_max` gauges
+    static GAUGES: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+    /// Signed in to Claude Max, with tokens expiring in `expires_in` seconds
+    fn signed_in(store: Arc<MemoryStore>, server: &mockito::Server, expires_in: i64) -> TokenManager {
+        store
+            .store_oauth_tokens(
+                "anthropic_max",
+                &OAuthTokens {
+                    access_token: "old-access".into(),
+                    refresh_token: Some("old-refresh".into()),
+                    expires_at: Some(Utc::now().timestamp() + expires_in),
+                },
+            )
+            .unwrap();
+        TokenManager::with_store(store).with_clients(
+            AnthropicOAuth::with_token_url(format!("{}/v1/oauth/token", server.url())),
+            GitHubOAuth::with_base_urls(server.url(), server.url()),
+        )
+    }
+
+    async fn mock_refresh(server: &mut mockito::Server) -> mockito::Mock {
+        server
+            .mock("POST", "/v1/oauth/token")
+            .match_body(mockito::Matcher::PartialJsonString(r#"{"refresh_token":"old-refresh"}"#.to_string()))
+            .with_body(
+                r#"{"token_type":"bearer","access_token":"new-access","expires_in":28800,
+                    "refresh_token":"new-refresh","scope":"user:inference"}"#,
+            )
+            .expect(1)
+            .create_async()
+            .await
+    }
 
     #[test]
     fn test_token_manager_creation() {
         let _manager = TokenManager::new();
     }
+
+    #[tokio::test]
+    async fn test_concurrent_requests_refresh_once() {
+        let mut server = mockito::Server::new_async().await;
+        let refresh = mock_refresh(&mut server).await;
+        let store = Arc::new(MemoryStore::default());
+        let manager = signed_in(store.clone(), &server, 60);
+
+        let tokens = futures::future::join_all((0..8).map(|_| manager.get_access_token("anthropic_max"))).await;
+        for token in tokens {
+            assert_eq!(token.unwrap().expose(), "new-access");
+        }
+        refresh.assert_async().await;
+
+        // The rotated refresh token was saved
+        let stored = store.tokens.lock().unwrap()["anthropic_max"].clone();
+        assert_eq!(stored.refresh_token.unwrap().expose(), "new-refresh");
+
+        // Later requests are served from memory
+        let reads = store.reads.load(Ordering::SeqCst);
+        for _ in 0..5 {
+            manager.get_access_token("anthropic_max").await.unwrap();
+        }
+        assert_eq!(store.reads.load(Ordering::SeqCst), reads);
+    }
+
+    #[tokio::test]
+    async fn test_check_refreshes_ahead_of_expiry() {
+        let _gauges = GAUGES.lock().await;
+        let mut server = mockito::Server::new_async().await;
+        let refresh = mock_refresh(&mut server).await;
+        let store = Arc::new(MemoryStore::default());
+
+        // Still valid for requests, but inside the refresher's window
+        let manager = signed_in(store, &server, 450);
+        assert_eq!(manager.get_access_token("anthropic_max").await.unwrap().expose(), "old-access");
+        manager.check("anthropic_max", &OAuthConfig::default()).await;
+        refresh.assert_async().await;
+        assert_eq!(manager.get_access_token("anthropic_max").await.unwrap().expose(), "new-access");
+
+        // Without auto_refresh, nearing expiry is only a warning
+        let manager = signed_in(Arc::new(MemoryStore::default()), &server, 450);
+        let oauth = OAuthConfig { auto_refresh: false, ..Default::default() };
+        manager.check("anthropic_max", &oauth).await;
+        let warning = crate::metrics::METRICS.oauth_token_warning.with_label_values(&["anthropic_max"]).get();
+        assert_eq!(warning, 1.0);
+        assert!(manager.warned.lock().unwrap().contains("anthropic_max"));
+    }
+
+    #[tokio::test]
+    async fn test_refreshed_tokens_survive_a_failed_save() {
+        let _gauges = GAUGES.lock().await;
+        let mut server = mockito::Server::new_async().await;
+        let refresh = mock_refresh(&mut server).await;
+        let store = Arc::new(MemoryStore::default());
+        let manager = signed_in(store.clone(), &server, 60);
+        store.read_only.store(true, Ordering::SeqCst);
+
+        assert_eq!(manager.get_access_token("anthropic_max").await.unwrap().expose(), "new-access");
+        refresh.assert_async().await;
+        let warning = crate::metrics::METRICS.oauth_token_warning.with_label_values(&["anthropic_max"]);
+        assert_eq!(warning.get(), 1.0);
+
+        // The store still has the spent tokens, but the rotated ones are used
+        manager.check("anthropic_max", &OAuthConfig::default()).await;
+        assert_eq!(manager.get_access_token("anthropic_max").await.unwrap().expose(), "new-access");
+        assert_eq!(warning.get(), 1.0);
+
+        // Saved once the store accepts writes again
+        store.read_only.store(false, Ordering::SeqCst);
+        manager.check("anthropic_max", &OAuthConfig::default()).await;
+        let stored = store.tokens.lock().unwrap()["anthropic_max"].clone();
+        assert_eq!(stored.refresh_token.unwrap().expose(), "new-refresh");
+        assert_eq!(warning.get(), 0.0);
+    }
 }
@@ -0,0 +1,425 @@
+/// Where OAuth tokens are kept
+///
+/// `oauth.token_store` picks the backend: the system keyring, or a file
+/// encrypted at rest for machines without a Secret Service (Docker images,
+/// systemd services, SSH sessions without D-Bus). The file is AES-256-GCM
+/// with a key derived by Argon2id from `oauth.token_passphrase` or the
+/// contents of `oauth.token_key_file`, and is only ever written with 0600
+/// permissions.
+use super::{KeyringStore, OAuthTokens};
+use crate::config::OAuthConfig;
+use crate::secret::Secret;
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use anyhow::{Context, Result};
+use once_cell::sync::Lazy;
+use rand::RngCore;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use zeroize::Zeroizing;
+
+/// Storage for OAuth tokens, by provider (`anthropic_max`, `github_copilot`)
+pub trait TokenStore: Send + Sync {
+    fn get_oauth_tokens(&self, provider: &str) -> Result<Option<OAuthTokens>>;
+
+    fn store_oauth_tokens(&self, provider: &str, tokens: &OAuthTokens) -> Result<()>;
+
+    /// Removing tokens that aren't there is not an error
+    fn delete_oauth_tokens(&self, provider: &str) -> Result<()>;
+
+    /// Where the tokens are, for messages
+    fn describe(&self) -> String;
+
+    fn has_tokens(&self, provider: &str) -> bool {
+        self.get_oauth_tokens(provider).ok().flatten().is_some()
+    }
+}
+
+impl TokenStore for KeyringStore {
+    fn get_oauth_tokens(&self, provider: &str) -> Result<Option<OAuthTokens>> {
+        KeyringStore::get_oauth_tokens(self, provider)
+    }
+
+    fn store_oauth_tokens(&self, provider: &str, tokens: &OAuthTokens) -> Result<()> {
+        KeyringStore::store_oauth_tokens(self, provider, tokens)
+    }
+
+    fn delete_oauth_tokens(&self, provider: &str) -> Result<()> {
+        KeyringStore::delete_oauth_tokens(self, provider)
+    }
+
+    fn describe(&self) -> String {
+        "system keyring".to_string()
+    }
+}
+
+/// Identifies the file format; the byte after it is the version
+const MAGIC: &[u8] = b"THANOSTK";
+const VERSION: u8 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+type Salt = [u8; SALT_LEN];
+type Key = Zeroizing<[u8; 32]>;
+
+/// Tokens in one encrypted file: `MAGIC`, version, salt, nonce, ciphertext
+pub struct FileTokenStore {
+    path: PathBuf,
+    passphrase: Secret,
+    /// Key for the salt in the file, so Argon2 runs once rather than per request
+    key: Mutex<Option<(Salt, Key)>>,
+    /// Read-modify-write of the file, one at a time
+    writing: Mutex<()>,
+}
+
+impl FileTokenStore {
+    pub fn new(path: impl Into<PathBuf>, passphrase: Secret) -> Self {
+        Self {
+            path: path.into(),
+            passphrase,
+            key: Mutex::new(None),
+            writing: Mutex::new(()),
+        }
+    }
+
+    fn cipher(&self, salt: &Salt) -> Result<Aes256Gcm> {
+        let mut cached = self.key.lock().unwrap();
+        if let Some((cached_salt, key)) = cached.as_ref()
+            && cached_salt == salt
+        {
+            return Ok(Aes256Gcm::new(key.as_ref().into()));
+        }
+
+        let mut key: Key = Zeroizing::new([0u8; 32]);
+        argon2::Argon2::default()
+            .hash_password_into(self.passphrase.expose().as_bytes(), salt, key.as_mut())
+            .map_err(|e| anyhow::anyhow!("Failed to derive token file key: {}", e))?;
+        let cipher = Aes256Gcm::new(key.as_ref().into());
+        *cached = Some((*salt, key));
+        Ok(cipher)
+    }
+
+    /// Every stored token, and the file's salt; an absent file is empty
+    fn read(&self) -> Result<(HashMap<String, OAuthTokens>, Option<Salt>)> {
+        let data = match std::fs::read(&self.path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok((HashMap::new(), None)),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read token file {}", self.path.display())),
+        };
+
+        #[cfg(unix)]
+        warn_if_shared(&self.path);
+
+        let header = MAGIC.len() + 1;
+        anyhow::ensure!(
+            data.len() > header + SALT_LEN + NONCE_LEN && data.starts_with(MAGIC),
+            "{} is not a Thanos token file",
+            self.path.display()
+        );
+        anyhow::ensure!(data[MAGIC.len()] == VERSION, "{}: unsupported token file version {}", self.path.display(), data[MAGIC.len()]);
+
+        let salt: Salt = data[header..header + SALT_LEN].try_into()?;
+        let nonce = Nonce::from_slice(&data[header + SALT_LEN..header + SALT_LEN + NONCE_LEN]);
+        let plaintext = Zeroizing::new(
+            self.cipher(&salt)?
+                .decrypt(nonce, &data[header + SALT_LEN + NONCE_LEN..])
+                .map_err(|_| anyhow::anyhow!("Failed to decrypt {}: wrong passphrase or corrupted file", self.path.display()))?,
+        );
+
+        let tokens = serde_json::from_slice(&plaintext)
+            .with_context(|| format!("Failed to parse token file {}", self.path.display()))?;
+        Ok((tokens, Some(salt)))
+    }
+
+    /// Replace the file, keeping its salt (and so the cached key)
+    fn write(&self, tokens: &HashMap<String, OAuthTokens>, salt: Option<Salt>) -> Result<()> {
+        let salt = salt.unwrap_or_else(|| {
+            let mut salt = [0u8; SALT_LEN];
+            rand::thread_rng().fill_bytes(&mut salt);
+            salt
+        });
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut nonce);
+
+        let plaintext = Zeroizing::new(serde_json::to_vec(tokens)?);
+        let ciphertext = self
+            .cipher(&salt)?
+            .encrypt(Nonce::from_slice(&nonce), plaintext.as_slice())
+            .map_err(|_| anyhow::anyhow!("Failed to encrypt tokens"))?;
+
+        let mut data = Vec::with_capacity(MAGIC.len() + 1 + SALT_LEN + NONCE_LEN + ciphertext.len());
+        data.extend_from_slice(MAGIC);
+        data.push(VERSION);
+        data.extend_from_slice(&salt);
+        data.extend_from_slice(&nonce);
+        data.extend_from_slice(&ciphertext);
+
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            create_private_dir(dir)?;
+        }
+
+        // Written beside the file and renamed over it, so a crash never leaves half a file
+        let temp = self.path.with_extension("tmp");
+        let _ = std::fs::remove_file(&temp);
+        write_private(&temp, &data).with_context(|| format!("Failed to write token file {}", temp.display()))?;
+        std::fs::rename(&temp, &self.path)
+            .with_context(|| format!("Failed to replace token file {}", self.path.display()))
+    }
+}
+
+impl TokenStore for FileTokenStore {
+    fn get_oauth_tokens(&self, provider: &str) -> Result<Option<OAuthTokens>> {
+        Ok(self.read()?.0.remove(provider))
+    }
+
+    fn store_oauth_tokens(&self, provider: &str, tokens: &OAuthTokens) -> Result<()> {
+        let _writing = self.writing.lock().unwrap();
+        let (mut stored, salt) = self.read()?;
+        stored.insert(provider.to_string(), tokens.clone());
+        self.write(&stored, salt)
+    }
+
+    fn delete_oauth_tokens(&self, provider: &str) -> Result<()> {
+        let _writing = self.writing.lock().unwrap();
+        let (mut stored, salt) = self.read()?;
+        if stored.remove(provider).is_some() {
+            self.write(&stored, salt)?;
+        }
+        Ok(())
+    }
+
+    fn describe(&self) -> String {
+        format!("encrypted token file {}", self.path.display())
+    }
+}
+
+#[cfg(unix)]
+fn write_private(path: &Path, data: &[u8]) -> Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    let mut file = std::fs::OpenOptions::new().write(true).create_new(true).mode(0o600).open(path)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn write_private(path: &Path, data: &[u8]) -> Result<()> {
+    std::fs::write(path, data)?;
+    Ok(())
+}
+
+#[cfg(unix)]
+fn create_private_dir(dir: &Path) -> Result<()> {
+    use std::os::unix::fs::DirBuilderExt;
+
+    if !dir.exists() {
+        std::fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn create_private_dir(dir: &Path) -> Result<()> {
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))
+}
+
+#[cfg(unix)]
+fn warn_if_shared(path: &Path) {
+    use std::os::unix::fs::PermissionsExt;
+
+    if let Ok(meta) = std::fs::metadata(path)
+        && meta.permissions().mode() & 0o077 != 0
+    {
+        tracing::warn!(
+            "⚠️  Token file {} has permissions {:o}; run: chmod 600 {}",
+            path.display(),
+            meta.permissions().mode() & 0o777,
+            path.display()
+        );
+    }
+}
+
+/// `$XDG_DATA_HOME/thanos/tokens.enc`, or `~/.local/share/thanos/tokens.enc`
+pub fn default_token_file() -> PathBuf {
+    std::env::var_os("XDG_DATA_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("thanos")
+        .join("tokens.enc")
+}
+
+/// The store `[oauth]` asks for
+pub fn from_config(oauth: &OAuthConfig) -> Result<Arc<dyn TokenStore>> {
+    match oauth.token_store.as_str() {
+        "keyring" => Ok(Arc::new(KeyringStore::new(&oauth.keyring_service))),
+        "file" => {
+            let passphrase = match (&oauth.token_passphrase, &oauth.token_key_file) {
+                (Some(passphrase), _) if !passphrase.is_empty() => passphrase.clone(),
+                (_, Some(key_file)) => {
+                    let key = Zeroizing::new(
+                        std::fs::read_to_string(key_file)
+                            .with_context(|| format!("Failed to read oauth.token_key_file {}", key_file))?,
+                    );
+                    anyhow::ensure!(!key.trim().is_empty(), "oauth.token_key_file {} is empty", key_file);
+                    Secret::new(key.trim())
+                }
+                _ => anyhow::bail!("oauth.token_store = \"file\" needs token_passphrase or token_key_file"),
+            };
+            let path = oauth.token_file.as_ref().map(PathBuf::from).unwrap_or_else(default_token_file);
+            Ok(Arc::new(FileTokenStore::new(path, passphrase)))
+        }
+        other => anyhow::bail!("Unknown oauth.token_store '{}' (expected \"keyring\" or \"file\")", other),
+    }
+}
+
+/// Settings the current store was built from
+#[derive(PartialEq)]
+struct StoreSettings {
+    token_store: String,
+    keyring_service: String,
+    token_file: Option<String>,
+    token_passphrase: Option<Secret>,
+    token_key_file: Option<String>,
+}
+
+impl StoreSettings {
+    fn of(oauth: &OAuthConfig) -> Self {
+        Self {
+            token_store: oauth.token_store.clone(),
+            keyring_service: oauth.keyring_service.clone(),
+            token_file: oauth.token_file.clone(),
+            token_passphrase: oauth.token_passphrase.clone(),
+            token_key_file: oauth.token_key_file.clone(),
+        }
+    }
+}
+
+type Configured = (Option<StoreSettings>, Arc<dyn TokenStore>);
+
+/// The store OAuth providers use; the default keyring until `configure` runs
+static TOKEN_STORE: Lazy<RwLock<Configured>> =
+    Lazy::new(|| RwLock::new((None, Arc::new(KeyringStore::new("thanos")))));
+
+pub fn token_store() -> Arc<dyn TokenStore> {
+    TOKEN_STORE.read().unwrap().1.clone()
+}
+
+/// Switch to the store `[oauth]` asks for, keeping the current one (and its
+/// derived key) if the settings haven't changed
+pub fn configure(oauth: &OAuthConfig) -> Result<()> {
+    let settings = StoreSettings::of(oauth);
+    if TOKEN_STORE.read().unwrap().0.as_ref() == Some(&settings) {
+        return Ok(());
+    }
+
+    let store = from_config(oauth)?;
+    tracing::debug!("OAuth tokens are kept in the {}", store.describe());
+    *TOKEN_STORE.write().unwrap() = (Some(settings), store);
+    super::TOKENS.clear_cache();
+    Ok(())
+}
+
+/// Tokens in memory, counting reads
+#[cfg(test)]
+#[derive(Default)]
+pub(crate) struct MemoryStore {
+    pub tokens: Mutex<HashMap<String, OAuthTokens>>,
+    pub reads: std::sync::atomic::AtomicUsize,
+    /// Make writes fail, like a locked keyring or a full disk
+    pub read_only: std::sync::atomic::AtomicBool,
+}
+
+#[cfg(test)]
+impl TokenStore for MemoryStore {
+    fn get_oauth_tokens(&self, provider: &str) -> Result<Option<OAuthTokens>> {
+        self.reads.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        Ok(self.tokens.lock().unwrap().get(provider).cloned())
+    }
+
+    fn store_oauth_tokens(&self, provider: &str, tokens: &OAuthTokens) -> Result<()> {
+        if self.read_only.load(std::sync::atomic::Ordering::SeqCst) {
+            anyhow::bail!("memory store is read-only");
+        }
+        self.tokens.lock().unwrap().insert(provider.to_string(), tokens.clone());
+        Ok(())
+    }
+
+    fn delete_oauth_tokens(&self, provider: &str) -> Result<()> {
+        self.tokens.lock().unwrap().remove(provider);
+        Ok(())
+    }
+
+    fn describe(&self) -> String {
+        "memory".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(access: &str) -> OAuthTokens {
+        OAuthTokens {
+            access_token: access.into(),
+            refresh_token: Some("refresh-secret".into()),
+            expires_at: Some(1_900_000_000),
+        }
+    }
+
+    #[test]
+    fn test_file_store_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("thanos-tokens-{}", std::process::id()));
+        let path = dir.join("nested").join("tokens.enc");
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let store = FileTokenStore::new(&path, "correct horse".into());
+        assert!(store.get_oauth_tokens("anthropic_max").unwrap().is_none());
+        store.store_oauth_tokens("anthropic_max", &tokens("access-one")).unwrap();
+        store.store_oauth_tokens("github_copilot", &tokens("access-two")).unwrap();
+
+        // Encrypted at rest, owner-only
+        let data = std::fs::read(&path).unwrap();
+        assert!(data.starts_with(MAGIC));
+        assert!(!String::from_utf8_lossy(&data).contains("secret"));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+
+        // A second store (another process) reads it back with the passphrase
+        let reopened = FileTokenStore::new(&path, "correct horse".into());
+        let read = reopened.get_oauth_tokens("anthropic_max").unwrap().unwrap();
+        assert_eq!(read.access_token.expose(), "access-one");
+        assert_eq!(read.refresh_token.unwrap().expose(), "refresh-secret");
+
+        reopened.delete_oauth_tokens("anthropic_max").unwrap();
+        assert!(!store.has_tokens("anthropic_max"));
+        assert!(store.has_tokens("github_copilot"));
+
+        let error = FileTokenStore::new(&path, "wrong".into()).get_oauth_tokens("github_copilot").unwrap_err();
+        assert!(error.to_string().contains("wrong passphrase"), "{}", error);
+    }
+
+    #[test]
+    fn test_store_from_config() {
+        let mut oauth = OAuthConfig { token_store: "file".to_string(), ..Default::default() };
+        assert!(from_config(&oauth).is_err(), "file store needs a passphrase or key file");
+
+        oauth.token_passphrase = Some("passphrase".into());
+        oauth.token_file = Some("/tmp/thanos-tokens.enc".to_string());
+        assert_eq!(from_config(&oauth).unwrap().describe(), "encrypted token file /tmp/thanos-tokens.enc");
+
+        oauth.token_store = "vault".to_string();
+        assert!(from_config(&oauth).is_err());
+    }
+}
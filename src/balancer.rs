@@ -0,0 +1,203 @@
+/// Load balancing over the `routing.load_balance` list
+///
+/// Entries are `"name"` or `"name:weight"`. Two policies are available:
+/// smooth weighted round-robin (nginx-style: interleaves picks instead of
+/// sending bursts to the heaviest provider) and least outstanding requests
+/// (fewest in-flight calls relative to weight).
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Parse a `load_balance` entry into provider name and weight (default 1)
+pub fn parse_entry(entry: &str) -> Result<(String, u32)> {
+    let (name, weight) = match entry.rsplit_once(':') {
+        Some((name, weight)) => {
+            let weight: u32 = weight
+                .trim()
+                .parse()
+                .map_err(|_| anyhow!("Invalid weight in load_balance entry '{}'", entry))?;
+            (name.trim(), weight)
+        }
+        None => (entry.trim(), 1),
+    };
+
+    if name.is_empty() {
+        return Err(anyhow!("Missing provider name in load_balance entry '{}'", entry));
+    }
+    if weight == 0 {
+        return Err(anyhow!("Weight must be at least 1 in load_balance entry '{}'", entry));
+    }
+
+    Ok((name.to_string(), weight))
+}
+
+/// Shared balancing state for a router
+pub struct LoadBalancer {
+    current_weights: Mutex<HashMap<String, i64>>,
+    outstanding: Arc<Mutex<HashMap<String, usize>>>,
+}
+
+impl LoadBalancer {
+    pub fn new() -> Self {
+        Self {
+            current_weights: Mutex::new(HashMap::new()),
+            outstanding: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Smooth weighted round-robin: index of the next pick in `candidates`
+    ///
+    /// Every candidate gains its weight, the largest running total wins and
+    /// pays back the sum of all weights. Over `sum` picks each candidate is
+    /// chosen exactly `weight` times, spread out as evenly as possible.
+    pub fn pick_weighted(&self, candidates: &[(String, u32)]) -> Option<usize> {
+        if candidates.is_empty() {
+            return None;
+        }
+
+        let mut current = self.current_weights.lock().unwrap();
+        let total: i64 = candidates.iter().map(|(_, weight)| *weight as i64).sum();
+
+        let mut best = 0;
+        let mut best_weight = i64::MIN;
+        for (index, (name, weight)) in candidates.iter().enumerate() {
+            let entry = current.entry(name.clone()).or_insert(0);
+            *entry += *weight as i64;
+            if *entry > best_weight {
+                best = index;
+                best_weight = *entry;
+            }
+        }
+
+        *current.get_mut(&candidates[best].0).unwrap() -= total;
+        Some(best)
+    }
+
+    /// Candidate with the fewest in-flight requests per unit of weight
+    ///
+    /// Ties go to the earlier entry.
+    pub fn pick_least_outstanding(&self, candidates: &[(String, u32)]) -> Option<usize> {
+        let outstanding = self.outstanding.lock().unwrap();
+
+        candidates
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| {
+                let load = |(name, weight): &(String, u32)| {
+                    *outstanding.get(name).unwrap_or(&0) as f64 / *weight as f64
+                };
+                load(a).total_cmp(&load(b))
+            })
+            .map(|(index, _)| index)
+    }
+
+    /// Count a request as in flight until the returned guard is dropped
+    pub fn start(&self, provider: &str) -> OutstandingGuard {
+        *self.outstanding.lock().unwrap().entry(provider.to_string()).or_insert(0) += 1;
+
+        OutstandingGuard {
+            outstanding: self.outstanding.clone(),
+            provider: provider.to_string(),
+        }
+    }
+
+    /// Requests currently in flight to a provider
+    pub fn outstanding(&self, provider: &str) -> usize {
+        *self.outstanding.lock().unwrap().get(provider).unwrap_or(&0)
+    }
+}
+
+impl Default for LoadBalancer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// In-flight marker from [`LoadBalancer::start`]
+pub struct OutstandingGuard {
+    outstanding: Arc<Mutex<HashMap<String, usize>>>,
+    provider: String,
+}
+
+impl Drop for OutstandingGuard {
+    fn drop(&mut self) {
+        if let Some(count) = self.outstanding.lock().unwrap().get_mut(&self.provider) {
+            *count = count.saturating_sub(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn weighted(entries: &[(&str, u32)]) -> Vec<(String, u32)> {
+        entries.iter().map(|(name, weight)| (name.to_string(), *weight)).collect()
+    }
+
+    #[test]
+    fn test_parse_entry() {
+        assert_eq!(parse_entry("anthropic").unwrap(), ("anthropic".to_string(), 1));
+        assert_eq!(parse_entry("anthropic:3").unwrap(), ("anthropic".to_string(), 3));
+        assert_eq!(parse_entry(" openai : 2 ").unwrap(), ("openai".to_string(), 2));
+        assert!(parse_entry("anthropic:0").is_err());
+        assert!(parse_entry("anthropic:x").is_err());
+        assert!(parse_entry(":2").is_err());
+    }
+
+    #[test]
+    fn test_smooth_weighted_sequence() {
+        let balancer = LoadBalancer::new();
+        let candidates = weighted(&[("a", 5), ("b", 1), ("c", 1)]);
+
+        let picks: String = (0..7)
+            .map(|_| candidates[balancer.pick_weighted(&candidates).unwrap()].0.clone())
+            .collect();
+
+        // nginx's reference sequence for weights 5/1/1
+        assert_eq!(picks, "aabacaa");
+    }
+
+    #[test]
+    fn test_weighted_distribution() {
+        let balancer = LoadBalancer::new();
+        let candidates = weighted(&[("anthropic", 3), ("openai", 1)]);
+        let mut counts = [0; 2];
+
+        for _ in 0..100 {
+            counts[balancer.pick_weighted(&candidates).unwrap()] += 1;
+        }
+
+        assert_eq!(counts, [75, 25]);
+    }
+
+    #[test]
+    fn test_least_outstanding() {
+        let balancer = LoadBalancer::new();
+        let candidates = weighted(&[("a", 1), ("b", 1)]);
+
+        assert_eq!(balancer.pick_least_outstanding(&candidates), Some(0));
+
+        let guard = balancer.start("a");
+        assert_eq!(balancer.outstanding("a"), 1);
+        assert_eq!(balancer.pick_least_outstanding(&candidates), Some(1));
+
+        drop(guard);
+        assert_eq!(balancer.outstanding("a"), 0);
+        assert_eq!(balancer.pick_least_outstanding(&candidates), Some(0));
+    }
+
+    #[test]
+    fn test_least_outstanding_respects_weight() {
+        let balancer = LoadBalancer::new();
+        let candidates = weighted(&[("a", 3), ("b", 1)]);
+
+        // a can carry three times the load before b is preferred
+        let mut guards: Vec<_> = (0..2).map(|_| balancer.start("a")).collect();
+        guards.push(balancer.start("b"));
+        assert_eq!(balancer.pick_least_outstanding(&candidates), Some(0));
+
+        guards.extend((0..2).map(|_| balancer.start("a")));
+        assert_eq!(balancer.pick_least_outstanding(&candidates), Some(1));
+    }
+}
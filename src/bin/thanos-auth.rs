@@ -1,9 +1,10 @@
-/// Thanos OAuth CLI Tool
-/// Usage:
-///   cargo run --bin thanos-auth-claude
-///   cargo run --bin thanos-auth-copilot
+//! Thanos OAuth CLI Tool
+//! Usage:
+//!   cargo run --bin thanos-auth-claude
+//!   cargo run --bin thanos-auth-copilot
 
-use thanos::auth::{AnthropicOAuth, GitHubOAuth, KeyringStore, OAuthTokens};
+use thanos::auth::{token_store, AnthropicOAuth, GitHubOAuth, OAuthTokens};
+use thanos::config::{Config, OAuthConfig, Sources};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -23,7 +24,10 @@ async fn main() -> anyhow::Result<()> {
         std::process::exit(1);
     };
 
-    let keyring = KeyringStore::new("thanos");
+    // The token store from [oauth], so the server finds the tokens
+    let sources = Sources::discover(None);
+    let oauth = if sources.files.is_empty() { OAuthConfig::default() } else { Config::load_sources(&sources)?.oauth };
+    let store = token_store::from_config(&oauth)?;
 
     match command {
         "github" => {
@@ -32,16 +36,16 @@ async fn main() -> anyhow::Result<()> {
             let oauth = GitHubOAuth::new();
             let (github_token, copilot_token, expires_at) = oauth.authorize().await?;
 
-            // Store tokens in keyring
+            // Store tokens
             let tokens = OAuthTokens {
                 access_token: copilot_token,
                 refresh_token: Some(github_token),
                 expires_at: Some(expires_at),
             };
 
-            keyring.store_oauth_tokens("github_copilot", &tokens)?;
+            store.store_oauth_tokens("github_copilot", &tokens)?;
 
-            println!("✅ Tokens stored in system keyring!");
+            println!("✅ Tokens stored in {}!", store.describe());
             println!("   Provider: github_copilot");
             println!("   Expires: {}", chrono::DateTime::from_timestamp(expires_at, 0).unwrap());
         }
@@ -55,16 +59,16 @@ async fn main() -> anyhow::Result<()> {
             // Calculate expiry timestamp
             let expires_at = chrono::Utc::now().timestamp() + token_response.expires_in as i64;
 
-            // Store tokens in keyring
+            // Store tokens
             let tokens = OAuthTokens {
                 access_token: token_response.access_token,
                 refresh_token: Some(token_response.refresh_token),
                 expires_at: Some(expires_at),
             };
 
-            keyring.store_oauth_tokens("anthropic_max", &tokens)?;
+            store.store_oauth_tokens("anthropic_max", &tokens)?;
 
-            println!("✅ Tokens stored in system keyring!");
+            println!("✅ Tokens stored in {}!", store.describe());
             println!("   Provider: anthropic_max");
             println!("   Expires: {}", chrono::DateTime::from_timestamp(expires_at, 0).unwrap());
         }
@@ -78,7 +82,7 @@ async fn main() -> anyhow::Result<()> {
             ];
 
             for (id, name) in providers {
-                if let Ok(Some(tokens)) = keyring.get_oauth_tokens(id) {
+                if let Ok(Some(tokens)) = store.get_oauth_tokens(id) {
                     println!("✅ {}", name);
                     println!("   Provider ID: {}", id);
                     if let Some(expires_at) = tokens.expires_at {
@@ -103,10 +107,10 @@ async fn main() -> anyhow::Result<()> {
         "clear" => {
             println!("Clearing all stored tokens...\n");
 
-            keyring.delete_oauth_tokens("github_copilot")?;
-            keyring.delete_oauth_tokens("anthropic_max")?;
+            store.delete_oauth_tokens("github_copilot")?;
+            store.delete_oauth_tokens("anthropic_max")?;
 
-            println!("✅ All tokens cleared from keyring");
+            println!("✅ All tokens cleared from {}", store.describe());
         }
 
         _ => {
@@ -56,14 +56,13 @@ impl ResponseCache {
         let mut entries = self.entries.lock().unwrap();
 
         // Evict if at capacity (simple LRU: remove oldest by creation time)
-        if entries.len() >= self.max_size && !entries.contains_key(&key) {
-            if let Some(oldest_key) = entries
+        if entries.len() >= self.max_size && !entries.contains_key(&key)
+            && let Some(oldest_key) = entries
                 .iter()
                 .min_by_key(|(_, entry)| entry.created_at)
                 .map(|(k, _)| k.clone())
-            {
-                entries.remove(&oldest_key);
-            }
+        {
+            entries.remove(&oldest_key);
         }
 
         entries.insert(key, CacheEntry {
@@ -1,20 +1,42 @@
 /// Circuit breaker implementation for provider resilience
-use std::collections::HashMap;
+///
+/// A circuit opens after `failure_threshold` consecutive failures, or when the
+/// share of failed attempts within `window_secs` reaches `failure_rate` (once
+/// `min_requests` attempts were seen). After `open_secs` it goes half-open and
+/// lets a single probe through at a time; `success_threshold` successful
+/// probes close it again, a failed probe reopens it.
+use crate::config::{CircuitBreakerConfig, Config};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+/// Weight of the newest outcome in the smoothed error rate
+const ERROR_RATE_ALPHA: f64 = 0.2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum CircuitState {
     Closed,     // Normal operation
     Open,       // Failing, reject requests
     HalfOpen,   // Testing if service recovered
 }
 
+impl CircuitState {
+    /// Value of the `thanos_circuit_breaker_state` gauge
+    fn metric(self) -> f64 {
+        match self {
+            CircuitState::Closed => 0.0,
+            CircuitState::Open => 1.0,
+            CircuitState::HalfOpen => 2.0,
+        }
+    }
+}
+
 pub struct CircuitBreaker {
     providers: Arc<Mutex<HashMap<String, ProviderCircuit>>>,
-    failure_threshold: u32,
-    success_threshold: u32,
-    timeout: Duration,
+    defaults: CircuitBreakerConfig,
+    overrides: HashMap<String, CircuitBreakerConfig>,
 }
 
 struct ProviderCircuit {
@@ -23,112 +45,477 @@ struct ProviderCircuit {
     successes: u32,
     last_failure_time: Option<Instant>,
     next_attempt: Option<Instant>,
+    /// Half-open probe currently in flight, and when it was let through
+    probe_started: Option<Instant>,
+    /// Opened by an operator; stays open until reset
+    forced: bool,
+    /// Outcomes within the failure-rate window (true = failure)
+    window: VecDeque<(Instant, bool)>,
+    /// Exponentially weighted share of recent attempts that failed
+    error_rate: f64,
 }
 
-impl CircuitBreaker {
-    pub fn new(failure_threshold: u32, success_threshold: u32, timeout_secs: u64) -> Self {
-        Self {
-            providers: Arc::new(Mutex::new(HashMap::new())),
-            failure_threshold,
-            success_threshold,
-            timeout: Duration::from_secs(timeout_secs),
-        }
-    }
+/// Snapshot of one provider's circuit for the admin API
+#[derive(Debug, Clone, Serialize)]
+pub struct CircuitSnapshot {
+    pub provider: String,
+    pub state: CircuitState,
+    pub forced: bool,
+    pub consecutive_failures: u32,
+    /// Attempts and failures within the failure-rate window
+    pub window_requests: usize,
+    pub window_failures: usize,
+    pub error_rate: f64,
+    /// Seconds until an open circuit lets a probe through
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_in_secs: Option<u64>,
+    /// Seconds since the last counted failure
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_failure_secs: Option<u64>,
+}
 
-    pub fn can_attempt(&self, provider: &str) -> bool {
-        let mut providers = self.providers.lock().unwrap();
-        let circuit = providers.entry(provider.to_string()).or_insert_with(|| ProviderCircuit {
+impl ProviderCircuit {
+    fn new() -> Self {
+        Self {
             state: CircuitState::Closed,
             failures: 0,
             successes: 0,
             last_failure_time: None,
             next_attempt: None,
+            probe_started: None,
+            forced: false,
+            window: VecDeque::new(),
+            error_rate: 0.0,
+        }
+    }
+
+    fn record(&mut self, now: Instant, failed: bool, window: Duration) {
+        self.window.push_back((now, failed));
+        while self.window.front().is_some_and(|(at, _)| now.duration_since(*at) > window) {
+            self.window.pop_front();
+        }
+    }
+
+    fn window_failures(&self) -> usize {
+        self.window.iter().filter(|(_, failed)| *failed).count()
+    }
+}
+
+impl CircuitBreaker {
+    /// Breaker with the same settings for every provider
+    pub fn new(config: CircuitBreakerConfig) -> Self {
+        Self {
+            providers: Arc::new(Mutex::new(HashMap::new())),
+            defaults: config,
+            overrides: HashMap::new(),
+        }
+    }
+
+    /// `routing.circuit_breaker`, with `[providers.<name>.circuit_breaker]` overrides
+    pub fn from_config(config: &Config) -> Self {
+        let overrides = config.providers
+            .iter()
+            .filter_map(|(name, provider)| Some((name.clone(), provider.circuit_breaker.clone()?)))
+            .collect();
+
+        Self {
+            overrides,
+            ..Self::new(config.routing.circuit_breaker.clone())
+        }
+    }
+
+    /// Breaker with a reloaded config's settings that keeps the circuits of
+    /// providers still configured
+    pub fn reconfigured(&self, config: &Config) -> Self {
+        self.providers.lock().unwrap().retain(|provider, _| {
+            let keep = config.providers.contains_key(provider);
+            if !keep {
+                let _ = crate::metrics::METRICS.circuit_breaker_state.remove_label_values(&[provider]);
+            }
+            keep
         });
 
+        Self {
+            providers: Arc::clone(&self.providers),
+            ..Self::from_config(config)
+        }
+    }
+
+    /// Settings in effect for a provider
+    pub fn settings(&self, provider: &str) -> &CircuitBreakerConfig {
+        self.overrides.get(provider).unwrap_or(&self.defaults)
+    }
+
+    pub fn can_attempt(&self, provider: &str) -> bool {
+        let settings = self.settings(provider);
+        if !settings.enabled {
+            return true;
+        }
+
+        let mut providers = self.providers.lock().unwrap();
+        let circuit = providers.entry(provider.to_string()).or_insert_with(ProviderCircuit::new);
         let now = Instant::now();
+        let open_for = Duration::from_secs(settings.open_secs);
 
         match circuit.state {
             CircuitState::Closed => true,
+            CircuitState::Open if circuit.forced => false,
             CircuitState::Open => {
-                if let Some(next_attempt) = circuit.next_attempt {
-                    if now >= next_attempt {
-                        circuit.state = CircuitState::HalfOpen;
-                        circuit.successes = 0;
-                        true
-                    } else {
-                        false
-                    }
+                if circuit.next_attempt.is_some_and(|next| now >= next) {
+                    set_state(provider, circuit, CircuitState::HalfOpen);
+                    circuit.successes = 0;
+                    circuit.probe_started = Some(now);
+                    true
                 } else {
                     false
                 }
             }
-            CircuitState::HalfOpen => true,
+            CircuitState::HalfOpen => {
+                // One probe at a time; a probe that never reported back (e.g.
+                // cancelled) stops blocking after another open period
+                if circuit.probe_started.is_some_and(|started| now.duration_since(started) < open_for) {
+                    false
+                } else {
+                    circuit.probe_started = Some(now);
+                    true
+                }
+            }
         }
     }
 
     pub fn record_success(&self, provider: &str) {
+        let settings = self.settings(provider);
         let mut providers = self.providers.lock().unwrap();
-        if let Some(circuit) = providers.get_mut(provider) {
-            match circuit.state {
-                CircuitState::Closed => {
+        let circuit = providers.entry(provider.to_string()).or_insert_with(ProviderCircuit::new);
+
+        circuit.error_rate *= 1.0 - ERROR_RATE_ALPHA;
+        circuit.record(Instant::now(), false, Duration::from_secs(settings.window_secs));
+
+        match circuit.state {
+            CircuitState::Closed => {
+                circuit.failures = 0;
+            }
+            CircuitState::HalfOpen => {
+                circuit.probe_started = None;
+                circuit.successes += 1;
+                if circuit.successes >= settings.success_threshold {
+                    set_state(provider, circuit, CircuitState::Closed);
                     circuit.failures = 0;
+                    circuit.successes = 0;
+                    circuit.window.clear();
                 }
-                CircuitState::HalfOpen => {
-                    circuit.successes += 1;
-                    if circuit.successes >= self.success_threshold {
-                        circuit.state = CircuitState::Closed;
-                        circuit.failures = 0;
-                        circuit.successes = 0;
-                    }
-                }
-                CircuitState::Open => {}
             }
+            CircuitState::Open => {}
         }
     }
 
     pub fn record_failure(&self, provider: &str) {
+        let settings = self.settings(provider);
         let mut providers = self.providers.lock().unwrap();
-        let circuit = providers.entry(provider.to_string()).or_insert_with(|| ProviderCircuit {
-            state: CircuitState::Closed,
-            failures: 0,
-            successes: 0,
-            last_failure_time: None,
-            next_attempt: None,
-        });
+        let circuit = providers.entry(provider.to_string()).or_insert_with(ProviderCircuit::new);
 
         let now = Instant::now();
         circuit.last_failure_time = Some(now);
+        circuit.error_rate = circuit.error_rate * (1.0 - ERROR_RATE_ALPHA) + ERROR_RATE_ALPHA;
+        circuit.record(now, true, Duration::from_secs(settings.window_secs));
 
-        match circuit.state {
+        let trip = match circuit.state {
             CircuitState::Closed => {
                 circuit.failures += 1;
-                if circuit.failures >= self.failure_threshold {
-                    circuit.state = CircuitState::Open;
-                    circuit.next_attempt = Some(now + self.timeout);
-
-                    // Record in metrics
-                    crate::metrics::METRICS.circuit_breaker_state
-                        .with_label_values(&[provider])
-                        .set(1.0); // Open
-                    crate::metrics::METRICS.circuit_breaker_failures
-                        .with_label_values(&[provider])
-                        .inc();
-                }
+                let requests = circuit.window.len();
+                let rate = circuit.window_failures() as f64 / requests as f64;
+
+                circuit.failures >= settings.failure_threshold
+                    || (requests >= settings.min_requests as usize && rate >= settings.failure_rate)
             }
             CircuitState::HalfOpen => {
-                circuit.state = CircuitState::Open;
-                circuit.next_attempt = Some(now + self.timeout);
-
-                crate::metrics::METRICS.circuit_breaker_state
-                    .with_label_values(&[provider])
-                    .set(1.0); // Open
+                circuit.probe_started = None;
+                true
             }
-            CircuitState::Open => {}
+            CircuitState::Open => false,
+        };
+
+        if trip && settings.enabled {
+            set_state(provider, circuit, CircuitState::Open);
+            circuit.next_attempt = Some(now + Duration::from_secs(settings.open_secs));
+
+            crate::metrics::METRICS.circuit_breaker_failures
+                .with_label_values(&[provider])
+                .inc();
+        }
+    }
+
+    /// Open a circuit now (e.g. the provider failed its health checks); it
+    /// recovers through the usual half-open probing
+    pub fn trip(&self, provider: &str) {
+        let settings = self.settings(provider);
+        if !settings.enabled {
+            return;
+        }
+
+        let mut providers = self.providers.lock().unwrap();
+        let circuit = providers.entry(provider.to_string()).or_insert_with(ProviderCircuit::new);
+        if circuit.state != CircuitState::Open {
+            set_state(provider, circuit, CircuitState::Open);
+            circuit.next_attempt = Some(Instant::now() + Duration::from_secs(settings.open_secs));
+            circuit.probe_started = None;
         }
     }
 
+    /// The provider passed a health check: an open circuit (unless forced)
+    /// skips the rest of its cooldown and starts probing
+    pub fn recovered(&self, provider: &str) {
+        let mut providers = self.providers.lock().unwrap();
+        if let Some(circuit) = providers.get_mut(provider)
+            && circuit.state == CircuitState::Open
+            && !circuit.forced
+        {
+            set_state(provider, circuit, CircuitState::HalfOpen);
+            circuit.successes = 0;
+            circuit.probe_started = None;
+        }
+    }
+
+    /// An attempt that says nothing about provider health (e.g. a rejected
+    /// request) finished; frees the half-open probe slot
+    pub fn record_ignored(&self, provider: &str) {
+        let mut providers = self.providers.lock().unwrap();
+        if let Some(circuit) = providers.get_mut(provider) {
+            circuit.probe_started = None;
+        }
+    }
+
+    /// Whether a request would be let through now or once its probe slot frees
+    /// up: closed, half-open, or open past its cooldown (unless forced).
+    /// Unlike `can_attempt` this changes nothing.
+    pub fn is_available(&self, provider: &str) -> bool {
+        let providers = self.providers.lock().unwrap();
+        providers.get(provider).is_none_or(|circuit| match circuit.state {
+            CircuitState::Closed | CircuitState::HalfOpen => true,
+            CircuitState::Open => {
+                !circuit.forced && circuit.next_attempt.is_some_and(|next| Instant::now() >= next)
+            }
+        })
+    }
+
     pub fn get_state(&self, provider: &str) -> CircuitState {
         let providers = self.providers.lock().unwrap();
         providers.get(provider).map(|c| c.state).unwrap_or(CircuitState::Closed)
     }
+
+    /// Recent error rate (0.0 - 1.0); an open circuit counts as fully failing
+    pub fn error_rate(&self, provider: &str) -> f64 {
+        let providers = self.providers.lock().unwrap();
+        match providers.get(provider) {
+            Some(circuit) if circuit.state == CircuitState::Open => 1.0,
+            Some(circuit) => circuit.error_rate,
+            None => 0.0,
+        }
+    }
+
+    /// Open a circuit until it is reset, regardless of provider health
+    pub fn force_open(&self, provider: &str) {
+        let mut providers = self.providers.lock().unwrap();
+        let circuit = providers.entry(provider.to_string()).or_insert_with(ProviderCircuit::new);
+
+        set_state(provider, circuit, CircuitState::Open);
+        circuit.forced = true;
+        circuit.next_attempt = None;
+        circuit.probe_started = None;
+    }
+
+    /// Close a circuit and forget its history
+    pub fn reset(&self, provider: &str) {
+        let mut providers = self.providers.lock().unwrap();
+        if let Some(circuit) = providers.get_mut(provider) {
+            *circuit = ProviderCircuit::new();
+        }
+        crate::metrics::METRICS.circuit_breaker_state
+            .with_label_values(&[provider])
+            .set(CircuitState::Closed.metric());
+    }
+
+    /// Snapshot of a provider's circuit (closed and empty if never used)
+    pub fn snapshot(&self, provider: &str) -> CircuitSnapshot {
+        let providers = self.providers.lock().unwrap();
+        let now = Instant::now();
+        let window = Duration::from_secs(self.settings(provider).window_secs);
+        let fresh = ProviderCircuit::new();
+        let circuit = providers.get(provider).unwrap_or(&fresh);
+        let recent: Vec<_> = circuit.window.iter().filter(|(at, _)| now.duration_since(*at) <= window).collect();
+
+        CircuitSnapshot {
+            provider: provider.to_string(),
+            state: circuit.state,
+            forced: circuit.forced,
+            consecutive_failures: circuit.failures,
+            window_requests: recent.len(),
+            window_failures: recent.iter().filter(|(_, failed)| *failed).count(),
+            error_rate: circuit.error_rate,
+            retry_in_secs: circuit
+                .next_attempt
+                .filter(|_| circuit.state == CircuitState::Open)
+                .map(|next| next.saturating_duration_since(now).as_secs()),
+            last_failure_secs: circuit.last_failure_time.map(|at| now.duration_since(at).as_secs()),
+        }
+    }
+}
+
+/// Change state and keep the metric in sync
+fn set_state(provider: &str, circuit: &mut ProviderCircuit, state: CircuitState) {
+    circuit.state = state;
+    if state != CircuitState::Open {
+        circuit.forced = false;
+    }
+    crate::metrics::METRICS.circuit_breaker_state
+        .with_label_values(&[provider])
+        .set(state.metric());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn breaker(open_secs: u64) -> CircuitBreaker {
+        CircuitBreaker::new(CircuitBreakerConfig {
+            failure_threshold: 3,
+            success_threshold: 2,
+            open_secs,
+            min_requests: 10,
+            failure_rate: 0.5,
+            ..Default::default()
+        })
+    }
+
+    fn gauge(provider: &str) -> f64 {
+        crate::metrics::METRICS.circuit_breaker_state
+            .with_label_values(&[provider])
+            .get()
+    }
+
+    #[test]
+    fn test_opens_after_consecutive_failures() {
+        let cb = breaker(30);
+        for _ in 0..3 {
+            assert!(cb.can_attempt("cb_consecutive"));
+            cb.record_failure("cb_consecutive");
+        }
+
+        assert_eq!(cb.get_state("cb_consecutive"), CircuitState::Open);
+        assert!(!cb.can_attempt("cb_consecutive"));
+        assert_eq!(gauge("cb_consecutive"), 1.0);
+    }
+
+    #[test]
+    fn test_opens_on_failure_rate() {
+        let cb = breaker(30);
+        // Alternating outcomes never reach 3 in a row, but half of them fail
+        for _ in 0..5 {
+            cb.record_success("cb_rate");
+            cb.record_failure("cb_rate");
+        }
+
+        assert_eq!(cb.get_state("cb_rate"), CircuitState::Open);
+    }
+
+    #[test]
+    fn test_half_open_single_probe() {
+        let cb = breaker(0);
+        for _ in 0..3 {
+            cb.record_failure("cb_probe");
+        }
+
+        // Cooldown elapsed: exactly one probe goes through
+        assert!(cb.can_attempt("cb_probe"));
+        assert_eq!(cb.get_state("cb_probe"), CircuitState::HalfOpen);
+        assert_eq!(gauge("cb_probe"), 2.0);
+
+        cb.record_success("cb_probe");
+        assert_eq!(cb.get_state("cb_probe"), CircuitState::HalfOpen);
+        cb.can_attempt("cb_probe");
+        cb.record_success("cb_probe");
+        assert_eq!(cb.get_state("cb_probe"), CircuitState::Closed);
+        assert_eq!(gauge("cb_probe"), 0.0);
+    }
+
+    #[test]
+    fn test_probe_blocks_concurrent_requests() {
+        let cb = breaker(30);
+        for _ in 0..3 {
+            cb.record_failure("cb_blocked");
+        }
+        // Pretend the cooldown already passed
+        cb.providers.lock().unwrap().get_mut("cb_blocked").unwrap().next_attempt = Some(Instant::now());
+
+        assert!(cb.can_attempt("cb_blocked"));
+        assert!(!cb.can_attempt("cb_blocked"));
+
+        // A failed probe reopens the circuit
+        cb.record_failure("cb_blocked");
+        assert_eq!(cb.get_state("cb_blocked"), CircuitState::Open);
+        assert!(!cb.can_attempt("cb_blocked"));
+    }
+
+    #[test]
+    fn test_available_after_cooldown() {
+        let cb = breaker(30);
+        for _ in 0..3 {
+            cb.record_failure("cb_available");
+        }
+        assert!(!cb.is_available("cb_available"));
+
+        // Still open until a request probes it, but no longer unavailable
+        cb.providers.lock().unwrap().get_mut("cb_available").unwrap().next_attempt = Some(Instant::now());
+        assert!(cb.is_available("cb_available"));
+        assert_eq!(cb.get_state("cb_available"), CircuitState::Open);
+
+        cb.force_open("cb_available");
+        assert!(!cb.is_available("cb_available"));
+        assert!(cb.is_available("cb_never_used"));
+    }
+
+    #[test]
+    fn test_force_open_and_reset() {
+        let cb = breaker(0);
+        cb.force_open("cb_forced");
+
+        // Forced circuits ignore the cooldown
+        assert!(!cb.can_attempt("cb_forced"));
+        assert!(cb.snapshot("cb_forced").forced);
+
+        cb.reset("cb_forced");
+        assert_eq!(cb.get_state("cb_forced"), CircuitState::Closed);
+        assert!(cb.can_attempt("cb_forced"));
+        assert_eq!(gauge("cb_forced"), 0.0);
+    }
+
+    #[test]
+    fn test_health_trip_and_recovery() {
+        let cb = breaker(30);
+        cb.trip("cb_health");
+        assert!(!cb.can_attempt("cb_health"));
+
+        // A passing health check starts probing before the cooldown ends
+        cb.recovered("cb_health");
+        assert_eq!(cb.get_state("cb_health"), CircuitState::HalfOpen);
+        assert!(cb.can_attempt("cb_health"));
+
+        // Forced circuits stay open
+        cb.force_open("cb_health");
+        cb.recovered("cb_health");
+        assert_eq!(cb.get_state("cb_health"), CircuitState::Open);
+    }
+
+    #[test]
+    fn test_disabled_breaker() {
+        let cb = CircuitBreaker::new(CircuitBreakerConfig {
+            enabled: false,
+            failure_threshold: 1,
+            ..Default::default()
+        });
+        cb.record_failure("cb_disabled");
+
+        assert!(cb.can_attempt("cb_disabled"));
+        assert_eq!(cb.get_state("cb_disabled"), CircuitState::Closed);
+    }
 }
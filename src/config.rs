@@ -1,9 +1,19 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, env, fs, path::Path};
+use std::{collections::{HashMap, HashSet}, env, fs, path::{Path, PathBuf}};
 
+use crate::secret::Secret;
 use crate::types::AuthMethod;
 
+/// System-wide config, the lowest layer
+const SYSTEM_CONFIG: &str = "/etc/thanos/config.toml";
+/// Project config files looked for in the working directory, in order
+const PROJECT_CONFIGS: &[&str] = &["./config.toml", "./thanos.toml"];
+/// `THANOS__SECTION__KEY=value` overrides a single setting
+const ENV_PREFIX: &str = "THANOS__";
+/// Settings that may hold a secret reference (`keyring:`, `file:`, `cmd:`)
+const SECRET_REFERENCE_KEYS: &[&str] = &["api_key", "token_passphrase"];
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     pub server: ServerConfig,
@@ -19,6 +29,12 @@ pub struct Config {
     pub metrics: MetricsConfig,
     #[serde(default)]
     pub oauth: OAuthConfig,
+    #[serde(default)]
+    pub tokenizer: TokenizerConfig,
+    #[serde(default)]
+    pub health_check: HealthCheckConfig,
+    #[serde(default)]
+    pub secrets: SecretsConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -33,6 +49,50 @@ pub struct ServerConfig {
     pub uds_path: Option<String>,
     #[serde(default = "default_true")]
     pub uds_enabled: bool,
+    /// Bearer token required by the `/admin` endpoints (Unix socket only if unset)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub admin_token: Option<Secret>,
+    /// TLS for the HTTP and gRPC listeners (required by HTTP/3)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls: Option<TlsConfig>,
+    #[serde(default)]
+    pub http3: Http3Config,
+    /// On shutdown, how long in-flight streams may run before being closed (seconds)
+    #[serde(default = "default_drain_timeout")]
+    pub drain_timeout_secs: u64,
+    /// How often to check the config file for changes and reload it (seconds, 0 = never)
+    #[serde(default = "default_config_watch")]
+    pub config_watch_secs: u64,
+}
+
+/// PEM-encoded certificate chain and private key, plus optional mutual TLS
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TlsConfig {
+    pub cert_path: String,
+    pub key_path: String,
+    /// CA bundle that client certificates must chain to (enables mutual TLS)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_ca_path: Option<String>,
+    /// With `client_ca_path`: refuse clients that present no certificate
+    #[serde(default = "default_true")]
+    pub require_client_cert: bool,
+    /// Client identities (certificate CN, or the full subject when there is
+    /// no CN) allowed to connect; empty allows any certificate the CA signed
+    #[serde(default)]
+    pub allowed_clients: Vec<String>,
+    /// How often to check the certificate files for changes (seconds, 0 = never)
+    #[serde(default = "default_tls_reload")]
+    pub reload_secs: u64,
+}
+
+/// HTTP/3 (QUIC) listener serving the same routes as the HTTP server
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Http3Config {
+    #[serde(default)]
+    pub enabled: bool,
+    /// UDP address; defaults to `server.bind` (same port as HTTP, over UDP)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bind: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -41,8 +101,151 @@ pub struct RoutingConfig {
     pub strategy: String,
     #[serde(default)]
     pub fallback_chain: Vec<String>,
+    /// Providers for `round-robin`, as `"name"` or `"name:weight"`
     #[serde(default)]
     pub load_balance: Vec<String>,
+    /// How `round-robin` picks from `load_balance`: "weighted" (smooth
+    /// weighted round-robin) or "least-outstanding"
+    #[serde(default = "default_balance")]
+    pub balance: String,
+    /// Overall deadline per request in seconds, including retries and fallbacks
+    /// (for streams: until the first chunk arrives)
+    #[serde(default = "default_request_timeout")]
+    pub request_timeout: u64,
+    #[serde(default)]
+    pub hedge: HedgeConfig,
+    #[serde(default)]
+    pub adaptive: AdaptiveConfig,
+    /// Defaults for every provider's circuit breaker
+    #[serde(default)]
+    pub circuit_breaker: CircuitBreakerConfig,
+    /// Evaluated in order before dispatching; the first match wins
+    #[serde(default)]
+    pub rules: Vec<RoutingRule>,
+    #[serde(default)]
+    pub context_window: ContextWindowConfig,
+}
+
+/// Checks of request size against each model's context window
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContextWindowConfig {
+    /// Skip providers whose model can't fit the estimated prompt
+    #[serde(default = "default_true")]
+    pub check: bool,
+    /// Drop or shorten middle turns (keeping system prompts and the latest
+    /// message) instead of skipping the provider
+    #[serde(default)]
+    pub fit_prompt: bool,
+    /// Lower `max_tokens` to the model's output limit and remaining context
+    #[serde(default = "default_true")]
+    pub clamp_max_tokens: bool,
+}
+
+/// A `[[routing.rules]]` entry
+///
+/// All conditions under `match` must hold. A matching rule can pin the
+/// provider, rewrite the model and/or switch the strategy; anything it leaves
+/// unset keeps its normal value.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RoutingRule {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default, rename = "match")]
+    pub conditions: RuleConditions,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strategy: Option<String>,
+}
+
+/// Conditions of a routing rule (unset conditions always match)
+///
+/// String conditions are globs (`*` and `?`).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RuleConditions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Client API key (`Authorization: Bearer` or `x-api-key`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client: Option<String>,
+    /// `x-thanos-tenant` header
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    /// Header name (case-insensitive) to glob
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub headers: HashMap<String, String>,
+    /// Prompt length in characters, system prompt included
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_prompt_chars: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_prompt_chars: Option<usize>,
+    /// Reserved: rejected until image parts and tools reach providers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub has_images: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub has_tools: Option<bool>,
+    /// UTC window `"HH:MM-HH:MM"`; may wrap midnight (`"22:00-06:00"`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time: Option<String>,
+}
+
+/// When a provider's circuit opens and how it recovers
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CircuitBreakerConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Consecutive failures that open the circuit
+    #[serde(default = "default_cb_failure_threshold")]
+    pub failure_threshold: u32,
+    /// Share of failed attempts within `window_secs` that opens the circuit
+    #[serde(default = "default_cb_failure_rate")]
+    pub failure_rate: f64,
+    /// Sliding window for `failure_rate` (seconds)
+    #[serde(default = "default_cb_window")]
+    pub window_secs: u64,
+    /// Attempts needed in the window before `failure_rate` applies
+    #[serde(default = "default_cb_min_requests")]
+    pub min_requests: u32,
+    /// Successful half-open probes (sent one at a time) that close the circuit
+    #[serde(default = "default_cb_success_threshold")]
+    pub success_threshold: u32,
+    /// How long the circuit stays open before probing (seconds)
+    #[serde(default = "default_cb_open")]
+    pub open_secs: u64,
+}
+
+/// Settings for the `hedged` strategy (and per-request `hedge: true`)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HedgeConfig {
+    /// How long to wait for a response (or first stream chunk) before
+    /// sending the same request to the next provider
+    #[serde(default = "default_hedge_delay")]
+    pub delay_ms: u64,
+    /// Extra providers that may be raced against the first one
+    #[serde(default = "default_max_hedges")]
+    pub max_hedges: usize,
+}
+
+/// Scoring for the `adaptive` strategy (lower score wins)
+///
+/// Each signal is normalized to 0.0 - 1.0 across the candidates before the
+/// weights are applied, so the weights express relative importance.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AdaptiveConfig {
+    #[serde(default = "default_latency_weight")]
+    pub latency_weight: f64,
+    #[serde(default = "default_error_weight")]
+    pub error_weight: f64,
+    #[serde(default = "default_cost_weight")]
+    pub cost_weight: f64,
+    /// Probability of trying a random candidate first so stale scores get refreshed
+    #[serde(default = "default_exploration")]
+    pub exploration: f64,
+    /// Latency samples older than this are ignored (seconds)
+    #[serde(default = "default_stale_after")]
+    pub stale_after: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -51,7 +254,7 @@ pub struct ProviderConfig {
     pub enabled: bool,
     pub auth_method: AuthMethod,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub api_key: Option<String>,
+    pub api_key: Option<Secret>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub base_url: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -64,6 +267,63 @@ pub struct ProviderConfig {
     pub temperature: Option<f32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub client_id: Option<String>,
+    /// External-process provider (see docs/PLUGINS.md)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub plugin: Option<PluginConfig>,
+    #[serde(default)]
+    pub retry: RetryConfig,
+    /// Context window in tokens, overriding models.dev (e.g. Ollama's num_ctx)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context_length: Option<u32>,
+    /// Maximum completion tokens, overriding models.dev
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_limit: Option<u32>,
+    /// Circuit breaker settings replacing `routing.circuit_breaker` for this provider
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub circuit_breaker: Option<CircuitBreakerConfig>,
+}
+
+/// Retry policy for transient failures of a single provider
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RetryConfig {
+    /// Total attempts including the first one (1 disables retries)
+    #[serde(default = "default_retry_attempts")]
+    pub max_attempts: u32,
+    #[serde(default = "default_retry_initial_backoff")]
+    pub initial_backoff_ms: u64,
+    /// Upper bound for computed backoff and for honored Retry-After values
+    #[serde(default = "default_retry_max_backoff")]
+    pub max_backoff_ms: u64,
+    #[serde(default = "default_retry_multiplier")]
+    pub multiplier: f64,
+    /// Fraction of each delay that is randomized (0.0 - 1.0)
+    #[serde(default = "default_retry_jitter")]
+    pub jitter: f64,
+    /// Error classes worth retrying (see `ProviderError::kind`)
+    #[serde(default = "default_retry_on")]
+    pub retry_on: Vec<String>,
+    /// Wait for the upstream `Retry-After` delay instead of our own backoff
+    #[serde(default = "default_true")]
+    pub respect_retry_after: bool,
+}
+
+/// Launch settings for an external-process provider plugin
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PluginConfig {
+    /// Executable to launch
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub working_dir: Option<String>,
+    /// Per-request timeout in seconds
+    #[serde(default = "default_plugin_timeout")]
+    pub request_timeout: u64,
+    /// Initial delay before restarting a crashed plugin (doubles up to 30s)
+    #[serde(default = "default_plugin_restart_backoff")]
+    pub restart_backoff_ms: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -112,10 +372,72 @@ pub struct OAuthConfig {
     pub refresh_warning_hours: u64,
     #[serde(default = "default_keyring_service")]
     pub keyring_service: String,
+    /// "keyring" (the system keyring) or "file" (encrypted `token_file`)
+    #[serde(default = "default_token_store")]
+    pub token_store: String,
+    /// Defaults to `$XDG_DATA_HOME/thanos/tokens.enc`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_file: Option<String>,
+    /// Passphrase the token file key is derived from; may be a secret
+    /// reference such as `file:/run/secrets/thanos-tokens`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_passphrase: Option<Secret>,
+    /// File whose contents are used as the passphrase instead
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_key_file: Option<String>,
+}
+
+/// Background provider health checks
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HealthCheckConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Seconds between checks of each provider
+    #[serde(default = "default_health_interval")]
+    pub interval_secs: u64,
+    #[serde(default = "default_health_timeout")]
+    pub timeout_secs: u64,
+    /// Consecutive failed checks before a provider is unhealthy and its
+    /// circuit is opened
+    #[serde(default = "default_health_unhealthy_threshold")]
+    pub unhealthy_threshold: u32,
+    /// "models" (list models, free) or "completion" (1-token completion with
+    /// the provider's configured model, which also proves the model is served)
+    #[serde(default = "default_health_probe")]
+    pub probe: String,
+}
+
+/// Resolving `keyring:`, `file:` and `cmd:` references in `api_key`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SecretsConfig {
+    /// Reuse a resolved secret for this long, across reloads (0 = look it up
+    /// on every load)
+    #[serde(default)]
+    pub cache_ttl_secs: u64,
+    /// Look secrets up again this often and switch to rotated ones (0 = only
+    /// on reload)
+    #[serde(default)]
+    pub refresh_secs: u64,
+    /// How long a `cmd:` reference may run
+    #[serde(default = "default_secret_command_timeout")]
+    pub command_timeout_secs: u64,
+}
+
+/// Token counting for `/v1/tokenize`, `/v1/count_tokens` and `CountTokens`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TokenizerConfig {
+    /// Ask the provider for an exact count where it has an endpoint for it
+    /// (Anthropic `count_tokens`, Gemini `countTokens`); falls back to the
+    /// local count when the call fails
+    #[serde(default)]
+    pub provider_count: bool,
 }
 
 // Defaults
 fn default_http_bind() -> String { "0.0.0.0:8080".to_string() }
+fn default_tls_reload() -> u64 { 60 }
+fn default_drain_timeout() -> u64 { 30 }
+fn default_config_watch() -> u64 { 5 }
 fn default_grpc_bind() -> String { "0.0.0.0:50051".to_string() }
 fn default_log_level() -> String { "info".to_string() }
 fn default_strategy() -> String { "preferred".to_string() }
@@ -128,6 +450,37 @@ fn default_rph() -> u32 { 1000 }
 fn default_prometheus_port() -> u16 { 9090 }
 fn default_refresh_warning() -> u64 { 2 }
 fn default_keyring_service() -> String { "thanos".to_string() }
+fn default_token_store() -> String { "keyring".to_string() }
+fn default_secret_command_timeout() -> u64 { 10 }
+fn default_plugin_timeout() -> u64 { 300 }
+fn default_plugin_restart_backoff() -> u64 { 500 }
+fn default_request_timeout() -> u64 { 300 }
+fn default_balance() -> String { "weighted".to_string() }
+fn default_hedge_delay() -> u64 { 500 }
+fn default_max_hedges() -> usize { 1 }
+fn default_latency_weight() -> f64 { 1.0 }
+fn default_error_weight() -> f64 { 2.0 }
+fn default_cost_weight() -> f64 { 0.5 }
+fn default_exploration() -> f64 { 0.05 }
+fn default_stale_after() -> u64 { 600 }
+fn default_cb_failure_threshold() -> u32 { 5 }
+fn default_cb_failure_rate() -> f64 { 0.5 }
+fn default_cb_window() -> u64 { 60 }
+fn default_cb_min_requests() -> u32 { 20 }
+fn default_cb_success_threshold() -> u32 { 3 }
+fn default_cb_open() -> u64 { 30 }
+fn default_health_interval() -> u64 { 60 }
+fn default_health_timeout() -> u64 { 10 }
+fn default_health_unhealthy_threshold() -> u32 { 2 }
+fn default_health_probe() -> String { "models".to_string() }
+fn default_retry_attempts() -> u32 { 3 }
+fn default_retry_initial_backoff() -> u64 { 250 }
+fn default_retry_max_backoff() -> u64 { 10_000 }
+fn default_retry_multiplier() -> f64 { 2.0 }
+fn default_retry_jitter() -> f64 { 0.2 }
+fn default_retry_on() -> Vec<String> {
+    vec!["rate_limited".to_string(), "timeout".to_string(), "unavailable".to_string()]
+}
 
 impl Default for ServerConfig {
     fn default() -> Self {
@@ -137,6 +490,11 @@ impl Default for ServerConfig {
             log_level: default_log_level(),
             uds_path: None,
             uds_enabled: true,
+            admin_token: None,
+            tls: None,
+            http3: Http3Config::default(),
+            drain_timeout_secs: default_drain_timeout(),
+            config_watch_secs: default_config_watch(),
         }
     }
 }
@@ -147,6 +505,87 @@ impl Default for RoutingConfig {
             strategy: default_strategy(),
             fallback_chain: vec![],
             load_balance: vec![],
+            balance: default_balance(),
+            request_timeout: default_request_timeout(),
+            hedge: HedgeConfig::default(),
+            adaptive: AdaptiveConfig::default(),
+            circuit_breaker: CircuitBreakerConfig::default(),
+            rules: vec![],
+            context_window: ContextWindowConfig::default(),
+        }
+    }
+}
+
+impl Default for ContextWindowConfig {
+    fn default() -> Self {
+        Self {
+            check: true,
+            fit_prompt: false,
+            clamp_max_tokens: true,
+        }
+    }
+}
+
+impl CircuitBreakerConfig {
+    fn validate(&self) -> Result<()> {
+        if self.failure_threshold == 0 || self.success_threshold == 0 {
+            return Err(anyhow::anyhow!("failure_threshold and success_threshold must be at least 1"));
+        }
+        if !(self.failure_rate > 0.0 && self.failure_rate <= 1.0) {
+            return Err(anyhow::anyhow!("failure_rate must be in (0, 1], got {}", self.failure_rate));
+        }
+        if self.window_secs == 0 {
+            return Err(anyhow::anyhow!("window_secs must be at least 1"));
+        }
+        Ok(())
+    }
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            failure_threshold: default_cb_failure_threshold(),
+            failure_rate: default_cb_failure_rate(),
+            window_secs: default_cb_window(),
+            min_requests: default_cb_min_requests(),
+            success_threshold: default_cb_success_threshold(),
+            open_secs: default_cb_open(),
+        }
+    }
+}
+
+impl Default for HedgeConfig {
+    fn default() -> Self {
+        Self {
+            delay_ms: default_hedge_delay(),
+            max_hedges: default_max_hedges(),
+        }
+    }
+}
+
+impl Default for AdaptiveConfig {
+    fn default() -> Self {
+        Self {
+            latency_weight: default_latency_weight(),
+            error_weight: default_error_weight(),
+            cost_weight: default_cost_weight(),
+            exploration: default_exploration(),
+            stale_after: default_stale_after(),
+        }
+    }
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: default_retry_attempts(),
+            initial_backoff_ms: default_retry_initial_backoff(),
+            max_backoff_ms: default_retry_max_backoff(),
+            multiplier: default_retry_multiplier(),
+            jitter: default_retry_jitter(),
+            retry_on: default_retry_on(),
+            respect_retry_after: true,
         }
     }
 }
@@ -181,6 +620,18 @@ impl Default for RateLimitingConfig {
     }
 }
 
+impl Default for HealthCheckConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_secs: default_health_interval(),
+            timeout_secs: default_health_timeout(),
+            unhealthy_threshold: default_health_unhealthy_threshold(),
+            probe: default_health_probe(),
+        }
+    }
+}
+
 impl Default for MetricsConfig {
     fn default() -> Self {
         Self {
@@ -190,73 +641,338 @@ impl Default for MetricsConfig {
     }
 }
 
+impl Default for SecretsConfig {
+    fn default() -> Self {
+        Self {
+            cache_ttl_secs: 0,
+            refresh_secs: 0,
+            command_timeout_secs: default_secret_command_timeout(),
+        }
+    }
+}
+
 impl Default for OAuthConfig {
     fn default() -> Self {
         Self {
             auto_refresh: true,
             refresh_warning_hours: default_refresh_warning(),
             keyring_service: default_keyring_service(),
+            token_store: default_token_store(),
+            token_file: None,
+            token_passphrase: None,
+            token_key_file: None,
         }
     }
 }
 
 impl Config {
-    /// Load configuration from file and environment
+    /// Load configuration from the system, user and project files, the
+    /// profile in `THANOS_PROFILE` and `THANOS__SECTION__KEY` overrides
     pub fn load() -> Result<Self> {
         // Load .env file if it exists
         dotenvy::dotenv().ok();
 
-        // Try to find config file
-        let config_path = env::var("THANOS_CONFIG")
-            .unwrap_or_else(|_| {
-                // Check common locations
-                let home_config = format!("{}/.config/thanos/config.toml", env::var("HOME").unwrap_or_default());
-                let locations = vec![
-                    "./config.toml",
-                    "./thanos.toml",
-                    "/etc/thanos/config.toml",
-                    home_config.as_str(),
-                ];
-
-                for path in locations {
-                    if Path::new(path).exists() {
-                        return path.to_string();
+        Self::load_sources(&Sources::discover(None))
+    }
+
+    /// Project config file: `THANOS_CONFIG`, or `config.toml` / `thanos.toml`
+    /// in the working directory
+    pub fn path() -> String {
+        env::var("THANOS_CONFIG").unwrap_or_else(|_| {
+            PROJECT_CONFIGS
+                .iter()
+                .find(|path| Path::new(path).exists())
+                .unwrap_or(&PROJECT_CONFIGS[0])
+                .to_string()
+        })
+    }
+
+    /// Read, parse and validate a config file
+    ///
+    /// Warnings are logged; any error fails the load, listing every problem found.
+    pub fn load_from(config_path: &str) -> Result<Self> {
+        Self::load_sources(&Sources::file(config_path))
+    }
+
+    /// Merge, parse and validate every layer of a config
+    pub fn load_sources(sources: &Sources) -> Result<Self> {
+        let (config, problems) = Self::check(sources)?;
+        Self::enforce(&sources.label(), &problems)?;
+        Ok(config)
+    }
+
+    /// Parse a config file and collect every problem in it, with line numbers
+    ///
+    /// Only an unreadable or unparsable file is an `Err`. API key providers
+    /// without a key come back disabled.
+    pub fn check_file(config_path: &str) -> Result<(Self, Vec<Problem>)> {
+        Self::check(&Sources::file(config_path))
+    }
+
+    /// Merge every layer of a config and collect every problem in it
+    ///
+    /// Each problem names the file and line, environment variable or flag
+    /// that set the offending value.
+    pub fn check(sources: &Sources) -> Result<(Self, Vec<Problem>)> {
+        let Layers { merged, files: layers, overrides, refused, mut problems } = Self::layers(sources)?;
+
+        // Parse TOML; a lone file keeps toml's line numbers in type errors
+        let mut config: Config = if let [(source, raw)] = layers.as_slice()
+            && sources.profile.is_none()
+            && overrides.is_empty()
+        {
+            toml::from_str(&Self::substitute_env_vars(raw))
+                .with_context(|| format!("Failed to parse config file: {}", source))?
+        } else {
+            toml::Value::Table(merged)
+                .try_into()
+                .with_context(|| format!("Failed to parse config from {}", sources.label()))?
+        };
+
+        let resolved = config.resolve_secrets(&refused);
+
+        // Locate problems in the files as written
+        let documents: Vec<_> = layers
+            .iter()
+            .map(|(source, raw)| (source, raw, toml_edit::ImDocument::parse(raw.as_str()).ok()))
+            .collect();
+        for mut problem in resolved.into_iter().chain(config.problems()) {
+            if let Some((origin, _)) = overrides.iter().rev().find(|(_, key)| covers(key, &problem.key)) {
+                problem.source = Some(origin.clone());
+                problems.push(problem);
+                continue;
+            }
+
+            // The deepest match wins; on a tie, the layer applied last
+            let mut best: Option<(usize, &String, usize)> = None;
+            for (source, raw, document) in &documents {
+                let Some(document) = document else { continue };
+                let mut found = vec![locate(document, raw, &problem.key)];
+                if let Some(name) = &sources.profile {
+                    let in_profile = locate(document, raw, &format!("profile.{}.{}", name, problem.key));
+                    found.push(in_profile.filter(|(_, depth)| *depth > 2).map(|(line, depth)| (line, depth - 2)));
+                }
+                for (line, depth) in found.into_iter().flatten() {
+                    if best.is_none_or(|(_, _, best_depth)| depth >= best_depth) {
+                        best = Some((line, *source, depth));
                     }
                 }
+            }
 
-                // Default
-                "./config.toml".to_string()
-            });
+            match best {
+                Some((line, source, _)) => {
+                    problem.line = Some(line);
+                    problem.source = Some(source.clone());
+                }
+                None => problem.source = layers.last().map(|(source, _)| source.clone()),
+            }
+            problems.push(problem);
+        }
 
-        // Validate config file permissions on Unix systems
-        #[cfg(unix)]
-        Self::validate_file_permissions(&config_path)?;
+        config.disable_providers_without_keys();
+        Ok((config, problems))
+    }
+
+    /// `[oauth]` from every layer of a config, for `thanos auth`
+    ///
+    /// Nothing else is validated and provider secrets are left unresolved,
+    /// so a key can be stored before the config pointing at it works.
+    pub fn load_oauth(sources: &Sources) -> Result<OAuthConfig> {
+        let layers = Self::layers(sources)?;
+        let config: Config = toml::Value::Table(layers.merged)
+            .try_into()
+            .with_context(|| format!("Failed to parse config from {}", sources.label()))?;
+
+        // Only the file store needs its passphrase
+        let mut oauth = config.oauth.clone();
+        if oauth.token_store == "file"
+            && let Some(reference) = &oauth.token_passphrase
+        {
+            anyhow::ensure!(
+                !layers.refused.contains(reference.expose()),
+                "oauth.token_passphrase: cmd: secrets are not run from a config file found in the working directory"
+            );
+            if let Some(secret) = crate::auth::SECRETS.resolve(reference.expose(), &config) {
+                oauth.token_passphrase = Some(secret.context("oauth.token_passphrase")?);
+            }
+        }
+        Ok(oauth)
+    }
+
+    /// Read and merge the files, profile and overrides of a config
+    fn layers(sources: &Sources) -> Result<Layers> {
+        if sources.files.is_empty() {
+            anyhow::bail!(
+                "No config file found (looked for {}, {} and {})",
+                SYSTEM_CONFIG,
+                user_config().map(|path| path.display().to_string()).unwrap_or_else(|| "~/.config/thanos/config.toml".to_string()),
+                PROJECT_CONFIGS.join(" / ")
+            );
+        }
 
-        let config_content = fs::read_to_string(&config_path)
-            .with_context(|| format!("Failed to read config file: {}", config_path))?;
+        let mut problems = Vec::new();
+        let mut layers = Vec::new();
+        let mut merged = toml::Table::new();
+        // `cmd:` references from untrusted files, which are not run
+        let mut refused = HashSet::new();
 
-        // Substitute environment variables
-        let config_content = Self::substitute_env_vars(&config_content);
+        for path in &sources.files {
+            let source = path.display().to_string();
 
-        // Parse TOML
-        let mut config: Config = toml::from_str(&config_content)
-            .with_context(|| format!("Failed to parse config file: {}", config_path))?;
+            // Validate config file permissions on Unix systems
+            #[cfg(unix)]
+            Self::validate_file_permissions(&source)?;
 
-        // Validate providers
-        config.validate_providers()?;
+            let raw = fs::read_to_string(path)
+                .with_context(|| format!("Failed to read config file: {}", source))?;
 
-        Ok(config)
+            for problem in Self::missing_env_vars(&raw) {
+                problems.push(Problem { source: Some(source.clone()), ..problem });
+            }
+
+            // Substitute environment variables
+            let table: toml::Table = toml::from_str(&Self::substitute_env_vars(&raw))
+                .with_context(|| format!("Failed to parse config file: {}", source))?;
+
+            // A checkout's config.toml must not run commands just because thanos started there
+            if sources.untrusted.iter().any(|untrusted| same_file(untrusted, path)) {
+                let document = toml_edit::ImDocument::parse(raw.as_str()).ok();
+                for (key, reference) in command_secrets(&table, "") {
+                    problems.push(Problem {
+                        source: Some(source.clone()),
+                        line: document.as_ref().and_then(|document| locate(document, &raw, &key)).map(|(line, _)| line),
+                        ..Problem::error(
+                            key,
+                            "cmd: secrets are not run from a config file found in the working directory; \
+                             move it to the user config or pass it with --config",
+                        )
+                    });
+                    refused.insert(reference);
+                }
+            }
+            merge(&mut merged, table);
+            layers.push((source, raw));
+        }
+
+        let profiles = merged.remove("profile");
+        if let Some(name) = &sources.profile {
+            let profile = profiles
+                .as_ref()
+                .and_then(|profiles| profiles.get(name))
+                .and_then(toml::Value::as_table)
+                .with_context(|| {
+                    let known: Vec<_> = profiles
+                        .as_ref()
+                        .and_then(toml::Value::as_table)
+                        .map(|profiles| profiles.keys().cloned().collect())
+                        .unwrap_or_default();
+                    format!("Unknown profile '{}' (defined: {})", name, if known.is_empty() { "none".to_string() } else { known.join(", ") })
+                })?;
+            merge(&mut merged, profile.clone());
+        }
+
+        // (where it came from, dotted key) for every override, in the order applied
+        let mut overrides = Vec::new();
+        if sources.env {
+            let mut vars: Vec<_> = env::vars().filter(|(var, _)| var.starts_with(ENV_PREFIX)).collect();
+            vars.sort();
+            for (var, value) in vars {
+                let key = var[ENV_PREFIX.len()..].split("__").map(str::to_lowercase).collect::<Vec<_>>().join(".");
+                set(&mut merged, &key, &value).with_context(|| format!("Invalid override {}", var))?;
+                overrides.push((var, key));
+            }
+        }
+        for entry in &sources.overrides {
+            let (key, value) = entry
+                .split_once('=')
+                .with_context(|| format!("Invalid override '{}' (expected section.key=value)", entry))?;
+            let key = key.trim();
+            set(&mut merged, key, value).with_context(|| format!("Invalid override --set {}", entry))?;
+            overrides.push((format!("--set {}", key), key.to_string()));
+        }
+
+        Ok(Layers { merged, files: layers, overrides, refused, problems })
+    }
+
+    /// Replace secret references in API keys with the secrets they point at
+    ///
+    /// References in `refused` are left as they are (and reported elsewhere).
+    fn resolve_secrets(&mut self, refused: &HashSet<String>) -> Vec<Problem> {
+        let mut problems = Vec::new();
+        let config = self.clone();
+        let mut names: Vec<_> = self.providers.keys().cloned().collect();
+        names.sort();
+
+        for name in names {
+            let provider = self.providers.get_mut(&name).unwrap();
+            let Some(reference) = provider.api_key.as_ref().filter(|key| !refused.contains(key.expose())) else {
+                continue;
+            };
+            match crate::auth::SECRETS.resolve(reference.expose(), &config) {
+                Some(Ok(secret)) => provider.api_key = Some(secret),
+                Some(Err(e)) => problems.push(Problem::error(format!("providers.{}.api_key", name), format!("{:#}", e))),
+                None => {}
+            }
+        }
+
+        if let Some(reference) = self.oauth.token_passphrase.as_ref().filter(|key| !refused.contains(key.expose())) {
+            match crate::auth::SECRETS.resolve(reference.expose(), &config) {
+                Some(Ok(secret)) => self.oauth.token_passphrase = Some(secret),
+                Some(Err(e)) => problems.push(Problem::error("oauth.token_passphrase", format!("{:#}", e))),
+                None => {}
+            }
+        }
+
+        problems
+    }
+
+    /// Check every section; run before a reload is applied
+    pub fn validate(&mut self) -> Result<()> {
+        let problems = self.problems();
+        self.disable_providers_without_keys();
+        Self::enforce("config", &problems)
+    }
+
+    /// Log warnings, and fail listing every error if there are any
+    fn enforce(source: &str, problems: &[Problem]) -> Result<()> {
+        for problem in problems.iter().filter(|problem| problem.severity == Severity::Warning) {
+            tracing::warn!("{}", problem.display(source));
+        }
+
+        let errors: Vec<String> = problems
+            .iter()
+            .filter(|problem| problem.severity == Severity::Error)
+            .map(|problem| problem.display(source))
+            .collect();
+        if errors.is_empty() {
+            return Ok(());
+        }
+
+        Err(anyhow::anyhow!(
+            "Invalid configuration ({} error{}):\n  {}",
+            errors.len(),
+            if errors.len() == 1 { "" } else { "s" },
+            errors.join("\n  ")
+        ))
     }
 
     /// Substitute ${VAR_NAME} with environment variable values
+    ///
+    /// `${VAR_NAME:-default}` uses the default when the variable is unset or empty.
     fn substitute_env_vars(content: &str) -> String {
         let mut result = content.to_string();
 
         // Find all ${VAR} patterns
         while let Some(start) = result.find("${") {
             if let Some(end) = result[start..].find('}') {
-                let var_name = &result[start + 2..start + end];
-                let value = env::var(var_name).unwrap_or_default();
+                let expr = &result[start + 2..start + end];
+                let value = match expr.split_once(":-") {
+                    Some((var_name, default)) => env::var(var_name)
+                        .ok()
+                        .filter(|value| !value.is_empty())
+                        .unwrap_or_else(|| default.to_string()),
+                    None => env::var(expr).unwrap_or_default(),
+                };
                 result.replace_range(start..start + end + 1, &value);
             } else {
                 break;
@@ -266,41 +982,233 @@ impl Config {
         result
     }
 
-    /// Validate provider configurations
-    fn validate_providers(&mut self) -> Result<()> {
-        for (name, provider) in &self.providers {
-            if !provider.enabled {
+    /// `${VAR}` references (outside comments, without a default) to variables
+    /// that aren't set
+    fn missing_env_vars(content: &str) -> Vec<Problem> {
+        let mut problems = Vec::new();
+
+        for (index, line) in content.lines().enumerate() {
+            if line.trim_start().starts_with('#') {
                 continue;
             }
 
-            match provider.auth_method {
-                AuthMethod::ApiKey => {
-                    if provider.api_key.is_none() || provider.api_key.as_ref().unwrap().is_empty() {
-                        tracing::warn!(
-                            "Provider '{}' enabled but missing API key - will be disabled",
-                            name
-                        );
-                    }
+            let mut rest = line;
+            while let Some(start) = rest.find("${") {
+                let Some(end) = rest[start..].find('}') else { break };
+                let var_name = &rest[start + 2..start + end];
+                if !var_name.contains(":-") && env::var(var_name).is_err() {
+                    problems.push(Problem {
+                        line: Some(index + 1),
+                        ..Problem::warning(
+                            format!("${{{}}}", var_name),
+                            format!("environment variable {} is not set, using an empty string", var_name),
+                        )
+                    });
+                }
+                rest = &rest[start + end + 1..];
+            }
+        }
+
+        problems
+    }
+
+    /// Everything wrong with this config (without line numbers)
+    pub fn problems(&self) -> Vec<Problem> {
+        let mut problems = Vec::new();
+        self.check_providers(&mut problems);
+        self.check_routing(&mut problems);
+        self.check_health_check(&mut problems);
+        self.check_server(&mut problems);
+        self.check_oauth(&mut problems);
+        problems
+    }
+
+    /// An API key provider can't work without its key
+    fn missing_api_key(provider: &ProviderConfig) -> bool {
+        provider.enabled
+            && provider.auth_method == AuthMethod::ApiKey
+            && provider.api_key.as_ref().is_none_or(Secret::is_empty)
+    }
+
+    fn disable_providers_without_keys(&mut self) {
+        for provider in self.providers.values_mut() {
+            if Self::missing_api_key(provider) {
+                provider.enabled = false;
+            }
+        }
+    }
+
+    /// Validate provider configurations
+    fn check_providers(&self, problems: &mut Vec<Problem>) {
+        let mut names: Vec<_> = self.providers.keys().collect();
+        names.sort();
+
+        for name in names {
+            let provider = &self.providers[name];
+            if Self::missing_api_key(provider) {
+                problems.push(Problem::warning(
+                    format!("providers.{}.api_key", name),
+                    format!("provider '{}' is enabled but has no API key, disabling it", name),
+                ));
+            }
+
+            if let Some(circuit_breaker) = &provider.circuit_breaker
+                && let Err(e) = circuit_breaker.validate()
+            {
+                problems.push(Problem::error(format!("providers.{}.circuit_breaker", name), e.to_string()));
+            }
+        }
+    }
+
+    /// HTTP/3 needs TLS; client identity rules need mutual TLS
+    fn check_server(&self, problems: &mut Vec<Problem>) {
+        if self.server.http3.enabled && self.server.tls.is_none() {
+            problems.push(Problem::error("server.http3.enabled", "HTTP/3 requires [server.tls] cert_path and key_path"));
+        }
+        if let Some(tls) = &self.server.tls
+            && !tls.allowed_clients.is_empty()
+            && tls.client_ca_path.is_none()
+        {
+            problems.push(Problem::error("server.tls.allowed_clients", "allowed_clients requires client_ca_path"));
+        }
+    }
+
+    /// The token store has to be one we know, and a file store needs a key
+    fn check_oauth(&self, problems: &mut Vec<Problem>) {
+        let oauth = &self.oauth;
+        match oauth.token_store.as_str() {
+            "keyring" => {}
+            "file" => {
+                if oauth.token_passphrase.as_ref().is_none_or(Secret::is_empty) && oauth.token_key_file.is_none() {
+                    problems.push(Problem::error(
+                        "oauth.token_store",
+                        "the file token store requires token_passphrase or token_key_file",
+                    ));
                 }
-                AuthMethod::OAuth => {
-                    // OAuth tokens will be loaded from keyring
-                    tracing::debug!("Provider '{}' uses OAuth - tokens loaded from keyring", name);
+            }
+            other => problems.push(Problem::error(
+                "oauth.token_store",
+                format!("unknown token store '{}' (expected \"keyring\" or \"file\")", other),
+            )),
+        }
+    }
+
+    /// Validate background health check settings
+    fn check_health_check(&self, problems: &mut Vec<Problem>) {
+        let health_check = &self.health_check;
+        for (key, value) in [
+            ("interval_secs", health_check.interval_secs),
+            ("timeout_secs", health_check.timeout_secs),
+            ("unhealthy_threshold", health_check.unhealthy_threshold as u64),
+        ] {
+            if value == 0 {
+                problems.push(Problem::error(format!("health_check.{}", key), "must be at least 1"));
+            }
+        }
+
+        if !matches!(health_check.probe.as_str(), "models" | "completion") {
+            problems.push(Problem::error(
+                "health_check.probe",
+                format!("unknown probe '{}' (expected \"models\" or \"completion\")", health_check.probe),
+            ));
+        }
+    }
+
+    /// Validate routing settings
+    fn check_routing(&self, problems: &mut Vec<Problem>) {
+        let routing = &self.routing;
+
+        if !crate::router::STRATEGIES.contains(&routing.strategy.as_str()) {
+            problems.push(Problem::error(
+                "routing.strategy",
+                format!(
+                    "unknown strategy '{}' (expected one of: {})",
+                    routing.strategy,
+                    crate::router::STRATEGIES.join(", ")
+                ),
+            ));
+        }
+
+        for (index, name) in routing.fallback_chain.iter().enumerate() {
+            let key = format!("routing.fallback_chain[{}]", index);
+            match self.providers.get(name) {
+                None => problems.push(Problem::error(key, format!("unknown provider '{}'", name))),
+                Some(provider) if !provider.enabled => {
+                    problems.push(Problem::warning(key, format!("provider '{}' is disabled and will be skipped", name)))
                 }
-                AuthMethod::None => {
-                    // No auth required (e.g., Ollama)
+                Some(_) => {}
+            }
+        }
+
+        for (index, entry) in routing.load_balance.iter().enumerate() {
+            let key = format!("routing.load_balance[{}]", index);
+            match crate::balancer::parse_entry(entry) {
+                Ok((name, _)) if !self.providers.contains_key(&name) => {
+                    problems.push(Problem::error(key, format!("unknown provider '{}'", name)))
                 }
+                Ok(_) => {}
+                Err(e) => problems.push(Problem::error(key, e.to_string())),
             }
         }
 
-        Ok(())
+        for (index, rule) in routing.rules.iter().enumerate() {
+            if let Err(e) = crate::rules::validate(rule, &self.providers) {
+                problems.push(Problem::error(format!("routing.rules[{}]", index), format!("rule '{}': {}", rule.name, e)));
+            }
+        }
+
+        if let Err(e) = routing.circuit_breaker.validate() {
+            problems.push(Problem::error("routing.circuit_breaker", e.to_string()));
+        }
+
+        if !matches!(routing.balance.as_str(), "weighted" | "least-outstanding") {
+            problems.push(Problem::error(
+                "routing.balance",
+                format!("unknown balance '{}' (expected \"weighted\" or \"least-outstanding\")", routing.balance),
+            ));
+        }
     }
 
-    /// Get enabled providers
+    /// The config as TOML, with API keys, tokens and other secrets redacted
+    pub fn to_redacted_toml(&self) -> Result<String> {
+        let mut value = toml::Value::try_from(self).context("Failed to serialize config")?;
+        redact(&mut value);
+        toml::to_string_pretty(&value).context("Failed to serialize config")
+    }
+
+    /// Get enabled providers, sorted by name so iteration order is stable
     pub fn enabled_providers(&self) -> Vec<(String, &ProviderConfig)> {
-        self.providers
+        let mut enabled: Vec<_> = self.providers
             .iter()
             .filter(|(_, config)| config.enabled)
             .map(|(name, config)| (name.clone(), config))
+            .collect();
+        enabled.sort_by(|a, b| a.0.cmp(&b.0));
+        enabled
+    }
+
+    /// `load_balance` entries that name an enabled provider, with their weights
+    ///
+    /// Falls back to every enabled provider at weight 1 when the list is empty.
+    pub fn load_balance_providers(&self) -> Vec<(String, u32, &ProviderConfig)> {
+        let enabled: HashMap<_, _> = self.enabled_providers().into_iter().collect();
+
+        if self.routing.load_balance.is_empty() {
+            return self.enabled_providers()
+                .into_iter()
+                .map(|(name, config)| (name, 1, config))
+                .collect();
+        }
+
+        self.routing.load_balance
+            .iter()
+            .filter_map(|entry| match crate::balancer::parse_entry(entry) {
+                Ok((name, weight)) => enabled.get(&name).map(|config| (name, weight, *config)),
+                Err(e) => {
+                    tracing::warn!("Ignoring load_balance entry: {}", e);
+                    None
+                }
+            })
             .collect()
     }
 
@@ -358,6 +1266,260 @@ impl Config {
     }
 }
 
+/// How bad a config problem is
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// The config is not loaded
+    Error,
+    /// Loaded, but probably not what was meant
+    Warning,
+}
+
+/// Something wrong in a config, and where
+#[derive(Debug, Clone, Serialize)]
+pub struct Problem {
+    pub severity: Severity,
+    /// Dotted path of the setting, e.g. `routing.fallback_chain[1]`
+    pub key: String,
+    pub message: String,
+    /// File, environment variable or flag the setting came from, when known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// Line in the config file, when known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+}
+
+impl Problem {
+    pub fn error(key: impl Into<String>, message: impl Into<String>) -> Self {
+        Self { severity: Severity::Error, key: key.into(), message: message.into(), source: None, line: None }
+    }
+
+    pub fn warning(key: impl Into<String>, message: impl Into<String>) -> Self {
+        Self { severity: Severity::Warning, key: key.into(), message: message.into(), source: None, line: None }
+    }
+
+    /// `file:line: severity: key: message`, naming `source` when the problem
+    /// doesn't say where it came from
+    pub fn display(&self, source: &str) -> String {
+        let source = self.source.as_deref().unwrap_or(source);
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        match self.line {
+            Some(line) => format!("{}:{}: {}: {}: {}", source, line, severity, self.key, self.message),
+            None => format!("{}: {}: {}: {}", source, severity, self.key, self.message),
+        }
+    }
+}
+
+/// The layers of a config, merged but not yet parsed
+struct Layers {
+    merged: toml::Table,
+    /// (source, contents) of each file, in merge order
+    files: Vec<(String, String)>,
+    /// (where it came from, dotted key) for every override, in the order applied
+    overrides: Vec<(String, String)>,
+    /// `cmd:` references from untrusted files, which are not run
+    refused: HashSet<String>,
+    problems: Vec<Problem>,
+}
+
+/// Where a config is assembled from, lowest precedence first
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Sources {
+    /// Config files, merged in order
+    pub files: Vec<PathBuf>,
+    /// `[profile.<name>]` merged over the files
+    pub profile: Option<String>,
+    /// Apply `THANOS__SECTION__KEY` environment overrides
+    pub env: bool,
+    /// `section.key=value` overrides from the command line, applied last
+    pub overrides: Vec<String>,
+    /// Files picked up from the working directory rather than named by the
+    /// user; they may not run commands (`cmd:` secrets)
+    pub untrusted: Vec<PathBuf>,
+}
+
+impl Sources {
+    /// Just this file: no profile, no overrides
+    pub fn file(path: impl Into<PathBuf>) -> Self {
+        Self { files: vec![path.into()], ..Default::default() }
+    }
+
+    /// The system, user and project files that exist, the profile in
+    /// `THANOS_PROFILE` and environment overrides
+    ///
+    /// `project` replaces `Config::path()` as the project file, and must exist.
+    pub fn discover(project: Option<PathBuf>) -> Self {
+        let mut files: Vec<PathBuf> = [Some(PathBuf::from(SYSTEM_CONFIG)), user_config()]
+            .into_iter()
+            .flatten()
+            .filter(|path| path.exists())
+            .collect();
+
+        let explicit = project.is_some() || env::var_os("THANOS_CONFIG").is_some();
+        let project = project.unwrap_or_else(|| PathBuf::from(Config::path()));
+        let duplicate = files.iter().any(|path| same_file(path, &project));
+        let mut untrusted = Vec::new();
+        if !duplicate && (explicit || project.exists()) {
+            if !explicit {
+                untrusted.push(project.clone());
+            }
+            files.push(project);
+        }
+
+        Self {
+            files,
+            profile: env::var("THANOS_PROFILE").ok().filter(|profile| !profile.is_empty()),
+            env: true,
+            overrides: Vec::new(),
+            untrusted,
+        }
+    }
+
+    /// The files, joined for messages
+    pub fn label(&self) -> String {
+        self.files.iter().map(|path| path.display().to_string()).collect::<Vec<_>>().join(", ")
+    }
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// Line of a setting (or of its closest parent that is written out) in the
+/// file, and how many levels of the key were found
+fn locate(document: &toml_edit::ImDocument<&str>, raw: &str, key: &str) -> Option<(usize, usize)> {
+    let mut item = document.as_item();
+    let mut span = None;
+    let mut depth = 0;
+
+    for segment in key.split('.') {
+        let (name, index) = match segment.split_once('[') {
+            Some((name, index)) => (name, index.trim_end_matches(']').parse::<usize>().ok()),
+            None => (segment, None),
+        };
+
+        let Some(next) = item.get(name) else { break };
+        item = next;
+        span = item.span().or(span);
+        depth += 1;
+
+        if let Some(index) = index {
+            let Some(next) = item.get(index) else { break };
+            item = next;
+            span = item.span().or(span);
+            depth += 1;
+        }
+    }
+
+    let offset = span?.start;
+    Some((raw[..offset].matches('\n').count() + 1, depth))
+}
+
+/// Whether an override of `key` set the value at `problem_key`
+fn covers(key: &str, problem_key: &str) -> bool {
+    problem_key
+        .strip_prefix(key)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('.') || rest.starts_with('['))
+}
+
+/// `(dotted key, reference)` for every `cmd:` secret set in a config table,
+/// profiles included
+fn command_secrets(table: &toml::Table, prefix: &str) -> Vec<(String, String)> {
+    let mut found = Vec::new();
+    for (name, value) in table {
+        let key = if prefix.is_empty() { name.clone() } else { format!("{}.{}", prefix, name) };
+        match value {
+            toml::Value::Table(table) => found.extend(command_secrets(table, &key)),
+            toml::Value::String(reference)
+                if SECRET_REFERENCE_KEYS.contains(&name.as_str()) && reference.trim_start().starts_with("cmd:") =>
+            {
+                found.push((key, reference.clone()))
+            }
+            _ => {}
+        }
+    }
+    found
+}
+
+/// Merge `over` into `base`: tables merge key by key, anything else replaces
+fn merge(base: &mut toml::Table, over: toml::Table) {
+    for (key, value) in over {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(over)) => merge(base, over),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Set a dotted key from an override
+///
+/// The value is read as TOML (`true`, `8`, `["a", "b"]`), or taken as a
+/// string if it isn't valid TOML or replaces a string.
+fn set(table: &mut toml::Table, key: &str, value: &str) -> Result<()> {
+    let mut segments: Vec<&str> = key.split('.').collect();
+    let last = segments.pop().filter(|last| !last.is_empty()).context("empty key")?;
+
+    let mut table = table;
+    for segment in segments {
+        table = table
+            .entry(segment)
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+            .as_table_mut()
+            .with_context(|| format!("{} is not a table", segment))?;
+    }
+
+    let parsed = match table.get(last) {
+        Some(toml::Value::String(_)) => None,
+        _ => toml::from_str::<toml::Table>(&format!("value = {}", value))
+            .ok()
+            .and_then(|mut parsed| parsed.remove("value")),
+    };
+    table.insert(last.to_string(), parsed.unwrap_or_else(|| toml::Value::String(value.to_string())));
+    Ok(())
+}
+
+/// `$XDG_CONFIG_HOME/thanos/config.toml`, or `~/.config/thanos/config.toml`
+fn user_config() -> Option<PathBuf> {
+    let base = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(base.join("thanos").join("config.toml"))
+}
+
+/// Settings named like secrets (`api_key`, `admin_token`, `OPENAI_API_KEY`, ...)
+fn is_secret(key: &str) -> bool {
+    let key = key.to_ascii_lowercase();
+    ["key", "token", "secret", "password"].iter().any(|suffix| key.ends_with(suffix))
+}
+
+fn redact(value: &mut toml::Value) {
+    match value {
+        toml::Value::Table(table) => {
+            for (key, value) in table.iter_mut() {
+                match value {
+                    toml::Value::String(secret) if is_secret(key) && !secret.is_empty() => {
+                        *secret = "[redacted]".to_string();
+                    }
+                    _ => redact(value),
+                }
+            }
+        }
+        toml::Value::Array(items) => items.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -397,6 +1559,282 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_env_var_default() {
+        unsafe {
+            env::set_var("THANOS_TEST_DEFAULT_SET", "from-env");
+            env::set_var("THANOS_TEST_DEFAULT_EMPTY", "");
+        }
+
+        let input = "a = \"${THANOS_TEST_DEFAULT_SET:-x}\"\nb = \"${THANOS_TEST_DEFAULT_EMPTY:-y}\"\nc = \"${THANOS_TEST_DEFAULT_UNSET:-http://localhost:11434}\"";
+        let output = Config::substitute_env_vars(input);
+
+        assert_eq!(output, "a = \"from-env\"\nb = \"y\"\nc = \"http://localhost:11434\"");
+        assert!(Config::missing_env_vars(input).is_empty());
+
+        unsafe {
+            env::remove_var("THANOS_TEST_DEFAULT_SET");
+            env::remove_var("THANOS_TEST_DEFAULT_EMPTY");
+        }
+    }
+
+    fn write_config(name: &str, contents: &str) -> String {
+        let path = std::env::temp_dir().join(format!("thanos-config-{}-{}.toml", name, std::process::id()));
+        fs::write(&path, contents).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+        }
+        path.display().to_string()
+    }
+
+    #[test]
+    fn test_check_reports_every_problem_with_lines() {
+        let path = write_config(
+            "check",
+            r#"[server]
+
+[routing]
+strategy = "fastest"
+fallback_chain = ["ollama", "missing"]
+load_balance = ["ollama:2", "ghost"]
+
+[providers.ollama]
+enabled = true
+auth_method = "none"
+
+[providers.openai]
+enabled = true
+auth_method = "api_key"
+api_key = "${THANOS_CHECK_UNSET_KEY}"
+"#,
+        );
+
+        let (config, problems) = Config::check_file(&path).unwrap();
+        let found: Vec<_> = problems.iter().map(|p| (p.severity, p.key.as_str(), p.line)).collect();
+        assert_eq!(
+            found,
+            vec![
+                (Severity::Warning, "${THANOS_CHECK_UNSET_KEY}", Some(15)),
+                (Severity::Warning, "providers.openai.api_key", Some(15)),
+                (Severity::Error, "routing.strategy", Some(4)),
+                (Severity::Error, "routing.fallback_chain[1]", Some(5)),
+                (Severity::Error, "routing.load_balance[1]", Some(6)),
+            ]
+        );
+
+        // The provider without a key is disabled, and loading fails listing all errors
+        assert!(!config.providers["openai"].enabled);
+        let error = Config::load_from(&path).unwrap_err().to_string();
+        assert!(error.contains("3 errors"), "{}", error);
+        assert!(error.contains(&format!("{}:5: error: routing.fallback_chain[1]: unknown provider 'missing'", path)));
+    }
+
+    #[test]
+    fn test_layers_profiles_and_overrides() {
+        let system = write_config(
+            "system",
+            r#"[server]
+admin_token = "system"
+
+[routing]
+fallback_chain = ["ollama"]
+
+[providers.ollama]
+enabled = true
+auth_method = "none"
+base_url = "http://localhost:11434"
+
+[profile.work.providers.ollama]
+base_url = "http://gpu-box:11434"
+
+[profile.work.routing]
+fallback_chain = ["ollama", "missing"]
+"#,
+        );
+        let project = write_config(
+            "project",
+            r#"[server]
+bind = "127.0.0.1:8080"
+
+[cache]
+enabled = true
+"#,
+        );
+
+        unsafe {
+            env::set_var("THANOS__SERVER__ADMIN_TOKEN", "12345");
+            env::set_var("THANOS__CACHE__MAX_SIZE", "50");
+        }
+        let sources = Sources {
+            files: vec![system.clone().into(), project.into()],
+            profile: Some("work".to_string()),
+            env: true,
+            overrides: vec!["cache.max_size=5".to_string(), "routing.strategy=fastest".to_string()],
+            untrusted: Vec::new(),
+        };
+        let checked = Config::check(&sources);
+        let unknown_profile = Config::check(&Sources { profile: Some("home".to_string()), ..sources.clone() });
+        unsafe {
+            env::remove_var("THANOS__SERVER__ADMIN_TOKEN");
+            env::remove_var("THANOS__CACHE__MAX_SIZE");
+        }
+
+        // Later layers win; a value replacing a string stays a string
+        let (config, problems) = checked.unwrap();
+        assert_eq!(config.server.bind, "127.0.0.1:8080");
+        assert_eq!(config.server.admin_token.as_ref().map(Secret::expose), Some("12345"));
+        assert_eq!(config.providers["ollama"].base_url.as_deref(), Some("http://gpu-box:11434"));
+        assert!(config.cache.enabled);
+        assert_eq!(config.cache.max_size, 5);
+
+        // Problems point at the flag or the profile line that set the value
+        let found: Vec<_> = problems.iter().map(|p| (p.key.as_str(), p.source.clone(), p.line)).collect();
+        assert_eq!(
+            found,
+            vec![
+                ("routing.strategy", Some("--set routing.strategy".to_string()), None),
+                ("routing.fallback_chain[1]", Some(system), Some(16)),
+            ]
+        );
+
+        let error = unknown_profile.unwrap_err().to_string();
+        assert!(error.contains("Unknown profile 'home' (defined: work)"), "{}", error);
+    }
+
+    #[test]
+    fn test_secret_references() {
+        let secret = std::env::temp_dir().join(format!("thanos-config-secret-{}", std::process::id()));
+        fs::write(&secret, "sk-from-file\n").unwrap();
+        let path = write_config(
+            "secrets",
+            &format!(
+                r#"[server]
+[routing]
+
+[providers.openai]
+enabled = true
+auth_method = "api_key"
+api_key = "file:{}"
+
+[providers.anthropic]
+enabled = true
+auth_method = "api_key"
+api_key = "cmd:exit 1"
+"#,
+                secret.display()
+            ),
+        );
+
+        let (config, problems) = Config::check_file(&path).unwrap();
+        assert_eq!(config.providers["openai"].api_key.as_ref().map(Secret::expose), Some("sk-from-file"));
+        let found: Vec<_> = problems.iter().map(|p| (p.severity, p.key.as_str(), p.line)).collect();
+        assert_eq!(found, vec![(Severity::Error, "providers.anthropic.api_key", Some(12))]);
+        assert!(problems[0].message.contains("secret cmd:exit 1"), "{}", problems[0].message);
+    }
+
+    #[test]
+    fn test_untrusted_file_runs_no_commands() {
+        let marker = std::env::temp_dir().join(format!("thanos-config-ran-{}", std::process::id()));
+        let _ = fs::remove_file(&marker);
+        let path = write_config(
+            "untrusted",
+            &format!(
+                r#"[server]
+[routing]
+
+[providers.openai]
+enabled = true
+auth_method = "api_key"
+api_key = "cmd:touch {} && echo sk-from-cmd"
+
+[profile.work.providers.openai]
+api_key = "cmd:true"
+"#,
+                marker.display()
+            ),
+        );
+
+        // Found in the working directory: refused, and not run
+        let sources = Sources { untrusted: vec![path.clone().into()], ..Sources::file(&path) };
+        let (_, problems) = Config::check(&sources).unwrap();
+        let found: Vec<_> = problems.iter().map(|p| (p.severity, p.key.as_str(), p.line)).collect();
+        assert_eq!(
+            found,
+            vec![
+                (Severity::Error, "profile.work.providers.openai.api_key", Some(10)),
+                (Severity::Error, "providers.openai.api_key", Some(7)),
+            ]
+        );
+        assert!(!marker.exists());
+
+        // Named by the user: run
+        let (config, problems) = Config::check_file(&path).unwrap();
+        assert!(problems.is_empty(), "{:?}", problems);
+        assert_eq!(config.providers["openai"].api_key.as_ref().map(Secret::expose), Some("sk-from-cmd"));
+        assert!(marker.exists());
+    }
+
+    #[test]
+    fn test_oauth_loads_before_secrets_are_stored() {
+        let passphrase = std::env::temp_dir().join(format!("thanos-config-passphrase-{}", std::process::id()));
+        fs::write(&passphrase, "correct horse\n").unwrap();
+        let path = write_config(
+            "oauth-unresolved",
+            &format!(
+                r#"[server]
+[routing]
+
+[oauth]
+keyring_service = "thanos-test"
+token_store = "file"
+token_passphrase = "file:{}"
+
+[providers.openai]
+enabled = true
+auth_method = "api_key"
+api_key = "file:/nonexistent/openai-key"
+
+[providers.anthropic]
+enabled = true
+auth_method = "api_key"
+api_key = "keyring:anthropic"
+"#,
+                passphrase.display()
+            ),
+        );
+
+        // `thanos auth set` runs before the keys it stores can be resolved
+        assert!(Config::load_from(&path).is_err());
+        let oauth = Config::load_oauth(&Sources::file(&path)).unwrap();
+        assert_eq!(oauth.keyring_service, "thanos-test");
+        assert_eq!(oauth.token_passphrase.as_ref().map(Secret::expose), Some("correct horse"));
+    }
+
+    #[test]
+    fn test_redacted_toml() {
+        let config: Config = toml::from_str(
+            r#"
+            [server]
+            admin_token = "admin-secret"
+            [routing]
+            [providers.openai]
+            enabled = true
+            auth_method = "api_key"
+            api_key = "sk-secret"
+            max_tokens = 1024
+            "#,
+        )
+        .unwrap();
+
+        let shown = config.to_redacted_toml().unwrap();
+        assert!(!shown.contains("admin-secret") && !shown.contains("sk-secret"), "{}", shown);
+        assert!(shown.contains(r#"api_key = "[redacted]""#));
+        assert!(shown.contains("max_tokens = 1024"));
+        assert!(!format!("{:?}", config).contains("sk-secret"));
+    }
+
     #[test]
     fn test_enabled_providers_filter() {
         let mut providers = HashMap::new();
@@ -406,13 +1844,18 @@ mod tests {
             ProviderConfig {
                 enabled: true,
                 auth_method: AuthMethod::ApiKey,
-                api_key: Some("key1".to_string()),
+                api_key: Some("key1".into()),
                 base_url: None,
                 endpoint: None,
                 model: None,
                 max_tokens: None,
                 temperature: None,
                 client_id: None,
+                plugin: None,
+                retry: Default::default(),
+                context_length: None,
+                output_limit: None,
+                circuit_breaker: None,
             },
         );
 
@@ -428,6 +1871,11 @@ mod tests {
                 max_tokens: None,
                 temperature: None,
                 client_id: None,
+                plugin: None,
+                retry: Default::default(),
+                context_length: None,
+                output_limit: None,
+                circuit_breaker: None,
             },
         );
 
@@ -440,6 +1888,9 @@ mod tests {
             rate_limiting: Default::default(),
             metrics: Default::default(),
             oauth: Default::default(),
+            tokenizer: Default::default(),
+            health_check: Default::default(),
+            secrets: Default::default(),
         };
 
         let enabled = config.enabled_providers();
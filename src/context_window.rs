@@ -0,0 +1,306 @@
+/// Context-window checks before a request is sent
+///
+/// Counts the prompt with the model's tokenizer and compares it with the model's limits (provider
+/// overrides, then models.dev, then the built-in table). Requests that can't
+/// fit are rejected locally with `ContextLengthExceeded` so the router moves on
+/// without an upstream round trip; optionally the conversation is trimmed to
+/// fit instead, and `max_tokens` is clamped to what the model can produce.
+use crate::config::{ContextWindowConfig, ProviderConfig};
+use crate::error::ProviderError;
+use crate::models_dev::ModelLimits;
+use crate::tokenizer::{Tokenizer, MESSAGE_OVERHEAD, REPLY_OVERHEAD};
+use crate::types::{ChatRequest, Role};
+use std::borrow::Cow;
+use tracing::debug;
+
+/// Marker left where a message was shortened
+const TRUNCATION_MARKER: &str = "\n[... truncated ...]\n";
+
+/// Prompts longer than this (in characters) are checked on the blocking pool,
+/// as tokenizing them would hold up other requests on the runtime
+pub const BLOCKING_PROMPT_CHARS: usize = 64 * 1024;
+
+/// Estimated prompt tokens for a request (exact for OpenAI vocabularies)
+pub fn estimate_tokens(request: &ChatRequest) -> u32 {
+    Tokenizer::for_model(&request.model).count_request(request) as u32
+}
+
+/// Limits for `model` on a provider, with the provider's own settings winning
+pub fn limits_for(provider_config: &ProviderConfig, model: &str) -> ModelLimits {
+    let known = crate::models_dev::limits_with_fallback(model).unwrap_or_default();
+
+    ModelLimits {
+        context: provider_config.context_length.or(known.context),
+        output: provider_config.output_limit.or(known.output),
+    }
+}
+
+/// Make `request` fit `limits`, or explain why it can't
+pub fn prepare<'a>(
+    config: &ContextWindowConfig,
+    provider_name: &str,
+    limits: ModelLimits,
+    request: &'a ChatRequest,
+) -> Result<Cow<'a, ChatRequest>, ProviderError> {
+    let mut request = Cow::Borrowed(request);
+
+    if config.clamp_max_tokens
+        && let (Some(output), Some(max_tokens)) = (limits.output, request.max_tokens)
+        && max_tokens > output as i32
+    {
+        debug!("Clamping max_tokens {} to {} for {}", max_tokens, output, request.model);
+        request.to_mut().max_tokens = Some(output as i32);
+    }
+
+    let Some(context) = limits.context else {
+        return Ok(request);
+    };
+
+    let prompt = estimate_tokens(&request);
+    let reserved = request.max_tokens.map_or(0, |n| n.max(0) as u32);
+    if prompt + reserved <= context {
+        return Ok(request);
+    }
+
+    // The prompt fits, just not with the requested completion: shrink the completion
+    if config.clamp_max_tokens && prompt < context {
+        debug!("Clamping max_tokens to {} so the reply fits {}", context - prompt, request.model);
+        request.to_mut().max_tokens = Some((context - prompt) as i32);
+        return Ok(request);
+    }
+
+    if config.fit_prompt
+        && let Some(fitted) = fit_messages(&request, context.saturating_sub(reserved))
+    {
+        debug!(
+            "Fitted prompt for {} from ~{} to ~{} tokens ({} of {} messages kept)",
+            request.model,
+            prompt,
+            estimate_tokens(&fitted),
+            fitted.messages.len(),
+            request.messages.len()
+        );
+        return Ok(Cow::Owned(fitted));
+    }
+
+    if !config.check {
+        return Ok(request);
+    }
+
+    Err(ProviderError::ContextLengthExceeded {
+        provider: provider_name.to_string(),
+        message: format!(
+            "prompt is ~{} tokens{} but {} has a {}-token context window",
+            prompt,
+            if reserved > 0 { format!(" plus {} for the reply", reserved) } else { String::new() },
+            request.model,
+            context
+        ),
+    })
+}
+
+/// Drop (or shorten) the oldest turns between the system prompt and the latest
+/// message until the estimate is within `target`
+///
+/// System messages and the final message are always kept intact; returns
+/// `None` if those alone don't fit.
+pub fn fit_messages(request: &ChatRequest, target: u32) -> Option<ChatRequest> {
+    let tokenizer = Tokenizer::for_model(&request.model);
+    let text_tokens = |text: &str| tokenizer.count(text) as u32;
+    let marker_tokens = text_tokens(TRUNCATION_MARKER);
+    let mut fitted = request.clone();
+    let last = fitted.messages.len().checked_sub(1)?;
+    let mut index = 0;
+    let mut last_index = last;
+
+    // Count each message once and keep a running total (as `estimate_tokens`)
+    let mut counts: Vec<u32> = fitted.messages.iter().map(|m| text_tokens(&m.content)).collect();
+    let system = fitted.system.as_deref().map_or(0, |s| text_tokens(s) + MESSAGE_OVERHEAD as u32);
+    let mut total = counts.iter().map(|n| n + MESSAGE_OVERHEAD as u32).sum::<u32>() + system + REPLY_OVERHEAD as u32;
+
+    while total > target {
+        // Next removable turn: not a system message, not the latest message
+        while index < last_index && fitted.messages[index].role == Role::System {
+            index += 1;
+        }
+        if index >= last_index {
+            return None;
+        }
+
+        let excess = total - target;
+        let content_tokens = counts[index];
+
+        if content_tokens + MESSAGE_OVERHEAD as u32 <= excess + MESSAGE_OVERHEAD as u32 + marker_tokens {
+            fitted.messages.remove(index);
+            counts.remove(index);
+            total -= content_tokens + MESSAGE_OVERHEAD as u32;
+            last_index -= 1;
+        } else {
+            // Cheaper to keep the start and end of this turn
+            let content = &fitted.messages[index].content;
+            let chars: Vec<char> = content.chars().collect();
+            let chars_per_token = chars.len() as f64 / content_tokens.max(1) as f64;
+            let remove = ((excess + marker_tokens + 1) as f64 * chars_per_token).ceil() as usize;
+            let keep = chars.len().saturating_sub(remove);
+            let head: String = chars[..keep / 2].iter().collect();
+            let tail: String = chars[chars.len() - (keep - keep / 2)..].iter().collect();
+            let shortened = format!("{}{}{}", head, TRUNCATION_MARKER, tail);
+
+            counts[index] = text_tokens(&shortened);
+            total = total - content_tokens + counts[index];
+            fitted.messages[index].content = shortened;
+        }
+    }
+
+    Some(fitted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::ChatMessage;
+
+    /// `tokens` tokens of text (" x" is a single token)
+    fn message(role: Role, tokens: usize) -> ChatMessage {
+        ChatMessage {
+            role,
+            content: " x".repeat(tokens),
+        }
+    }
+
+    fn request(messages: Vec<ChatMessage>, max_tokens: Option<i32>) -> ChatRequest {
+        ChatRequest {
+            model: "test-model".to_string(),
+            messages,
+            stream: false,
+            temperature: None,
+            max_tokens,
+            top_p: None,
+            system: None,
+            hedge: None,
+        }
+    }
+
+    fn limits(context: u32, output: u32) -> ModelLimits {
+        ModelLimits {
+            context: Some(context),
+            output: Some(output),
+        }
+    }
+
+    fn config(fit_prompt: bool) -> ContextWindowConfig {
+        ContextWindowConfig {
+            fit_prompt,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_estimate_tokens() {
+        let req = request(vec![message(Role::User, 100)], None);
+        assert_eq!(estimate_tokens(&req), (100 + MESSAGE_OVERHEAD + REPLY_OVERHEAD) as u32);
+    }
+
+    #[test]
+    fn test_small_request_untouched() {
+        let req = request(vec![message(Role::User, 10)], Some(100));
+        let prepared = prepare(&config(false), "openai", limits(1000, 500), &req).unwrap();
+        assert!(matches!(prepared, Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_clamps_max_tokens() {
+        let req = request(vec![message(Role::User, 10)], Some(100_000));
+        let prepared = prepare(&config(false), "openai", limits(200_000, 8192), &req).unwrap();
+        assert_eq!(prepared.max_tokens, Some(8192));
+
+        // Prompt fits but not with the full reply
+        let req = request(vec![message(Role::User, 900)], Some(500));
+        let prepared = prepare(&config(false), "openai", limits(1000, 1000), &req).unwrap();
+        assert_eq!(prepared.max_tokens, Some(1000 - estimate_tokens(&req) as i32));
+    }
+
+    #[test]
+    fn test_rejects_oversized_prompt() {
+        let req = request(vec![message(Role::User, 2000)], None);
+        let err = prepare(&config(false), "openai", limits(1000, 500), &req).unwrap_err();
+
+        assert_eq!(err.kind(), "context_length_exceeded");
+        assert!(err.should_fallback());
+    }
+
+    #[test]
+    fn test_fit_drops_middle_turns() {
+        let req = request(
+            vec![
+                message(Role::System, 10),
+                message(Role::User, 500),
+                message(Role::Assistant, 500),
+                message(Role::User, 100),
+            ],
+            Some(100),
+        );
+        let prepared = prepare(&config(true), "openai", limits(600, 1000), &req).unwrap();
+
+        assert!(estimate_tokens(&prepared) + 100 <= 600);
+        assert_eq!(prepared.messages.first().unwrap().role, Role::System);
+        assert_eq!(prepared.messages.last().unwrap().content, req.messages[3].content);
+        assert!(prepared.messages.len() < req.messages.len());
+    }
+
+    #[test]
+    fn test_fit_truncates_when_close() {
+        let req = request(vec![message(Role::User, 500), message(Role::User, 10)], None);
+        let target = estimate_tokens(&req) - 50;
+        let fitted = fit_messages(&req, target).unwrap();
+
+        // The old turn is shortened rather than dropped
+        assert_eq!(fitted.messages.len(), 2);
+        assert!(fitted.messages[0].content.contains("truncated"));
+        assert!(estimate_tokens(&fitted) <= target);
+    }
+
+    #[test]
+    fn test_fit_long_conversation() {
+        let mut req = request((0..5000).map(|_| message(Role::User, 20)).collect(), None);
+        req.system = Some(" x".repeat(50));
+        let fitted = fit_messages(&req, 1000).unwrap();
+
+        // The running total matches a full recount, system prompt included
+        assert!(estimate_tokens(&fitted) <= 1000);
+        assert!(estimate_tokens(&fitted) > 1000 - 20 - MESSAGE_OVERHEAD as u32);
+        assert_eq!(fitted.system, req.system);
+    }
+
+    #[test]
+    fn test_fit_impossible() {
+        let req = request(vec![message(Role::System, 1000), message(Role::User, 1000)], None);
+        assert!(fit_messages(&req, 500).is_none());
+        assert!(prepare(&config(true), "openai", limits(500, 100), &req).is_err());
+    }
+
+    #[test]
+    fn test_provider_overrides() {
+        let provider = ProviderConfig {
+            enabled: true,
+            auth_method: crate::types::AuthMethod::None,
+            api_key: None,
+            base_url: None,
+            endpoint: None,
+            model: None,
+            max_tokens: None,
+            temperature: None,
+            client_id: None,
+            plugin: None,
+            retry: Default::default(),
+            context_length: Some(8192),
+            output_limit: None,
+            circuit_breaker: None,
+        };
+
+        let limits = limits_for(&provider, "gpt-4o");
+        assert_eq!(limits.context, Some(8192));
+        assert_eq!(limits.output, Some(16_384));
+        assert_eq!(limits_for(&provider, "unknown-model").output, None);
+    }
+}
@@ -0,0 +1,489 @@
+/// Typed provider errors
+///
+/// Providers classify upstream failures into a `ProviderError` and return it
+/// inside `anyhow::Error`. The router uses the class to decide whether retrying
+/// or falling back makes sense; the HTTP and gRPC layers map it to the matching
+/// status code and an OpenAI-style error body.
+use axum::{
+    http::{header, HeaderValue, StatusCode},
+    response::{IntoResponse, Json, Response},
+};
+use serde_json::{json, Value};
+use std::time::Duration;
+use thiserror::Error;
+
+/// Longest upstream message we pass through to clients
+const MAX_MESSAGE_LEN: usize = 1000;
+
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum ProviderError {
+    /// The request itself is malformed or unsupported (4xx)
+    #[error("{provider}: invalid request: {message}")]
+    InvalidRequest { provider: String, message: String },
+
+    /// Credentials missing, invalid or expired (401)
+    #[error("{provider}: authentication failed: {message}")]
+    Authentication { provider: String, message: String },
+
+    /// Credentials valid but not allowed to do this (403)
+    #[error("{provider}: permission denied: {message}")]
+    PermissionDenied { provider: String, message: String },
+
+    /// Model or endpoint does not exist at this provider (404)
+    #[error("{provider}: not found: {message}")]
+    NotFound { provider: String, message: String },
+
+    /// Upstream rate limit or quota hit (429)
+    #[error("{provider}: rate limited: {message}")]
+    RateLimited {
+        provider: String,
+        message: String,
+        retry_after: Option<Duration>,
+    },
+
+    /// Prompt plus requested output does not fit the model's context window
+    #[error("{provider}: context length exceeded: {message}")]
+    ContextLengthExceeded { provider: String, message: String },
+
+    /// Request or response blocked by the provider's safety filters
+    #[error("{provider}: content filtered: {message}")]
+    ContentFiltered { provider: String, message: String },
+
+    /// Upstream did not answer in time
+    #[error("{provider}: timed out: {message}")]
+    Timeout { provider: String, message: String },
+
+    /// Upstream down, overloaded or unreachable (5xx, connection errors)
+    #[error("{provider}: unavailable: {message}")]
+    Unavailable { provider: String, message: String },
+
+    /// Not sent: the provider's circuit breaker is open
+    #[error("{provider}: circuit breaker open")]
+    CircuitOpen { provider: String },
+}
+
+impl ProviderError {
+    /// Classify a non-success upstream response, consuming its body
+    pub async fn from_response(provider: &str, res: reqwest::Response) -> Self {
+        let status = res.status().as_u16();
+        let retry_after = res
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(parse_retry_after);
+        let body = res.text().await.unwrap_or_default();
+
+        Self::from_status(provider, status, &body, retry_after)
+    }
+
+    /// Classify an upstream status code and error body
+    pub fn from_status(provider: &str, status: u16, body: &str, retry_after: Option<Duration>) -> Self {
+        let provider = provider.to_string();
+        let message = extract_message(body);
+        let lower = body.to_lowercase();
+
+        // Only bad-request answers are told apart by their body; a rate limit
+        // or outage mentioning tokens or safety is still a rate limit or outage
+        match status {
+            413 => Self::ContextLengthExceeded { provider, message },
+            400 if is_context_overflow(&lower) => Self::ContextLengthExceeded { provider, message },
+            400 if is_content_filter(&lower) => Self::ContentFiltered { provider, message },
+            401 => Self::Authentication { provider, message },
+            403 => Self::PermissionDenied { provider, message },
+            404 => Self::NotFound { provider, message },
+            408 => Self::Timeout { provider, message },
+            429 => Self::RateLimited { provider, message, retry_after },
+            400..=499 => Self::InvalidRequest { provider, message },
+            _ => Self::Unavailable { provider, message },
+        }
+    }
+
+    /// Classify a transport-level failure (connect, timeout, broken body)
+    pub fn from_reqwest(provider: &str, err: &reqwest::Error) -> Self {
+        let provider = provider.to_string();
+        let message = err.to_string();
+
+        if err.is_timeout() {
+            Self::Timeout { provider, message }
+        } else if let Some(status) = err.status() {
+            Self::from_status(&provider, status.as_u16(), &message, None)
+        } else {
+            Self::Unavailable { provider, message }
+        }
+    }
+
+    /// Find a typed provider error anywhere in an error chain
+    pub fn find(err: &anyhow::Error) -> Option<&ProviderError> {
+        err.chain().find_map(|e| e.downcast_ref::<ProviderError>())
+    }
+
+    pub fn provider(&self) -> &str {
+        match self {
+            Self::InvalidRequest { provider, .. }
+            | Self::Authentication { provider, .. }
+            | Self::PermissionDenied { provider, .. }
+            | Self::NotFound { provider, .. }
+            | Self::RateLimited { provider, .. }
+            | Self::ContextLengthExceeded { provider, .. }
+            | Self::ContentFiltered { provider, .. }
+            | Self::Timeout { provider, .. }
+            | Self::Unavailable { provider, .. }
+            | Self::CircuitOpen { provider } => provider,
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            Self::InvalidRequest { message, .. }
+            | Self::Authentication { message, .. }
+            | Self::PermissionDenied { message, .. }
+            | Self::NotFound { message, .. }
+            | Self::RateLimited { message, .. }
+            | Self::ContextLengthExceeded { message, .. }
+            | Self::ContentFiltered { message, .. }
+            | Self::Timeout { message, .. }
+            | Self::Unavailable { message, .. } => message,
+            Self::CircuitOpen { .. } => "circuit breaker open",
+        }
+    }
+
+    /// Short label used in metrics and logs
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::InvalidRequest { .. } => "invalid_request",
+            Self::Authentication { .. } => "authentication",
+            Self::PermissionDenied { .. } => "permission_denied",
+            Self::NotFound { .. } => "not_found",
+            Self::RateLimited { .. } => "rate_limited",
+            Self::ContextLengthExceeded { .. } => "context_length_exceeded",
+            Self::ContentFiltered { .. } => "content_filtered",
+            Self::Timeout { .. } => "timeout",
+            Self::Unavailable { .. } => "unavailable",
+            Self::CircuitOpen { .. } => "circuit_open",
+        }
+    }
+
+    /// Server-suggested delay before retrying, if any
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::RateLimited { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+
+    /// Transient failure: the same request to the same provider may succeed later
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            Self::RateLimited { .. } | Self::Timeout { .. } | Self::Unavailable { .. }
+        )
+    }
+
+    /// Another provider might serve this request
+    ///
+    /// Client-side mistakes and safety blocks would fail the same way everywhere,
+    /// so those are returned as-is instead of walking the fallback chain.
+    pub fn should_fallback(&self) -> bool {
+        !matches!(self, Self::InvalidRequest { .. } | Self::ContentFiltered { .. })
+    }
+
+    /// Whether this failure says anything about the provider's health
+    pub fn counts_against_provider(&self) -> bool {
+        matches!(
+            self,
+            Self::Authentication { .. } | Self::Timeout { .. } | Self::Unavailable { .. }
+        )
+    }
+
+    pub fn http_status(&self) -> StatusCode {
+        match self {
+            Self::InvalidRequest { .. }
+            | Self::ContextLengthExceeded { .. }
+            | Self::ContentFiltered { .. } => StatusCode::BAD_REQUEST,
+            Self::Authentication { .. } => StatusCode::UNAUTHORIZED,
+            Self::PermissionDenied { .. } => StatusCode::FORBIDDEN,
+            Self::NotFound { .. } => StatusCode::NOT_FOUND,
+            Self::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+            Self::Timeout { .. } => StatusCode::GATEWAY_TIMEOUT,
+            Self::Unavailable { .. } | Self::CircuitOpen { .. } => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    pub fn grpc_code(&self) -> tonic::Code {
+        match self {
+            Self::InvalidRequest { .. } | Self::ContentFiltered { .. } => tonic::Code::InvalidArgument,
+            Self::ContextLengthExceeded { .. } => tonic::Code::OutOfRange,
+            Self::Authentication { .. } => tonic::Code::Unauthenticated,
+            Self::PermissionDenied { .. } => tonic::Code::PermissionDenied,
+            Self::NotFound { .. } => tonic::Code::NotFound,
+            Self::RateLimited { .. } => tonic::Code::ResourceExhausted,
+            Self::Timeout { .. } => tonic::Code::DeadlineExceeded,
+            Self::Unavailable { .. } | Self::CircuitOpen { .. } => tonic::Code::Unavailable,
+        }
+    }
+
+    /// OpenAI `error.type`
+    fn error_type(&self) -> &'static str {
+        match self {
+            Self::InvalidRequest { .. }
+            | Self::ContextLengthExceeded { .. }
+            | Self::ContentFiltered { .. }
+            | Self::NotFound { .. } => "invalid_request_error",
+            Self::Authentication { .. } => "authentication_error",
+            Self::PermissionDenied { .. } => "permission_error",
+            Self::RateLimited { .. } => "rate_limit_error",
+            Self::Timeout { .. } | Self::Unavailable { .. } | Self::CircuitOpen { .. } => "server_error",
+        }
+    }
+
+    /// OpenAI `error.code`
+    fn error_code(&self) -> &'static str {
+        match self {
+            Self::ContextLengthExceeded { .. } => "context_length_exceeded",
+            Self::ContentFiltered { .. } => "content_filter",
+            Self::NotFound { .. } => "model_not_found",
+            Self::RateLimited { .. } => "rate_limit_exceeded",
+            Self::Authentication { .. } => "invalid_api_key",
+            Self::PermissionDenied { .. } => "permission_denied",
+            Self::Timeout { .. } => "upstream_timeout",
+            Self::Unavailable { .. } | Self::CircuitOpen { .. } => "upstream_unavailable",
+            Self::InvalidRequest { .. } => "invalid_request",
+        }
+    }
+
+    /// OpenAI-style `{"error": {...}}` body
+    pub fn to_json(&self) -> Value {
+        json!({
+            "error": {
+                "message": self.to_string(),
+                "type": self.error_type(),
+                "code": self.error_code(),
+                "param": null,
+                "provider": self.provider(),
+            }
+        })
+    }
+
+    pub fn to_status(&self) -> tonic::Status {
+        tonic::Status::new(self.grpc_code(), self.to_string())
+    }
+}
+
+impl IntoResponse for ProviderError {
+    fn into_response(self) -> Response {
+        let mut response = (self.http_status(), Json(self.to_json())).into_response();
+        if let Some(delay) = self.retry_after() {
+            let secs = delay.as_secs_f64().ceil() as u64;
+            if let Ok(value) = HeaderValue::from_str(&secs.to_string()) {
+                response.headers_mut().insert(header::RETRY_AFTER, value);
+            }
+        }
+        response
+    }
+}
+
+/// HTTP status for any error coming out of the router
+pub fn http_status(err: &anyhow::Error) -> StatusCode {
+    ProviderError::find(err)
+        .map(|e| e.http_status())
+        .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// OpenAI-style error response for any error coming out of the router
+pub fn error_response(err: &anyhow::Error) -> Response {
+    match ProviderError::find(err) {
+        Some(provider_error) => provider_error.clone().into_response(),
+        None => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "error": {
+                    "message": err.to_string(),
+                    "type": "server_error",
+                    "code": null,
+                    "param": null,
+                }
+            })),
+        )
+            .into_response(),
+    }
+}
+
+/// gRPC status for any error coming out of the router
+pub fn grpc_status(err: &anyhow::Error) -> tonic::Status {
+    match ProviderError::find(err) {
+        Some(provider_error) => provider_error.to_status(),
+        None => tonic::Status::internal(err.to_string()),
+    }
+}
+
+/// Parse a Retry-After header (delta-seconds or HTTP date)
+pub fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<f64>() {
+        return (secs >= 0.0 && secs.is_finite()).then(|| Duration::from_secs_f64(secs));
+    }
+
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let delta = date.signed_duration_since(chrono::Utc::now());
+    Some(delta.to_std().unwrap_or(Duration::ZERO))
+}
+
+/// Pull a human-readable message out of an upstream error body
+fn extract_message(body: &str) -> String {
+    let message = serde_json::from_str::<Value>(body)
+        .ok()
+        .and_then(|v| {
+            v.pointer("/error/message")
+                .or_else(|| v.get("message"))
+                .or_else(|| v.get("error"))
+                .and_then(|m| m.as_str().map(str::to_string))
+        })
+        .unwrap_or_else(|| body.trim().to_string());
+
+    if message.is_empty() {
+        return "no error details from upstream".to_string();
+    }
+
+    match message.char_indices().nth(MAX_MESSAGE_LEN) {
+        Some((idx, _)) => format!("{}…", &message[..idx]),
+        None => message,
+    }
+}
+
+fn is_context_overflow(body: &str) -> bool {
+    [
+        "context_length_exceeded",
+        "maximum context length",
+        "context window",
+        "prompt is too long",
+        "input is too long",
+        "too many tokens",
+        "exceeds the maximum number of tokens",
+        "input token count",
+    ]
+    .iter()
+    .any(|pattern| body.contains(pattern))
+}
+
+fn is_content_filter(body: &str) -> bool {
+    [
+        "content_filter",
+        "content_policy",
+        "content policy",
+        "responsible_ai_policy",
+        "safety settings",
+        "blocked due to safety",
+    ]
+    .iter()
+    .any(|pattern| body.contains(pattern))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_classification() {
+        assert!(matches!(ProviderError::from_status("openai", 401, "", None), ProviderError::Authentication { .. }));
+        assert!(matches!(ProviderError::from_status("openai", 403, "", None), ProviderError::PermissionDenied { .. }));
+        assert!(matches!(ProviderError::from_status("openai", 404, "", None), ProviderError::NotFound { .. }));
+        assert!(matches!(ProviderError::from_status("openai", 422, "", None), ProviderError::InvalidRequest { .. }));
+        assert!(matches!(ProviderError::from_status("anthropic", 529, "", None), ProviderError::Unavailable { .. }));
+        assert!(matches!(ProviderError::from_status("xai", 502, "", None), ProviderError::Unavailable { .. }));
+
+        let err = ProviderError::from_status("openai", 429, "", Some(Duration::from_secs(7)));
+        assert_eq!(err.retry_after(), Some(Duration::from_secs(7)));
+        assert!(err.is_retryable());
+    }
+
+    #[test]
+    fn test_body_classification() {
+        let openai = r#"{"error":{"message":"This model's maximum context length is 8192 tokens","type":"invalid_request_error","code":"context_length_exceeded"}}"#;
+        let err = ProviderError::from_status("openai", 400, openai, None);
+        assert!(matches!(err, ProviderError::ContextLengthExceeded { .. }));
+        assert_eq!(err.message(), "This model's maximum context length is 8192 tokens");
+
+        let anthropic = r#"{"type":"error","error":{"type":"invalid_request_error","message":"prompt is too long: 210000 tokens > 200000 maximum"}}"#;
+        assert!(matches!(
+            ProviderError::from_status("anthropic", 400, anthropic, None),
+            ProviderError::ContextLengthExceeded { .. }
+        ));
+
+        let filtered = r#"{"error":{"message":"blocked","code":"content_filter"}}"#;
+        assert!(matches!(
+            ProviderError::from_status("openai", 400, filtered, None),
+            ProviderError::ContentFiltered { .. }
+        ));
+
+        // Rate limits and outages keep their class whatever the body says
+        let tokens = r#"{"error":{"message":"Rate limit reached for tokens per min (TPM): maximum context length"}}"#;
+        assert!(matches!(
+            ProviderError::from_status("openai", 429, tokens, None),
+            ProviderError::RateLimited { .. }
+        ));
+        assert!(matches!(
+            ProviderError::from_status("openai", 503, filtered, None),
+            ProviderError::Unavailable { .. }
+        ));
+        assert!(matches!(
+            ProviderError::from_status("openai", 413, "", None),
+            ProviderError::ContextLengthExceeded { .. }
+        ));
+    }
+
+    #[test]
+    fn test_routing_decisions() {
+        let invalid = ProviderError::from_status("openai", 400, "bad", None);
+        assert!(!invalid.is_retryable());
+        assert!(!invalid.should_fallback());
+        assert!(!invalid.counts_against_provider());
+
+        let auth = ProviderError::from_status("openai", 401, "bad key", None);
+        assert!(!auth.is_retryable());
+        assert!(auth.should_fallback());
+
+        let down = ProviderError::from_status("openai", 503, "down", None);
+        assert!(down.is_retryable());
+        assert!(down.should_fallback());
+        assert!(down.counts_against_provider());
+
+        // Never reached the provider: try the next one instead of waiting
+        let open = ProviderError::CircuitOpen { provider: "openai".to_string() };
+        assert!(!open.is_retryable());
+        assert!(open.should_fallback());
+        assert!(!open.counts_against_provider());
+    }
+
+    #[test]
+    fn test_status_mapping() {
+        let err = ProviderError::from_status("openai", 429, "slow down", Some(Duration::from_secs(2)));
+        assert_eq!(err.http_status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(err.grpc_code(), tonic::Code::ResourceExhausted);
+
+        let response = err.clone().into_response();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers().get(header::RETRY_AFTER).unwrap(), "2");
+
+        let body = err.to_json();
+        assert_eq!(body["error"]["type"], "rate_limit_error");
+        assert_eq!(body["error"]["code"], "rate_limit_exceeded");
+    }
+
+    #[test]
+    fn test_find_through_context() {
+        let err: anyhow::Error = ProviderError::from_status("gemini", 401, "nope", None).into();
+        let err = err.context("All providers in fallback chain failed");
+
+        assert_eq!(http_status(&err), StatusCode::UNAUTHORIZED);
+        assert_eq!(grpc_status(&err).code(), tonic::Code::Unauthenticated);
+        assert_eq!(http_status(&anyhow::anyhow!("boom")), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        assert_eq!(parse_retry_after("30"), Some(Duration::from_secs(30)));
+        assert_eq!(parse_retry_after("1.5"), Some(Duration::from_millis(1500)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), Some(Duration::ZERO));
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+}
@@ -1,8 +1,22 @@
+/// Provider health tracking
+///
+/// A background task checks every enabled provider on `health_check.interval_secs`
+/// with its real credentials (see `Provider::health`). Results are kept here for
+/// the HTTP and gRPC health endpoints and fed to the router's circuit breaker,
+/// so providers that fail their checks are skipped before users hit them.
+///
+/// Readiness (`/readyz`, `grpc.health.v1`) is separate: the config is loaded,
+/// every listener has bound, at least one provider can take traffic, and the
+/// server isn't draining for shutdown.
+use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::sync::Arc;
-use std::time::{Duration, SystemTime};
+use std::collections::{BTreeSet, HashMap};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
 use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
 
 /// Health status for a single provider
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
@@ -13,204 +27,279 @@ pub enum HealthStatus {
     Unhealthy,
 }
 
+impl HealthStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HealthStatus::Healthy => "healthy",
+            HealthStatus::Degraded => "degraded",
+            HealthStatus::Unhealthy => "unhealthy",
+        }
+    }
+}
+
 /// Health check result for a provider
 #[derive(Debug, Clone, Serialize)]
 pub struct ProviderHealth {
     pub status: HealthStatus,
     pub latency_ms: Option<u64>,
+    /// Unix timestamp of the check
     pub last_check: u64,
     pub error: Option<String>,
+    /// Failed checks in a row
+    pub consecutive_failures: u32,
+}
+
+struct Entry {
+    health: ProviderHealth,
+    checked: Instant,
 }
 
-/// Cached health results
-struct HealthCache {
-    results: HashMap<String, (ProviderHealth, SystemTime)>,
-    ttl: Duration,
+/// Latest check result per provider, plus process uptime
+pub struct HealthChecker {
+    results: Arc<RwLock<HashMap<String, Entry>>>,
+    started: Instant,
 }
 
-impl HealthCache {
-    fn new(ttl_seconds: u64) -> Self {
+impl HealthChecker {
+    pub fn new() -> Self {
         Self {
-            results: HashMap::new(),
-            ttl: Duration::from_secs(ttl_seconds),
+            results: Arc::new(RwLock::new(HashMap::new())),
+            started: Instant::now(),
         }
     }
 
-    fn get(&self, provider: &str) -> Option<ProviderHealth> {
-        self.results.get(provider).and_then(|(health, timestamp)| {
-            if timestamp.elapsed().ok()? < self.ttl {
-                Some(health.clone())
-            } else {
-                None
-            }
-        })
+    /// Seconds since this checker (i.e. the server) started
+    pub fn uptime_secs(&self) -> u64 {
+        self.started.elapsed().as_secs()
     }
 
-    fn set(&mut self, provider: String, health: ProviderHealth) {
-        self.results.insert(provider, (health, SystemTime::now()));
+    /// Latest result for a provider, if checked within `max_age`
+    pub async fn get(&self, provider: &str, max_age: Duration) -> Option<ProviderHealth> {
+        let results = self.results.read().await;
+        results
+            .get(provider)
+            .filter(|entry| entry.checked.elapsed() <= max_age)
+            .map(|entry| entry.health.clone())
+    }
+
+    /// Store the outcome of a check
+    ///
+    /// A failure is `Degraded` until `unhealthy_threshold` checks in a row
+    /// have failed, then `Unhealthy`.
+    pub async fn record(
+        &self,
+        provider: &str,
+        latency: Duration,
+        error: Option<String>,
+        unhealthy_threshold: u32,
+    ) -> ProviderHealth {
+        let mut results = self.results.write().await;
+        let previous_failures = results.get(provider).map_or(0, |entry| entry.health.consecutive_failures);
+
+        let consecutive_failures = if error.is_some() { previous_failures + 1 } else { 0 };
+        let status = match &error {
+            None => HealthStatus::Healthy,
+            Some(_) if consecutive_failures >= unhealthy_threshold => HealthStatus::Unhealthy,
+            Some(_) => HealthStatus::Degraded,
+        };
+
+        let health = ProviderHealth {
+            status,
+            latency_ms: Some(latency.as_millis() as u64),
+            last_check: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            error,
+            consecutive_failures,
+        };
+
+        results.insert(provider.to_string(), Entry {
+            health: health.clone(),
+            checked: Instant::now(),
+        });
+        health
     }
 }
 
-/// Health checker with caching
-pub struct HealthChecker {
-    cache: Arc<RwLock<HealthCache>>,
+impl Default for HealthChecker {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-impl HealthChecker {
-    pub fn new() -> Self {
-        Self {
-            cache: Arc::new(RwLock::new(HealthCache::new(30))), // 30s TTL
+/// Overall status: healthy if every provider is, unhealthy if none is or
+/// there are no providers at all
+pub fn overall_status<'a>(providers: impl IntoIterator<Item = &'a ProviderHealth>) -> HealthStatus {
+    let (mut healthy, mut total) = (0, 0);
+    for health in providers {
+        total += 1;
+        if health.status == HealthStatus::Healthy {
+            healthy += 1;
         }
     }
 
-    /// Check health of a provider (with caching)
-    pub async fn check_provider(&self, provider: &str, endpoint: &str) -> ProviderHealth {
-        // Check cache first
-        {
-            let cache = self.cache.read().await;
-            if let Some(cached) = cache.get(provider) {
-                return cached;
-            }
-        }
+    if total == 0 {
+        HealthStatus::Unhealthy
+    } else if healthy == total {
+        HealthStatus::Healthy
+    } else if healthy > 0 {
+        HealthStatus::Degraded
+    } else {
+        HealthStatus::Unhealthy
+    }
+}
 
-        // Perform actual health check
-        let health = self.ping_endpoint(provider, endpoint).await;
+/// Process-wide startup progress for readiness probes
+pub struct Startup {
+    config_loaded: AtomicBool,
+    expected: Mutex<BTreeSet<&'static str>>,
+    bound: Mutex<BTreeSet<&'static str>>,
+}
 
-        // Update cache
-        {
-            let mut cache = self.cache.write().await;
-            cache.set(provider.to_string(), health.clone());
-        }
+pub static STARTUP: Lazy<Startup> = Lazy::new(|| Startup {
+    config_loaded: AtomicBool::new(false),
+    expected: Mutex::new(BTreeSet::new()),
+    bound: Mutex::new(BTreeSet::new()),
+});
 
-        health
+impl Startup {
+    pub fn config_loaded(&self) {
+        self.config_loaded.store(true, Ordering::Relaxed);
     }
 
-    /// Ping a provider endpoint
-    async fn ping_endpoint(&self, provider: &str, base_url: &str) -> ProviderHealth {
-        let start = SystemTime::now();
-        let client = reqwest::Client::builder()
-            .timeout(Duration::from_secs(5))
-            .build()
-            .unwrap();
-
-        // Provider-specific health check endpoints
-        let (url, method) = match provider {
-            "anthropic" => (format!("{}/v1/messages", base_url), "POST"),
-            "openai" => (format!("{}/v1/models", base_url), "GET"),
-            "gemini" => (format!("{}/v1beta/models", base_url), "GET"),
-            "xai" => (format!("{}/v1/models", base_url), "GET"),
-            "ollama" => (format!("{}/api/tags", base_url), "GET"),
-            "github_copilot" => {
-                // GitHub Copilot requires auth, just check GitHub API
-                ("https://api.github.com/zen".to_string(), "GET")
-            }
-            _ => return ProviderHealth {
-                status: HealthStatus::Unhealthy,
-                latency_ms: None,
-                last_check: SystemTime::now()
-                    .duration_since(SystemTime::UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs(),
-                error: Some("Unknown provider".to_string()),
-            },
-        };
+    /// Declare a listener that must bind before the server is ready
+    pub fn expect_listener(&self, name: &'static str) {
+        self.expected.lock().unwrap().insert(name);
+    }
 
-        let result = match method {
-            "GET" => client.get(&url).send().await,
-            "POST" => {
-                // For POST endpoints, send minimal request
-                client
-                    .post(&url)
-                    .header("Content-Type", "application/json")
-                    .body("{}")
-                    .send()
-                    .await
-            }
-            _ => unreachable!(),
-        };
+    pub fn listener_bound(&self, name: &'static str) {
+        self.bound.lock().unwrap().insert(name);
+    }
+
+    /// Expected listeners that haven't bound yet
+    pub fn pending_listeners(&self) -> Vec<&'static str> {
+        let bound = self.bound.lock().unwrap();
+        self.expected.lock().unwrap().difference(&bound).copied().collect()
+    }
+}
+
+/// Result of a readiness check
+#[derive(Debug, Clone, Serialize)]
+pub struct Readiness {
+    pub ready: bool,
+    /// "ready", "not_ready", or "draining" during shutdown
+    pub status: &'static str,
+    pub config_loaded: bool,
+    pub listeners_bound: bool,
+    pub pending_listeners: Vec<&'static str>,
+    /// Enabled providers that are not unhealthy and whose circuit isn't open
+    pub available_providers: Vec<String>,
+}
+
+/// Whether this server should receive traffic
+pub async fn readiness(router: &crate::router::Router) -> Readiness {
+    let config_loaded = STARTUP.config_loaded.load(Ordering::Relaxed);
+    let pending_listeners = STARTUP.pending_listeners();
+    let available_providers = router.available_providers().await;
+    let draining = crate::shutdown::SHUTDOWN.is_draining();
 
-        let latency = start.elapsed().ok().map(|d| d.as_millis() as u64);
-        let timestamp = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-
-        match result {
-            Ok(response) => {
-                let status_code = response.status().as_u16();
-                let status = match status_code {
-                    200..=299 => HealthStatus::Healthy,
-                    400..=499 => {
-                        // Client errors might mean endpoint exists but auth failed (healthy)
-                        if status_code == 401 || status_code == 403 {
-                            HealthStatus::Healthy // Auth error means service is up
-                        } else {
-                            HealthStatus::Degraded
-                        }
+    let ready = !draining && config_loaded && pending_listeners.is_empty() && !available_providers.is_empty();
+    let status = match (draining, ready) {
+        (true, _) => "draining",
+        (false, true) => "ready",
+        (false, false) => "not_ready",
+    };
+
+    Readiness {
+        ready,
+        status,
+        config_loaded,
+        listeners_bound: pending_listeners.is_empty(),
+        pending_listeners,
+        available_providers,
+    }
+}
+
+/// Start checking the current router's providers in the background
+///
+/// `[health_check]` is re-read before every round, so reloads can turn the
+/// checks on or off and change their interval.
+pub fn spawn_monitor(live: Arc<crate::reload::LiveRouter>) -> JoinHandle<()> {
+    let settings = live.config().health_check.clone();
+    if settings.enabled {
+        info!("Provider health checks every {}s ({} probe)", settings.interval_secs, settings.probe);
+    }
+
+    tokio::spawn(async move {
+        loop {
+            let router = live.current();
+            let settings = router.config().health_check.clone();
+            if settings.enabled {
+                let results = router.check_all_providers().await;
+                for (provider, health) in &results {
+                    match health.status {
+                        HealthStatus::Healthy => debug!("Health check {}: healthy", provider),
+                        _ => warn!(
+                            "Health check {}: {} ({})",
+                            provider,
+                            health.status.as_str(),
+                            health.error.as_deref().unwrap_or("unknown error")
+                        ),
                     }
-                    500..=599 => HealthStatus::Degraded,
-                    _ => HealthStatus::Unhealthy,
-                };
-
-                ProviderHealth {
-                    status,
-                    latency_ms: latency,
-                    last_check: timestamp,
-                    error: if status != HealthStatus::Healthy {
-                        Some(format!("HTTP {}", status_code))
-                    } else {
-                        None
-                    },
-                }
-            }
-            Err(e) => {
-                // Connection errors
-                ProviderHealth {
-                    status: HealthStatus::Unhealthy,
-                    latency_ms: latency,
-                    last_check: timestamp,
-                    error: Some(format!("{}", e)),
                 }
             }
+
+            // Checks in progress finish on the router they started with
+            drop(router);
+            tokio::time::sleep(Duration::from_secs(settings.interval_secs)).await;
         }
-    }
+    })
+}
 
-    /// Check all providers in parallel
-    pub async fn check_all(
-        &self,
-        providers: HashMap<String, String>,
-    ) -> HashMap<String, ProviderHealth> {
-        use futures::future::join_all;
-
-        // Spawn parallel health checks
-        let futures: Vec<_> = providers
-            .into_iter()
-            .map(|(provider, endpoint)| {
-                let checker = self.clone();
-                async move {
-                    let health = checker.check_provider(&provider, &endpoint).await;
-                    (provider, health)
-                }
-            })
-            .collect();
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        // Wait for all checks to complete
-        let results = join_all(futures).await;
+    #[tokio::test]
+    async fn test_failures_become_unhealthy() {
+        let checker = HealthChecker::new();
+        let latency = Duration::from_millis(5);
 
-        // Convert to HashMap
-        results.into_iter().collect()
-    }
+        let first = checker.record("p", latency, Some("HTTP 503".to_string()), 2).await;
+        assert_eq!(first.status, HealthStatus::Degraded);
 
-    /// Clone for parallel execution
-    fn clone(&self) -> Self {
-        Self {
-            cache: Arc::clone(&self.cache),
-        }
+        let second = checker.record("p", latency, Some("HTTP 503".to_string()), 2).await;
+        assert_eq!(second.status, HealthStatus::Unhealthy);
+        assert_eq!(second.consecutive_failures, 2);
+
+        let recovered = checker.record("p", latency, None, 2).await;
+        assert_eq!(recovered.status, HealthStatus::Healthy);
+        assert_eq!(recovered.consecutive_failures, 0);
+
+        assert!(checker.get("p", Duration::from_secs(60)).await.is_some());
+        assert!(checker.get("p", Duration::ZERO).await.is_none());
     }
-}
 
-impl Default for HealthChecker {
-    fn default() -> Self {
-        Self::new()
+    #[test]
+    fn test_overall_status() {
+        let health = |status| ProviderHealth {
+            status,
+            latency_ms: None,
+            last_check: 0,
+            error: None,
+            consecutive_failures: 0,
+        };
+
+        let all = [health(HealthStatus::Healthy), health(HealthStatus::Healthy)];
+        assert_eq!(overall_status(&all), HealthStatus::Healthy);
+
+        let mixed = [health(HealthStatus::Healthy), health(HealthStatus::Unhealthy)];
+        assert_eq!(overall_status(&mixed), HealthStatus::Degraded);
+
+        let none = [health(HealthStatus::Degraded)];
+        assert_eq!(overall_status(&none), HealthStatus::Unhealthy);
+
+        assert_eq!(overall_status(&[]), HealthStatus::Unhealthy);
     }
 }
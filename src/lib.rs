@@ -10,6 +10,16 @@ pub mod circuit_breaker;
 pub mod cache;
 pub mod models_dev;
 pub mod health;
+pub mod error;
+pub mod retry;
+pub mod adaptive;
+pub mod balancer;
+pub mod rules;
+pub mod context_window;
+pub mod tokenizer;
+pub mod shutdown;
+pub mod reload;
+pub mod secret;
 
 // Re-export commonly used types
 pub use config::Config;
@@ -1,5 +1,5 @@
 use anyhow::Result;
-use thanos::{auth::{AnthropicOAuth, GitHubOAuth, KeyringStore, OAuthTokens}, config::Config, server};
+use thanos::{auth::{token_store, AnthropicOAuth, GitHubOAuth, KeyringStore, OAuthTokens}, config::{Config, OAuthConfig, Severity, Sources}, secret::Secret, server};
 use tracing::info;
 use tracing_subscriber::{fmt, prelude::*, EnvFilter};
 
@@ -11,11 +11,15 @@ async fn main() -> Result<()> {
     if args.len() >= 2 && args[1] == "auth" {
         // Handle auth subcommand
         if args.len() < 3 {
-            eprintln!("Usage: thanos auth <claude|copilot|status|clear>");
+            eprintln!("Usage: thanos auth <claude|copilot|set <provider>|status|clear> [--config <file>] [--profile <name>]");
             std::process::exit(1);
         }
 
-        let keyring = KeyringStore::new("thanos");
+        // Tokens go wherever the server will look for them
+        dotenvy::dotenv().ok();
+        let (sources, rest) = config_sources(args.get(3..).unwrap_or_default())?;
+        let oauth = oauth_config(&sources)?;
+        let store = token_store::from_config(&oauth)?;
 
         match args[2].as_str() {
             "claude" => {
@@ -30,9 +34,9 @@ async fn main() -> Result<()> {
                     expires_at: Some(expires_at),
                 };
 
-                keyring.store_oauth_tokens("anthropic_max", &tokens)?;
+                store.store_oauth_tokens("anthropic_max", &tokens)?;
 
-                println!("🔒 Tokens stored securely in system keyring");
+                println!("🔒 Tokens stored securely in {}", store.describe());
                 println!("⏰ Expires: {}\n", chrono::DateTime::from_timestamp(expires_at, 0).unwrap());
             }
 
@@ -46,12 +50,29 @@ async fn main() -> Result<()> {
                     expires_at: Some(expires_at),
                 };
 
-                keyring.store_oauth_tokens("github_copilot", &tokens)?;
+                store.store_oauth_tokens("github_copilot", &tokens)?;
 
-                println!("🔒 Tokens stored securely in system keyring");
+                println!("🔒 Tokens stored securely in {}", store.describe());
                 println!("⏰ Expires: {}\n", chrono::DateTime::from_timestamp(expires_at, 0).unwrap());
             }
 
+            "set" => {
+                let Some(name) = rest.first() else {
+                    eprintln!("Usage: thanos auth set <provider>   (reads the API key from stdin)");
+                    std::process::exit(1);
+                };
+
+                let secret = read_secret(&format!("API key for {}: ", name))?;
+                if secret.is_empty() {
+                    eprintln!("No API key given, nothing stored");
+                    std::process::exit(1);
+                }
+                KeyringStore::new(&oauth.keyring_service).store_secret(name, &secret)?;
+
+                println!("🔒 API key stored securely in system keyring");
+                println!("   Use it with: [providers.{}] api_key = \"keyring:{}\"\n", name, name);
+            }
+
             "status" => {
                 println!("\n🔐 Authentication Status");
                 println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n");
@@ -62,7 +83,7 @@ async fn main() -> Result<()> {
                 ];
 
                 for (id, name) in providers {
-                    if let Ok(Some(tokens)) = keyring.get_oauth_tokens(id) {
+                    if let Ok(Some(tokens)) = store.get_oauth_tokens(id) {
                         if let Some(expires_at) = tokens.expires_at {
                             let expires = chrono::DateTime::from_timestamp(expires_at, 0).unwrap();
                             let now = chrono::Utc::now();
@@ -92,15 +113,86 @@ async fn main() -> Result<()> {
             }
 
             "clear" => {
-                keyring.delete_oauth_tokens("github_copilot")?;
-                keyring.delete_oauth_tokens("anthropic_max")?;
+                store.delete_oauth_tokens("github_copilot")?;
+                store.delete_oauth_tokens("anthropic_max")?;
 
-                println!("\n✅ All tokens cleared from keyring\n");
+                println!("\n✅ All tokens cleared from {}\n", store.describe());
             }
 
             _ => {
                 eprintln!("Unknown auth command: {}", args[2]);
-                eprintln!("Use: claude, copilot, status, or clear");
+                eprintln!("Use: claude, copilot, set, status, or clear");
+                std::process::exit(1);
+            }
+        }
+
+        return Ok(());
+    }
+
+    if args.len() >= 2 && args[1] == "config" {
+        dotenvy::dotenv().ok();
+
+        // A path after the command is the project file, like --config
+        let (sources, rest) = config_sources(args.get(3..).unwrap_or_default())?;
+        let effective = rest.iter().any(|arg| arg == "--effective");
+        let sources = match rest.iter().find(|arg| !arg.starts_with("--")) {
+            Some(path) => {
+                let named = Sources::discover(Some(path.into()));
+                Sources { files: named.files, untrusted: named.untrusted, ..sources }
+            }
+            None => sources,
+        };
+        let label = sources.label();
+
+        match args.get(2).map(String::as_str) {
+            Some("check") => {
+                let problems = match Config::check(&sources) {
+                    Ok((_, problems)) => problems,
+                    Err(e) => {
+                        eprintln!("❌ {}: {:#}", label, e);
+                        std::process::exit(1);
+                    }
+                };
+
+                for problem in &problems {
+                    println!("{}", problem.display(&label));
+                }
+
+                let errors = problems.iter().filter(|p| p.severity == Severity::Error).count();
+                let warnings = problems.len() - errors;
+                if problems.is_empty() {
+                    println!("✅ {}: no problems found", label);
+                } else {
+                    println!("\n{}: {} error(s), {} warning(s)", label, errors, warnings);
+                }
+                if errors > 0 {
+                    std::process::exit(1);
+                }
+            }
+
+            Some("show") if effective => {
+                // Layers merged, defaults filled in, variables substituted, secrets redacted
+                let (config, problems) = Config::check(&sources)?;
+                for problem in &problems {
+                    eprintln!("{}", problem.display(&label));
+                }
+                println!("# {}", label);
+                if let Some(profile) = &sources.profile {
+                    println!("# profile: {}", profile);
+                }
+                print!("{}", config.to_redacted_toml()?);
+            }
+
+            Some("show") => {
+                // Each file as written, lowest precedence first
+                for path in &sources.files {
+                    println!("# {}", path.display());
+                    print!("{}", std::fs::read_to_string(path)?);
+                }
+            }
+
+            _ => {
+                eprintln!("Usage: thanos config <check|show [--effective]> [path] [--profile <name>] [--set <key=value>]...");
                 std::process::exit(1);
             }
         }
@@ -122,8 +214,16 @@ async fn main() -> Result<()> {
     info!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
 
     // Load configuration
-    let config = Config::load()?;
-    info!("✓ Configuration loaded");
+    dotenvy::dotenv().ok();
+    let (sources, rest) = config_sources(&args[1..])?;
+    if let Some(arg) = rest.first() {
+        anyhow::bail!("Unknown argument: {} (expected --config, --profile or --set)", arg);
+    }
+    let config = Config::load_sources(&sources)?;
+    info!("✓ Configuration loaded from {}", sources.label());
+    if let Some(profile) = &sources.profile {
+        info!("  Profile: {}", profile);
+    }
     info!("  HTTP: {}", config.server.bind);
     info!("  gRPC: {}", config.server.grpc);
 
@@ -141,7 +241,64 @@ async fn main() -> Result<()> {
     }
 
     // Start servers (HTTP + gRPC concurrently)
-    server::run(config).await?;
+    server::run(config, sources).await?;
 
     Ok(())
 }
+
+/// One line from stdin, without echoing it when typed at a terminal
+fn read_secret(prompt: &str) -> Result<Secret> {
+    use std::io::{BufRead, IsTerminal, Write};
+
+    let interactive = std::io::stdin().is_terminal();
+    let stty = |arg: &str| {
+        let _ = std::process::Command::new("stty").arg(arg).stdin(std::process::Stdio::inherit()).status();
+    };
+    if interactive {
+        eprint!("{}", prompt);
+        std::io::stderr().flush()?;
+        stty("-echo");
+    }
+
+    let mut line = zeroize::Zeroizing::new(String::new());
+    let read = std::io::stdin().lock().read_line(&mut line);
+    if interactive {
+        stty("echo");
+        eprintln!();
+    }
+    read?;
+    Ok(Secret::new(line.trim()))
+}
+
+/// `[oauth]` from the config files, or the defaults when there are none
+fn oauth_config(sources: &Sources) -> Result<OAuthConfig> {
+    if sources.files.is_empty() {
+        return Ok(OAuthConfig::default());
+    }
+    Config::load_oauth(sources)
+}
+
+/// Config layers from `--config <file>`, `--profile <name>` and
+/// `--set <section.key=value>` flags, and the arguments left over
+fn config_sources(args: &[String]) -> Result<(Sources, Vec<String>)> {
+    let mut project = None;
+    let mut profile = None;
+    let mut overrides = Vec::new();
+    let mut rest = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = |flag: &str| args.next().cloned().ok_or_else(|| anyhow::anyhow!("{} needs a value", flag));
+        match arg.as_str() {
+            "--config" | "-c" => project = Some(value(arg)?.into()),
+            "--profile" | "-p" => profile = Some(value(arg)?),
+            "--set" => overrides.push(value(arg)?),
+            _ => rest.push(arg.clone()),
+        }
+    }
+
+    let mut sources = Sources::discover(project);
+    sources.profile = profile.or(sources.profile);
+    sources.overrides = overrides;
+    Ok((sources, rest))
+}
@@ -11,11 +11,15 @@ pub struct Metrics {
     pub requests_total: CounterVec,
     pub request_duration_seconds: HistogramVec,
     pub requests_in_flight: GaugeVec,
+    pub client_requests_total: CounterVec,
 
     // Provider metrics
     pub provider_requests_total: CounterVec,
     pub provider_errors_total: CounterVec,
     pub provider_duration_seconds: HistogramVec,
+    pub provider_retries_total: CounterVec,
+    pub hedges_total: CounterVec,
+    pub routing_rule_matches_total: CounterVec,
 
     // Token metrics
     pub tokens_used_total: CounterVec,
@@ -32,6 +36,15 @@ pub struct Metrics {
     // Circuit breaker metrics
     pub circuit_breaker_state: GaugeVec, // 0 = closed, 1 = open, 2 = half-open
     pub circuit_breaker_failures: CounterVec,
+
+    // Config reload metrics
+    pub config_reloads_total: CounterVec,
+    pub config_last_reload_success: Gauge,
+
+    // OAuth token metrics
+    pub oauth_refreshes_total: CounterVec,
+    pub oauth_token_expires_in_seconds: GaugeVec,
+    pub oauth_token_warning: GaugeVec,
 }
 
 impl Metrics {
@@ -87,6 +100,38 @@ impl Metrics {
             &["provider", "model"],
         )?;
 
+        let provider_retries_total = CounterVec::new(
+            Opts::new(
+                "thanos_provider_retries_total",
+                "Total number of retried provider attempts",
+            ),
+            &["provider", "reason"],
+        )?;
+
+        let hedges_total = CounterVec::new(
+            Opts::new(
+                "thanos_hedges_total",
+                "Hedged requests fired and won, per hedge provider",
+            ),
+            &["provider", "event"], // event: fired, won
+        )?;
+
+        let client_requests_total = CounterVec::new(
+            Opts::new(
+                "thanos_client_requests_total",
+                "Requests per mutual TLS client identity",
+            ),
+            &["client", "transport"], // transport: http, http3, grpc
+        )?;
+
+        let routing_rule_matches_total = CounterVec::new(
+            Opts::new(
+                "thanos_routing_rule_matches_total",
+                "Requests routed by each [[routing.rules]] entry",
+            ),
+            &["rule"],
+        )?;
+
         // Token metrics
         let tokens_used_total = CounterVec::new(
             Opts::new(
@@ -146,6 +191,45 @@ impl Metrics {
             &["provider"],
         )?;
 
+        // Config reload metrics
+        let config_reloads_total = CounterVec::new(
+            Opts::new(
+                "thanos_config_reloads_total",
+                "Total number of config reloads by result",
+            ),
+            &["result", "trigger"],
+        )?;
+
+        let config_last_reload_success = Gauge::new(
+            "thanos_config_last_reload_success",
+            "Whether the last config reload succeeded (1) or failed (0)",
+        )?;
+
+        // OAuth token metrics
+        let oauth_refreshes_total = CounterVec::new(
+            Opts::new(
+                "thanos_oauth_refreshes_total",
+                "OAuth token refreshes by result",
+            ),
+            &["provider", "result"], // result: success, failure
+        )?;
+
+        let oauth_token_expires_in_seconds = GaugeVec::new(
+            Opts::new(
+                "thanos_oauth_token_expires_in_seconds",
+                "Seconds until each provider's OAuth access token expires",
+            ),
+            &["provider"],
+        )?;
+
+        let oauth_token_warning = GaugeVec::new(
+            Opts::new(
+                "thanos_oauth_token_warning",
+                "1 when a token's refresh failed, or it expires within refresh_warning_hours and can't be refreshed",
+            ),
+            &["provider"],
+        )?;
+
         // Register all metrics
         registry.register(Box::new(requests_total.clone()))?;
         registry.register(Box::new(request_duration_seconds.clone()))?;
@@ -153,6 +237,10 @@ impl Metrics {
         registry.register(Box::new(provider_requests_total.clone()))?;
         registry.register(Box::new(provider_errors_total.clone()))?;
         registry.register(Box::new(provider_duration_seconds.clone()))?;
+        registry.register(Box::new(provider_retries_total.clone()))?;
+        registry.register(Box::new(hedges_total.clone()))?;
+        registry.register(Box::new(client_requests_total.clone()))?;
+        registry.register(Box::new(routing_rule_matches_total.clone()))?;
         registry.register(Box::new(tokens_used_total.clone()))?;
         registry.register(Box::new(estimated_cost_usd.clone()))?;
         registry.register(Box::new(cache_hits_total.clone()))?;
@@ -161,15 +249,24 @@ impl Metrics {
         registry.register(Box::new(rate_limit_exceeded_total.clone()))?;
         registry.register(Box::new(circuit_breaker_state.clone()))?;
         registry.register(Box::new(circuit_breaker_failures.clone()))?;
+        registry.register(Box::new(config_reloads_total.clone()))?;
+        registry.register(Box::new(config_last_reload_success.clone()))?;
+        registry.register(Box::new(oauth_refreshes_total.clone()))?;
+        registry.register(Box::new(oauth_token_expires_in_seconds.clone()))?;
+        registry.register(Box::new(oauth_token_warning.clone()))?;
 
         Ok(Self {
             registry,
             requests_total,
             request_duration_seconds,
             requests_in_flight,
+            client_requests_total,
             provider_requests_total,
             provider_errors_total,
             provider_duration_seconds,
+            provider_retries_total,
+            hedges_total,
+            routing_rule_matches_total,
             tokens_used_total,
             estimated_cost_usd,
             cache_hits_total,
@@ -178,6 +275,11 @@ impl Metrics {
             rate_limit_exceeded_total,
             circuit_breaker_state,
             circuit_breaker_failures,
+            config_reloads_total,
+            config_last_reload_success,
+            oauth_refreshes_total,
+            oauth_token_expires_in_seconds,
+            oauth_token_warning,
         })
     }
 }
@@ -19,6 +19,13 @@ pub struct ModelInfo {
     pub supports_reasoning: Option<bool>,
 }
 
+/// Token limits of a model
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModelLimits {
+    pub context: Option<u32>, // prompt + completion tokens
+    pub output: Option<u32>,  // completion tokens
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Pricing {
     pub input: f64,  // USD per 1M tokens
@@ -192,6 +199,23 @@ impl ModelsDevClient {
         let cache = self.cache.lock().unwrap();
         cache.models.values().cloned().collect()
     }
+
+    /// Limits from the cache only (never fetches, safe on the request path)
+    pub fn cached_limits(&self, model_id: &str) -> Option<ModelLimits> {
+        let cache = self.cache.lock().unwrap();
+        let model = cache.models.get(model_id)?;
+        let limits = ModelLimits {
+            context: model.context_length.and_then(|n| u32::try_from(n).ok()).filter(|&n| n > 0),
+            output: model.output_limit.and_then(|n| u32::try_from(n).ok()).filter(|&n| n > 0),
+        };
+        (limits != ModelLimits::default()).then_some(limits)
+    }
+
+    /// Pricing from the cache only (never fetches, safe on the request path)
+    pub fn cached_pricing(&self, model_id: &str) -> Option<Pricing> {
+        let cache = self.cache.lock().unwrap();
+        cache.models.get(model_id).and_then(|m| m.pricing.clone())
+    }
 }
 
 /// Global models.dev client
@@ -263,6 +287,45 @@ pub fn get_fallback_pricing(model_id: &str) -> Option<Pricing> {
     }
 }
 
+/// Well-known model limits (fallback when models.dev is unavailable)
+pub fn get_fallback_limits(model_id: &str) -> Option<ModelLimits> {
+    let (context, output) = match model_id {
+        // Anthropic Claude
+        "claude-opus-4-20250514" => (200_000, 32_000),
+        "claude-sonnet-4-5-20250513" => (200_000, 64_000),
+        "claude-haiku-4-5-20250513" => (200_000, 64_000),
+        // OpenAI
+        "gpt-5" => (400_000, 128_000),
+        "gpt-4o" => (128_000, 16_384),
+        "o3-mini" => (200_000, 100_000),
+        // Google Gemini
+        "gemini-2.5-pro" => (1_048_576, 65_536),
+        "gemini-2.0-flash-exp" => (1_048_576, 8_192),
+        // xAI Grok
+        "grok-2-latest" => (131_072, 32_768),
+        _ => return None,
+    };
+
+    Some(ModelLimits {
+        context: Some(context),
+        output: Some(output),
+    })
+}
+
+/// Limits for a model from cached models.dev data or the built-in table
+pub fn limits_with_fallback(model_id: &str) -> Option<ModelLimits> {
+    MODELS_DEV_CLIENT
+        .cached_limits(model_id)
+        .or_else(|| get_fallback_limits(model_id))
+}
+
+/// Pricing for a model from cached models.dev data or the built-in table
+pub fn pricing_with_fallback(model_id: &str) -> Option<Pricing> {
+    MODELS_DEV_CLIENT
+        .cached_pricing(model_id)
+        .or_else(|| get_fallback_pricing(model_id))
+}
+
 /// Calculate cost with fallback to hardcoded pricing
 pub async fn calculate_cost_with_fallback(
     model_id: &str,
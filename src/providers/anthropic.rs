@@ -1,4 +1,6 @@
+use crate::error::ProviderError;
 use crate::providers::Provider;
+use crate::secret::Secret;
 use crate::types::{ChatRequest, ChatResponse, Role, Usage};
 use anyhow::Result;
 use async_trait::async_trait;
@@ -6,7 +8,7 @@ use serde::{Deserialize, Serialize};
 use tokio::sync::mpsc;
 
 pub struct AnthropicProvider {
-    api_key: Option<String>,
+    api_key: Option<Secret>,
     use_oauth: bool,
     base_url: String,
     #[allow(dead_code)]
@@ -14,9 +16,9 @@ pub struct AnthropicProvider {
 }
 
 impl AnthropicProvider {
-    pub fn new(api_key: String, model: String) -> Self {
+    pub fn new(api_key: impl Into<Secret>, model: String) -> Self {
         Self {
-            api_key: Some(api_key),
+            api_key: Some(api_key.into()),
             use_oauth: false,
             base_url: "https://api.anthropic.com".to_string(),
             model,
@@ -37,20 +39,25 @@ impl AnthropicProvider {
             .unwrap_or_else(|| "claude-sonnet-4-5-20250513".to_string());
 
         // Check if this is OAuth (anthropic_max) or API key
-        if config.auth_method == crate::types::AuthMethod::OAuth {
-            Ok(Self::new_oauth(model))
+        let mut provider = if config.auth_method == crate::types::AuthMethod::OAuth {
+            Self::new_oauth(model)
         } else {
             let api_key = config.api_key.clone()
                 .ok_or_else(|| anyhow::anyhow!("Anthropic API key not configured"))?;
-            Ok(Self::new(api_key, model))
+            Self::new(api_key, model)
+        };
+
+        if let Some(base_url) = &config.base_url {
+            provider.base_url = base_url.trim_end_matches('/').to_string();
         }
+
+        Ok(provider)
     }
 
-    async fn get_api_key(&self) -> Result<String> {
+    async fn get_api_key(&self) -> Result<Secret> {
         if self.use_oauth {
-            // Use TokenManager to get/refresh OAuth token
-            let token_manager = crate::auth::TokenManager::new();
-            token_manager.get_access_token("anthropic_max").await
+            // Shared, cached token, refreshed once for all requests
+            crate::auth::TOKENS.get_access_token("anthropic_max").await
         } else {
             self.api_key.clone()
                 .ok_or_else(|| anyhow::anyhow!("No API key configured"))
@@ -67,6 +74,55 @@ impl AnthropicProvider {
     ) -> Result<mpsc::Receiver<Result<ChatResponse>>> {
         <Self as Provider>::chat_completion_stream(self, request).await
     }
+
+    /// Exact prompt token count from the Messages `count_tokens` endpoint
+    pub async fn count_tokens(&self, request: &ChatRequest) -> Result<u32> {
+        let api_key = self.get_api_key().await?;
+        let client = reqwest::Client::new();
+
+        let messages: Vec<AnthropicMessage> = request
+            .messages
+            .iter()
+            .filter(|m| m.role != Role::System)
+            .map(|m| AnthropicMessage {
+                role: match m.role {
+                    Role::Assistant => "assistant".to_string(),
+                    _ => "user".to_string(),
+                },
+                content: m.content.clone(),
+            })
+            .collect();
+
+        let system = request
+            .messages
+            .iter()
+            .find(|m| m.role == Role::System)
+            .map(|m| m.content.clone())
+            .or_else(|| request.system.clone());
+
+        let count_req = CountTokensRequest {
+            model: request.model.clone(),
+            messages,
+            system,
+        };
+
+        let res = client
+            .post(format!("{}/v1/messages/count_tokens", self.base_url))
+            .header("x-api-key", api_key.expose())
+            .header("anthropic-version", "2023-06-01")
+            .header("content-type", "application/json")
+            .json(&count_req)
+            .send()
+            .await
+            .map_err(|e| ProviderError::from_reqwest("anthropic", &e))?;
+
+        if !res.status().is_success() {
+            return Err(ProviderError::from_response("anthropic", res).await.into());
+        }
+
+        let count: CountTokensResponse = res.json().await?;
+        Ok(count.input_tokens)
+    }
 }
 
 // Anthropic API types
@@ -82,6 +138,19 @@ struct AnthropicRequest {
     stream: bool,
 }
 
+#[derive(Serialize)]
+struct CountTokensRequest {
+    model: String,
+    messages: Vec<AnthropicMessage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct CountTokensResponse {
+    input_tokens: u32,
+}
+
 #[derive(Serialize, Deserialize)]
 struct AnthropicMessage {
     role: String,
@@ -182,8 +251,14 @@ impl Provider for AnthropicProvider {
     }
 
     async fn health(&self) -> Result<bool> {
-        // Simple health check: verify we can get an API key
-        Ok(self.get_api_key().await.is_ok())
+        // Listing one model is free and exercises the credentials
+        let api_key = self.get_api_key().await?;
+        let request = reqwest::Client::new()
+            .get(format!("{}/v1/models?limit=1", self.base_url))
+            .header("x-api-key", api_key.expose())
+            .header("anthropic-version", "2023-06-01");
+
+        super::probe("anthropic", request).await
     }
 
     async fn chat_completion(&self, request: &ChatRequest) -> Result<ChatResponse> {
@@ -224,16 +299,16 @@ impl Provider for AnthropicProvider {
 
         let res = client
             .post(format!("{}/v1/messages", self.base_url))
-            .header("x-api-key", &api_key)
+            .header("x-api-key", api_key.expose())
             .header("anthropic-version", "2023-06-01")
             .header("content-type", "application/json")
             .json(&anthropic_req)
             .send()
-            .await?;
+            .await
+            .map_err(|e| ProviderError::from_reqwest("anthropic", &e))?;
 
         if !res.status().is_success() {
-            let error_text = res.text().await?;
-            anyhow::bail!("Anthropic API error: {}", error_text);
+            return Err(ProviderError::from_response("anthropic", res).await.into());
         }
 
         let anthropic_res: AnthropicResponse = res.json().await?;
@@ -300,12 +375,12 @@ impl Provider for AnthropicProvider {
         let base_url = self.base_url.clone();
         let model = request.model.clone();
 
-        tokio::spawn(async move {
+        super::spawn_stream("anthropic", tx.clone(), async move {
             let client = reqwest::Client::new();
 
             let res = match client
                 .post(format!("{}/v1/messages", base_url))
-                .header("x-api-key", &api_key)
+                .header("x-api-key", api_key.expose())
                 .header("anthropic-version", "2023-06-01")
                 .header("content-type", "application/json")
                 .json(&anthropic_req)
@@ -314,14 +389,13 @@ impl Provider for AnthropicProvider {
             {
                 Ok(r) => r,
                 Err(e) => {
-                    let _ = tx.send(Err(e.into())).await;
+                    let _ = tx.send(Err(ProviderError::from_reqwest("anthropic", &e).into())).await;
                     return;
                 }
             };
 
             if !res.status().is_success() {
-                let error_text = res.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-                let _ = tx.send(Err(anyhow::anyhow!("Anthropic API error: {}", error_text))).await;
+                let _ = tx.send(Err(ProviderError::from_response("anthropic", res).await.into())).await;
                 return;
             }
 
@@ -354,59 +428,59 @@ impl Provider for AnthropicProvider {
                             }
 
                             // Process the event
-                            if let Some(data) = event_data {
-                                if let Ok(event) = serde_json::from_str::<StreamEvent>(&data) {
-                                    match event {
-                                        StreamEvent::MessageStart { message } => {
-                                            input_tokens = message.usage.input_tokens;
-                                        }
-                                        StreamEvent::ContentBlockDelta { delta, .. } => {
-                                            let ContentDelta::TextDelta { text } = delta;
-                                            let response = ChatResponse {
-                                                provider: "anthropic".to_string(),
-                                                model: model.clone(),
-                                                content: text,
-                                                done: false,
-                                                usage: None,
-                                                finish_reason: None,
-                                            };
-
-                                            if tx.send(Ok(response)).await.is_err() {
-                                                return;
-                                            }
-                                        }
-                                        StreamEvent::MessageDelta { delta, usage } => {
-                                            finish_reason = delta.stop_reason;
-                                            if let Some(u) = usage {
-                                                output_tokens = u.output_tokens;
-                                            }
-                                        }
-                                        StreamEvent::MessageStop => {
-                                            // Send final chunk with usage
-                                            let response = ChatResponse {
-                                                provider: "anthropic".to_string(),
-                                                model: model.clone(),
-                                                content: String::new(),
-                                                done: true,
-                                                usage: Some(Usage {
-                                                    prompt_tokens: input_tokens,
-                                                    completion_tokens: output_tokens,
-                                                    total_tokens: input_tokens + output_tokens,
-                                                }),
-                                                finish_reason: finish_reason.clone(),
-                                            };
-
-                                            let _ = tx.send(Ok(response)).await;
+                            if let Some(data) = event_data
+                                && let Ok(event) = serde_json::from_str::<StreamEvent>(&data)
+                            {
+                                match event {
+                                    StreamEvent::MessageStart { message } => {
+                                        input_tokens = message.usage.input_tokens;
+                                    }
+                                    StreamEvent::ContentBlockDelta { delta, .. } => {
+                                        let ContentDelta::TextDelta { text } = delta;
+                                        let response = ChatResponse {
+                                            provider: "anthropic".to_string(),
+                                            model: model.clone(),
+                                            content: text,
+                                            done: false,
+                                            usage: None,
+                                            finish_reason: None,
+                                        };
+
+                                        if tx.send(Ok(response)).await.is_err() {
                                             return;
                                         }
-                                        _ => {} // Ignore other events
                                     }
+                                    StreamEvent::MessageDelta { delta, usage } => {
+                                        finish_reason = delta.stop_reason;
+                                        if let Some(u) = usage {
+                                            output_tokens = u.output_tokens;
+                                        }
+                                    }
+                                    StreamEvent::MessageStop => {
+                                        // Send final chunk with usage
+                                        let response = ChatResponse {
+                                            provider: "anthropic".to_string(),
+                                            model: model.clone(),
+                                            content: String::new(),
+                                            done: true,
+                                            usage: Some(Usage {
+                                                prompt_tokens: input_tokens,
+                                                completion_tokens: output_tokens,
+                                                total_tokens: input_tokens + output_tokens,
+                                            }),
+                                            finish_reason: finish_reason.clone(),
+                                        };
+
+                                        let _ = tx.send(Ok(response)).await;
+                                        return;
+                                    }
+                                    _ => {} // Ignore other events
                                 }
                             }
                         }
                     }
                     Err(e) => {
-                        let _ = tx.send(Err(e.into())).await;
+                        let _ = tx.send(Err(ProviderError::from_reqwest("anthropic", &e).into())).await;
                         return;
                     }
                 }
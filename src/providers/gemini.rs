@@ -1,21 +1,26 @@
+use crate::error::ProviderError;
 use crate::providers::Provider;
+use crate::secret::Secret;
 use crate::types::{ChatRequest, ChatResponse, Role, Usage};
 use anyhow::Result;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use tokio::sync::mpsc;
 
+/// Sent as a header rather than `?key=`, so it can't end up in logged URLs
+const API_KEY_HEADER: &str = "x-goog-api-key";
+
 pub struct GeminiProvider {
-    api_key: String,
+    api_key: Secret,
     base_url: String,
     #[allow(dead_code)]
     model: String,
 }
 
 impl GeminiProvider {
-    pub fn new(api_key: String, model: String) -> Self {
+    pub fn new(api_key: impl Into<Secret>, model: String) -> Self {
         Self {
-            api_key,
+            api_key: api_key.into(),
             base_url: "https://generativelanguage.googleapis.com".to_string(),
             model,
         }
@@ -27,7 +32,12 @@ impl GeminiProvider {
         let model = config.model.clone()
             .unwrap_or_else(|| "gemini-2.5-pro".to_string());
 
-        Ok(Self::new(api_key, model))
+        let mut provider = Self::new(api_key, model);
+        if let Some(base_url) = &config.base_url {
+            provider.base_url = base_url.trim_end_matches('/').to_string();
+        }
+
+        Ok(provider)
     }
 
     pub async fn chat_completion(&self, request: &ChatRequest) -> Result<ChatResponse> {
@@ -40,6 +50,45 @@ impl GeminiProvider {
     ) -> Result<mpsc::Receiver<Result<ChatResponse>>> {
         <Self as Provider>::chat_completion_stream(self, request).await
     }
+
+    /// Exact prompt token count from the `countTokens` endpoint
+    ///
+    /// System text is counted as a leading user turn, since `countTokens`
+    /// only accepts `contents`.
+    pub async fn count_tokens(&self, request: &ChatRequest) -> Result<u32> {
+        let client = reqwest::Client::new();
+
+        let system = request.system.iter().map(|s| (Role::User, s));
+        let messages = request.messages.iter().map(|m| (m.role.clone(), &m.content));
+        let contents: Vec<GeminiContent> = system
+            .chain(messages)
+            .map(|(role, text)| GeminiContent {
+                parts: vec![GeminiPart { text: text.clone() }],
+                role: Some(match role {
+                    Role::Assistant => "model".to_string(),
+                    _ => "user".to_string(),
+                }),
+            })
+            .collect();
+
+        let url = format!("{}/v1beta/models/{}:countTokens", self.base_url, self.model);
+
+        let res = client
+            .post(&url)
+            .header(API_KEY_HEADER, self.api_key.expose())
+            .header("content-type", "application/json")
+            .json(&CountTokensRequest { contents })
+            .send()
+            .await
+            .map_err(|e| ProviderError::from_reqwest("gemini", &e))?;
+
+        if !res.status().is_success() {
+            return Err(ProviderError::from_response("gemini", res).await.into());
+        }
+
+        let count: CountTokensResponse = res.json().await?;
+        Ok(count.total_tokens)
+    }
 }
 
 // Gemini API types
@@ -52,6 +101,17 @@ struct GeminiRequest {
     generation_config: Option<GenerationConfig>,
 }
 
+#[derive(Serialize)]
+struct CountTokensRequest {
+    contents: Vec<GeminiContent>,
+}
+
+#[derive(Deserialize)]
+struct CountTokensResponse {
+    #[serde(rename = "totalTokens")]
+    total_tokens: u32,
+}
+
 #[derive(Serialize)]
 struct GeminiContent {
     parts: Vec<GeminiPart>,
@@ -88,6 +148,17 @@ struct GeminiError {
     status: Option<String>,
 }
 
+/// Classify an error object embedded in a Gemini response body
+fn gemini_error(error: &GeminiError) -> ProviderError {
+    let message = format!(
+        "{} (status: {})",
+        error.message,
+        error.status.as_deref().unwrap_or("UNKNOWN")
+    );
+    let code = error.code.and_then(|c| u16::try_from(c).ok()).unwrap_or(500);
+    ProviderError::from_status("gemini", code, &message, None)
+}
+
 #[derive(Deserialize, Debug)]
 struct GeminiCandidate {
     content: GeminiContentResponse,
@@ -136,8 +207,11 @@ impl Provider for GeminiProvider {
     }
 
     async fn health(&self) -> Result<bool> {
-        // Simple health check: verify API key format
-        Ok(!self.api_key.is_empty())
+        let request = reqwest::Client::new()
+            .get(format!("{}/v1beta/models?pageSize=1", self.base_url))
+            .header(API_KEY_HEADER, self.api_key.expose());
+
+        super::probe("gemini", request).await
     }
 
     async fn chat_completion(&self, request: &ChatRequest) -> Result<ChatResponse> {
@@ -200,36 +274,29 @@ impl Provider for GeminiProvider {
             generation_config,
         };
 
-        let url = format!(
-            "{}/v1beta/models/{}:generateContent?key={}",
-            self.base_url, self.model, self.api_key
-        );
+        let url = format!("{}/v1beta/models/{}:generateContent", self.base_url, self.model);
 
         let res = client
             .post(&url)
+            .header(API_KEY_HEADER, self.api_key.expose())
             .header("content-type", "application/json")
             .json(&gemini_req)
             .send()
-            .await?;
+            .await
+            .map_err(|e| ProviderError::from_reqwest("gemini", &e))?;
 
-        let status = res.status();
-        let response_text = res.text().await?;
-
-        if !status.is_success() {
-            anyhow::bail!("Gemini API error ({}): {}", status, response_text);
+        if !res.status().is_success() {
+            return Err(ProviderError::from_response("gemini", res).await.into());
         }
 
+        let response_text = res.text().await?;
+
         let gemini_res: GeminiResponse = serde_json::from_str(&response_text)
             .map_err(|e| anyhow::anyhow!("Failed to parse Gemini response: {}. Response: {}", e, response_text))?;
 
         // Check for API error in response
         if let Some(error) = gemini_res.error {
-            anyhow::bail!(
-                "Gemini API error {}: {} (status: {})",
-                error.code.unwrap_or(0),
-                error.message,
-                error.status.unwrap_or_else(|| "UNKNOWN".to_string())
-            );
+            return Err(gemini_error(&error).into());
         }
 
         // Extract content from candidates
@@ -337,16 +404,14 @@ impl Provider for GeminiProvider {
         let model_name = self.model.clone();
         let request_model = request.model.clone();
 
-        tokio::spawn(async move {
+        super::spawn_stream("gemini", tx.clone(), async move {
             let client = reqwest::Client::new();
 
-            let url = format!(
-                "{}/v1beta/models/{}:streamGenerateContent?key={}",
-                base_url, model_name, api_key
-            );
+            let url = format!("{}/v1beta/models/{}:streamGenerateContent", base_url, model_name);
 
             let res = match client
                 .post(&url)
+                .header(API_KEY_HEADER, api_key.expose())
                 .header("content-type", "application/json")
                 .json(&gemini_req)
                 .send()
@@ -354,15 +419,13 @@ impl Provider for GeminiProvider {
             {
                 Ok(r) => r,
                 Err(e) => {
-                    let _ = tx.send(Err(e.into())).await;
+                    let _ = tx.send(Err(ProviderError::from_reqwest("gemini", &e).into())).await;
                     return;
                 }
             };
 
-            let status = res.status();
-            if !status.is_success() {
-                let error_text = res.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-                let _ = tx.send(Err(anyhow::anyhow!("Gemini API error ({}): {}", status, error_text))).await;
+            if !res.status().is_success() {
+                let _ = tx.send(Err(ProviderError::from_response("gemini", res).await.into())).await;
                 return;
             }
 
@@ -391,44 +454,38 @@ impl Provider for GeminiProvider {
                                 Ok(stream_res) => {
                                     // Check for error in response
                                     if let Some(error) = stream_res.error {
-                                        let err_msg = format!(
-                                            "Gemini API error {}: {} (status: {})",
-                                            error.code.unwrap_or(0),
-                                            error.message,
-                                            error.status.unwrap_or_else(|| "UNKNOWN".to_string())
-                                        );
-                                        let _ = tx.send(Err(anyhow::anyhow!(err_msg))).await;
+                                        let _ = tx.send(Err(gemini_error(&error).into())).await;
                                         return;
                                     }
 
                                     // Process candidates
-                                    if let Some(candidates) = stream_res.candidates.as_ref() {
-                                        if let Some(candidate) = candidates.first() {
-                                            let content = candidate
-                                                .content
-                                                .parts
-                                                .iter()
-                                                .map(|p| p.text.clone())
-                                                .collect::<Vec<_>>()
-                                                .join("");
-
-                                            if !content.is_empty() {
-                                                let response = ChatResponse {
-                                                    provider: "gemini".to_string(),
-                                                    model: request_model.clone(),
-                                                    content,
-                                                    done: candidate.finish_reason.is_some(),
-                                                    usage: stream_res.usage_metadata.as_ref().map(|u| Usage {
-                                                        prompt_tokens: u.prompt_token_count.unwrap_or(0),
-                                                        completion_tokens: u.candidates_token_count.unwrap_or(0),
-                                                        total_tokens: u.total_token_count.unwrap_or(0),
-                                                    }),
-                                                    finish_reason: candidate.finish_reason.clone(),
-                                                };
-
-                                                if tx.send(Ok(response)).await.is_err() {
-                                                    return;
-                                                }
+                                    if let Some(candidates) = stream_res.candidates.as_ref()
+                                        && let Some(candidate) = candidates.first()
+                                    {
+                                        let content = candidate
+                                            .content
+                                            .parts
+                                            .iter()
+                                            .map(|p| p.text.clone())
+                                            .collect::<Vec<_>>()
+                                            .join("");
+
+                                        if !content.is_empty() {
+                                            let response = ChatResponse {
+                                                provider: "gemini".to_string(),
+                                                model: request_model.clone(),
+                                                content,
+                                                done: candidate.finish_reason.is_some(),
+                                                usage: stream_res.usage_metadata.as_ref().map(|u| Usage {
+                                                    prompt_tokens: u.prompt_token_count.unwrap_or(0),
+                                                    completion_tokens: u.candidates_token_count.unwrap_or(0),
+                                                    total_tokens: u.total_token_count.unwrap_or(0),
+                                                }),
+                                                finish_reason: candidate.finish_reason.clone(),
+                                            };
+
+                                            if tx.send(Ok(response)).await.is_err() {
+                                                return;
                                             }
                                         }
                                     }
@@ -442,7 +499,7 @@ impl Provider for GeminiProvider {
                         }
                     }
                     Err(e) => {
-                        let _ = tx.send(Err(e.into())).await;
+                        let _ = tx.send(Err(ProviderError::from_reqwest("gemini", &e).into())).await;
                         return;
                     }
                 }
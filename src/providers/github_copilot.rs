@@ -1,3 +1,4 @@
+use crate::error::ProviderError;
 use crate::providers::Provider;
 use crate::types::{ChatRequest, ChatResponse, Role, Usage};
 use anyhow::Result;
@@ -24,9 +25,8 @@ impl GitHubCopilotProvider {
         Ok(Self::new(model))
     }
 
-    async fn get_copilot_token(&self) -> Result<String> {
-        let token_manager = crate::auth::TokenManager::new();
-        token_manager.get_access_token("github_copilot").await
+    async fn get_copilot_token(&self) -> Result<crate::secret::Secret> {
+        crate::auth::TOKENS.get_access_token("github_copilot").await
     }
 
     pub async fn chat_completion(&self, request: &ChatRequest) -> Result<ChatResponse> {
@@ -85,7 +85,8 @@ impl Provider for GitHubCopilotProvider {
     }
 
     async fn health(&self) -> Result<bool> {
-        Ok(self.get_copilot_token().await.is_ok())
+        // Exchanging the GitHub token for a Copilot token is authenticated
+        self.get_copilot_token().await.map(|_| true)
     }
 
     async fn chat_completion(&self, request: &ChatRequest) -> Result<ChatResponse> {
@@ -116,17 +117,17 @@ impl Provider for GitHubCopilotProvider {
         // GitHub Copilot endpoint
         let res = client
             .post("https://api.githubcopilot.com/chat/completions")
-            .header("Authorization", format!("Bearer {}", token))
+            .header("Authorization", format!("Bearer {}", token.expose()))
             .header("Content-Type", "application/json")
             .header("Editor-Version", "vscode/1.85.0")
             .header("Editor-Plugin-Version", "copilot-chat/0.11.1")
             .json(&copilot_req)
             .send()
-            .await?;
+            .await
+            .map_err(|e| ProviderError::from_reqwest("github_copilot", &e))?;
 
         if !res.status().is_success() {
-            let error_text = res.text().await?;
-            anyhow::bail!("GitHub Copilot API error: {}", error_text);
+            return Err(ProviderError::from_response("github_copilot", res).await.into());
         }
 
         let copilot_res: CopilotResponse = res.json().await?;
@@ -3,12 +3,15 @@ pub mod gemini;
 pub mod github_copilot;
 pub mod ollama;
 pub mod openai;
+pub mod plugin;
 pub mod xai;
 
 use crate::types::{ChatRequest, ChatResponse};
 use anyhow::Result;
 use async_trait::async_trait;
+use std::future::Future;
 use tokio::sync::mpsc;
+use tracing::debug;
 
 /// Provider trait for AI model inference
 #[async_trait]
@@ -16,7 +19,9 @@ pub trait Provider: Send + Sync {
     /// Provider name
     fn name(&self) -> &str;
 
-    /// Check if provider is healthy
+    /// Check if provider is healthy, using its real credentials
+    ///
+    /// Should be cheap (e.g. listing models); errors explain what is wrong.
     async fn health(&self) -> Result<bool>;
 
     /// Chat completion (non-streaming)
@@ -28,3 +33,36 @@ pub trait Provider: Send + Sync {
         request: &ChatRequest,
     ) -> Result<mpsc::Receiver<Result<ChatResponse>>>;
 }
+
+/// Send a cheap authenticated request for a health check; any non-2xx reply
+/// becomes the matching `ProviderError`
+pub(crate) async fn probe(provider: &str, request: reqwest::RequestBuilder) -> Result<bool> {
+    let res = request
+        .send()
+        .await
+        .map_err(|e| crate::error::ProviderError::from_reqwest(provider, &e))?;
+
+    if !res.status().is_success() {
+        return Err(crate::error::ProviderError::from_response(provider, res).await.into());
+    }
+    Ok(true)
+}
+
+/// Spawn a provider's streaming task, tied to the lifetime of its receiver
+///
+/// When the consumer drops the receiver (client disconnected) the task is
+/// dropped mid-flight, which drops the in-progress reqwest request or body
+/// stream and closes the upstream connection instead of letting it finish.
+pub(crate) fn spawn_stream<F>(provider: &'static str, watcher: mpsc::Sender<Result<ChatResponse>>, task: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    tokio::spawn(async move {
+        tokio::select! {
+            _ = task => {}
+            _ = watcher.closed() => {
+                debug!("{} stream receiver dropped, aborting upstream request", provider);
+            }
+        }
+    });
+}
@@ -1,3 +1,4 @@
+use crate::error::ProviderError;
 use crate::providers::Provider;
 use crate::types::{ChatRequest, ChatResponse, Role};
 use anyhow::Result;
@@ -64,13 +65,9 @@ impl Provider for OllamaProvider {
     }
 
     async fn health(&self) -> Result<bool> {
-        let client = reqwest::Client::new();
-        let res = client
-            .get(format!("{}/api/tags", self.endpoint))
-            .send()
-            .await;
+        let request = reqwest::Client::new().get(format!("{}/api/tags", self.endpoint));
 
-        Ok(res.is_ok())
+        super::probe("ollama", request).await
     }
 
     async fn chat_completion(&self, request: &ChatRequest) -> Result<ChatResponse> {
@@ -99,11 +96,11 @@ impl Provider for OllamaProvider {
             .post(format!("{}/api/chat", self.endpoint))
             .json(&ollama_req)
             .send()
-            .await?;
+            .await
+            .map_err(|e| ProviderError::from_reqwest("ollama", &e))?;
 
         if !res.status().is_success() {
-            let error_text = res.text().await?;
-            anyhow::bail!("Ollama API error: {}", error_text);
+            return Err(ProviderError::from_response("ollama", res).await.into());
         }
 
         let ollama_res: OllamaResponse = res.json().await?;
@@ -150,7 +147,7 @@ impl Provider for OllamaProvider {
         let endpoint = self.endpoint.clone();
         let model = request.model.clone();
 
-        tokio::spawn(async move {
+        super::spawn_stream("ollama", tx.clone(), async move {
             let client = reqwest::Client::new();
 
             let res = match client
@@ -161,14 +158,13 @@ impl Provider for OllamaProvider {
             {
                 Ok(r) => r,
                 Err(e) => {
-                    let _ = tx.send(Err(e.into())).await;
+                    let _ = tx.send(Err(ProviderError::from_reqwest("ollama", &e).into())).await;
                     return;
                 }
             };
 
             if !res.status().is_success() {
-                let error_text = res.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-                let _ = tx.send(Err(anyhow::anyhow!("Ollama API error: {}", error_text))).await;
+                let _ = tx.send(Err(ProviderError::from_response("ollama", res).await.into())).await;
                 return;
             }
 
@@ -218,7 +214,7 @@ impl Provider for OllamaProvider {
                         }
                     }
                     Err(e) => {
-                        let _ = tx.send(Err(e.into())).await;
+                        let _ = tx.send(Err(ProviderError::from_reqwest("ollama", &e).into())).await;
                         return;
                     }
                 }
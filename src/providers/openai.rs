@@ -1,4 +1,6 @@
+use crate::error::ProviderError;
 use crate::providers::Provider;
+use crate::secret::Secret;
 use crate::types::{ChatRequest, ChatResponse, Role, Usage};
 use anyhow::Result;
 use async_trait::async_trait;
@@ -6,16 +8,16 @@ use serde::{Deserialize, Serialize};
 use tokio::sync::mpsc;
 
 pub struct OpenAIProvider {
-    api_key: String,
+    api_key: Secret,
     base_url: String,
     #[allow(dead_code)]
     model: String,
 }
 
 impl OpenAIProvider {
-    pub fn new(api_key: String, model: String) -> Self {
+    pub fn new(api_key: impl Into<Secret>, model: String) -> Self {
         Self {
-            api_key,
+            api_key: api_key.into(),
             base_url: "https://api.openai.com/v1".to_string(),
             model,
         }
@@ -104,7 +106,11 @@ impl Provider for OpenAIProvider {
     }
 
     async fn health(&self) -> Result<bool> {
-        Ok(!self.api_key.is_empty())
+        let request = reqwest::Client::new()
+            .get(format!("{}/models", self.base_url))
+            .bearer_auth(self.api_key.expose());
+
+        super::probe("openai", request).await
     }
 
     async fn chat_completion(&self, request: &ChatRequest) -> Result<ChatResponse> {
@@ -133,15 +139,15 @@ impl Provider for OpenAIProvider {
 
         let res = client
             .post(format!("{}/chat/completions", self.base_url))
-            .header("Authorization", format!("Bearer {}", self.api_key))
+            .header("Authorization", format!("Bearer {}", self.api_key.expose()))
             .header("content-type", "application/json")
             .json(&openai_req)
             .send()
-            .await?;
+            .await
+            .map_err(|e| ProviderError::from_reqwest("openai", &e))?;
 
         if !res.status().is_success() {
-            let error_text = res.text().await?;
-            anyhow::bail!("OpenAI API error: {}", error_text);
+            return Err(ProviderError::from_response("openai", res).await.into());
         }
 
         let openai_res: OpenAIResponse = res.json().await?;
@@ -197,12 +203,12 @@ impl Provider for OpenAIProvider {
         let base_url = self.base_url.clone();
         let model = request.model.clone();
 
-        tokio::spawn(async move {
+        super::spawn_stream("openai", tx.clone(), async move {
             let client = reqwest::Client::new();
 
             let res = match client
                 .post(format!("{}/chat/completions", base_url))
-                .header("Authorization", format!("Bearer {}", api_key))
+                .header("Authorization", format!("Bearer {}", api_key.expose()))
                 .header("content-type", "application/json")
                 .json(&openai_req)
                 .send()
@@ -210,14 +216,13 @@ impl Provider for OpenAIProvider {
             {
                 Ok(r) => r,
                 Err(e) => {
-                    let _ = tx.send(Err(e.into())).await;
+                    let _ = tx.send(Err(ProviderError::from_reqwest("openai", &e).into())).await;
                     return;
                 }
             };
 
             if !res.status().is_success() {
-                let error_text = res.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-                let _ = tx.send(Err(anyhow::anyhow!("OpenAI API error: {}", error_text))).await;
+                let _ = tx.send(Err(ProviderError::from_response("openai", res).await.into())).await;
                 return;
             }
 
@@ -243,22 +248,21 @@ impl Provider for OpenAIProvider {
                                         return;
                                     }
 
-                                    if let Ok(chunk) = serde_json::from_str::<StreamChunk>(data) {
-                                        if let Some(choice) = chunk.choices.first() {
-                                            if let Some(content) = &choice.delta.content {
-                                                let response = ChatResponse {
-                                                    provider: "openai".to_string(),
-                                                    model: model.clone(),
-                                                    content: content.clone(),
-                                                    done: choice.finish_reason.is_some(),
-                                                    usage: None,
-                                                    finish_reason: choice.finish_reason.clone(),
-                                                };
-
-                                                if tx.send(Ok(response)).await.is_err() {
-                                                    return;
-                                                }
-                                            }
+                                    if let Ok(chunk) = serde_json::from_str::<StreamChunk>(data)
+                                        && let Some(choice) = chunk.choices.first()
+                                        && let Some(content) = &choice.delta.content
+                                    {
+                                        let response = ChatResponse {
+                                            provider: "openai".to_string(),
+                                            model: model.clone(),
+                                            content: content.clone(),
+                                            done: choice.finish_reason.is_some(),
+                                            usage: None,
+                                            finish_reason: choice.finish_reason.clone(),
+                                        };
+
+                                        if tx.send(Ok(response)).await.is_err() {
+                                            return;
                                         }
                                     }
                                 }
@@ -266,7 +270,7 @@ impl Provider for OpenAIProvider {
                         }
                     }
                     Err(e) => {
-                        let _ = tx.send(Err(e.into())).await;
+                        let _ = tx.send(Err(ProviderError::from_reqwest("openai", &e).into())).await;
                         return;
                     }
                 }
@@ -0,0 +1,586 @@
+/// External-process provider plugins
+///
+/// A plugin is any executable that speaks JSON-RPC 2.0 over stdin/stdout, one
+/// JSON object per line. Thanos launches the process on first use, keeps it
+/// running across requests and restarts it with backoff if it exits.
+/// The wire protocol is documented in docs/PLUGINS.md.
+use crate::config::PluginConfig;
+use crate::error::ProviderError;
+use crate::providers::Provider;
+use crate::types::{ChatRequest, ChatResponse};
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::Command;
+use tokio::sync::{mpsc, watch};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+/// Protocol version announced to plugins in the `initialize` notification
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Maximum delay between restarts of a crashing plugin
+const MAX_RESTART_BACKOFF: Duration = Duration::from_secs(30);
+
+/// A plugin that stays up this long resets its restart backoff
+const STABLE_UPTIME: Duration = Duration::from_secs(30);
+
+/// How long a request waits for the plugin process to (re)start
+const STARTUP_WAIT: Duration = Duration::from_secs(10);
+
+/// How long to keep reading stdout after the plugin process exits
+const DRAIN_WAIT: Duration = Duration::from_secs(1);
+
+/// Running plugin hosts, keyed by provider name
+static PLUGIN_HOSTS: once_cell::sync::Lazy<Mutex<HashMap<String, Arc<PluginHost>>>> =
+    once_cell::sync::Lazy::new(|| Mutex::new(HashMap::new()));
+
+// JSON-RPC wire types
+#[derive(Serialize)]
+struct RpcRequest<'a, P: Serialize> {
+    jsonrpc: &'static str,
+    id: u64,
+    method: &'a str,
+    params: P,
+}
+
+#[derive(Serialize)]
+struct RpcNotification<'a, P: Serialize> {
+    jsonrpc: &'static str,
+    method: &'a str,
+    params: P,
+}
+
+#[derive(Deserialize, Debug)]
+struct RpcMessage {
+    id: Option<u64>,
+    method: Option<String>,
+    params: Option<Value>,
+    result: Option<Value>,
+    error: Option<RpcError>,
+}
+
+#[derive(Deserialize, Debug)]
+struct RpcError {
+    code: i64,
+    message: String,
+    #[serde(default)]
+    data: Option<RpcErrorData>,
+}
+
+/// Optional error classification, using upstream HTTP semantics
+#[derive(Deserialize, Debug)]
+struct RpcErrorData {
+    status: Option<u16>,
+    retry_after: Option<f64>,
+}
+
+impl RpcError {
+    fn into_provider_error(self, name: &str) -> ProviderError {
+        let (status, retry_after) = match &self.data {
+            Some(data) => (
+                data.status.unwrap_or(502),
+                data.retry_after.and_then(|s| Duration::try_from_secs_f64(s).ok()),
+            ),
+            None => (502, None),
+        };
+        let message = format!("{} (code {})", self.message, self.code);
+        ProviderError::from_status(name, status, &message, retry_after)
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct ChunkParams {
+    id: u64,
+    response: ChatResponse,
+}
+
+#[derive(Deserialize, Debug)]
+struct HealthResult {
+    healthy: bool,
+}
+
+/// A message routed back to the caller that issued a request
+#[derive(Debug)]
+enum Incoming {
+    Chunk(ChatResponse),
+    Result(Value),
+    Error(ProviderError),
+}
+
+/// Shared state between the supervisor task and request callers
+struct HostState {
+    /// Line writer for the current process; `None` while it is down
+    writer: watch::Sender<Option<mpsc::Sender<String>>>,
+    pending: Mutex<HashMap<u64, mpsc::UnboundedSender<Incoming>>>,
+}
+
+impl HostState {
+    /// Route a line read from plugin stdout to the waiting request
+    fn dispatch(&self, name: &str, line: &str) {
+        let message: RpcMessage = match serde_json::from_str(line) {
+            Ok(message) => message,
+            Err(e) => {
+                warn!("Plugin {} wrote invalid JSON-RPC line: {} ({})", name, line, e);
+                return;
+            }
+        };
+
+        // Streaming chunk notification
+        if message.method.as_deref() == Some("chunk") {
+            match message.params.map(serde_json::from_value::<ChunkParams>) {
+                Some(Ok(chunk)) => {
+                    let sender = self.pending.lock().unwrap().get(&chunk.id).cloned();
+                    if let Some(sender) = sender {
+                        let _ = sender.send(Incoming::Chunk(chunk.response));
+                    }
+                }
+                _ => warn!("Plugin {} sent malformed chunk notification", name),
+            }
+            return;
+        }
+
+        let Some(id) = message.id else {
+            debug!("Plugin {} sent unsolicited message: {}", name, line);
+            return;
+        };
+
+        let sender = self.pending.lock().unwrap().remove(&id);
+        if let Some(sender) = sender {
+            let incoming = match message.error {
+                Some(error) => Incoming::Error(error.into_provider_error(name)),
+                None => Incoming::Result(message.result.unwrap_or(Value::Null)),
+            };
+            let _ = sender.send(incoming);
+        }
+    }
+
+    /// Fail every in-flight request (process exited)
+    fn fail_pending(&self, name: &str, reason: &str) {
+        let pending: Vec<_> = self.pending.lock().unwrap().drain().collect();
+        for (_, sender) in pending {
+            let _ = sender.send(Incoming::Error(ProviderError::Unavailable {
+                provider: name.to_string(),
+                message: reason.to_string(),
+            }));
+        }
+    }
+}
+
+/// A supervised plugin process
+pub struct PluginHost {
+    name: String,
+    config: PluginConfig,
+    next_id: AtomicU64,
+    state: Arc<HostState>,
+    shutdown: CancellationToken,
+}
+
+impl PluginHost {
+    /// Get the running host for a provider, launching (or relaunching on config change) as needed
+    pub fn get_or_spawn(name: &str, config: &PluginConfig) -> Arc<PluginHost> {
+        let mut hosts = PLUGIN_HOSTS.lock().unwrap();
+
+        if let Some(host) = hosts.get(name)
+            && host.config == *config
+        {
+            return Arc::clone(host);
+        }
+
+        let host = Arc::new(Self::spawn(name, config.clone()));
+        // Replacing an old host drops it, which stops its process
+        hosts.insert(name.to_string(), Arc::clone(&host));
+        host
+    }
+
+    /// Stop the hosts of providers a reloaded config no longer runs as plugins
+    pub fn retain_configured(config: &crate::config::Config) {
+        PLUGIN_HOSTS.lock().unwrap().retain(|name, _| {
+            let keep = config.providers.get(name).is_some_and(|provider| provider.enabled && provider.plugin.is_some());
+            if !keep {
+                info!("Stopping plugin host for removed provider {}", name);
+            }
+            keep
+        });
+    }
+
+    fn spawn(name: &str, config: PluginConfig) -> Self {
+        let (writer, _) = watch::channel(None);
+        let state = Arc::new(HostState {
+            writer,
+            pending: Mutex::new(HashMap::new()),
+        });
+        let shutdown = CancellationToken::new();
+
+        tokio::spawn(supervise(
+            name.to_string(),
+            config.clone(),
+            Arc::clone(&state),
+            shutdown.clone(),
+        ));
+
+        Self {
+            name: name.to_string(),
+            config,
+            next_id: AtomicU64::new(1),
+            state,
+            shutdown,
+        }
+    }
+
+    /// Send a request and return the channel its replies arrive on
+    async fn request<P: Serialize>(&self, method: &str, params: P) -> Result<(u64, mpsc::UnboundedReceiver<Incoming>)> {
+        let writer = self.writer().await?;
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+
+        let line = serde_json::to_string(&RpcRequest {
+            jsonrpc: "2.0",
+            id,
+            method,
+            params,
+        })?;
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.state.pending.lock().unwrap().insert(id, tx);
+
+        if writer.send(line).await.is_err() {
+            self.state.pending.lock().unwrap().remove(&id);
+            return Err(ProviderError::Unavailable {
+                provider: self.name.clone(),
+                message: "plugin exited before the request was sent".to_string(),
+            }
+            .into());
+        }
+
+        Ok((id, rx))
+    }
+
+    /// Tell the plugin to stop working on a request (best effort)
+    fn cancel(&self, id: u64) {
+        if self.state.pending.lock().unwrap().remove(&id).is_none() {
+            return;
+        }
+
+        let writer = self.state.writer.borrow().clone();
+        if let Some(writer) = writer
+            && let Ok(line) = serde_json::to_string(&RpcNotification {
+                jsonrpc: "2.0",
+                method: "cancel",
+                params: serde_json::json!({ "id": id }),
+            })
+        {
+            let _ = writer.try_send(line);
+        }
+    }
+
+    /// Wait for the process to be up and return its writer
+    async fn writer(&self) -> Result<mpsc::Sender<String>> {
+        let mut rx = self.state.writer.subscribe();
+        let ready = tokio::time::timeout(STARTUP_WAIT, rx.wait_for(|w| w.is_some()))
+            .await
+            .map_err(|_| ProviderError::Unavailable {
+                provider: self.name.clone(),
+                message: "plugin is not running".to_string(),
+            })?
+            .map_err(|_| anyhow!("Plugin {} supervisor stopped", self.name))?;
+
+        Ok(ready.clone().expect("checked by wait_for"))
+    }
+
+    fn timeout(&self) -> Duration {
+        Duration::from_secs(self.config.request_timeout)
+    }
+}
+
+impl Drop for PluginHost {
+    fn drop(&mut self) {
+        self.shutdown.cancel();
+    }
+}
+
+/// Keep the plugin process running, restarting it with backoff when it exits
+async fn supervise(name: String, config: PluginConfig, state: Arc<HostState>, shutdown: CancellationToken) {
+    let initial_backoff = Duration::from_millis(config.restart_backoff_ms);
+    let mut backoff = initial_backoff;
+
+    loop {
+        let started = Instant::now();
+
+        match run_process(&name, &config, &state, &shutdown).await {
+            Ok(status) => warn!("Plugin {} exited ({})", name, status),
+            Err(e) => warn!("Plugin {} failed: {:#}", name, e),
+        }
+
+        state.writer.send_replace(None);
+        state.fail_pending(&name, &format!("Plugin {} exited", name));
+
+        if shutdown.is_cancelled() {
+            debug!("Plugin {} supervisor stopped", name);
+            return;
+        }
+
+        crate::metrics::METRICS.provider_errors_total
+            .with_label_values(&[&name, "plugin_restart"])
+            .inc();
+
+        if started.elapsed() >= STABLE_UPTIME {
+            backoff = initial_backoff;
+        }
+
+        info!("Restarting plugin {} in {:?}", name, backoff);
+        tokio::select! {
+            _ = tokio::time::sleep(backoff) => {}
+            _ = shutdown.cancelled() => return,
+        }
+        backoff = (backoff * 2).min(MAX_RESTART_BACKOFF);
+    }
+}
+
+/// Launch the plugin once and pump its stdio until it exits
+async fn run_process(
+    name: &str,
+    config: &PluginConfig,
+    state: &HostState,
+    shutdown: &CancellationToken,
+) -> Result<String> {
+    let mut command = Command::new(&config.command);
+    command
+        .args(&config.args)
+        .envs(&config.env)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+
+    if let Some(dir) = &config.working_dir {
+        command.current_dir(dir);
+    }
+
+    let mut child = command
+        .spawn()
+        .with_context(|| format!("Failed to launch plugin command: {}", config.command))?;
+
+    let mut stdin = child.stdin.take().context("Plugin stdin unavailable")?;
+    let stdout = child.stdout.take().context("Plugin stdout unavailable")?;
+    let stderr = child.stderr.take().context("Plugin stderr unavailable")?;
+
+    info!("✓ Plugin {} started (pid {:?})", name, child.id());
+
+    // Forward plugin stderr to our logs
+    let log_name = name.to_string();
+    tokio::spawn(async move {
+        let mut lines = BufReader::new(stderr).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            info!(target: "thanos::plugin", "[{}] {}", log_name, line);
+        }
+    });
+
+    let (line_tx, mut line_rx) = mpsc::channel::<String>(100);
+
+    // Announce ourselves before any request is written
+    let init = serde_json::to_string(&RpcNotification {
+        jsonrpc: "2.0",
+        method: "initialize",
+        params: serde_json::json!({
+            "protocol_version": PROTOCOL_VERSION,
+            "thanos_version": crate::VERSION,
+            "provider": name,
+        }),
+    })?;
+    stdin.write_all(init.as_bytes()).await?;
+    stdin.write_all(b"\n").await?;
+    stdin.flush().await?;
+
+    state.writer.send_replace(Some(line_tx));
+
+    let writer = async {
+        while let Some(line) = line_rx.recv().await {
+            stdin.write_all(line.as_bytes()).await?;
+            stdin.write_all(b"\n").await?;
+            stdin.flush().await?;
+        }
+        Ok::<_, std::io::Error>(())
+    };
+
+    let reader = async {
+        let mut lines = BufReader::new(stdout).lines();
+        while let Some(line) = lines.next_line().await? {
+            if !line.trim().is_empty() {
+                state.dispatch(name, &line);
+            }
+        }
+        Ok::<_, std::io::Error>(())
+    };
+
+    tokio::pin!(reader);
+
+    tokio::select! {
+        status = child.wait() => {
+            // Hold new requests for the restart instead of writing to a dead pipe
+            state.writer.send_replace(None);
+            // Replies written just before exit may still be buffered in the pipe
+            let _ = tokio::time::timeout(DRAIN_WAIT, &mut reader).await;
+            Ok(status?.to_string())
+        }
+        res = writer => {
+            res.context("Failed to write to plugin stdin")?;
+            Ok("stdin closed".to_string())
+        }
+        res = &mut reader => {
+            res.context("Failed to read plugin stdout")?;
+            let status = child.wait().await?;
+            Ok(status.to_string())
+        }
+        _ = shutdown.cancelled() => {
+            let _ = child.kill().await;
+            Ok("stopped".to_string())
+        }
+    }
+}
+
+/// Sends `cancel` for a request unless it already completed
+struct CancelOnDrop<'a> {
+    host: &'a PluginHost,
+    id: u64,
+}
+
+impl Drop for CancelOnDrop<'_> {
+    fn drop(&mut self) {
+        self.host.cancel(self.id);
+    }
+}
+
+/// Provider backed by an external plugin process
+pub struct PluginProvider {
+    host: Arc<PluginHost>,
+}
+
+impl PluginProvider {
+    pub fn from_config(name: &str, config: &PluginConfig) -> Result<Self> {
+        if config.command.is_empty() {
+            anyhow::bail!("Plugin provider {} has no command configured", name);
+        }
+
+        Ok(Self {
+            host: PluginHost::get_or_spawn(name, config),
+        })
+    }
+
+    pub async fn chat_completion(&self, request: &ChatRequest) -> Result<ChatResponse> {
+        <Self as Provider>::chat_completion(self, request).await
+    }
+
+    pub async fn chat_completion_stream(
+        &self,
+        request: &ChatRequest,
+    ) -> Result<mpsc::Receiver<Result<ChatResponse>>> {
+        <Self as Provider>::chat_completion_stream(self, request).await
+    }
+
+    /// Issue a request that produces a single result
+    async fn call<P: Serialize>(&self, method: &str, params: P, timeout: Duration) -> Result<Value> {
+        let (id, mut rx) = self.host.request(method, params).await?;
+        // Tells the plugin to stop if we time out or the caller goes away
+        let _cancel = CancelOnDrop { host: &self.host, id };
+
+        let reply = match tokio::time::timeout(timeout, rx.recv()).await {
+            Ok(reply) => reply,
+            Err(_) => {
+                return Err(ProviderError::Timeout {
+                    provider: self.host.name.clone(),
+                    message: format!("no reply to {} within {:?}", method, timeout),
+                }
+                .into());
+            }
+        };
+
+        match reply {
+            Some(Incoming::Result(value)) => Ok(value),
+            Some(Incoming::Error(error)) => Err(error.into()),
+            Some(Incoming::Chunk(_)) => Err(anyhow!("Plugin {} streamed a reply to {}", self.host.name, method)),
+            None => Err(anyhow!("Plugin {} dropped request {}", self.host.name, id)),
+        }
+    }
+}
+
+#[async_trait]
+impl Provider for PluginProvider {
+    fn name(&self) -> &str {
+        &self.host.name
+    }
+
+    async fn health(&self) -> Result<bool> {
+        let value = self.call("health", serde_json::json!({}), Duration::from_secs(5)).await?;
+        let result: HealthResult = serde_json::from_value(value)?;
+        Ok(result.healthy)
+    }
+
+    async fn chat_completion(&self, request: &ChatRequest) -> Result<ChatResponse> {
+        let value = self.call("chat", request, self.host.timeout()).await?;
+        serde_json::from_value(value)
+            .with_context(|| format!("Plugin {} returned an invalid chat response", self.host.name))
+    }
+
+    async fn chat_completion_stream(
+        &self,
+        request: &ChatRequest,
+    ) -> Result<mpsc::Receiver<Result<ChatResponse>>> {
+        let (id, mut replies) = self.host.request("chat_stream", request).await?;
+        let (tx, rx) = mpsc::channel(100);
+        let host = Arc::clone(&self.host);
+        let timeout = host.timeout();
+
+        tokio::spawn(async move {
+            let deadline = tokio::time::sleep(timeout);
+            tokio::pin!(deadline);
+
+            loop {
+                let reply = tokio::select! {
+                    reply = replies.recv() => reply,
+                    _ = tx.closed() => {
+                        // Consumer went away; let the plugin stop generating
+                        host.cancel(id);
+                        return;
+                    }
+                    _ = &mut deadline => {
+                        host.cancel(id);
+                        let error = ProviderError::Timeout {
+                            provider: host.name.clone(),
+                            message: format!("stream did not finish within {:?}", timeout),
+                        };
+                        let _ = tx.send(Err(error.into())).await;
+                        return;
+                    }
+                };
+
+                match reply {
+                    Some(Incoming::Chunk(response)) => {
+                        if tx.send(Ok(response)).await.is_err() {
+                            host.cancel(id);
+                            return;
+                        }
+                    }
+                    // Final reply ends the stream
+                    Some(Incoming::Result(_)) => return,
+                    Some(Incoming::Error(error)) => {
+                        let _ = tx.send(Err(error.into())).await;
+                        return;
+                    }
+                    None => {
+                        let _ = tx.send(Err(anyhow!("Plugin {} dropped stream {}", host.name, id))).await;
+                        return;
+                    }
+                }
+            }
+        });
+
+        Ok(rx)
+    }
+}
@@ -1,4 +1,6 @@
+use crate::error::ProviderError;
 use crate::providers::Provider;
+use crate::secret::Secret;
 use crate::types::{ChatRequest, ChatResponse, Role, Usage};
 use anyhow::Result;
 use async_trait::async_trait;
@@ -6,16 +8,16 @@ use serde::{Deserialize, Serialize};
 use tokio::sync::mpsc;
 
 pub struct XAIProvider {
-    api_key: String,
+    api_key: Secret,
     base_url: String,
     #[allow(dead_code)]
     model: String,
 }
 
 impl XAIProvider {
-    pub fn new(api_key: String, model: String) -> Self {
+    pub fn new(api_key: impl Into<Secret>, model: String) -> Self {
         Self {
-            api_key,
+            api_key: api_key.into(),
             base_url: "https://api.x.ai".to_string(),
             model,
         }
@@ -104,8 +106,11 @@ impl Provider for XAIProvider {
     }
 
     async fn health(&self) -> Result<bool> {
-        // Simple health check: verify API key format
-        Ok(!self.api_key.is_empty())
+        let request = reqwest::Client::new()
+            .get(format!("{}/v1/models", self.base_url))
+            .bearer_auth(self.api_key.expose());
+
+        super::probe("xai", request).await
     }
 
     async fn chat_completion(&self, request: &ChatRequest) -> Result<ChatResponse> {
@@ -135,15 +140,15 @@ impl Provider for XAIProvider {
 
         let res = client
             .post(format!("{}/v1/chat/completions", self.base_url))
-            .header("Authorization", format!("Bearer {}", self.api_key))
+            .header("Authorization", format!("Bearer {}", self.api_key.expose()))
             .header("content-type", "application/json")
             .json(&xai_req)
             .send()
-            .await?;
+            .await
+            .map_err(|e| ProviderError::from_reqwest("xai", &e))?;
 
         if !res.status().is_success() {
-            let error_text = res.text().await?;
-            anyhow::bail!("xAI API error: {}", error_text);
+            return Err(ProviderError::from_response("xai", res).await.into());
         }
 
         let xai_res: XAIResponse = res.json().await?;
@@ -199,12 +204,12 @@ impl Provider for XAIProvider {
         let base_url = self.base_url.clone();
         let model = request.model.clone();
 
-        tokio::spawn(async move {
+        super::spawn_stream("xai", tx.clone(), async move {
             let client = reqwest::Client::new();
 
             let res = match client
                 .post(format!("{}/v1/chat/completions", base_url))
-                .header("Authorization", format!("Bearer {}", api_key))
+                .header("Authorization", format!("Bearer {}", api_key.expose()))
                 .header("content-type", "application/json")
                 .json(&xai_req)
                 .send()
@@ -212,14 +217,13 @@ impl Provider for XAIProvider {
             {
                 Ok(r) => r,
                 Err(e) => {
-                    let _ = tx.send(Err(e.into())).await;
+                    let _ = tx.send(Err(ProviderError::from_reqwest("xai", &e).into())).await;
                     return;
                 }
             };
 
             if !res.status().is_success() {
-                let error_text = res.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-                let _ = tx.send(Err(anyhow::anyhow!("xAI API error: {}", error_text))).await;
+                let _ = tx.send(Err(ProviderError::from_response("xai", res).await.into())).await;
                 return;
             }
 
@@ -245,22 +249,21 @@ impl Provider for XAIProvider {
                                         return;
                                     }
 
-                                    if let Ok(chunk) = serde_json::from_str::<StreamChunk>(data) {
-                                        if let Some(choice) = chunk.choices.first() {
-                                            if let Some(content) = &choice.delta.content {
-                                                let response = ChatResponse {
-                                                    provider: "xai".to_string(),
-                                                    model: model.clone(),
-                                                    content: content.clone(),
-                                                    done: choice.finish_reason.is_some(),
-                                                    usage: None,
-                                                    finish_reason: choice.finish_reason.clone(),
-                                                };
-
-                                                if tx.send(Ok(response)).await.is_err() {
-                                                    return;
-                                                }
-                                            }
+                                    if let Ok(chunk) = serde_json::from_str::<StreamChunk>(data)
+                                        && let Some(choice) = chunk.choices.first()
+                                        && let Some(content) = &choice.delta.content
+                                    {
+                                        let response = ChatResponse {
+                                            provider: "xai".to_string(),
+                                            model: model.clone(),
+                                            content: content.clone(),
+                                            done: choice.finish_reason.is_some(),
+                                            usage: None,
+                                            finish_reason: choice.finish_reason.clone(),
+                                        };
+
+                                        if tx.send(Ok(response)).await.is_err() {
+                                            return;
                                         }
                                     }
                                 }
@@ -268,7 +271,7 @@ impl Provider for XAIProvider {
                         }
                     }
                     Err(e) => {
-                        let _ = tx.send(Err(e.into())).await;
+                        let _ = tx.send(Err(ProviderError::from_reqwest("xai", &e).into())).await;
                         return;
                     }
                 }
@@ -1,15 +1,29 @@
 /// Simple token bucket rate limiter
+///
+/// Buckets left alone for an hour are full again and past their hourly reset,
+/// so they are dropped instead of kept for every client ever seen.
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
+/// How long a bucket goes unused before it is dropped
+const IDLE_AFTER: Duration = Duration::from_secs(3600);
+
+/// How often idle buckets are looked for
+const SWEEP_EVERY: Duration = Duration::from_secs(60);
+
 #[derive(Clone)]
 pub struct RateLimiter {
-    buckets: Arc<Mutex<HashMap<String, TokenBucket>>>,
+    buckets: Arc<Mutex<Buckets>>,
     requests_per_minute: u32,
     requests_per_hour: u32,
 }
 
+struct Buckets {
+    by_key: HashMap<String, TokenBucket>,
+    swept: Instant,
+}
+
 struct TokenBucket {
     tokens: f64,
     last_refill: Instant,
@@ -20,7 +34,10 @@ struct TokenBucket {
 impl RateLimiter {
     pub fn new(requests_per_minute: u32, requests_per_hour: u32) -> Self {
         Self {
-            buckets: Arc::new(Mutex::new(HashMap::new())),
+            buckets: Arc::new(Mutex::new(Buckets {
+                by_key: HashMap::new(),
+                swept: Instant::now(),
+            })),
             requests_per_minute,
             requests_per_hour,
         }
@@ -29,8 +46,11 @@ impl RateLimiter {
     pub fn check_rate_limit(&self, key: &str) -> bool {
         let mut buckets = self.buckets.lock().unwrap();
         let now = Instant::now();
+        if now.duration_since(buckets.swept) >= SWEEP_EVERY {
+            buckets.evict_idle(now);
+        }
 
-        let bucket = buckets.entry(key.to_string()).or_insert_with(|| TokenBucket {
+        let bucket = buckets.by_key.entry(key.to_string()).or_insert_with(|| TokenBucket {
             tokens: self.requests_per_minute as f64,
             last_refill: now,
             hourly_count: 0,
@@ -64,3 +84,39 @@ impl RateLimiter {
         }
     }
 }
+
+impl Buckets {
+    /// Drop the buckets nobody has drawn from for `IDLE_AFTER`
+    fn evict_idle(&mut self, now: Instant) {
+        self.by_key
+            .retain(|_, bucket| now.duration_since(bucket.last_refill) < IDLE_AFTER);
+        self.swept = now;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limits_per_key() {
+        let limiter = RateLimiter::new(2, 100);
+        assert!(limiter.check_rate_limit("a"));
+        assert!(limiter.check_rate_limit("a"));
+        assert!(!limiter.check_rate_limit("a"));
+        assert!(limiter.check_rate_limit("b"));
+    }
+
+    #[test]
+    fn test_idle_buckets_evicted() {
+        let limiter = RateLimiter::new(60, 1000);
+        assert!(limiter.check_rate_limit("a"));
+        assert!(limiter.check_rate_limit("b"));
+
+        let mut buckets = limiter.buckets.lock().unwrap();
+        let later = Instant::now() + IDLE_AFTER;
+        buckets.by_key.get_mut("b").unwrap().last_refill = later;
+        buckets.evict_idle(later);
+        assert_eq!(buckets.by_key.keys().collect::<Vec<_>>(), ["b"]);
+    }
+}
@@ -0,0 +1,401 @@
+/// Hot configuration reload
+///
+/// The router in use, and the config it was built from, sit behind one
+/// swappable `Arc`. SIGHUP, a change to the config file, or
+/// `POST /admin/reload` re-reads and validates the config layers (files,
+/// profile and environment overrides), builds a router that
+/// carries over the old one's state, and swaps it in. Requests already running
+/// hold the router they started with and finish on the old config. Listener
+/// settings (addresses, TLS, UDS, HTTP/3) only change on restart. With
+/// `secrets.refresh_secs` set, secret references are looked up again on that
+/// schedule and a rotated API key is switched to the same way.
+use crate::config::{Config, Sources};
+use crate::router::Router;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime};
+use tracing::{info, warn};
+
+/// `[server]` settings that apply without a restart
+const LIVE_SERVER_SETTINGS: &[&str] = &["admin_token", "config_watch_secs"];
+
+/// What asked for a reload
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Trigger {
+    Signal,
+    FileChange,
+    Admin,
+    SecretRotation,
+}
+
+impl Trigger {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Trigger::Signal => "sighup",
+            Trigger::FileChange => "file",
+            Trigger::Admin => "admin",
+            Trigger::SecretRotation => "secrets",
+        }
+    }
+}
+
+/// What a reload changed
+#[derive(Debug, Default, Serialize)]
+pub struct ReloadSummary {
+    pub providers_added: Vec<String>,
+    pub providers_removed: Vec<String>,
+    pub providers_changed: Vec<String>,
+    /// Settings that changed in the file but need a restart to take effect
+    pub restart_required: Vec<String>,
+}
+
+impl ReloadSummary {
+    fn between(old: &Config, new: &Config) -> Self {
+        let mut summary = Self::default();
+
+        for (name, provider) in &new.providers {
+            match old.providers.get(name) {
+                None => summary.providers_added.push(name.clone()),
+                // Keys serialize redacted, so compare them directly
+                Some(previous) if !same(previous, provider) || previous.api_key != provider.api_key => {
+                    summary.providers_changed.push(name.clone())
+                }
+                Some(_) => {}
+            }
+        }
+        summary.providers_removed = old.providers.keys().filter(|name| !new.providers.contains_key(*name)).cloned().collect();
+
+        let old_server = serde_json::to_value(&old.server).unwrap_or_default();
+        let new_server = serde_json::to_value(&new.server).unwrap_or_default();
+        if let (Some(old_server), Some(new_server)) = (old_server.as_object(), new_server.as_object()) {
+            let keys = old_server.keys().chain(new_server.keys().filter(|key| !old_server.contains_key(*key)));
+            summary.restart_required = keys
+                .filter(|key| !LIVE_SERVER_SETTINGS.contains(&key.as_str()))
+                .filter(|key| old_server.get(*key) != new_server.get(*key))
+                .map(|key| format!("server.{}", key))
+                .collect();
+        }
+
+        summary.providers_added.sort();
+        summary.providers_removed.sort();
+        summary.providers_changed.sort();
+        summary
+    }
+}
+
+fn same<T: Serialize>(a: &T, b: &T) -> bool {
+    serde_json::to_value(a).ok() == serde_json::to_value(b).ok()
+}
+
+/// The router for the current config, swapped whole on reload
+pub struct LiveRouter {
+    current: RwLock<Arc<Router>>,
+    /// Config layers reloads read from
+    sources: Option<Sources>,
+    modified: Mutex<Vec<Option<SystemTime>>>,
+    /// Reloads run one at a time
+    reloading: Mutex<()>,
+}
+
+impl LiveRouter {
+    /// Live router that can only be changed with `apply`
+    pub fn new(router: Arc<Router>) -> Arc<Self> {
+        Arc::new(Self {
+            current: RwLock::new(router),
+            sources: None,
+            modified: Mutex::new(Vec::new()),
+            reloading: Mutex::new(()),
+        })
+    }
+
+    /// Live router reloaded from the config file it was loaded from
+    pub fn with_file(router: Arc<Router>, path: impl Into<std::path::PathBuf>) -> Arc<Self> {
+        Self::with_sources(router, Sources::file(path))
+    }
+
+    /// Live router reloaded from the config layers it was loaded from
+    pub fn with_sources(router: Arc<Router>, sources: Sources) -> Arc<Self> {
+        Arc::new(Self {
+            current: RwLock::new(router),
+            modified: Mutex::new(files_modified(&sources)),
+            sources: Some(sources),
+            reloading: Mutex::new(()),
+        })
+    }
+
+    /// Router for the current config; hold on to it for the whole request
+    pub fn current(&self) -> Arc<Router> {
+        self.current.read().unwrap().clone()
+    }
+
+    pub fn config(&self) -> Arc<Config> {
+        self.current().shared_config()
+    }
+
+    /// Re-read the config layers and switch to them
+    ///
+    /// On error nothing changes and the current config stays in effect.
+    pub fn reload(&self, trigger: Trigger) -> Result<ReloadSummary> {
+        let result = self.sources.as_ref().context("No config file to reload from").and_then(|sources| {
+            *self.modified.lock().unwrap() = files_modified(sources);
+            let config = Config::load_sources(sources)?;
+            self.swap(config)
+        });
+        record(trigger, result)
+    }
+
+    /// `reload` on the blocking pool, for async callers: loading reads files,
+    /// the keyring and `cmd:` secrets, and a new token store derives its key
+    pub async fn reload_async(self: &Arc<Self>, trigger: Trigger) -> Result<ReloadSummary> {
+        let live = self.clone();
+        tokio::task::spawn_blocking(move || live.reload(trigger))
+            .await
+            .context("Reload task failed")?
+    }
+
+    /// Look secrets up again, and switch to them if any API key changed
+    ///
+    /// Returns whether a rotated secret was applied.
+    pub fn refresh_secrets(&self) -> Result<bool> {
+        let sources = self.sources.as_ref().context("No config file to reload from")?;
+        crate::auth::SECRETS.clear_cache();
+        let config = Config::load_sources(sources)?;
+
+        let current = self.config();
+        let rotated = config.providers.iter().any(|(name, provider)| {
+            current.providers.get(name).is_some_and(|old| old.api_key != provider.api_key)
+        });
+        if !rotated {
+            return Ok(false);
+        }
+
+        *self.modified.lock().unwrap() = files_modified(sources);
+        record(Trigger::SecretRotation, self.swap(config)).map(|_| true)
+    }
+
+    /// Validate a config and switch to it
+    pub fn apply(&self, mut config: Config, trigger: Trigger) -> Result<ReloadSummary> {
+        let result = config.validate().and_then(|()| self.swap(config));
+        record(trigger, result)
+    }
+
+    /// Switch to a validated config
+    fn swap(&self, config: Config) -> Result<ReloadSummary> {
+        let _reloading = self.reloading.lock().unwrap();
+        // Before anything changes, so an unreadable key file keeps the old config
+        crate::auth::token_store::configure(&config.oauth)?;
+        let old = self.current();
+        let summary = ReloadSummary::between(old.config(), &config);
+        let router = Arc::new(old.reconfigure(Arc::new(config)));
+
+        crate::providers::plugin::PluginHost::retain_configured(router.config());
+        *self.current.write().unwrap() = router;
+        Ok(summary)
+    }
+
+    /// Whether a config file changed since they were last read
+    fn file_changed(&self) -> bool {
+        self.sources
+            .as_ref()
+            .is_some_and(|sources| files_modified(sources) != *self.modified.lock().unwrap())
+    }
+
+    /// Reload on SIGHUP, and when a config file changes (checked every
+    /// `server.config_watch_secs`)
+    pub fn spawn_watchers(self: &Arc<Self>) {
+        #[cfg(unix)]
+        {
+            let live = self.clone();
+            tokio::spawn(async move {
+                let mut hangup = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()) {
+                    Ok(hangup) => hangup,
+                    Err(e) => {
+                        warn!("Failed to install SIGHUP handler, reload on signal disabled: {}", e);
+                        return;
+                    }
+                };
+                while hangup.recv().await.is_some() {
+                    info!("Received SIGHUP, reloading config");
+                    let _ = live.reload_async(Trigger::Signal).await;
+                }
+            });
+        }
+
+        if self.sources.is_none() {
+            return;
+        }
+
+        let live = self.clone();
+        tokio::spawn(async move {
+            loop {
+                // Re-read every time, so a reload can change the interval
+                let watch_secs = live.config().server.config_watch_secs;
+                tokio::time::sleep(Duration::from_secs(watch_secs.max(1))).await;
+
+                if watch_secs > 0 && live.file_changed() {
+                    info!("Config file changed, reloading");
+                    let _ = live.reload_async(Trigger::FileChange).await;
+                }
+            }
+        });
+
+        let live = self.clone();
+        tokio::spawn(async move {
+            loop {
+                let refresh_secs = live.config().secrets.refresh_secs;
+                tokio::time::sleep(Duration::from_secs(refresh_secs.max(1))).await;
+
+                if refresh_secs == 0 {
+                    continue;
+                }
+                // Keyring lookups and `cmd:` secrets block
+                let refreshing = live.clone();
+                match tokio::task::spawn_blocking(move || refreshing.refresh_secrets()).await {
+                    Ok(Err(e)) => warn!("Secret refresh failed, keeping the current secrets: {:#}", e),
+                    Err(e) => warn!("Secret refresh failed, keeping the current secrets: {}", e),
+                    Ok(Ok(_)) => {}
+                }
+            }
+        });
+    }
+}
+
+fn files_modified(sources: &Sources) -> Vec<Option<SystemTime>> {
+    sources
+        .files
+        .iter()
+        .map(|path| std::fs::metadata(path).and_then(|meta| meta.modified()).ok())
+        .collect()
+}
+
+/// Log a reload's outcome and count it
+fn record(trigger: Trigger, result: Result<ReloadSummary>) -> Result<ReloadSummary> {
+    let metrics = &crate::metrics::METRICS;
+    match &result {
+        Ok(summary) => {
+            metrics.config_reloads_total.with_label_values(&["success", trigger.as_str()]).inc();
+            metrics.config_last_reload_success.set(1.0);
+            info!(
+                "✓ Config reloaded ({}): added {:?}, removed {:?}, changed {:?}",
+                trigger.as_str(),
+                summary.providers_added,
+                summary.providers_removed,
+                summary.providers_changed
+            );
+            if !summary.restart_required.is_empty() {
+                warn!("Restart Thanos to apply {}", summary.restart_required.join(", "));
+            }
+        }
+        Err(e) => {
+            metrics.config_reloads_total.with_label_values(&["failure", trigger.as_str()]).inc();
+            metrics.config_last_reload_success.set(0.0);
+            warn!("Config reload failed ({}), keeping the current config: {:#}", trigger.as_str(), e);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secret::Secret;
+
+    fn config(toml: &str) -> Config {
+        toml::from_str(toml).unwrap()
+    }
+
+    const BASE: &str = r#"
+        [server]
+        [routing]
+        [providers.ollama]
+        enabled = true
+        auth_method = "none"
+        base_url = "http://localhost:11434"
+    "#;
+
+    #[test]
+    fn test_summary_lists_changes() {
+        let old = config(BASE);
+        let new = config(
+            r#"
+            [server]
+            bind = "0.0.0.0:9999"
+            admin_token = "secret"
+            [routing]
+            [providers.ollama]
+            enabled = true
+            auth_method = "none"
+            base_url = "http://gpu-box:11434"
+            [providers.openai]
+            enabled = true
+            auth_method = "api_key"
+            api_key = "sk-test"
+            "#,
+        );
+
+        let summary = ReloadSummary::between(&old, &new);
+        assert_eq!(summary.providers_added, vec!["openai"]);
+        assert_eq!(summary.providers_changed, vec!["ollama"]);
+        assert!(summary.providers_removed.is_empty());
+        assert_eq!(summary.restart_required, vec!["server.bind"]);
+    }
+
+    #[test]
+    fn test_invalid_config_keeps_current() {
+        let live = LiveRouter::new(Arc::new(Router::new(Arc::new(config(BASE)))));
+        let mut invalid = config(BASE);
+        invalid.routing.balance = "random".to_string();
+
+        assert!(live.apply(invalid, Trigger::Admin).is_err());
+        assert_eq!(live.config().routing.balance, "weighted");
+        assert!(live.reload(Trigger::Signal).is_err(), "no file to reload from");
+    }
+
+    #[test]
+    fn test_rotated_secret_is_applied() {
+        let dir = std::env::temp_dir().join(format!("thanos-rotate-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let secret = dir.join("openai");
+        std::fs::write(&secret, "sk-one\n").unwrap();
+        let path = dir.join("config.toml");
+        std::fs::write(
+            &path,
+            format!(
+                "[server]\n[routing]\n[secrets]\ncache_ttl_secs = 3600\n[providers.openai]\nenabled = true\nauth_method = \"api_key\"\napi_key = \"file:{}\"\n",
+                secret.display()
+            ),
+        )
+        .unwrap();
+
+        let config = Config::load_from(&path.to_string_lossy()).unwrap();
+        assert_eq!(config.providers["openai"].api_key.as_ref().map(Secret::expose), Some("sk-one"));
+        let live = LiveRouter::with_file(Arc::new(Router::new(Arc::new(config))), &path);
+
+        assert!(!live.refresh_secrets().unwrap());
+        std::fs::write(&secret, "sk-two\n").unwrap();
+        assert!(live.refresh_secrets().unwrap());
+        assert_eq!(live.config().providers["openai"].api_key.as_ref().map(Secret::expose), Some("sk-two"));
+    }
+
+    #[tokio::test]
+    async fn test_reload_leaves_the_runtime_free() {
+        let path = std::env::temp_dir().join(format!("thanos-slow-reload-{}.toml", std::process::id()));
+        std::fs::write(
+            &path,
+            "[server]\n[routing]\n[providers.openai]\nenabled = true\nauth_method = \"api_key\"\napi_key = \"cmd:sleep 1; echo sk-slow\"\n",
+        )
+        .unwrap();
+        let live = LiveRouter::with_file(Arc::new(Router::new(Arc::new(config(BASE)))), &path);
+
+        // A single-threaded runtime keeps ticking while the command runs
+        let ticker = tokio::spawn(async {
+            for _ in 0..5 {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        });
+        live.reload_async(Trigger::Admin).await.unwrap();
+        assert!(ticker.is_finished(), "runtime was blocked during the reload");
+        assert_eq!(live.config().providers["openai"].api_key.as_ref().map(Secret::expose), Some("sk-slow"));
+    }
+}
@@ -0,0 +1,145 @@
+/// Retry policy for transient provider failures
+///
+/// Decides whether a failed attempt is worth repeating on the same provider and
+/// how long to wait first: exponential backoff with jitter, or the upstream's
+/// `Retry-After` when it sent one.
+use crate::config::RetryConfig;
+use crate::error::ProviderError;
+use rand::Rng;
+use std::time::Duration;
+
+/// Delay before attempt `attempt + 1`, or `None` if we should stop retrying
+///
+/// `attempt` is the 1-based number of the attempt that just failed.
+pub fn next_delay(config: &RetryConfig, attempt: u32, err: &anyhow::Error) -> Option<Duration> {
+    if attempt >= config.max_attempts {
+        return None;
+    }
+
+    // Untyped errors are configuration or parsing problems; repeating won't help
+    let provider_error = ProviderError::find(err)?;
+    if matches!(provider_error, ProviderError::CircuitOpen { .. }) {
+        // Wouldn't reach the provider either; let fallback take over
+        return None;
+    }
+    if !config.retry_on.iter().any(|kind| kind == provider_error.kind()) {
+        return None;
+    }
+
+    if config.respect_retry_after
+        && let Some(retry_after) = provider_error.retry_after()
+    {
+        // Asked to wait longer than we're willing to; let fallback take over
+        if retry_after > Duration::from_millis(config.max_backoff_ms) {
+            return None;
+        }
+        return Some(retry_after);
+    }
+
+    Some(backoff(config, attempt))
+}
+
+/// Exponential backoff for the given failed attempt, with jitter applied
+pub fn backoff(config: &RetryConfig, attempt: u32) -> Duration {
+    let exponent = attempt.saturating_sub(1).min(31) as i32;
+    let base_ms = (config.initial_backoff_ms as f64 * config.multiplier.max(1.0).powi(exponent))
+        .min(config.max_backoff_ms as f64);
+
+    // Randomize the top `jitter` fraction so synchronized clients spread out
+    let jitter = config.jitter.clamp(0.0, 1.0);
+    let factor = 1.0 - jitter * rand::thread_rng().r#gen::<f64>();
+
+    Duration::from_millis((base_ms * factor) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> RetryConfig {
+        RetryConfig {
+            jitter: 0.0,
+            ..Default::default()
+        }
+    }
+
+    fn error(status: u16, retry_after: Option<Duration>) -> anyhow::Error {
+        ProviderError::from_status("openai", status, "", retry_after).into()
+    }
+
+    #[test]
+    fn test_backoff_grows_and_caps() {
+        let config = RetryConfig {
+            max_backoff_ms: 1000,
+            ..config()
+        };
+
+        assert_eq!(backoff(&config, 1), Duration::from_millis(250));
+        assert_eq!(backoff(&config, 2), Duration::from_millis(500));
+        assert_eq!(backoff(&config, 3), Duration::from_millis(1000));
+        assert_eq!(backoff(&config, 10), Duration::from_millis(1000));
+    }
+
+    #[test]
+    fn test_jitter_stays_in_range() {
+        let config = RetryConfig {
+            jitter: 0.5,
+            ..Default::default()
+        };
+
+        for _ in 0..100 {
+            let delay = backoff(&config, 1);
+            assert!(delay >= Duration::from_millis(125) && delay <= Duration::from_millis(250));
+        }
+    }
+
+    #[test]
+    fn test_retryable_classes() {
+        let config = config();
+
+        assert!(next_delay(&config, 1, &error(503, None)).is_some());
+        assert!(next_delay(&config, 1, &error(429, None)).is_some());
+        assert!(next_delay(&config, 1, &error(400, None)).is_none());
+        assert!(next_delay(&config, 1, &error(401, None)).is_none());
+        assert!(next_delay(&config, 1, &anyhow::anyhow!("parse failure")).is_none());
+
+        let only_rate_limits = RetryConfig {
+            retry_on: vec!["rate_limited".to_string()],
+            ..config
+        };
+        assert!(next_delay(&only_rate_limits, 1, &error(503, None)).is_none());
+
+        let circuit_open = ProviderError::CircuitOpen { provider: "openai".to_string() }.into();
+        let opted_in = RetryConfig {
+            retry_on: vec!["circuit_open".to_string()],
+            ..only_rate_limits
+        };
+        assert!(next_delay(&opted_in, 1, &circuit_open).is_none());
+    }
+
+    #[test]
+    fn test_max_attempts() {
+        let config = config();
+
+        assert!(next_delay(&config, 2, &error(503, None)).is_some());
+        assert!(next_delay(&config, 3, &error(503, None)).is_none());
+    }
+
+    #[test]
+    fn test_retry_after() {
+        let config = config();
+
+        let delay = next_delay(&config, 1, &error(429, Some(Duration::from_secs(3))));
+        assert_eq!(delay, Some(Duration::from_secs(3)));
+
+        // Longer than max_backoff: give up on this provider
+        assert!(next_delay(&config, 1, &error(429, Some(Duration::from_secs(60)))).is_none());
+
+        let ignore = RetryConfig {
+            respect_retry_after: false,
+            ..config
+        };
+        let delay = next_delay(&ignore, 1, &error(429, Some(Duration::from_secs(60))));
+        assert_eq!(delay, Some(Duration::from_millis(250)));
+    }
+}
@@ -1,43 +1,302 @@
 use crate::config::Config;
+use crate::error::ProviderError;
+use crate::rules::RequestContext;
 use crate::types::{ChatRequest, ChatResponse, Provider};
-use anyhow::{anyhow, Result};
+use anyhow::{Result, anyhow};
+use serde::Serialize;
+use std::borrow::Cow;
 use std::sync::Arc;
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::time::Instant;
-use tracing::{debug, warn};
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+/// Routing strategies understood by the router
+pub const STRATEGIES: &[&str] = &[
+    "preferred",
+    "fallback",
+    "round-robin",
+    "hedged",
+    "adaptive",
+    "omen",
+];
+
+/// How a request will be routed, after `[[routing.rules]]`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RoutePlan {
+    /// Name of the matching rule (or `rule #N` if unnamed)
+    pub rule: Option<String>,
+    /// 0-based position of the matching rule
+    pub rule_index: Option<usize>,
+    pub strategy: String,
+    /// Provider pinned by the rule; otherwise the strategy chooses
+    pub provider: Option<String>,
+    pub model: String,
+}
 
 /// Router for selecting and routing to providers
 pub struct Router {
     config: Arc<Config>,
-    cache: crate::cache::ResponseCache,
-    circuit_breaker: crate::circuit_breaker::CircuitBreaker,
-    round_robin_counter: AtomicUsize,
+    cache: Arc<crate::cache::ResponseCache>,
+    circuit_breaker: Arc<crate::circuit_breaker::CircuitBreaker>,
+    latency: Arc<crate::adaptive::LatencyTracker>,
+    balancer: Arc<crate::balancer::LoadBalancer>,
+    health: Arc<crate::health::HealthChecker>,
+    rate_limiter: Option<crate::rate_limit::RateLimiter>,
 }
 
 impl Router {
     pub fn new(config: Arc<Config>) -> Self {
+        Self::with_health_checker(config, Arc::new(crate::health::HealthChecker::new()))
+    }
+
+    /// Router that records provider health checks in a shared `HealthChecker`
+    pub fn with_health_checker(
+        config: Arc<Config>,
+        health: Arc<crate::health::HealthChecker>,
+    ) -> Self {
         // Initialize cache and circuit breaker based on config
-        let cache = crate::cache::ResponseCache::new(
-            config.cache.max_size,
-            config.cache.ttl,
-        );
+        let cache = crate::cache::ResponseCache::new(config.cache.max_size, config.cache.ttl);
+
+        let circuit_breaker = crate::circuit_breaker::CircuitBreaker::from_config(&config);
+
+        Self {
+            rate_limiter: Self::build_rate_limiter(&config),
+            config,
+            cache: Arc::new(cache),
+            circuit_breaker: Arc::new(circuit_breaker),
+            latency: Arc::new(crate::adaptive::LatencyTracker::new()),
+            balancer: Arc::new(crate::balancer::LoadBalancer::new()),
+            health,
+        }
+    }
+
+    /// Router for a reloaded config that carries over this router's state
+    ///
+    /// Health results, latency samples, in-flight counts and the circuits of
+    /// providers still configured are kept; the cache and rate limiter are kept
+    /// unless their settings changed.
+    pub fn reconfigure(&self, config: Arc<Config>) -> Self {
+        let cache = if config.cache.max_size == self.config.cache.max_size
+            && config.cache.ttl == self.config.cache.ttl
+        {
+            Arc::clone(&self.cache)
+        } else {
+            Arc::new(crate::cache::ResponseCache::new(
+                config.cache.max_size,
+                config.cache.ttl,
+            ))
+        };
 
-        let circuit_breaker = crate::circuit_breaker::CircuitBreaker::new(
-            5,  // failure_threshold
-            3,  // success_threshold
-            30, // timeout_secs
+        let old_limits = (
+            self.config.rate_limiting.requests_per_minute,
+            self.config.rate_limiting.requests_per_hour,
         );
+        let new_limits = (
+            config.rate_limiting.requests_per_minute,
+            config.rate_limiting.requests_per_hour,
+        );
+        let rate_limiter = match &self.rate_limiter {
+            Some(limiter) if config.rate_limiting.enabled && old_limits == new_limits => {
+                Some(limiter.clone())
+            }
+            _ => Self::build_rate_limiter(&config),
+        };
 
         Self {
+            circuit_breaker: Arc::new(self.circuit_breaker.reconfigured(&config)),
             config,
             cache,
-            circuit_breaker,
-            round_robin_counter: AtomicUsize::new(0),
+            latency: Arc::clone(&self.latency),
+            balancer: Arc::clone(&self.balancer),
+            health: Arc::clone(&self.health),
+            rate_limiter,
+        }
+    }
+
+    fn build_rate_limiter(config: &Config) -> Option<crate::rate_limit::RateLimiter> {
+        config.rate_limiting.enabled.then(|| {
+            crate::rate_limit::RateLimiter::new(
+                config.rate_limiting.requests_per_minute,
+                config.rate_limiting.requests_per_hour,
+            )
+        })
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// The config this router was built from, for holding past the router's lifetime
+    pub fn shared_config(&self) -> Arc<Config> {
+        Arc::clone(&self.config)
+    }
+
+    pub fn health_checker(&self) -> &Arc<crate::health::HealthChecker> {
+        &self.health
+    }
+
+    /// Apply `[rate_limiting]` to a request from `client`, whichever transport
+    /// it came in on
+    ///
+    /// `client` must come from the connection (see `server::client_key`), not
+    /// from anything the request itself says.
+    pub fn check_rate_limit(&self, client: &str, endpoint: &str) -> Result<()> {
+        let Some(limiter) = &self.rate_limiter else {
+            return Ok(());
+        };
+        if limiter.check_rate_limit(client) {
+            return Ok(());
+        }
+
+        crate::metrics::METRICS
+            .rate_limit_exceeded_total
+            .with_label_values(&[endpoint])
+            .inc();
+        Err(ProviderError::RateLimited {
+            provider: "thanos".to_string(),
+            message: format!(
+                "client rate limit exceeded ({} requests/minute, {} requests/hour)",
+                self.config.rate_limiting.requests_per_minute,
+                self.config.rate_limiting.requests_per_hour
+            ),
+            retry_after: None,
+        }
+        .into())
+    }
+
+    /// Decide how a request would be routed without sending it
+    pub fn plan(&self, request: &ChatRequest, context: &RequestContext) -> RoutePlan {
+        let routing = &self.config.routing;
+        let matched = crate::rules::evaluate(&routing.rules, request, context, chrono::Utc::now());
+
+        let Some((index, rule)) = matched else {
+            return RoutePlan {
+                rule: None,
+                rule_index: None,
+                strategy: effective_strategy(&routing.strategy, request).to_string(),
+                provider: None,
+                model: request.model.clone(),
+            };
+        };
+
+        let strategy = rule.strategy.as_deref().unwrap_or(&routing.strategy);
+        RoutePlan {
+            rule: Some(if rule.name.is_empty() {
+                format!("rule #{}", index + 1)
+            } else {
+                rule.name.clone()
+            }),
+            rule_index: Some(index),
+            strategy: effective_strategy(strategy, request).to_string(),
+            provider: rule.provider.clone(),
+            model: rule.model.clone().unwrap_or_else(|| request.model.clone()),
+        }
+    }
+
+    /// Apply a plan's model rewrite to the request
+    fn planned_request<'a>(plan: &RoutePlan, request: &'a ChatRequest) -> Cow<'a, ChatRequest> {
+        if let Some(rule) = &plan.rule {
+            debug!("Routing rule '{}' matched: {:?}", rule, plan);
+            crate::metrics::METRICS
+                .routing_rule_matches_total
+                .with_label_values(&[rule])
+                .inc();
+        }
+
+        if plan.model == request.model {
+            Cow::Borrowed(request)
+        } else {
+            let mut rewritten = request.clone();
+            rewritten.model = plan.model.clone();
+            Cow::Owned(rewritten)
+        }
+    }
+
+    /// Prompt tokens for a request
+    ///
+    /// Counted locally unless an exact count is wanted (`exact`, or
+    /// `tokenizer.provider_count` when unset) and an enabled provider can
+    /// count the model: Anthropic for `claude*`, Gemini for `gemini*`, or the
+    /// named `provider`. Provider failures fall back to the local count.
+    pub async fn count_tokens(
+        &self,
+        request: &ChatRequest,
+        provider: Option<&str>,
+        exact: Option<bool>,
+    ) -> crate::tokenizer::TokenCount {
+        let local = crate::tokenizer::count_request(request);
+        if !exact.unwrap_or(self.config.tokenizer.provider_count) {
+            return local;
+        }
+
+        let Some((provider_name, provider_config)) =
+            self.counting_provider(&request.model, provider)
+        else {
+            return local;
+        };
+
+        use crate::providers::{anthropic::AnthropicProvider, gemini::GeminiProvider};
+        let counted = match Provider::from_str(&provider_name) {
+            Some(Provider::Anthropic) | Some(Provider::AnthropicMax) => {
+                match AnthropicProvider::from_config(provider_config) {
+                    Ok(p) => p.count_tokens(request).await,
+                    Err(e) => Err(e),
+                }
+            }
+            Some(Provider::Gemini) => match GeminiProvider::from_config(provider_config) {
+                Ok(p) => p.count_tokens(request).await,
+                Err(e) => Err(e),
+            },
+            _ => return local,
+        };
+
+        match counted {
+            Ok(tokens) => crate::tokenizer::TokenCount {
+                tokens: tokens as usize,
+                tokenizer: format!("{}:count_tokens", provider_name),
+                exact: true,
+            },
+            Err(e) => {
+                warn!(
+                    "Token count from {} failed, using local count: {}",
+                    provider_name, e
+                );
+                local
+            }
         }
     }
 
+    /// Enabled built-in provider with a counting endpoint for `model`
+    fn counting_provider(
+        &self,
+        model: &str,
+        provider: Option<&str>,
+    ) -> Option<(String, &crate::config::ProviderConfig)> {
+        let can_count = |name: &str| match Provider::from_str(name) {
+            Some(Provider::Anthropic) | Some(Provider::AnthropicMax) => model.starts_with("claude"),
+            Some(Provider::Gemini) => model.starts_with("gemini"),
+            _ => false,
+        };
+
+        self.config
+            .enabled_providers()
+            .into_iter()
+            .filter(|(_, config)| config.plugin.is_none())
+            .filter(|(name, _)| provider.is_none_or(|wanted| wanted == name))
+            .find(|(name, _)| can_count(name))
+    }
+
     /// Route a chat completion request to the appropriate provider
     pub async fn route_chat_completion(&self, request: &ChatRequest) -> Result<ChatResponse> {
+        self.route_chat_completion_with(request, &RequestContext::default())
+            .await
+    }
+
+    /// Route a chat completion, evaluating routing rules against `context`
+    pub async fn route_chat_completion_with(
+        &self,
+        request: &ChatRequest,
+        context: &RequestContext,
+    ) -> Result<ChatResponse> {
         // Check cache first (skip for streaming requests)
         if !request.stream && self.config.cache.enabled {
             let cache_key = crate::cache::cache_key(request);
@@ -47,23 +306,35 @@ impl Router {
             }
         }
 
-        let strategy = &self.config.routing.strategy;
+        let plan = self.plan(request, context);
+        let routed = Self::planned_request(&plan, request);
         let start = Instant::now();
-
-        let result = match strategy.as_str() {
-            "preferred" => self.route_preferred(request).await,
-            "fallback" => self.route_fallback(request).await,
-            "round-robin" => self.route_round_robin(request).await,
-            "omen" => self.route_omen(request).await,
-            _ => {
-                warn!("Unknown routing strategy '{}', falling back to preferred", strategy);
-                self.route_preferred(request).await
+        let deadline = self.deadline();
+        // Fires if this future is dropped mid-flight (client disconnected)
+        let mut cancel_guard = OnCancel::new(record_request_cancelled);
+
+        let result = match (&plan.provider, plan.strategy.as_str()) {
+            (Some(provider_name), _) => self.route_pinned(provider_name, &routed, deadline).await,
+            (None, "preferred") => self.route_preferred(&routed, deadline).await,
+            (None, "fallback") => self.route_fallback(&routed, deadline).await,
+            (None, "round-robin") => self.route_round_robin(&routed, deadline).await,
+            (None, "hedged") => self.route_hedged(&routed, deadline).await,
+            (None, "adaptive") => self.route_adaptive(&routed, deadline).await,
+            (None, "omen") => self.route_omen(&routed).await,
+            (None, strategy) => {
+                warn!(
+                    "Unknown routing strategy '{}', falling back to preferred",
+                    strategy
+                );
+                self.route_preferred(&routed, deadline).await
             }
         };
+        cancel_guard.disarm();
 
         // Record request duration
         let duration = start.elapsed().as_secs_f64();
-        crate::metrics::METRICS.request_duration_seconds
+        crate::metrics::METRICS
+            .request_duration_seconds
             .with_label_values(&["chat_completions", "POST"])
             .observe(duration);
 
@@ -75,17 +346,20 @@ impl Router {
             }
 
             // Record successful request
-            crate::metrics::METRICS.requests_total
+            crate::metrics::METRICS
+                .requests_total
                 .with_label_values(&["chat_completions", "POST", "200"])
                 .inc();
 
             // Record token usage and cost
             if let Some(ref usage) = response.usage {
-                crate::metrics::METRICS.tokens_used_total
+                crate::metrics::METRICS
+                    .tokens_used_total
                     .with_label_values(&[&response.provider, &response.model, "input"])
                     .inc_by(usage.prompt_tokens as f64);
 
-                crate::metrics::METRICS.tokens_used_total
+                crate::metrics::METRICS
+                    .tokens_used_total
                     .with_label_values(&[&response.provider, &response.model, "output"])
                     .inc_by(usage.completion_tokens as f64);
 
@@ -97,15 +371,18 @@ impl Router {
                 )
                 .await
                 {
-                    crate::metrics::METRICS.estimated_cost_usd
+                    crate::metrics::METRICS
+                        .estimated_cost_usd
                         .with_label_values(&[&response.provider, &response.model])
                         .inc_by(cost);
                 }
             }
-        } else {
+        } else if let Err(ref e) = result {
             // Record failed request
-            crate::metrics::METRICS.requests_total
-                .with_label_values(&["chat_completions", "POST", "500"])
+            let status = crate::error::http_status(e);
+            crate::metrics::METRICS
+                .requests_total
+                .with_label_values(&["chat_completions", "POST", status.as_str()])
                 .inc();
         }
 
@@ -117,23 +394,61 @@ impl Router {
         &self,
         request: &ChatRequest,
     ) -> Result<tokio::sync::mpsc::Receiver<Result<ChatResponse>>> {
-        let strategy = &self.config.routing.strategy;
-
-        match strategy.as_str() {
-            "preferred" => self.stream_preferred(request).await,
-            "fallback" => self.stream_fallback(request).await,
-            "round-robin" => self.stream_round_robin(request).await,
-            "omen" => self.stream_omen(request).await,
-            _ => {
-                warn!("Unknown routing strategy '{}', falling back to preferred", strategy);
-                self.stream_preferred(request).await
+        self.route_chat_completion_stream_with(request, &RequestContext::default())
+            .await
+    }
+
+    /// Stream a chat completion, evaluating routing rules against `context`
+    pub async fn route_chat_completion_stream_with(
+        &self,
+        request: &ChatRequest,
+        context: &RequestContext,
+    ) -> Result<tokio::sync::mpsc::Receiver<Result<ChatResponse>>> {
+        let plan = self.plan(request, context);
+        let routed = Self::planned_request(&plan, request);
+        let deadline = self.deadline();
+        let mut cancel_guard = OnCancel::new(record_request_cancelled);
+
+        let result = match (&plan.provider, plan.strategy.as_str()) {
+            (Some(provider_name), _) => self.stream_pinned(provider_name, &routed, deadline).await,
+            (None, "preferred") => self.stream_preferred(&routed, deadline).await,
+            (None, "fallback") => self.stream_fallback(&routed, deadline).await,
+            (None, "round-robin") => self.stream_round_robin(&routed, deadline).await,
+            (None, "hedged") => self.stream_hedged(&routed, deadline).await,
+            (None, "adaptive") => self.stream_adaptive(&routed, deadline).await,
+            (None, "omen") => self.stream_omen(&routed).await,
+            (None, strategy) => {
+                warn!(
+                    "Unknown routing strategy '{}', falling back to preferred",
+                    strategy
+                );
+                self.stream_preferred(&routed, deadline).await
             }
-        }
+        };
+        cancel_guard.disarm();
+
+        result
     }
 
-    /// Route to the first enabled provider
-    async fn route_preferred(&self, request: &ChatRequest) -> Result<ChatResponse> {
-        let providers = self.config.enabled_providers();
+    /// Route to the provider named by a routing rule
+    async fn route_pinned(
+        &self,
+        provider_name: &str,
+        request: &ChatRequest,
+        deadline: Instant,
+    ) -> Result<ChatResponse> {
+        let provider_config = self.enabled_provider(provider_name)?;
+        self.call_provider(provider_name, provider_config, request, deadline)
+            .await
+    }
+
+    /// Route to the highest-priority enabled provider
+    async fn route_preferred(
+        &self,
+        request: &ChatRequest,
+        deadline: Instant,
+    ) -> Result<ChatResponse> {
+        let providers = self.priority_providers();
 
         if providers.is_empty() {
             return Err(anyhow!("No enabled providers available"));
@@ -142,47 +457,115 @@ impl Router {
         let (provider_name, provider_config) = &providers[0];
         debug!("Routing to preferred provider: {}", provider_name);
 
-        self.call_provider(provider_name, provider_config, request).await
+        self.call_provider(provider_name, provider_config, request, deadline)
+            .await
     }
 
     /// Try providers in fallback chain order
-    async fn route_fallback(&self, request: &ChatRequest) -> Result<ChatResponse> {
+    async fn route_fallback(
+        &self,
+        request: &ChatRequest,
+        deadline: Instant,
+    ) -> Result<ChatResponse> {
         let fallback_chain = &self.config.routing.fallback_chain;
         let enabled_providers: std::collections::HashMap<_, _> =
             self.config.enabled_providers().into_iter().collect();
 
+        let mut last_error = None;
+
         for provider_name in fallback_chain {
             if let Some(provider_config) = enabled_providers.get(provider_name) {
                 debug!("Trying fallback provider: {}", provider_name);
 
-                match self.call_provider(provider_name, provider_config, request).await {
+                match self
+                    .call_provider(provider_name, provider_config, request, deadline)
+                    .await
+                {
                     Ok(response) => return Ok(response),
+                    Err(e) if !should_fallback(&e) => return Err(e),
                     Err(e) => {
                         warn!("Provider {} failed: {}, trying next", provider_name, e);
-                        continue;
+                        last_error = Some(e);
                     }
                 }
             }
         }
 
-        Err(anyhow!("All providers in fallback chain failed"))
+        Err(chain_exhausted(
+            last_error,
+            "All providers in fallback chain failed",
+        ))
     }
 
-    /// Round-robin load balancing with atomic counter
-    async fn route_round_robin(&self, request: &ChatRequest) -> Result<ChatResponse> {
-        let providers = self.config.enabled_providers();
+    /// Load balance across `routing.load_balance`
+    async fn route_round_robin(
+        &self,
+        request: &ChatRequest,
+        deadline: Instant,
+    ) -> Result<ChatResponse> {
+        let mut skip = self.unavailable_balanced().await;
+        let mut last_error = None;
 
-        if providers.is_empty() {
-            return Err(anyhow!("No enabled providers available"));
+        while let Some((provider_name, provider_config)) = self.next_balanced(&skip) {
+            debug!("Load balancing to provider {}", provider_name);
+
+            let _in_flight = self.balancer.start(&provider_name);
+            match self
+                .call_provider(&provider_name, provider_config, request, deadline)
+                .await
+            {
+                Ok(response) => return Ok(response),
+                Err(e) if !should_fallback(&e) => return Err(e),
+                Err(e) => {
+                    warn!("Provider {} failed: {}, trying next", provider_name, e);
+                    skip.push(provider_name);
+                    last_error = Some(e);
+                }
+            }
         }
 
-        // Get next provider index using atomic counter
-        let index = self.round_robin_counter.fetch_add(1, Ordering::Relaxed) % providers.len();
-        let (provider_name, provider_config) = &providers[index];
+        Err(chain_exhausted(
+            last_error,
+            "No load-balanced provider available",
+        ))
+    }
 
-        debug!("Round-robin routing to provider {} (index {})", provider_name, index);
+    /// Race providers in fallback order, adding one whenever the others are slow
+    async fn route_hedged(&self, request: &ChatRequest, deadline: Instant) -> Result<ChatResponse> {
+        let candidates = self.ordered_providers();
 
-        self.call_provider(provider_name, provider_config, request).await
+        self.hedge(&candidates, |index| {
+            let (provider_name, provider_config) = &candidates[index];
+            self.call_provider(provider_name, provider_config, request, deadline)
+        })
+        .await
+    }
+
+    /// Try providers best-score first (see `crate::adaptive`)
+    async fn route_adaptive(
+        &self,
+        request: &ChatRequest,
+        deadline: Instant,
+    ) -> Result<ChatResponse> {
+        let mut last_error = None;
+
+        for (provider_name, provider_config) in self.adaptive_order(request) {
+            debug!("Trying adaptive provider: {}", provider_name);
+
+            match self
+                .call_provider(&provider_name, provider_config, request, deadline)
+                .await
+            {
+                Ok(response) => return Ok(response),
+                Err(e) if !should_fallback(&e) => return Err(e),
+                Err(e) => {
+                    warn!("Provider {} failed: {}, trying next", provider_name, e);
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        Err(chain_exhausted(last_error, "All providers failed"))
     }
 
     /// Route through Omen for intelligent routing
@@ -191,12 +574,25 @@ impl Router {
         Err(anyhow!("Omen routing not yet implemented"))
     }
 
+    /// Stream from the provider named by a routing rule
+    async fn stream_pinned(
+        &self,
+        provider_name: &str,
+        request: &ChatRequest,
+        deadline: Instant,
+    ) -> Result<tokio::sync::mpsc::Receiver<Result<ChatResponse>>> {
+        let provider_config = self.enabled_provider(provider_name)?;
+        self.stream_provider(provider_name, provider_config, request, deadline)
+            .await
+    }
+
     /// Stream from preferred provider
     async fn stream_preferred(
         &self,
         request: &ChatRequest,
+        deadline: Instant,
     ) -> Result<tokio::sync::mpsc::Receiver<Result<ChatResponse>>> {
-        let providers = self.config.enabled_providers();
+        let providers = self.priority_providers();
 
         if providers.is_empty() {
             return Err(anyhow!("No enabled providers available"));
@@ -205,53 +601,131 @@ impl Router {
         let (provider_name, provider_config) = &providers[0];
         debug!("Streaming from preferred provider: {}", provider_name);
 
-        self.stream_provider(provider_name, provider_config, request).await
+        self.stream_provider(provider_name, provider_config, request, deadline)
+            .await
     }
 
     /// Stream with fallback
     async fn stream_fallback(
         &self,
         request: &ChatRequest,
+        deadline: Instant,
     ) -> Result<tokio::sync::mpsc::Receiver<Result<ChatResponse>>> {
         let fallback_chain = &self.config.routing.fallback_chain;
         let enabled_providers: std::collections::HashMap<_, _> =
             self.config.enabled_providers().into_iter().collect();
 
+        let mut last_error = None;
+
         for provider_name in fallback_chain {
             if let Some(provider_config) = enabled_providers.get(provider_name) {
                 debug!("Trying fallback stream provider: {}", provider_name);
 
-                match self.stream_provider(provider_name, provider_config, request).await {
+                match self
+                    .stream_provider(provider_name, provider_config, request, deadline)
+                    .await
+                {
                     Ok(receiver) => return Ok(receiver),
+                    Err(e) if !should_fallback(&e) => return Err(e),
                     Err(e) => {
-                        warn!("Provider {} stream failed: {}, trying next", provider_name, e);
-                        continue;
+                        warn!(
+                            "Provider {} stream failed: {}, trying next",
+                            provider_name, e
+                        );
+                        last_error = Some(e);
                     }
                 }
             }
         }
 
-        Err(anyhow!("All providers in fallback chain failed for streaming"))
+        Err(chain_exhausted(
+            last_error,
+            "All providers in fallback chain failed for streaming",
+        ))
     }
 
-    /// Round-robin streaming with atomic counter
+    /// Load-balanced streaming; the stream counts as in flight until it ends
     async fn stream_round_robin(
         &self,
         request: &ChatRequest,
+        deadline: Instant,
     ) -> Result<tokio::sync::mpsc::Receiver<Result<ChatResponse>>> {
-        let providers = self.config.enabled_providers();
+        let mut skip = self.unavailable_balanced().await;
+        let mut last_error = None;
 
-        if providers.is_empty() {
-            return Err(anyhow!("No enabled providers available"));
+        while let Some((provider_name, provider_config)) = self.next_balanced(&skip) {
+            debug!("Load balancing stream to provider {}", provider_name);
+
+            let in_flight = self.balancer.start(&provider_name);
+            match self
+                .stream_provider(&provider_name, provider_config, request, deadline)
+                .await
+            {
+                Ok(rx) => return Ok(hold_until_closed(rx, in_flight)),
+                Err(e) if !should_fallback(&e) => return Err(e),
+                Err(e) => {
+                    warn!(
+                        "Provider {} stream failed: {}, trying next",
+                        provider_name, e
+                    );
+                    skip.push(provider_name);
+                    last_error = Some(e);
+                }
+            }
         }
 
-        // Get next provider index using atomic counter
-        let index = self.round_robin_counter.fetch_add(1, Ordering::Relaxed) % providers.len();
-        let (provider_name, provider_config) = &providers[index];
+        Err(chain_exhausted(
+            last_error,
+            "No load-balanced provider available",
+        ))
+    }
 
-        debug!("Round-robin streaming to provider {} (index {})", provider_name, index);
+    /// Hedged streaming: the first provider to produce a chunk wins
+    async fn stream_hedged(
+        &self,
+        request: &ChatRequest,
+        deadline: Instant,
+    ) -> Result<tokio::sync::mpsc::Receiver<Result<ChatResponse>>> {
+        let candidates = self.ordered_providers();
 
-        self.stream_provider(provider_name, provider_config, request).await
+        self.hedge(&candidates, |index| {
+            let (provider_name, provider_config) = &candidates[index];
+            self.stream_provider(provider_name, provider_config, request, deadline)
+        })
+        .await
+    }
+
+    /// Adaptive streaming, ranked the same way as `route_adaptive`
+    async fn stream_adaptive(
+        &self,
+        request: &ChatRequest,
+        deadline: Instant,
+    ) -> Result<tokio::sync::mpsc::Receiver<Result<ChatResponse>>> {
+        let mut last_error = None;
+
+        for (provider_name, provider_config) in self.adaptive_order(request) {
+            debug!("Trying adaptive stream provider: {}", provider_name);
+
+            match self
+                .stream_provider(&provider_name, provider_config, request, deadline)
+                .await
+            {
+                Ok(receiver) => return Ok(receiver),
+                Err(e) if !should_fallback(&e) => return Err(e),
+                Err(e) => {
+                    warn!(
+                        "Provider {} stream failed: {}, trying next",
+                        provider_name, e
+                    );
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        Err(chain_exhausted(
+            last_error,
+            "All providers failed for streaming",
+        ))
     }
 
     /// Stream through Omen
@@ -262,80 +736,614 @@ impl Router {
         Err(anyhow!("Omen streaming not yet implemented"))
     }
 
-    /// Call a specific provider
+    /// Config of a provider that must be enabled
+    fn enabled_provider(&self, provider_name: &str) -> Result<&crate::config::ProviderConfig> {
+        self.config
+            .providers
+            .get(provider_name)
+            .filter(|config| config.enabled)
+            .ok_or_else(|| {
+                anyhow!(
+                    "Provider '{}' selected by routing rule is not enabled",
+                    provider_name
+                )
+            })
+    }
+
+    /// Priority order for `preferred`: fallback-chain entries first, then the
+    /// remaining enabled providers by name
+    fn priority_providers(&self) -> Vec<(String, &crate::config::ProviderConfig)> {
+        let mut providers = self.ordered_providers();
+        let rest: Vec<_> = self
+            .config
+            .enabled_providers()
+            .into_iter()
+            .filter(|(name, _)| !providers.iter().any(|(chained, _)| chained == name))
+            .collect();
+        providers.extend(rest);
+        providers
+    }
+
+    /// Enabled providers in fallback-chain order (all enabled, by name, if no chain)
+    fn ordered_providers(&self) -> Vec<(String, &crate::config::ProviderConfig)> {
+        let enabled = self.config.enabled_providers();
+
+        if self.config.routing.fallback_chain.is_empty() {
+            return enabled;
+        }
+
+        let enabled: std::collections::HashMap<_, _> = enabled.into_iter().collect();
+        self.config
+            .routing
+            .fallback_chain
+            .iter()
+            .filter_map(|name| enabled.get(name).map(|config| (name.clone(), *config)))
+            .collect()
+    }
+
+    /// Next provider from `load_balance` under the configured `routing.balance`,
+    /// leaving out `skip`
+    fn next_balanced(&self, skip: &[String]) -> Option<(String, &crate::config::ProviderConfig)> {
+        let mut providers = self.config.load_balance_providers();
+        providers.retain(|(name, _, _)| !skip.contains(name));
+        let weights: Vec<_> = providers
+            .iter()
+            .map(|(name, weight, _)| (name.clone(), *weight))
+            .collect();
+
+        let index = match self.config.routing.balance.as_str() {
+            "least-outstanding" => self.balancer.pick_least_outstanding(&weights),
+            _ => self.balancer.pick_weighted(&weights),
+        }?;
+
+        let (name, _, config) = &providers[index];
+        Some((name.clone(), *config))
+    }
+
+    /// `load_balance` providers that are failing health checks or whose circuit is open
+    async fn unavailable_balanced(&self) -> Vec<String> {
+        let mut unavailable = Vec::new();
+        for (name, _, _) in self.config.load_balance_providers() {
+            if !self.is_available(&name).await {
+                unavailable.push(name);
+            }
+        }
+        unavailable
+    }
+
+    /// Enabled providers ordered by adaptive score
+    fn adaptive_order(
+        &self,
+        request: &ChatRequest,
+    ) -> Vec<(String, &crate::config::ProviderConfig)> {
+        let adaptive = &self.config.routing.adaptive;
+        let max_age = Duration::from_secs(adaptive.stale_after);
+        let kind = crate::adaptive::LatencyKind::of(request.stream);
+        let providers = self.ordered_providers();
+
+        let candidates: Vec<_> = providers
+            .iter()
+            .map(|(name, config)| {
+                let model = config.model.as_deref().unwrap_or(&request.model);
+                crate::adaptive::Candidate {
+                    latency: self.latency.get(name, &request.model, kind, max_age),
+                    error_rate: self.circuit_breaker.error_rate(name),
+                    cost: crate::models_dev::pricing_with_fallback(model)
+                        .map(|pricing| pricing.input + pricing.output),
+                }
+            })
+            .collect();
+
+        let order = crate::adaptive::rank(adaptive, &candidates);
+        debug!(
+            "Adaptive order: {:?}",
+            order.iter().map(|&i| &providers[i].0).collect::<Vec<_>>()
+        );
+
+        order.into_iter().map(|i| providers[i].clone()).collect()
+    }
+
+    /// Run `launch(0)`, then start the next candidate each time `hedge.delay_ms`
+    /// passes without an answer (up to `hedge.max_hedges` extra), or right away
+    /// when an attempt fails. The first success wins; dropping the remaining
+    /// attempts cancels their upstream requests.
+    async fn hedge<T, F, Fut>(
+        &self,
+        candidates: &[(String, &crate::config::ProviderConfig)],
+        launch: F,
+    ) -> Result<T>
+    where
+        F: Fn(usize) -> Fut,
+        Fut: std::future::Future<Output = Result<T>>,
+    {
+        use futures::stream::{FuturesUnordered, StreamExt};
+
+        if candidates.is_empty() {
+            return Err(anyhow!("No enabled providers available"));
+        }
+
+        let hedge = &self.config.routing.hedge;
+        let delay = Duration::from_millis(hedge.delay_ms);
+        let limit = candidates.len().min(1 + hedge.max_hedges);
+
+        let start = |index: usize| {
+            let attempt = launch(index);
+            async move { (index, attempt.await) }
+        };
+
+        let mut in_flight = FuturesUnordered::new();
+        in_flight.push(start(0));
+        let mut launched = 1;
+
+        loop {
+            let hedge_timer = async {
+                if launched < limit {
+                    tokio::time::sleep(delay).await
+                } else {
+                    std::future::pending().await
+                }
+            };
+
+            tokio::select! {
+                Some((index, result)) = in_flight.next() => match result {
+                    Ok(value) => {
+                        if index > 0 {
+                            crate::metrics::METRICS.hedges_total
+                                .with_label_values(&[&candidates[index].0, "won"])
+                                .inc();
+                        }
+                        debug!("Hedged request won by {}", candidates[index].0);
+                        return Ok(value);
+                    }
+                    Err(e) if !should_fallback(&e) => return Err(e),
+                    Err(e) => {
+                        warn!("Hedged provider {} failed: {}", candidates[index].0, e);
+
+                        // A failed attempt is replaced immediately, like a fallback
+                        if launched < candidates.len() {
+                            in_flight.push(start(launched));
+                            launched += 1;
+                        } else if in_flight.is_empty() {
+                            return Err(e.context("All hedged providers failed"));
+                        }
+                    }
+                },
+                _ = hedge_timer => {
+                    let provider_name = &candidates[launched].0;
+                    debug!("No answer after {:?}, hedging to {}", delay, provider_name);
+                    crate::metrics::METRICS.hedges_total
+                        .with_label_values(&[provider_name, "fired"])
+                        .inc();
+                    in_flight.push(start(launched));
+                    launched += 1;
+                }
+            }
+        }
+    }
+
+    /// Deadline for a request starting now
+    fn deadline(&self) -> Instant {
+        Instant::now() + Duration::from_secs(self.config.routing.request_timeout)
+    }
+
+    /// Call a specific provider, retrying transient failures per its retry policy
     async fn call_provider(
         &self,
         provider_name: &str,
         provider_config: &crate::config::ProviderConfig,
         request: &ChatRequest,
+        deadline: Instant,
     ) -> Result<ChatResponse> {
-        // Check circuit breaker
-        if !self.circuit_breaker.can_attempt(provider_name) {
-            warn!("Circuit breaker open for provider: {}", provider_name);
-            return Err(anyhow!("Circuit breaker open for provider: {}", provider_name));
+        let request = &*self
+            .fit_context(provider_name, provider_config, request)
+            .await?;
+        let mut attempt = 1;
+
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let result = tokio::time::timeout(
+                remaining,
+                self.call_provider_once(provider_name, provider_config, request),
+            )
+            .await
+            .unwrap_or_else(|_| Err(deadline_exceeded(provider_name)));
+
+            let err = match result {
+                Ok(response) => return Ok(response),
+                Err(e) => e,
+            };
+
+            let delay = self
+                .retry_delay(provider_name, provider_config, attempt, &err, deadline)
+                .ok_or(err)?;
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// Open a stream from a specific provider, retrying transient setup failures
+    ///
+    /// Providers report upstream errors as the first item on the channel, so
+    /// the first item is awaited here; once it is a chunk the stream is handed
+    /// to the caller and never retried. The circuit breaker hears how the
+    /// stream ended, not just that it started.
+    async fn stream_provider(
+        &self,
+        provider_name: &str,
+        provider_config: &crate::config::ProviderConfig,
+        request: &ChatRequest,
+        deadline: Instant,
+    ) -> Result<tokio::sync::mpsc::Receiver<Result<ChatResponse>>> {
+        let request = &*self
+            .fit_context(provider_name, provider_config, request)
+            .await?;
+        let mut attempt = 1;
+
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let attempt_start = Instant::now();
+            let setup = async {
+                self.check_circuit(provider_name)?;
+                let mut rx = self
+                    .stream_provider_once(provider_name, provider_config, request)
+                    .await?;
+                match rx.recv().await {
+                    Some(Err(e)) => Err(e),
+                    first => Ok((first, rx)),
+                }
+            };
+            let result = tokio::time::timeout(remaining, setup)
+                .await
+                .unwrap_or_else(|_| Err(deadline_exceeded(provider_name)));
+
+            let err = match result {
+                Ok((first, rx)) => {
+                    // Time to first chunk is what a streaming client waits on
+                    self.latency.record(
+                        provider_name,
+                        &request.model,
+                        crate::adaptive::LatencyKind::FirstChunk,
+                        attempt_start.elapsed().as_secs_f64(),
+                    );
+                    return Ok(forward_stream(
+                        Arc::clone(&self.circuit_breaker),
+                        provider_name,
+                        &request.model,
+                        first,
+                        rx,
+                    ));
+                }
+                Err(e) => {
+                    if !matches!(
+                        ProviderError::find(&e),
+                        Some(ProviderError::CircuitOpen { .. })
+                    ) {
+                        self.record_outcome(provider_name, Some(&e));
+                    }
+                    e
+                }
+            };
+
+            let delay = self
+                .retry_delay(provider_name, provider_config, attempt, &err, deadline)
+                .ok_or(err)?;
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// Check (and optionally adjust) the request against the provider's context window
+    async fn fit_context<'a>(
+        &self,
+        provider_name: &str,
+        provider_config: &crate::config::ProviderConfig,
+        request: &'a ChatRequest,
+    ) -> Result<Cow<'a, ChatRequest>> {
+        let limits = crate::context_window::limits_for(provider_config, &request.model);
+        let config = &self.config.routing.context_window;
+
+        let prepared =
+            if crate::rules::prompt_chars(request) < crate::context_window::BLOCKING_PROMPT_CHARS {
+                crate::context_window::prepare(config, provider_name, limits, request)
+            } else {
+                let (config, name, owned) =
+                    (config.clone(), provider_name.to_string(), request.clone());
+                tokio::task::spawn_blocking(move || {
+                    crate::context_window::prepare(&config, &name, limits, &owned)
+                        .map(|fitted| Cow::Owned(fitted.into_owned()))
+                })
+                .await
+                .map_err(|e| anyhow!("Context window check failed: {}", e))?
+            };
+
+        prepared.map_err(|e| {
+            debug!("Skipping provider {}: {}", provider_name, e.message());
+            e.into()
+        })
+    }
+
+    /// Delay before retrying a failed attempt, or `None` to give up on this provider
+    fn retry_delay(
+        &self,
+        provider_name: &str,
+        provider_config: &crate::config::ProviderConfig,
+        attempt: u32,
+        err: &anyhow::Error,
+        deadline: Instant,
+    ) -> Option<Duration> {
+        let delay = crate::retry::next_delay(&provider_config.retry, attempt, err)?;
+
+        if Instant::now() + delay >= deadline {
+            debug!(
+                "Not retrying {}: request deadline would pass first",
+                provider_name
+            );
+            return None;
+        }
+
+        let reason = ProviderError::find(err).map_or("unknown", |e| e.kind());
+        crate::metrics::METRICS
+            .provider_retries_total
+            .with_label_values(&[provider_name, reason])
+            .inc();
+
+        warn!(
+            "Provider {} attempt {} failed ({}), retrying in {:?}",
+            provider_name, attempt, reason, delay
+        );
+        Some(delay)
+    }
+
+    /// Fail fast if the provider's circuit doesn't allow an attempt
+    fn check_circuit(&self, provider_name: &str) -> Result<()> {
+        if self.circuit_breaker.can_attempt(provider_name) {
+            return Ok(());
+        }
+
+        warn!("Circuit breaker open for provider: {}", provider_name);
+        Err(ProviderError::CircuitOpen {
+            provider: provider_name.to_string(),
         }
+        .into())
+    }
+
+    /// Feed an attempt's outcome to this router's circuit breaker
+    fn record_outcome(&self, provider_name: &str, error: Option<&anyhow::Error>) {
+        record_outcome(&self.circuit_breaker, provider_name, error);
+    }
 
+    /// Circuit breaker state for every configured provider
+    pub fn circuits(&self) -> Vec<crate::circuit_breaker::CircuitSnapshot> {
+        let mut names: Vec<_> = self.config.providers.keys().collect();
+        names.sort();
+        names
+            .into_iter()
+            .map(|name| self.circuit_breaker.snapshot(name))
+            .collect()
+    }
+
+    /// Circuit breaker state for one configured provider
+    pub fn circuit(&self, provider_name: &str) -> Option<crate::circuit_breaker::CircuitSnapshot> {
+        self.config
+            .providers
+            .contains_key(provider_name)
+            .then(|| self.circuit_breaker.snapshot(provider_name))
+    }
+
+    /// Open a provider's circuit until reset; false for unknown providers
+    pub fn force_open_circuit(&self, provider_name: &str) -> bool {
+        let known = self.config.providers.contains_key(provider_name);
+        if known {
+            warn!("Circuit for {} forced open", provider_name);
+            self.circuit_breaker.force_open(provider_name);
+        }
+        known
+    }
+
+    /// Close a provider's circuit and clear its history; false for unknown providers
+    pub fn reset_circuit(&self, provider_name: &str) -> bool {
+        let known = self.config.providers.contains_key(provider_name);
+        if known {
+            info!("Circuit for {} reset", provider_name);
+            self.circuit_breaker.reset(provider_name);
+        }
+        known
+    }
+
+    /// Run one health check against a provider with its real credentials
+    ///
+    /// Unhealthy providers get their circuit opened so routing skips them; a
+    /// passing check lets an open circuit start probing right away.
+    pub async fn check_provider_health(
+        &self,
+        provider_name: &str,
+        provider_config: &crate::config::ProviderConfig,
+    ) -> crate::health::ProviderHealth {
+        let settings = &self.config.health_check;
         let start = Instant::now();
-        use crate::providers::{anthropic::AnthropicProvider, gemini::GeminiProvider,
-                                github_copilot::GitHubCopilotProvider, ollama::OllamaProvider,
-                                openai::OpenAIProvider, xai::XAIProvider};
+        let result = tokio::time::timeout(
+            Duration::from_secs(settings.timeout_secs),
+            self.probe_provider(provider_name, provider_config),
+        )
+        .await
+        .unwrap_or_else(|_| {
+            Err(anyhow!(
+                "health check timed out after {}s",
+                settings.timeout_secs
+            ))
+        });
+
+        let error = match result {
+            Ok(true) => None,
+            Ok(false) => Some("provider reported unhealthy".to_string()),
+            Err(e) => Some(e.to_string()),
+        };
+        let health = self
+            .health
+            .record(
+                provider_name,
+                start.elapsed(),
+                error,
+                settings.unhealthy_threshold,
+            )
+            .await;
+
+        match health.status {
+            crate::health::HealthStatus::Healthy => self.circuit_breaker.recovered(provider_name),
+            crate::health::HealthStatus::Unhealthy => self.circuit_breaker.trip(provider_name),
+            crate::health::HealthStatus::Degraded => {}
+        }
+        health
+    }
 
-        let result = match Provider::from_str(provider_name) {
-            Some(Provider::Anthropic) | Some(Provider::AnthropicMax) => {
-                let provider = AnthropicProvider::from_config(provider_config)?;
-                provider.chat_completion(request).await
-            }
-            Some(Provider::OpenAI) => {
-                let provider = OpenAIProvider::from_config(provider_config)?;
-                provider.chat_completion(request).await
-            }
-            Some(Provider::Xai) => {
-                let provider = XAIProvider::from_config(provider_config)?;
-                provider.chat_completion(request).await
-            }
-            Some(Provider::Gemini) => {
-                let provider = GeminiProvider::from_config(provider_config)?;
-                provider.chat_completion(request).await
-            }
-            Some(Provider::Ollama) => {
-                let provider = OllamaProvider::from_config(provider_config)?;
-                provider.chat_completion(request).await
-            }
-            Some(Provider::GithubCopilot) => {
-                let provider = GitHubCopilotProvider::from_config(provider_config)?;
-                provider.chat_completion(request).await
-            }
-            Some(Provider::Omen) => {
-                Err(anyhow!("Omen provider not yet implemented"))
+    /// Check every enabled provider concurrently
+    pub async fn check_all_providers(&self) -> Vec<(String, crate::health::ProviderHealth)> {
+        let checks = self
+            .config
+            .enabled_providers()
+            .into_iter()
+            .map(|(name, config)| async move {
+                let health = self.check_provider_health(&name, config).await;
+                (name, health)
+            });
+        futures::future::join_all(checks).await
+    }
+
+    /// Health of every enabled provider, checking now any without a recent result
+    pub async fn provider_health(&self) -> Vec<(String, crate::health::ProviderHealth)> {
+        let max_age = self.health_max_age();
+        let checks = self
+            .config
+            .enabled_providers()
+            .into_iter()
+            .map(|(name, config)| async move {
+                let health = match self.health.get(&name, max_age).await {
+                    Some(health) => health,
+                    None => self.check_provider_health(&name, config).await,
+                };
+                (name, health)
+            });
+        futures::future::join_all(checks).await
+    }
+
+    /// Enabled providers that can take traffic: not failing recent health
+    /// checks and with a circuit that isn't open, or whose cooldown has run out
+    pub async fn available_providers(&self) -> Vec<String> {
+        let mut available = Vec::new();
+        for (name, _) in self.config.enabled_providers() {
+            if self.is_available(&name).await {
+                available.push(name);
             }
-            None => Err(anyhow!("Unknown provider: {}", provider_name)),
-        };
+        }
+        available
+    }
+
+    /// Whether a provider isn't failing recent health checks and its circuit lets calls through
+    async fn is_available(&self, provider_name: &str) -> bool {
+        let unhealthy = self
+            .health
+            .get(provider_name, self.health_max_age())
+            .await
+            .is_some_and(|health| health.status == crate::health::HealthStatus::Unhealthy);
+        !unhealthy && self.circuit_breaker.is_available(provider_name)
+    }
+
+    /// Age after which a health check result is too old to act on
+    fn health_max_age(&self) -> Duration {
+        Duration::from_secs(
+            self.config
+                .health_check
+                .interval_secs
+                .saturating_mul(HEALTH_STALE_INTERVALS),
+        )
+    }
+
+    /// The configured probe against one provider
+    async fn probe_provider(
+        &self,
+        provider_name: &str,
+        provider_config: &crate::config::ProviderConfig,
+    ) -> Result<bool> {
+        if self.config.health_check.probe == "completion"
+            && let Some(model) = &provider_config.model
+        {
+            let request = ChatRequest {
+                model: model.clone(),
+                messages: vec![crate::types::ChatMessage {
+                    role: crate::types::Role::User,
+                    content: "ping".to_string(),
+                }],
+                stream: false,
+                temperature: None,
+                max_tokens: Some(1),
+                top_p: None,
+                system: None,
+                hedge: None,
+            };
+            // Straight to the provider, so the check works while the circuit
+            // is open and leaves the breaker, latency and metrics alone
+            return send_to_provider(provider_name, provider_config, &request)
+                .await
+                .map(|_| true);
+        }
+
+        provider_for(provider_name, provider_config)?.health().await
+    }
+
+    /// Make a single attempt against a specific provider
+    async fn call_provider_once(
+        &self,
+        provider_name: &str,
+        provider_config: &crate::config::ProviderConfig,
+        request: &ChatRequest,
+    ) -> Result<ChatResponse> {
+        self.check_circuit(provider_name)?;
+
+        let start = Instant::now();
+        let mut cancel_guard =
+            OnCancel::new(|| record_provider_cancelled(provider_name, &request.model));
+        let result = send_to_provider(provider_name, provider_config, request).await;
+        cancel_guard.disarm();
 
         // Record metrics and update circuit breaker
         let duration = start.elapsed().as_secs_f64();
-        crate::metrics::METRICS.provider_duration_seconds
+        crate::metrics::METRICS
+            .provider_duration_seconds
             .with_label_values(&[provider_name, &request.model])
             .observe(duration);
 
         match &result {
             Ok(_response) => {
                 // Success
-                self.circuit_breaker.record_success(provider_name);
-
-                crate::metrics::METRICS.provider_requests_total
+                self.record_outcome(provider_name, None);
+                self.latency.record(
+                    provider_name,
+                    &request.model,
+                    crate::adaptive::LatencyKind::Complete,
+                    duration,
+                );
+
+                crate::metrics::METRICS
+                    .provider_requests_total
                     .with_label_values(&[provider_name, &request.model, "success"])
                     .inc();
             }
             Err(e) => {
-                // Failure
-                self.circuit_breaker.record_failure(provider_name);
+                // Failure - only provider-side problems count toward the breaker
+                let provider_error = ProviderError::find(e);
+                self.record_outcome(provider_name, Some(e));
 
-                crate::metrics::METRICS.provider_requests_total
+                crate::metrics::METRICS
+                    .provider_requests_total
                     .with_label_values(&[provider_name, &request.model, "error"])
                     .inc();
 
-                crate::metrics::METRICS.provider_errors_total
-                    .with_label_values(&[provider_name, "api_error"])
+                crate::metrics::METRICS
+                    .provider_errors_total
+                    .with_label_values(&[
+                        provider_name,
+                        provider_error.map_or("api_error", |pe| pe.kind()),
+                    ])
                     .inc();
 
                 warn!("Provider {} failed: {}", provider_name, e);
@@ -345,54 +1353,242 @@ impl Router {
         result
     }
 
-    /// Stream from a specific provider
-    async fn stream_provider(
+    /// Open a stream from a specific provider (single attempt)
+    async fn stream_provider_once(
         &self,
         provider_name: &str,
         provider_config: &crate::config::ProviderConfig,
         request: &ChatRequest,
     ) -> Result<tokio::sync::mpsc::Receiver<Result<ChatResponse>>> {
-        use crate::providers::{anthropic::AnthropicProvider, gemini::GeminiProvider,
-                                github_copilot::GitHubCopilotProvider, ollama::OllamaProvider,
-                                openai::OpenAIProvider, xai::XAIProvider};
+        provider_for(provider_name, provider_config)?
+            .chat_completion_stream(request)
+            .await
+    }
+}
 
-        match Provider::from_str(provider_name) {
-            Some(Provider::Anthropic) | Some(Provider::AnthropicMax) => {
-                let provider = AnthropicProvider::from_config(provider_config)?;
-                provider.chat_completion_stream(request).await
-            }
-            Some(Provider::OpenAI) => {
-                let provider = OpenAIProvider::from_config(provider_config)?;
-                provider.chat_completion_stream(request).await
-            }
-            Some(Provider::Xai) => {
-                let provider = XAIProvider::from_config(provider_config)?;
-                provider.chat_completion_stream(request).await
-            }
-            Some(Provider::Gemini) => {
-                let provider = GeminiProvider::from_config(provider_config)?;
-                provider.chat_completion_stream(request).await
-            }
-            Some(Provider::Ollama) => {
-                let provider = OllamaProvider::from_config(provider_config)?;
-                provider.chat_completion_stream(request).await
+/// Check intervals a health result stays current for
+const HEALTH_STALE_INTERVALS: u64 = 2;
+
+/// Routing strategy for a request, honoring its `hedge` opt-in/opt-out
+fn effective_strategy<'a>(strategy: &'a str, request: &ChatRequest) -> &'a str {
+    match request.hedge {
+        Some(true) => "hedged",
+        Some(false) if strategy == "hedged" => "fallback",
+        _ => strategy,
+    }
+}
+
+/// Forward a stream, keeping `guard` alive until it ends or the caller hangs up
+fn hold_until_closed<G: Send + 'static>(
+    mut rx: tokio::sync::mpsc::Receiver<Result<ChatResponse>>,
+    guard: G,
+) -> tokio::sync::mpsc::Receiver<Result<ChatResponse>> {
+    let (tx, out) = tokio::sync::mpsc::channel(100);
+
+    tokio::spawn(async move {
+        let _guard = guard;
+        loop {
+            let item = tokio::select! {
+                item = rx.recv() => item,
+                _ = tx.closed() => return,
+            };
+            let Some(item) = item else { return };
+            if tx.send(item).await.is_err() {
+                return;
             }
-            Some(Provider::GithubCopilot) => {
-                let provider = GitHubCopilotProvider::from_config(provider_config)?;
-                provider.chat_completion_stream(request).await
+        }
+    });
+
+    out
+}
+
+/// Send a request to a provider as is: no circuit breaker, latency or metrics
+async fn send_to_provider(
+    provider_name: &str,
+    provider_config: &crate::config::ProviderConfig,
+    request: &ChatRequest,
+) -> Result<ChatResponse> {
+    provider_for(provider_name, provider_config)?
+        .chat_completion(request)
+        .await
+}
+
+/// Build the provider a config section describes: its plugin if it has one,
+/// otherwise the built-in provider named `provider_name`
+fn provider_for(
+    provider_name: &str,
+    provider_config: &crate::config::ProviderConfig,
+) -> Result<Box<dyn crate::providers::Provider>> {
+    use crate::providers::{
+        anthropic::AnthropicProvider, gemini::GeminiProvider,
+        github_copilot::GitHubCopilotProvider, ollama::OllamaProvider, openai::OpenAIProvider,
+        plugin::PluginProvider, xai::XAIProvider,
+    };
+
+    if let Some(plugin_config) = &provider_config.plugin {
+        return Ok(Box::new(PluginProvider::from_config(
+            provider_name,
+            plugin_config,
+        )?));
+    }
+
+    Ok(match Provider::from_str(provider_name) {
+        Some(Provider::Anthropic) | Some(Provider::AnthropicMax) => {
+            Box::new(AnthropicProvider::from_config(provider_config)?)
+        }
+        Some(Provider::OpenAI) => Box::new(OpenAIProvider::from_config(provider_config)?),
+        Some(Provider::Xai) => Box::new(XAIProvider::from_config(provider_config)?),
+        Some(Provider::Gemini) => Box::new(GeminiProvider::from_config(provider_config)?),
+        Some(Provider::Ollama) => Box::new(OllamaProvider::from_config(provider_config)?),
+        Some(Provider::GithubCopilot) => {
+            Box::new(GitHubCopilotProvider::from_config(provider_config)?)
+        }
+        Some(Provider::Omen) => return Err(anyhow!("Omen provider not yet implemented")),
+        None => return Err(anyhow!("Unknown provider: {}", provider_name)),
+    })
+}
+
+/// Error for an attempt cut short by the request deadline
+fn deadline_exceeded(provider_name: &str) -> anyhow::Error {
+    ProviderError::Timeout {
+        provider: provider_name.to_string(),
+        message: "request deadline exceeded".to_string(),
+    }
+    .into()
+}
+
+/// Hand a provider stream to the caller, re-attaching the already received first item
+///
+/// The stream's outcome goes to the circuit breaker once it is known: an error
+/// item counts as a failure, reaching the end as a success. If the caller drops
+/// its receiver before the stream ends, the cancellation is recorded and `rx`
+/// is dropped, which aborts the provider's upstream request.
+fn forward_stream(
+    circuit_breaker: Arc<crate::circuit_breaker::CircuitBreaker>,
+    provider_name: &str,
+    model: &str,
+    first: Option<Result<ChatResponse>>,
+    mut rx: tokio::sync::mpsc::Receiver<Result<ChatResponse>>,
+) -> tokio::sync::mpsc::Receiver<Result<ChatResponse>> {
+    let (tx, out) = tokio::sync::mpsc::channel(100);
+    let provider_name = provider_name.to_string();
+    let model = model.to_string();
+
+    tokio::spawn(async move {
+        let mut next = first;
+        let mut recorded = false;
+
+        loop {
+            let Some(item) = next else {
+                if !recorded {
+                    record_outcome(&circuit_breaker, &provider_name, None);
+                }
+                return;
+            };
+            if let Err(e) = &item
+                && !recorded
+            {
+                record_outcome(&circuit_breaker, &provider_name, Some(e));
+                recorded = true;
             }
-            Some(Provider::Omen) => {
-                Err(anyhow!("Omen streaming not yet implemented"))
+            if tx.send(item).await.is_err() {
+                break;
             }
-            None => Err(anyhow!("Unknown provider: {}", provider_name)),
+
+            next = tokio::select! {
+                item = rx.recv() => item,
+                _ = tx.closed() => break,
+            };
+        }
+
+        // The caller went away; says nothing about the provider
+        record_provider_cancelled(&provider_name, &model);
+        if !recorded {
+            circuit_breaker.record_ignored(&provider_name);
+        }
+    });
+
+    out
+}
+
+/// Feed an attempt's outcome to the circuit breaker; only provider-side
+/// problems count as failures
+fn record_outcome(
+    circuit_breaker: &crate::circuit_breaker::CircuitBreaker,
+    provider_name: &str,
+    error: Option<&anyhow::Error>,
+) {
+    match error.map(ProviderError::find) {
+        None => circuit_breaker.record_success(provider_name),
+        Some(provider_error) if provider_error.is_none_or(|pe| pe.counts_against_provider()) => {
+            circuit_breaker.record_failure(provider_name)
+        }
+        Some(_) => circuit_breaker.record_ignored(provider_name),
+    }
+}
+
+/// Runs a callback when dropped unless disarmed first
+///
+/// Held across an await so that a future dropped mid-flight (the client went
+/// away) can still be told apart from one that completed.
+struct OnCancel<F: FnOnce()>(Option<F>);
+
+impl<F: FnOnce()> OnCancel<F> {
+    fn new(on_cancel: F) -> Self {
+        Self(Some(on_cancel))
+    }
+
+    fn disarm(&mut self) {
+        self.0 = None;
+    }
+}
+
+impl<F: FnOnce()> Drop for OnCancel<F> {
+    fn drop(&mut self) {
+        if let Some(on_cancel) = self.0.take() {
+            on_cancel();
         }
     }
 }
 
+fn record_request_cancelled() {
+    debug!("Request cancelled by client");
+    crate::metrics::METRICS
+        .requests_total
+        .with_label_values(&["chat_completions", "POST", "cancelled"])
+        .inc();
+}
+
+fn record_provider_cancelled(provider_name: &str, model: &str) {
+    debug!("Cancelled in-flight request to provider {}", provider_name);
+    crate::metrics::METRICS
+        .provider_requests_total
+        .with_label_values(&[provider_name, model, "cancelled"])
+        .inc();
+}
+
+/// Whether a failed attempt should move on to the next provider
+///
+/// Untyped errors (config problems, parse failures) keep the old behaviour of
+/// always trying the next provider.
+fn should_fallback(err: &anyhow::Error) -> bool {
+    ProviderError::find(err).is_none_or(|e| e.should_fallback())
+}
+
+/// Error returned once every provider in a chain has failed
+///
+/// Keeps the last provider's error in the chain so callers still see its class.
+fn chain_exhausted(last_error: Option<anyhow::Error>, message: &'static str) -> anyhow::Error {
+    match last_error {
+        Some(e) => e.context(message),
+        None => anyhow!(message),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::{ProviderConfig, RoutingConfig, ServerConfig, CacheConfig};
+    use crate::config::{CacheConfig, ProviderConfig, RoutingConfig, ServerConfig};
     use crate::types::{AuthMethod, ChatMessage, Role};
     use std::collections::HashMap;
 
@@ -404,13 +1600,18 @@ mod tests {
             ProviderConfig {
                 enabled: true,
                 auth_method: AuthMethod::ApiKey,
-                api_key: Some("test-key-1".to_string()),
+                api_key: Some("test-key-1".into()),
                 base_url: None,
                 endpoint: None,
                 model: Some("claude-3-5-sonnet-20241022".to_string()),
                 max_tokens: None,
                 temperature: None,
                 client_id: None,
+                plugin: None,
+                retry: Default::default(),
+                context_length: None,
+                output_limit: None,
+                circuit_breaker: None,
             },
         );
 
@@ -419,13 +1620,18 @@ mod tests {
             ProviderConfig {
                 enabled: true,
                 auth_method: AuthMethod::ApiKey,
-                api_key: Some("test-key-2".to_string()),
+                api_key: Some("test-key-2".into()),
                 base_url: None,
                 endpoint: None,
                 model: Some("gpt-4o".to_string()),
                 max_tokens: None,
                 temperature: None,
                 client_id: None,
+                plugin: None,
+                retry: Default::default(),
+                context_length: None,
+                output_limit: None,
+                circuit_breaker: None,
             },
         );
 
@@ -441,6 +1647,11 @@ mod tests {
                 max_tokens: None,
                 temperature: None,
                 client_id: None,
+                plugin: None,
+                retry: Default::default(),
+                context_length: None,
+                output_limit: None,
+                circuit_breaker: None,
             },
         );
 
@@ -451,11 +1662,23 @@ mod tests {
                 log_level: "info".to_string(),
                 uds_path: None,
                 uds_enabled: false,
+                admin_token: None,
+                tls: None,
+                http3: Default::default(),
+                drain_timeout_secs: 30,
+                config_watch_secs: 0,
             },
             routing: RoutingConfig {
                 strategy: "round-robin".to_string(),
                 fallback_chain: vec!["anthropic".to_string(), "openai".to_string()],
                 load_balance: vec!["anthropic".to_string(), "openai".to_string()],
+                balance: "weighted".to_string(),
+                request_timeout: 300,
+                hedge: Default::default(),
+                adaptive: Default::default(),
+                circuit_breaker: Default::default(),
+                rules: vec![],
+                context_window: Default::default(),
             },
             providers,
             models_dev: Default::default(),
@@ -467,6 +1690,9 @@ mod tests {
             rate_limiting: Default::default(),
             metrics: Default::default(),
             oauth: Default::default(),
+            tokenizer: Default::default(),
+            health_check: Default::default(),
+            secrets: Default::default(),
         }
     }
 
@@ -482,6 +1708,7 @@ mod tests {
             max_tokens: None,
             top_p: None,
             system: None,
+            hedge: None,
         }
     }
 
@@ -491,7 +1718,7 @@ mod tests {
         let router = Router::new(config);
 
         // Verify initial state
-        assert_eq!(router.round_robin_counter.load(Ordering::Relaxed), 0);
+        assert_eq!(router.balancer.outstanding("anthropic"), 0);
     }
 
     #[test]
@@ -499,50 +1726,150 @@ mod tests {
         let config = Arc::new(create_test_config());
         let enabled = config.enabled_providers();
 
-        // Should have 2 enabled providers
+        // Should have 2 enabled providers, in name order
         assert_eq!(enabled.len(), 2);
+        assert_eq!(enabled[0].0, "anthropic");
+        assert_eq!(enabled[1].0, "openai");
 
         // Check that disabled provider is not included
         assert!(!enabled.iter().any(|(name, _)| name == "disabled"));
     }
 
     #[test]
-    fn test_round_robin_counter_increment() {
+    fn test_round_robin_cycles() {
         let config = Arc::new(create_test_config());
-        let router = Router::new(config.clone());
-        let providers = config.enabled_providers();
-        let provider_count = providers.len();
+        let router = Router::new(config);
 
-        // Test that counter increments properly
+        // Unweighted entries alternate in list order
         for i in 0..10 {
-            let expected_index = i % provider_count;
-            let index = router.round_robin_counter.load(Ordering::Relaxed) % provider_count;
-            assert_eq!(index, expected_index);
-
-            // Increment for next iteration
-            router.round_robin_counter.fetch_add(1, Ordering::Relaxed);
+            let (name, _) = router.next_balanced(&[]).unwrap();
+            assert_eq!(name, if i % 2 == 0 { "anthropic" } else { "openai" });
         }
     }
 
     #[test]
     fn test_round_robin_distribution() {
-        let config = Arc::new(create_test_config());
-        let router = Router::new(config.clone());
-        let providers = config.enabled_providers();
+        let mut config = create_test_config();
+        config.routing.load_balance = vec![
+            "anthropic:3".to_string(),
+            "openai:1".to_string(),
+            "disabled:5".to_string(),
+        ];
+        let router = Router::new(Arc::new(config));
 
         // Simulate multiple requests
-        let mut distribution: HashMap<usize, usize> = HashMap::new();
+        let mut distribution: HashMap<String, usize> = HashMap::new();
 
         for _ in 0..100 {
-            let index = router.round_robin_counter.fetch_add(1, Ordering::Relaxed) % providers.len();
-            *distribution.entry(index).or_insert(0) += 1;
+            let (name, _) = router.next_balanced(&[]).unwrap();
+            *distribution.entry(name).or_insert(0) += 1;
         }
 
-        // Each provider should get roughly equal distribution
-        for count in distribution.values() {
-            // With 100 requests and 2 providers, each should get ~50
-            assert!(*count >= 45 && *count <= 55, "Distribution unbalanced: {}", count);
+        // Weights are honored exactly; disabled providers are skipped
+        assert_eq!(distribution["anthropic"], 75);
+        assert_eq!(distribution["openai"], 25);
+        assert!(!distribution.contains_key("disabled"));
+    }
+
+    #[test]
+    fn test_least_outstanding_balancing() {
+        let mut config = create_test_config();
+        config.routing.balance = "least-outstanding".to_string();
+        let router = Router::new(Arc::new(config));
+
+        let _busy = router.balancer.start("anthropic");
+        assert_eq!(router.next_balanced(&[]).unwrap().0, "openai");
+    }
+
+    #[tokio::test]
+    async fn test_round_robin_skips_unavailable() {
+        let mut server = mockito::Server::new_async().await;
+        let ollama = server
+            .mock("POST", "/api/chat")
+            .with_status(503)
+            .expect(1)
+            .create_async()
+            .await;
+        let anthropic = server
+            .mock("POST", "/v1/messages")
+            .with_body(
+                r#"{"content":[{"type":"text","text":"pong"}],"usage":{"input_tokens":1,"output_tokens":1},"stop_reason":"end_turn"}"#,
+            )
+            .expect(4)
+            .create_async()
+            .await;
+
+        let retry = crate::config::RetryConfig {
+            max_attempts: 1,
+            ..fast_retry()
+        };
+        let mut config = create_mock_config(server.url(), retry.clone());
+        config.routing.strategy = "round-robin".to_string();
+        config.routing.load_balance = vec!["ollama".to_string(), "anthropic".to_string()];
+        config.providers.get_mut("ollama").unwrap().circuit_breaker =
+            Some(crate::config::CircuitBreakerConfig {
+                failure_threshold: 1,
+                ..Default::default()
+            });
+        config.providers.insert(
+            "anthropic".to_string(),
+            ProviderConfig {
+                enabled: true,
+                auth_method: AuthMethod::ApiKey,
+                api_key: Some("test-key".into()),
+                base_url: Some(server.url()),
+                endpoint: None,
+                model: None,
+                max_tokens: None,
+                temperature: None,
+                client_id: None,
+                plugin: None,
+                retry,
+                context_length: None,
+                output_limit: None,
+                circuit_breaker: None,
+            },
+        );
+        let router = Router::new(Arc::new(config));
+
+        // The failing pick moves on to the next one and trips its circuit,
+        // so later requests don't try it at all
+        for _ in 0..4 {
+            let response = router
+                .route_chat_completion(&create_test_request())
+                .await
+                .unwrap();
+            assert_eq!(response.provider, "anthropic");
         }
+        assert_eq!(
+            router.circuit("ollama").unwrap().state,
+            crate::circuit_breaker::CircuitState::Open
+        );
+
+        ollama.assert_async().await;
+        anthropic.assert_async().await;
+    }
+
+    #[test]
+    fn test_preferred_is_deterministic() {
+        let mut config = create_test_config();
+        config.routing.fallback_chain = vec!["openai".to_string(), "anthropic".to_string()];
+        let router = Router::new(Arc::new(config.clone()));
+        assert_eq!(router.priority_providers()[0].0, "openai");
+
+        // Providers outside the chain come after it
+        config.routing.fallback_chain = vec!["openai".to_string()];
+        let router = Router::new(Arc::new(config.clone()));
+        let names: Vec<_> = router
+            .priority_providers()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(names, ["openai", "anthropic"]);
+
+        config.routing.fallback_chain.clear();
+        let router = Router::new(Arc::new(config));
+        assert_eq!(router.priority_providers()[0].0, "anthropic");
     }
 
     #[test]
@@ -561,6 +1888,251 @@ mod tests {
         assert_ne!(key1, key3);
     }
 
+    /// Config with a single Ollama provider pointed at a mock server
+    fn create_mock_config(endpoint: String, retry: crate::config::RetryConfig) -> Config {
+        let mut config = create_test_config();
+        config.routing.strategy = "preferred".to_string();
+        config.providers.clear();
+        config.providers.insert(
+            "ollama".to_string(),
+            ProviderConfig {
+                enabled: true,
+                auth_method: AuthMethod::None,
+                api_key: None,
+                base_url: None,
+                endpoint: Some(endpoint),
+                model: Some("llama3.2".to_string()),
+                max_tokens: None,
+                temperature: None,
+                client_id: None,
+                plugin: None,
+                retry,
+                context_length: None,
+                output_limit: None,
+                circuit_breaker: None,
+            },
+        );
+        config
+    }
+
+    fn fast_retry() -> crate::config::RetryConfig {
+        crate::config::RetryConfig {
+            initial_backoff_ms: 1,
+            jitter: 0.0,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retries_transient_errors() {
+        let mut server = mockito::Server::new_async().await;
+        let failures = server
+            .mock("POST", "/api/chat")
+            .with_status(503)
+            .expect(2)
+            .create_async()
+            .await;
+        let success = server
+            .mock("POST", "/api/chat")
+            .with_status(200)
+            .with_body(r#"{"message":{"role":"assistant","content":"hi"},"done":true}"#)
+            .create_async()
+            .await;
+
+        let router = Router::new(Arc::new(create_mock_config(server.url(), fast_retry())));
+        let response = router
+            .route_chat_completion(&create_test_request())
+            .await
+            .unwrap();
+
+        assert_eq!(response.content, "hi");
+        failures.assert_async().await;
+        success.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_no_retry_for_client_errors() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/api/chat")
+            .with_status(400)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let router = Router::new(Arc::new(create_mock_config(server.url(), fast_retry())));
+        let err = router
+            .route_chat_completion(&create_test_request())
+            .await
+            .unwrap_err();
+
+        assert!(matches!(
+            ProviderError::find(&err),
+            Some(ProviderError::InvalidRequest { .. })
+        ));
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_retry_gives_up_after_max_attempts() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/api/chat")
+            .with_status(502)
+            .expect(3)
+            .create_async()
+            .await;
+
+        let router = Router::new(Arc::new(create_mock_config(server.url(), fast_retry())));
+        let err = router
+            .route_chat_completion(&create_test_request())
+            .await
+            .unwrap_err();
+
+        assert!(matches!(
+            ProviderError::find(&err),
+            Some(ProviderError::Unavailable { .. })
+        ));
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_stream_setup_retried() {
+        let mut server = mockito::Server::new_async().await;
+        let failure = server
+            .mock("POST", "/api/chat")
+            .with_status(429)
+            .expect(1)
+            .create_async()
+            .await;
+        let success = server
+            .mock("POST", "/api/chat")
+            .with_status(200)
+            .with_body("{\"message\":{\"role\":\"assistant\",\"content\":\"hi\"},\"done\":true}\n")
+            .create_async()
+            .await;
+
+        let router = Router::new(Arc::new(create_mock_config(server.url(), fast_retry())));
+        let mut request = create_test_request();
+        request.stream = true;
+
+        let mut rx = router.route_chat_completion_stream(&request).await.unwrap();
+        let chunk = rx.recv().await.unwrap().unwrap();
+
+        assert_eq!(chunk.content, "hi");
+        failure.assert_async().await;
+        success.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_open_circuit_not_retried() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/api/chat")
+            .with_status(503)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let mut config = create_mock_config(server.url(), fast_retry());
+        config.providers.get_mut("ollama").unwrap().circuit_breaker =
+            Some(crate::config::CircuitBreakerConfig {
+                failure_threshold: 1,
+                ..Default::default()
+            });
+        let router = Router::new(Arc::new(config));
+        let err = router
+            .route_chat_completion(&create_test_request())
+            .await
+            .unwrap_err();
+
+        // The second attempt is refused by the circuit and not retried again
+        assert!(matches!(
+            ProviderError::find(&err),
+            Some(ProviderError::CircuitOpen { .. })
+        ));
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_stream_outcome_reaches_circuit() {
+        let breaker = Arc::new(crate::circuit_breaker::CircuitBreaker::new(
+            crate::config::CircuitBreakerConfig {
+                failure_threshold: 1,
+                ..Default::default()
+            },
+        ));
+        let chunk = ChatResponse {
+            provider: "ollama".to_string(),
+            model: "m".to_string(),
+            content: "partial".to_string(),
+            done: false,
+            usage: None,
+            finish_reason: None,
+        };
+
+        // A stream that completes is a success
+        let (_, rx) = tokio::sync::mpsc::channel(1);
+        let mut out = forward_stream(
+            Arc::clone(&breaker),
+            "stream_ok",
+            "m",
+            Some(Ok(chunk.clone())),
+            rx,
+        );
+        while out.recv().await.is_some() {}
+        let snapshot = breaker.snapshot("stream_ok");
+        assert_eq!((snapshot.window_requests, snapshot.window_failures), (1, 0));
+
+        // One that fails after its first chunk counts against the provider
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        let reset = ProviderError::Unavailable {
+            provider: "ollama".to_string(),
+            message: "connection reset".to_string(),
+        };
+        tx.send(Err(reset.into())).await.unwrap();
+        drop(tx);
+        let mut out = forward_stream(
+            Arc::clone(&breaker),
+            "stream_failed",
+            "m",
+            Some(Ok(chunk)),
+            rx,
+        );
+        assert!(out.recv().await.unwrap().is_ok());
+        assert!(out.recv().await.unwrap().is_err());
+        assert!(out.recv().await.is_none());
+        assert_eq!(
+            breaker.get_state("stream_failed"),
+            crate::circuit_breaker::CircuitState::Open
+        );
+    }
+
+    #[tokio::test]
+    async fn test_oversized_prompt_not_sent() {
+        let mut server = mockito::Server::new_async().await;
+        let upstream = server
+            .mock("POST", "/api/chat")
+            .expect(0)
+            .create_async()
+            .await;
+
+        let mut config = create_mock_config(server.url(), fast_retry());
+        config.providers.get_mut("ollama").unwrap().context_length = Some(8);
+        let router = Router::new(Arc::new(config));
+
+        let err = router
+            .route_chat_completion(&create_test_request())
+            .await
+            .unwrap_err();
+
+        assert_eq!(
+            ProviderError::find(&err).unwrap().kind(),
+            "context_length_exceeded"
+        );
+        upstream.assert_async().await;
+    }
+
     #[test]
     fn test_fallback_chain_order() {
         let config = Arc::new(create_test_config());
@@ -0,0 +1,326 @@
+/// Declarative routing rules (`[[routing.rules]]`)
+///
+/// Rules are checked in order against the request and what the transport
+/// knows about the caller (`RequestContext`); the first rule whose conditions
+/// all hold decides the provider, model and/or strategy.
+use crate::config::{ProviderConfig, RoutingRule, RuleConditions};
+use crate::types::ChatRequest;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Timelike, Utc};
+use std::collections::HashMap;
+
+/// Header carrying the tenant for `match.tenant`
+pub const TENANT_HEADER: &str = "x-thanos-tenant";
+
+/// Request attributes that aren't part of `ChatRequest`
+#[derive(Debug, Clone, Default)]
+pub struct RequestContext {
+    /// Client API key from `Authorization: Bearer` or `x-api-key`
+    pub client_key: Option<String>,
+    pub tenant: Option<String>,
+    /// Lowercased header names
+    pub headers: HashMap<String, String>,
+}
+
+impl RequestContext {
+    /// Build from request headers (HTTP headers or gRPC metadata)
+    pub fn from_headers<'a>(headers: impl IntoIterator<Item = (&'a str, &'a str)>) -> Self {
+        let headers: HashMap<String, String> = headers
+            .into_iter()
+            .map(|(name, value)| (name.to_ascii_lowercase(), value.to_string()))
+            .collect();
+
+        let client_key = headers
+            .get("authorization")
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .or_else(|| headers.get("x-api-key").map(String::as_str))
+            .map(|key| key.trim().to_string());
+
+        Self {
+            client_key,
+            tenant: headers.get(TENANT_HEADER).cloned(),
+            headers,
+        }
+    }
+}
+
+/// First rule matching the request, with its index
+pub fn evaluate<'a>(
+    rules: &'a [RoutingRule],
+    request: &ChatRequest,
+    context: &RequestContext,
+    now: DateTime<Utc>,
+) -> Option<(usize, &'a RoutingRule)> {
+    rules
+        .iter()
+        .enumerate()
+        .find(|(_, rule)| matches(&rule.conditions, request, context, now))
+}
+
+/// Whether every condition holds
+pub fn matches(
+    conditions: &RuleConditions,
+    request: &ChatRequest,
+    context: &RequestContext,
+    now: DateTime<Utc>,
+) -> bool {
+    let glob = |pattern: &Option<String>, value: Option<&str>| match pattern {
+        Some(pattern) => value.is_some_and(|value| glob_match(pattern, value)),
+        None => true,
+    };
+
+    let prompt_chars = prompt_chars(request);
+
+    glob(&conditions.model, Some(&request.model))
+        && glob(&conditions.client, context.client_key.as_deref())
+        && glob(&conditions.tenant, context.tenant.as_deref())
+        && conditions.headers.iter().all(|(name, pattern)| {
+            context
+                .headers
+                .get(&name.to_ascii_lowercase())
+                .is_some_and(|value| glob_match(pattern, value))
+        })
+        && conditions.min_prompt_chars.is_none_or(|min| prompt_chars >= min)
+        && conditions.max_prompt_chars.is_none_or(|max| prompt_chars <= max)
+        && conditions.time.as_deref().is_none_or(|window| {
+            // Unparseable windows are rejected at load time; never match here
+            parse_window(window).is_ok_and(|window| in_window(window, now))
+        })
+}
+
+/// Prompt length in characters (messages plus system prompt)
+pub fn prompt_chars(request: &ChatRequest) -> usize {
+    let messages: usize = request.messages.iter().map(|m| m.content.chars().count()).sum();
+    messages + request.system.as_ref().map_or(0, |s| s.chars().count())
+}
+
+/// Glob match supporting `*` (any run) and `?` (any one character)
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                // Let the last `*` swallow one more character
+                Some((star, matched)) => {
+                    p = star + 1;
+                    t = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Parse `"HH:MM-HH:MM"` into start/end minutes after midnight
+fn parse_window(window: &str) -> Result<(u32, u32)> {
+    let minutes = |time: &str| -> Result<u32> {
+        let (hours, minutes) = time
+            .trim()
+            .split_once(':')
+            .ok_or_else(|| anyhow!("expected HH:MM, got '{}'", time))?;
+        let hours: u32 = hours.parse().map_err(|_| anyhow!("invalid hour in '{}'", time))?;
+        let minutes: u32 = minutes.parse().map_err(|_| anyhow!("invalid minute in '{}'", time))?;
+        if hours > 23 || minutes > 59 {
+            return Err(anyhow!("time out of range: '{}'", time));
+        }
+        Ok(hours * 60 + minutes)
+    };
+
+    let (start, end) = window
+        .split_once('-')
+        .ok_or_else(|| anyhow!("expected \"HH:MM-HH:MM\", got '{}'", window))?;
+    Ok((minutes(start)?, minutes(end)?))
+}
+
+/// Whether `now` falls in `[start, end)`, wrapping past midnight if end < start
+fn in_window((start, end): (u32, u32), now: DateTime<Utc>) -> bool {
+    let minute = now.hour() * 60 + now.minute();
+    if start <= end {
+        minute >= start && minute < end
+    } else {
+        minute >= start || minute < end
+    }
+}
+
+/// Check a rule against the configured providers
+pub fn validate(rule: &RoutingRule, providers: &HashMap<String, ProviderConfig>) -> Result<()> {
+    if rule.provider.is_none() && rule.model.is_none() && rule.strategy.is_none() {
+        return Err(anyhow!("rule sets none of provider, model or strategy"));
+    }
+
+    if let Some(provider) = &rule.provider
+        && !providers.contains_key(provider)
+    {
+        return Err(anyhow!("unknown provider '{}'", provider));
+    }
+
+    if let Some(strategy) = &rule.strategy
+        && !crate::router::STRATEGIES.contains(&strategy.as_str())
+    {
+        return Err(anyhow!("unknown strategy '{}'", strategy));
+    }
+
+    // Image parts and tools aren't forwarded to providers, so such a rule
+    // could only route requests that then fail or lose them
+    if rule.conditions.has_images.is_some() || rule.conditions.has_tools.is_some() {
+        return Err(anyhow!("has_images and has_tools aren't supported yet (images and tools are not forwarded)"));
+    }
+
+    if let Some(window) = &rule.conditions.time {
+        parse_window(window)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{ChatMessage, Role};
+    use chrono::TimeZone;
+
+    fn request(model: &str, content: &str) -> ChatRequest {
+        ChatRequest {
+            model: model.to_string(),
+            messages: vec![ChatMessage {
+                role: Role::User,
+                content: content.to_string(),
+            }],
+            stream: false,
+            temperature: None,
+            max_tokens: None,
+            top_p: None,
+            system: None,
+            hedge: None,
+        }
+    }
+
+    fn rule(name: &str, conditions: RuleConditions) -> RoutingRule {
+        RoutingRule {
+            name: name.to_string(),
+            conditions,
+            provider: Some("ollama".to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 1, 1, hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("claude-*", "claude-sonnet-4-5"));
+        assert!(glob_match("*sonnet*", "claude-sonnet-4-5"));
+        assert!(glob_match("gpt-?o", "gpt-4o"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("claude-*", "gpt-4o"));
+        assert!(!glob_match("gpt-?o", "gpt-4-o"));
+        assert!(glob_match("a*b*c", "aXXbYYc"));
+        assert!(!glob_match("a*b*c", "aXXbYY"));
+    }
+
+    #[test]
+    fn test_first_match_wins() {
+        let rules = vec![
+            rule("gpt", RuleConditions { model: Some("gpt-*".to_string()), ..Default::default() }),
+            rule("long", RuleConditions { min_prompt_chars: Some(10), ..Default::default() }),
+            rule("catch-all", RuleConditions::default()),
+        ];
+        let context = RequestContext::default();
+
+        let hit = |request: &ChatRequest| evaluate(&rules, request, &context, at(12, 0)).map(|(_, r)| r.name.as_str());
+        assert_eq!(hit(&request("gpt-4o", "a very long prompt")), Some("gpt"));
+        assert_eq!(hit(&request("claude", "a very long prompt")), Some("long"));
+        assert_eq!(hit(&request("claude", "short")), Some("catch-all"));
+        assert_eq!(evaluate(&rules[..2], &request("claude", "short"), &context, at(12, 0)), None);
+    }
+
+    #[test]
+    fn test_context_conditions() {
+        let context = RequestContext::from_headers([
+            ("Authorization", "Bearer team-a-123"),
+            ("X-Thanos-Tenant", "acme"),
+            ("X-Team", "research"),
+        ]);
+        let request = request("m", "hi");
+        let check = |conditions: RuleConditions| matches(&conditions, &request, &context, at(12, 0));
+
+        assert!(check(RuleConditions { client: Some("team-a-*".to_string()), ..Default::default() }));
+        assert!(!check(RuleConditions { client: Some("team-b-*".to_string()), ..Default::default() }));
+        assert!(check(RuleConditions { tenant: Some("acme".to_string()), ..Default::default() }));
+        assert!(check(RuleConditions {
+            headers: HashMap::from([("x-team".to_string(), "res*".to_string())]),
+            ..Default::default()
+        }));
+        assert!(!check(RuleConditions {
+            headers: HashMap::from([("x-missing".to_string(), "*".to_string())]),
+            ..Default::default()
+        }));
+    }
+
+    #[test]
+    fn test_time_window() {
+        let office = RuleConditions { time: Some("09:00-17:00".to_string()), ..Default::default() };
+        let night = RuleConditions { time: Some("22:00-06:00".to_string()), ..Default::default() };
+        let request = request("m", "hi");
+        let context = RequestContext::default();
+
+        assert!(matches(&office, &request, &context, at(9, 0)));
+        assert!(!matches(&office, &request, &context, at(17, 0)));
+        assert!(matches(&night, &request, &context, at(23, 30)));
+        assert!(matches(&night, &request, &context, at(5, 59)));
+        assert!(!matches(&night, &request, &context, at(12, 0)));
+    }
+
+    #[test]
+    fn test_validate() {
+        let providers = HashMap::from([("ollama".to_string(), crate::config::ProviderConfig {
+            enabled: true,
+            auth_method: crate::types::AuthMethod::None,
+            api_key: None,
+            base_url: None,
+            endpoint: None,
+            model: None,
+            max_tokens: None,
+            temperature: None,
+            client_id: None,
+            plugin: None,
+            retry: Default::default(),
+            context_length: None,
+            output_limit: None,
+            circuit_breaker: None,
+        })]);
+
+        assert!(validate(&rule("ok", RuleConditions::default()), &providers).is_ok());
+        assert!(validate(&RoutingRule::default(), &providers).is_err());
+
+        let unknown = RoutingRule { provider: Some("nope".to_string()), ..Default::default() };
+        assert!(validate(&unknown, &providers).is_err());
+
+        let bad_strategy = RoutingRule { strategy: Some("random".to_string()), ..Default::default() };
+        assert!(validate(&bad_strategy, &providers).is_err());
+
+        let bad_time = rule("t", RuleConditions { time: Some("25:00-26:00".to_string()), ..Default::default() });
+        assert!(validate(&bad_time, &providers).is_err());
+
+        let tools = rule("tools", RuleConditions { has_tools: Some(true), ..Default::default() });
+        assert!(validate(&tools, &providers).is_err());
+    }
+}
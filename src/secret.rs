@@ -0,0 +1,107 @@
+/// Secret strings: API keys, admin tokens and OAuth tokens
+///
+/// `Debug`, `Display` and `Serialize` print `[redacted]`, the memory is
+/// wiped on drop, and the value is only reachable through `expose`. A field
+/// that has to be written out for real (tokens saved to the keyring) opts in
+/// with `#[serde(serialize_with = "crate::secret::expose")]`.
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use zeroize::Zeroizing;
+
+const REDACTED: &str = "[redacted]";
+
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Secret(Zeroizing<String>);
+
+impl Secret {
+    pub fn new(value: impl Into<String>) -> Self {
+        Self(Zeroizing::new(value.into()))
+    }
+
+    /// The secret itself, for the request that needs it; keep it out of logs
+    /// and error messages
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<String> for Secret {
+    fn from(value: String) -> Self {
+        Self::new(value)
+    }
+}
+
+impl From<&str> for Secret {
+    fn from(value: &str) -> Self {
+        Self::new(value)
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl fmt::Display for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+/// Redacted, except that an empty secret stays visibly empty
+impl Serialize for Secret {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(if self.is_empty() { "" } else { REDACTED })
+    }
+}
+
+impl<'de> Deserialize<'de> for Secret {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Self::new)
+    }
+}
+
+/// Serialize the secret itself, for storage that needs it back
+pub fn expose<S: Serializer>(secret: &Secret, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(secret.expose())
+}
+
+/// `expose` for an optional secret
+pub fn expose_option<S: Serializer>(secret: &Option<Secret>, serializer: S) -> Result<S::Ok, S::Error> {
+    match secret {
+        Some(secret) => serializer.serialize_some(secret.expose()),
+        None => serializer.serialize_none(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize, Deserialize)]
+    struct Stored {
+        #[serde(serialize_with = "expose")]
+        token: Secret,
+        shown: Secret,
+    }
+
+    #[test]
+    fn test_secret_is_redacted_unless_exposed() {
+        let secret = Secret::from("sk-live-123");
+        assert_eq!(format!("{:?} {}", secret, secret), "[redacted] [redacted]");
+        assert_eq!(serde_json::to_string(&secret).unwrap(), r#""[redacted]""#);
+        assert_eq!(serde_json::to_string(&Secret::default()).unwrap(), r#""""#);
+        assert_eq!(secret.expose(), "sk-live-123");
+
+        let stored = Stored { token: secret.clone(), shown: secret };
+        let json = serde_json::to_string(&stored).unwrap();
+        assert_eq!(json, r#"{"token":"sk-live-123","shown":"[redacted]"}"#);
+        let read: Stored = serde_json::from_str(&json).unwrap();
+        assert_eq!(read.token.expose(), "sk-live-123");
+    }
+}
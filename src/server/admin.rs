@@ -0,0 +1,214 @@
+/// Admin API - operational controls, guarded by `server.admin_token`
+///
+/// Mounted under `/admin` on the HTTP and UDS servers. Without a token the
+/// endpoints only answer on the Unix socket; network listeners refuse them. The OAuth login
+/// endpoints are also gRPC methods (`StartLogin`, `GetLogin`, `SubmitLoginCode`).
+use super::http::AppState;
+use crate::auth::{login, LOGINS};
+use crate::config::Config;
+use crate::secret::Secret;
+use axum::{
+    extract::{Path, Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Json, Response},
+    routing::{get, post},
+    Router,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+type AdminResult = Result<Json<Value>, (StatusCode, Json<Value>)>;
+
+/// Marks requests that arrived on the Unix socket, which may skip the admin token
+#[derive(Clone, Copy, Debug)]
+pub struct LocalSocket {
+    /// The peer's user id, when the OS reports it
+    pub uid: Option<u32>,
+}
+
+/// Admin routes, to be merged into a server's router
+pub fn routes(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/admin/circuits", get(circuits_handler))
+        .route("/admin/circuits/:provider", get(circuit_handler))
+        .route("/admin/circuits/:provider/open", post(open_circuit_handler))
+        .route("/admin/circuits/:provider/reset", post(reset_circuit_handler))
+        .route("/admin/reload", post(reload_handler))
+        .route("/admin/auth", get(auth_status_handler))
+        .route("/admin/auth/:provider/login", post(start_login_handler))
+        .route("/admin/auth/logins/:id", get(login_handler).delete(cancel_login_handler))
+        .route("/admin/auth/logins/:id/code", post(submit_code_handler))
+        .route_layer(middleware::from_fn_with_state(state, require_admin))
+}
+
+/// Let a request through to an admin handler only if `authorize` allows it
+async fn require_admin(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let local = request.extensions().get::<LocalSocket>().is_some();
+    match authorize(&state, request.headers(), local) {
+        Ok(()) => next.run(request).await,
+        Err(rejection) => rejection.into_response(),
+    }
+}
+
+/// Check the `Authorization: Bearer` header against `server.admin_token`
+///
+/// With no token configured, only `local` (Unix socket) requests are allowed.
+pub fn authorize(state: &AppState, headers: &HeaderMap, local: bool) -> Result<(), (StatusCode, Json<Value>)> {
+    let config = state.config();
+    if config.server.admin_token.is_none() {
+        return if local {
+            Ok(())
+        } else {
+            Err(error(
+                StatusCode::FORBIDDEN,
+                "admin API is only served on the Unix socket unless server.admin_token is set",
+            ))
+        };
+    }
+
+    let presented = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    if admin_token_valid(&config, presented) {
+        Ok(())
+    } else {
+        Err(error(StatusCode::UNAUTHORIZED, "invalid or missing admin token"))
+    }
+}
+
+/// Whether a presented bearer token matches `server.admin_token` (nothing does if unset)
+pub fn admin_token_valid(config: &Config, presented: Option<&str>) -> bool {
+    let Some(expected) = config.server.admin_token.as_ref().map(Secret::expose) else {
+        return false;
+    };
+    presented.is_some_and(|token| constant_time_eq(token.as_bytes(), expected.as_bytes()))
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn error(status: StatusCode, message: &str) -> (StatusCode, Json<Value>) {
+    (status, Json(json!({ "error": { "message": message, "type": "admin_error" } })))
+}
+
+fn unknown_provider(provider: &str) -> (StatusCode, Json<Value>) {
+    error(StatusCode::NOT_FOUND, &format!("unknown provider '{}'", provider))
+}
+
+/// GET /admin/circuits - Circuit breaker state for every provider
+async fn circuits_handler(State(state): State<AppState>) -> AdminResult {
+    let circuits = state.router().circuits();
+
+    Ok(Json(json!({
+        "object": "list",
+        "data": circuits,
+        "count": circuits.len(),
+    })))
+}
+
+/// GET /admin/circuits/:provider
+async fn circuit_handler(State(state): State<AppState>, Path(provider): Path<String>) -> AdminResult {
+    let circuit = state.router().circuit(&provider).ok_or_else(|| unknown_provider(&provider))?;
+
+    Ok(Json(json!(circuit)))
+}
+
+/// POST /admin/circuits/:provider/open - Stop routing to a provider until reset
+async fn open_circuit_handler(State(state): State<AppState>, Path(provider): Path<String>) -> AdminResult {
+    if !state.router().force_open_circuit(&provider) {
+        return Err(unknown_provider(&provider));
+    }
+
+    Ok(Json(json!(state.router().circuit(&provider))))
+}
+
+/// POST /admin/circuits/:provider/reset - Close a circuit and clear its history
+async fn reset_circuit_handler(State(state): State<AppState>, Path(provider): Path<String>) -> AdminResult {
+    if !state.router().reset_circuit(&provider) {
+        return Err(unknown_provider(&provider));
+    }
+
+    Ok(Json(json!(state.router().circuit(&provider))))
+}
+
+/// POST /admin/reload - Re-read the config file and switch to it
+async fn reload_handler(State(state): State<AppState>) -> AdminResult {
+    let summary = state
+        .live
+        .reload_async(crate::reload::Trigger::Admin)
+        .await
+        .map_err(|e| error(StatusCode::UNPROCESSABLE_ENTITY, &format!("config not reloaded: {:#}", e)))?;
+    Ok(Json(json!({ "reloaded": true, "changes": summary })))
+}
+
+/// GET /admin/auth - Which OAuth providers have tokens, and when they expire
+async fn auth_status_handler() -> AdminResult {
+    let store = crate::auth::token_store();
+
+    let providers: Vec<Value> = crate::auth::token_manager::OAUTH_PROVIDERS
+        .iter()
+        .map(|provider| match store.get_oauth_tokens(provider) {
+            Ok(tokens) => json!({
+                "provider": provider,
+                "authenticated": tokens.is_some(),
+                "expires_at": tokens.and_then(|tokens| tokens.expires_at),
+            }),
+            Err(e) => json!({ "provider": provider, "authenticated": false, "error": format!("{:#}", e) }),
+        })
+        .collect();
+
+    Ok(Json(json!({ "object": "list", "data": providers, "store": store.describe() })))
+}
+
+/// POST /admin/auth/:provider/login - Start an OAuth login for a remote client
+async fn start_login_handler(Path(provider): Path<String>) -> AdminResult {
+    if login::provider_id(&provider).is_none() {
+        return Err(unknown_provider(&provider));
+    }
+
+    let status = LOGINS
+        .start(&provider)
+        .await
+        .map_err(|e| error(StatusCode::BAD_GATEWAY, &format!("login not started: {:#}", e)))?;
+    Ok(Json(json!(status)))
+}
+
+fn unknown_login(id: &str) -> (StatusCode, Json<Value>) {
+    error(StatusCode::NOT_FOUND, &format!("unknown or expired login '{}'", id))
+}
+
+/// GET /admin/auth/logins/:id - A login's progress
+async fn login_handler(Path(id): Path<String>) -> AdminResult {
+    let status = LOGINS.status(&id).ok_or_else(|| unknown_login(&id))?;
+
+    Ok(Json(json!(status)))
+}
+
+/// DELETE /admin/auth/logins/:id - Abandon a login
+async fn cancel_login_handler(Path(id): Path<String>) -> AdminResult {
+    if !LOGINS.cancel(&id) {
+        return Err(unknown_login(&id));
+    }
+
+    Ok(Json(json!({ "id": id, "cancelled": true })))
+}
+
+#[derive(Deserialize)]
+struct SubmitCode {
+    code: String,
+}
+
+/// POST /admin/auth/logins/:id/code - Finish a Claude login with the pasted `code#state`
+async fn submit_code_handler(Path(id): Path<String>, Json(body): Json<SubmitCode>) -> AdminResult {
+    let status = LOGINS
+        .submit_code(&id, &body.code)
+        .await
+        .ok_or_else(|| unknown_login(&id))?
+        .map_err(|e| error(StatusCode::BAD_REQUEST, &format!("{:#}", e)))?;
+
+    Ok(Json(json!(status)))
+}
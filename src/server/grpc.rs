@@ -1,13 +1,13 @@
-use crate::{config::Config, proto, router::Router, types::{ChatMessage, ChatRequest as InternalChatRequest}};
+use crate::{proto, reload::LiveRouter, types::{ChatMessage, ChatRequest as InternalChatRequest}};
 use anyhow::Result;
 use std::sync::Arc;
-use tonic::{transport::Server, Request, Response, Status};
-use tracing::{info, error};
+use tonic::{service::interceptor::InterceptedService, transport::{server::TcpIncoming, Server}, Request, Response, Status};
+use tonic_health::ServingStatus;
+use tracing::{debug, error, info};
 
 /// gRPC service implementation
 pub struct ThanosServiceImpl {
-    config: Arc<Config>,
-    router: Arc<Router>,
+    live: Arc<LiveRouter>,
 }
 
 #[tonic::async_trait]
@@ -19,6 +19,20 @@ impl proto::thanos_service_server::ThanosService for ThanosServiceImpl {
         &self,
         request: Request<proto::ChatRequest>,
     ) -> Result<Response<Self::ChatCompletionStream>, Status> {
+        let client = super::client_key(
+            request.extensions().get::<super::tls::TlsConnectInfo>(),
+            request.remote_addr(),
+            None,
+        );
+        self.live
+            .current()
+            .check_rate_limit(&client, "chat_completions")
+            .map_err(|e| crate::error::grpc_status(&e))?;
+
+        let metadata = request.metadata().clone().into_headers();
+        let context = crate::rules::RequestContext::from_headers(
+            metadata.iter().filter_map(|(name, value)| Some((name.as_str(), value.to_str().ok()?))),
+        );
         let proto_req = request.into_inner();
 
         info!("gRPC chat completion request: model={}", proto_req.model);
@@ -31,13 +45,40 @@ impl proto::thanos_service_server::ThanosService for ThanosServiceImpl {
 
         // If streaming is requested, use stream routing
         if internal_req.stream {
-            let router = Arc::clone(&self.router);
+            let router = self.live.current();
 
             tokio::spawn(async move {
-                match router.route_chat_completion_stream(&internal_req).await {
+                let _in_flight = crate::shutdown::SHUTDOWN.track();
+                // Dropping the routing future on disconnect cancels the upstream request
+                let routed = tokio::select! {
+                    routed = router.route_chat_completion_stream_with(&internal_req, &context) => routed,
+                    _ = tx.closed() => {
+                        debug!("gRPC client disconnected before stream started");
+                        return;
+                    }
+                };
+
+                match routed {
                     Ok(mut stream_rx) => {
                         // Forward stream chunks from router to gRPC client
-                        while let Some(chunk_result) = stream_rx.recv().await {
+                        loop {
+                            let chunk_result = tokio::select! {
+                                chunk = stream_rx.recv() => match chunk {
+                                    Some(chunk) => chunk,
+                                    None => break,
+                                },
+                                _ = tx.closed() => {
+                                    // Dropping stream_rx aborts the provider stream
+                                    debug!("gRPC client disconnected mid-stream");
+                                    break;
+                                }
+                                _ = crate::shutdown::SHUTDOWN.expired() => {
+                                    let status = Status::unavailable(crate::shutdown::SHUTDOWN_MESSAGE);
+                                    let _ = tx.send(Err(status)).await;
+                                    break;
+                                }
+                            };
+
                             match chunk_result {
                                 Ok(response) => {
                                     let proto_response = internal_to_proto_response(response);
@@ -47,7 +88,7 @@ impl proto::thanos_service_server::ThanosService for ThanosServiceImpl {
                                 }
                                 Err(e) => {
                                     error!("Stream error: {}", e);
-                                    let _ = tx.send(Err(Status::internal(format!("Stream error: {}", e)))).await;
+                                    let _ = tx.send(Err(crate::error::grpc_status(&e))).await;
                                     break;
                                 }
                             }
@@ -55,23 +96,32 @@ impl proto::thanos_service_server::ThanosService for ThanosServiceImpl {
                     }
                     Err(e) => {
                         error!("Failed to route stream: {}", e);
-                        let _ = tx.send(Err(Status::internal(format!("Routing failed: {}", e)))).await;
+                        let _ = tx.send(Err(crate::error::grpc_status(&e))).await;
                     }
                 }
             });
         } else {
             // Non-streaming: make single request and send as final chunk
-            let router = Arc::clone(&self.router);
+            let router = self.live.current();
 
             tokio::spawn(async move {
-                match router.route_chat_completion(&internal_req).await {
+                let _in_flight = crate::shutdown::SHUTDOWN.track();
+                let routed = tokio::select! {
+                    routed = router.route_chat_completion_with(&internal_req, &context) => routed,
+                    _ = tx.closed() => {
+                        debug!("gRPC client disconnected, cancelling request");
+                        return;
+                    }
+                };
+
+                match routed {
                     Ok(response) => {
                         let proto_response = internal_to_proto_response(response);
                         let _ = tx.send(Ok(proto_response)).await;
                     }
                     Err(e) => {
                         error!("Failed to route request: {}", e);
-                        let _ = tx.send(Err(Status::internal(format!("Routing failed: {}", e)))).await;
+                        let _ = tx.send(Err(crate::error::grpc_status(&e))).await;
                     }
                 }
             });
@@ -85,7 +135,8 @@ impl proto::thanos_service_server::ThanosService for ThanosServiceImpl {
         _request: Request<proto::Empty>,
     ) -> Result<Response<proto::ModelsResponse>, Status> {
         // Collect models from all enabled providers
-        let providers = self.config.enabled_providers();
+        let config = self.live.config();
+        let providers = config.enabled_providers();
         let mut models = Vec::new();
 
         for (provider_name, provider_config) in providers {
@@ -111,28 +162,135 @@ impl proto::thanos_service_server::ThanosService for ThanosServiceImpl {
         &self,
         _request: Request<proto::Empty>,
     ) -> Result<Response<proto::HealthResponse>, Status> {
-        // Check health of all enabled providers
-        let providers = self.config.enabled_providers();
-        let mut provider_healths = Vec::new();
-
-        for (provider_name, _provider_config) in providers {
-            // For now, just return healthy status
-            // TODO: Actually ping providers to check health
-            provider_healths.push(proto::ProviderHealth {
-                name: provider_name,
-                status: "healthy".to_string(),
-                error: None,
-            });
-        }
+        // Latest background check results (providers without one are checked now)
+        let provider_health = self.live.current().provider_health().await;
+        let status = crate::health::overall_status(provider_health.iter().map(|(_, health)| health));
 
-        let status = "healthy".to_string();
+        let providers = provider_health
+            .into_iter()
+            .map(|(name, health)| proto::ProviderHealth {
+                name,
+                status: health.status.as_str().to_string(),
+                error: health.error,
+            })
+            .collect();
 
         Ok(Response::new(proto::HealthResponse {
-            status,
-            providers: provider_healths,
-            uptime: 0, // TODO: Track uptime from server start time
+            status: status.as_str().to_string(),
+            providers,
+            uptime: self.live.current().health_checker().uptime_secs() as i64,
+        }))
+    }
+
+    async fn count_tokens(
+        &self,
+        request: Request<proto::CountTokensRequest>,
+    ) -> Result<Response<proto::CountTokensResponse>, Status> {
+        let proto_req = request.into_inner();
+        let chat = proto_req
+            .request
+            .ok_or_else(|| Status::invalid_argument("Missing request"))?;
+        let internal_req = proto_to_internal_request(chat)
+            .map_err(|e| Status::invalid_argument(format!("Invalid request: {}", e)))?;
+
+        let count = self
+            .live
+            .current()
+            .count_tokens(&internal_req, proto_req.provider.as_deref(), proto_req.exact)
+            .await;
+
+        Ok(Response::new(proto::CountTokensResponse {
+            input_tokens: count.tokens.min(i32::MAX as usize) as i32,
+            tokenizer: count.tokenizer,
+            exact: count.exact,
         }))
     }
+
+    async fn start_login(
+        &self,
+        request: Request<proto::StartLoginRequest>,
+    ) -> Result<Response<proto::LoginStatus>, Status> {
+        self.authorize_admin(&request)?;
+        let provider = request.into_inner().provider;
+        if crate::auth::login::provider_id(&provider).is_none() {
+            return Err(Status::not_found(format!("unknown provider '{}'", provider)));
+        }
+
+        let status = crate::auth::LOGINS
+            .start(&provider)
+            .await
+            .map_err(|e| Status::unavailable(format!("login not started: {:#}", e)))?;
+        Ok(Response::new(login_to_proto(status)))
+    }
+
+    async fn get_login(
+        &self,
+        request: Request<proto::LoginRequest>,
+    ) -> Result<Response<proto::LoginStatus>, Status> {
+        self.authorize_admin(&request)?;
+        let id = request.into_inner().id;
+        let status = crate::auth::LOGINS.status(&id).ok_or_else(|| unknown_login(&id))?;
+
+        Ok(Response::new(login_to_proto(status)))
+    }
+
+    async fn submit_login_code(
+        &self,
+        request: Request<proto::SubmitLoginCodeRequest>,
+    ) -> Result<Response<proto::LoginStatus>, Status> {
+        self.authorize_admin(&request)?;
+        let proto::SubmitLoginCodeRequest { id, code } = request.into_inner();
+        let status = crate::auth::LOGINS
+            .submit_code(&id, &code)
+            .await
+            .ok_or_else(|| unknown_login(&id))?
+            .map_err(|e| Status::invalid_argument(format!("{:#}", e)))?;
+
+        Ok(Response::new(login_to_proto(status)))
+    }
+}
+
+impl ThanosServiceImpl {
+    /// Admin methods need `authorization: Bearer <server.admin_token>` metadata
+    ///
+    /// gRPC only listens on TCP, so they are refused outright when no token is set.
+    #[allow(clippy::result_large_err)] // tonic's Status
+    fn authorize_admin<T>(&self, request: &Request<T>) -> Result<(), Status> {
+        let config = self.live.config();
+        if config.server.admin_token.is_none() {
+            return Err(Status::permission_denied("admin methods are disabled unless server.admin_token is set"));
+        }
+
+        let presented = request
+            .metadata()
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+
+        if super::admin::admin_token_valid(&config, presented) {
+            Ok(())
+        } else {
+            Err(Status::unauthenticated("invalid or missing admin token"))
+        }
+    }
+}
+
+fn unknown_login(id: &str) -> Status {
+    Status::not_found(format!("unknown or expired login '{}'", id))
+}
+
+fn login_to_proto(status: crate::auth::LoginStatus) -> proto::LoginStatus {
+    proto::LoginStatus {
+        id: status.id,
+        provider: status.provider.to_string(),
+        flow: status.flow.to_string(),
+        url: status.url,
+        user_code: status.user_code,
+        state: status.state.as_str().to_string(),
+        error: status.error,
+        expires_in: status.expires_in,
+        token_expires_at: status.token_expires_at,
+    }
 }
 
 /// Convert proto request to internal request type
@@ -163,6 +321,7 @@ fn proto_to_internal_request(proto_req: proto::ChatRequest) -> anyhow::Result<In
         max_tokens: proto_req.max_tokens,
         top_p: proto_req.top_p,
         system: proto_req.system,
+        hedge: proto_req.hedge,
     })
 }
 
@@ -183,27 +342,24 @@ fn internal_to_proto_response(response: crate::types::ChatResponse) -> proto::Ch
 }
 
 /// Start gRPC server with advanced features
-pub async fn serve(config: Config) -> Result<()> {
+pub async fn serve(state: super::http::AppState) -> Result<()> {
+    let config = state.config();
     let addr = config.server.grpc.parse()?;
 
-    let config_arc = Arc::new(config.clone());
-    let router = Arc::new(Router::new(Arc::clone(&config_arc)));
+    // Standard grpc.health.v1 service, following readiness
+    let (health_reporter, health_service) = tonic_health::server::health_reporter();
+    tokio::spawn(report_readiness(health_reporter, state.live.clone()));
 
-    let service = ThanosServiceImpl {
-        config: config_arc,
-        router,
-    };
+    let service = ThanosServiceImpl { live: state.live };
 
-    info!("✓ gRPC server listening on {}", addr);
+    let tls = config.server.tls.as_ref().map(super::tls::ReloadableTls::for_tcp).transpose()?;
 
     // Build server with advanced features
     let mut server = Server::builder()
-        // Enable TCP keepalive
-        .tcp_keepalive(Some(std::time::Duration::from_secs(60)))
         // Set connection timeout
         .timeout(std::time::Duration::from_secs(300))
         // Add service with compression and message size limits
-        .add_service(
+        .add_service(InterceptedService::new(
             proto::thanos_service_server::ThanosServiceServer::new(service)
                 // Enable gzip compression for bandwidth optimization
                 .accept_compressed(tonic::codec::CompressionEncoding::Gzip)
@@ -211,7 +367,9 @@ pub async fn serve(config: Config) -> Result<()> {
                 // Set max message size (256MB for large requests/responses)
                 .max_decoding_message_size(256 * 1024 * 1024)
                 .max_encoding_message_size(256 * 1024 * 1024),
-        );
+            count_client_request,
+        ))
+        .add_service(health_service);
 
     // Add gRPC reflection for introspection (useful for grpcurl, Postman, etc.)
     #[cfg(debug_assertions)]
@@ -224,11 +382,75 @@ pub async fn serve(config: Config) -> Result<()> {
         info!("gRPC reflection enabled (debug mode)");
     }
 
-    // TODO: For production, add TLS:
-    // let tls_config = load_tls_config(...)?;
-    // server = server.tls_config(tls_config)?;
+    // Bind up front so readiness knows the listener is up
+    if let Some(tls) = tls {
+        let listener = tokio::net::TcpListener::bind(addr)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to bind gRPC listener on {}: {}", addr, e))?;
+        crate::health::STARTUP.listener_bound("grpc");
+        info!("✓ gRPC server listening on {} (TLS)", addr);
+
+        tls.spawn_watcher(|_| {});
+        server
+            .serve_with_incoming_shutdown(tls.incoming(listener), crate::shutdown::SHUTDOWN.draining())
+            .await?;
+    } else {
+        // Plain TCP, with keepalive
+        let incoming = TcpIncoming::new(addr, false, Some(std::time::Duration::from_secs(60)))
+            .map_err(|e| anyhow::anyhow!("Failed to bind gRPC listener on {}: {}", addr, e))?;
+        crate::health::STARTUP.listener_bound("grpc");
+        info!("✓ gRPC server listening on {}", addr);
 
-    server.serve(addr).await?;
+        server
+            .serve_with_incoming_shutdown(incoming, crate::shutdown::SHUTDOWN.draining())
+            .await?;
+    }
 
     Ok(())
 }
+
+/// Count requests per mutual TLS client identity
+#[allow(clippy::result_large_err)] // signature required by tonic interceptors
+fn count_client_request(request: Request<()>) -> Result<Request<()>, Status> {
+    if let Some(client) = request
+        .extensions()
+        .get::<super::tls::TlsConnectInfo>()
+        .and_then(|info| info.client.as_ref())
+    {
+        crate::metrics::METRICS
+            .client_requests_total
+            .with_label_values(&[&client.name, "grpc"])
+            .inc();
+    }
+    Ok(request)
+}
+
+/// Keep the grpc.health.v1 status of the whole server ("") and of
+/// `thanos.ThanosService` in line with readiness; `Watch` streams see changes
+async fn report_readiness(mut reporter: tonic_health::server::HealthReporter, live: Arc<LiveRouter>) {
+    use tonic::server::NamedService;
+    type Service = proto::thanos_service_server::ThanosServiceServer<ThanosServiceImpl>;
+
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(5));
+    let mut last = None;
+    loop {
+        // Report NOT_SERVING as soon as draining starts
+        let draining = crate::shutdown::SHUTDOWN.is_draining();
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = crate::shutdown::SHUTDOWN.draining(), if !draining => {}
+        }
+        let status = if crate::health::readiness(&live.current()).await.ready {
+            ServingStatus::Serving
+        } else {
+            ServingStatus::NotServing
+        };
+
+        if last != Some(status) {
+            debug!("gRPC health: {:?}", status);
+            reporter.set_service_status("", status).await;
+            reporter.set_service_status(<Service as NamedService>::NAME, status).await;
+            last = Some(status);
+        }
+    }
+}
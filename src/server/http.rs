@@ -1,11 +1,11 @@
 use crate::config::Config;
-use crate::health::HealthChecker;
 use crate::router::Router as ThanosRouter;
+use crate::rules::RequestContext;
 use crate::types::ChatRequest;
 use anyhow::Result;
 use axum::{
     extract::State,
-    http::StatusCode,
+    http::{HeaderMap, StatusCode},
     response::{sse::Event, IntoResponse, Json, Sse},
     routing::{get, post},
     Router,
@@ -22,108 +22,238 @@ use tracing::{error, info};
 /// HTTP server state
 #[derive(Clone)]
 pub struct AppState {
-    pub config: Arc<Config>,
-    pub router: Arc<ThanosRouter>,
-    pub health_checker: Arc<HealthChecker>,
+    /// Router and config in effect, replaced on config reload
+    pub live: Arc<crate::reload::LiveRouter>,
+}
+
+impl AppState {
+    /// State around a router that is not reloaded from a file
+    pub fn new(router: Arc<ThanosRouter>) -> Self {
+        Self {
+            live: crate::reload::LiveRouter::new(router),
+        }
+    }
+
+    /// Router for the current config; take it once per request
+    pub fn router(&self) -> Arc<ThanosRouter> {
+        self.live.current()
+    }
+
+    pub fn config(&self) -> Arc<Config> {
+        self.live.config()
+    }
 }
 
 /// Start HTTP server (OpenAI-compatible API)
-pub async fn serve(config: Config) -> Result<()> {
-    let config_arc = Arc::new(config.clone());
-    let router = Arc::new(ThanosRouter::new(config_arc.clone()));
-    let health_checker = Arc::new(HealthChecker::new());
-
-    let state = AppState {
-        config: config_arc,
-        router,
-        health_checker,
+pub async fn serve(state: AppState) -> Result<()> {
+    let config = state.config();
+    let app = app(state);
+    let tls = config.server.tls.as_ref().map(super::tls::ReloadableTls::for_tcp).transpose()?;
+
+    let listener = tokio::net::TcpListener::bind(&config.server.bind).await?;
+    crate::health::STARTUP.listener_bound("http");
+    info!(
+        "✓ HTTP server listening on {}{}",
+        config.server.bind,
+        if tls.is_some() { " (TLS)" } else { "" }
+    );
+
+    serve_listener(listener, app, tls).await
+}
+
+/// Serve the app on a bound listener, over TLS when given
+pub async fn serve_listener(
+    listener: tokio::net::TcpListener,
+    app: Router,
+    tls: Option<Arc<super::tls::ReloadableTls>>,
+) -> Result<()> {
+    let Some(tls) = tls else {
+        // Peer addresses key the rate limiter
+        axum::serve(listener, app.into_make_service_with_connect_info::<std::net::SocketAddr>())
+            .with_graceful_shutdown(crate::shutdown::SHUTDOWN.draining())
+            .await?;
+        return Ok(());
     };
 
-    let app = Router::new()
+    use futures::StreamExt;
+    use hyper_util::rt::{TokioExecutor, TokioIo};
+    use tower::Service;
+
+    let watcher = tls.spawn_watcher(|_| {});
+    let mut incoming = tls.incoming(listener);
+    let mut connections = tokio::task::JoinSet::new();
+    loop {
+        let conn = tokio::select! {
+            conn = incoming.next() => match conn {
+                Some(Ok(conn)) => conn,
+                _ => break,
+            },
+            _ = crate::shutdown::SHUTDOWN.draining() => break,
+        };
+
+        // Reap finished connections
+        while connections.try_join_next().is_some() {}
+
+        let app = app.clone();
+        connections.spawn(async move {
+            let info = conn.info().clone();
+            let service = hyper::service::service_fn(move |mut req: axum::extract::Request<hyper::body::Incoming>| {
+                req.extensions_mut().insert(info.clone());
+                let mut app = app.clone();
+                async move { app.call(req).await }
+            });
+
+            let builder = hyper_util::server::conn::auto::Builder::new(TokioExecutor::new());
+            let connection = builder.serve_connection(TokioIo::new(conn), service);
+            tokio::pin!(connection);
+
+            // Finish in-flight requests, then close, once draining starts
+            let result = tokio::select! {
+                result = connection.as_mut() => result,
+                _ = crate::shutdown::SHUTDOWN.draining() => {
+                    connection.as_mut().graceful_shutdown();
+                    connection.await
+                }
+            };
+            if let Err(e) = result {
+                tracing::debug!("TLS connection closed: {}", e);
+            }
+        });
+    }
+
+    // Stop accepting, then wait for open connections to finish
+    drop(incoming);
+    if let Some(watcher) = watcher {
+        watcher.abort();
+    }
+    while connections.join_next().await.is_some() {}
+
+    Ok(())
+}
+
+/// All HTTP API routes and middleware, shared by the HTTP and HTTP/3 listeners
+///
+/// When HTTP/3 is enabled, responses advertise it with `Alt-Svc`.
+pub fn app(state: AppState) -> Router {
+    let alt_svc = super::http3::alt_svc(&state.config());
+
+    Router::new()
         // Health check
         .route("/health", get(health_handler))
+        // Kubernetes probes
+        .route("/livez", get(livez_handler))
+        .route("/readyz", get(readyz_handler))
         // Metrics (Prometheus)
         .route("/metrics", get(metrics_handler))
         // List models
         .route("/v1/models", get(models_handler))
         // List providers
         .route("/v1/providers", get(providers_handler))
-        // Chat completions (OpenAI-compatible)
-        .route("/v1/chat/completions", post(chat_completions_handler))
+        // Chat completions (OpenAI-compatible), limited per client
+        .route(
+            "/v1/chat/completions",
+            post(chat_completions_handler).layer(axum::middleware::from_fn_with_state(state.clone(), rate_limit)),
+        )
+        // Show which routing rule a request would hit
+        .route("/v1/routing/dry-run", post(routing_dry_run_handler))
+        // Token counting
+        .route("/v1/tokenize", post(tokenize_handler))
+        .route("/v1/count_tokens", post(count_tokens_handler))
+        // Browser clients may call the API from any origin, but not the admin routes
+        .layer(CorsLayer::permissive())
+        // Operational controls
+        .merge(super::admin::routes(state.clone()))
         // Middleware
         .layer(TraceLayer::new_for_http())
         .layer(CompressionLayer::new())
-        .layer(CorsLayer::permissive())
-        .with_state(state);
-
-    let listener = tokio::net::TcpListener::bind(&config.server.bind).await?;
-    info!("✓ HTTP server listening on {}", config.server.bind);
-
-    axum::serve(listener, app).await?;
-
-    Ok(())
+        .layer(axum::middleware::map_request(count_client_request))
+        .layer(axum::middleware::from_fn(track_in_flight))
+        .layer(axum::middleware::map_response(move |mut response: axum::response::Response| {
+            let alt_svc = alt_svc.clone();
+            async move {
+                if let Some(value) = alt_svc {
+                    response.headers_mut().insert(axum::http::header::ALT_SVC, value);
+                }
+                response
+            }
+        }))
+        .with_state(state)
 }
 
-/// GET /health - Check health of Thanos and all providers
-pub async fn health_handler(State(state): State<AppState>) -> Json<Value> {
-    use std::collections::HashMap;
-
-    // Get provider endpoints for health checks
-    let mut provider_endpoints = HashMap::new();
+/// Hold off the end of a shutdown drain until the response is ready;
+/// streamed bodies hold their own guard
+pub async fn track_in_flight(request: axum::extract::Request, next: axum::middleware::Next) -> axum::response::Response {
+    let _in_flight = crate::shutdown::SHUTDOWN.track();
+    next.run(request).await
+}
 
-    for (name, provider_config) in state.config.enabled_providers() {
-        let endpoint = match name.as_str() {
-            "anthropic" | "anthropic_max" => {
-                provider_config.base_url.clone()
-                    .unwrap_or_else(|| "https://api.anthropic.com".to_string())
-            },
-            "openai" => {
-                provider_config.base_url.clone()
-                    .unwrap_or_else(|| "https://api.openai.com".to_string())
-            },
-            "gemini" => {
-                provider_config.base_url.clone()
-                    .unwrap_or_else(|| "https://generativelanguage.googleapis.com".to_string())
-            },
-            "xai" => {
-                provider_config.base_url.clone()
-                    .unwrap_or_else(|| "https://api.x.ai".to_string())
-            },
-            "ollama" => {
-                provider_config.endpoint.clone()
-                    .unwrap_or_else(|| "http://localhost:11434".to_string())
-            },
-            "github_copilot" => "https://api.github.com".to_string(),
-            _ => continue,
-        };
-        provider_endpoints.insert(name.clone(), endpoint);
+/// Turn away clients over `[rate_limiting]` with 429 before their request is handled
+pub async fn rate_limit(
+    State(state): State<AppState>,
+    request: axum::extract::Request,
+    next: axum::middleware::Next,
+) -> axum::response::Response {
+    let extensions = request.extensions();
+    let client = super::client_key(
+        extensions.get::<super::tls::TlsConnectInfo>(),
+        extensions
+            .get::<axum::extract::ConnectInfo<std::net::SocketAddr>>()
+            .map(|info| info.0),
+        extensions.get::<super::admin::LocalSocket>(),
+    );
+    match state.router().check_rate_limit(&client, "chat_completions") {
+        Ok(()) => next.run(request).await,
+        Err(e) => crate::error::error_response(&e),
     }
+}
 
-    // Check provider health (runs in parallel)
-    let provider_health = state.health_checker.check_all(provider_endpoints).await;
-
-    // Determine overall status
-    let overall_status = if provider_health
-        .values()
-        .all(|h| h.status == crate::health::HealthStatus::Healthy)
-    {
-        "healthy"
-    } else if provider_health
-        .values()
-        .any(|h| h.status == crate::health::HealthStatus::Healthy)
+/// Count requests per mutual TLS client identity
+async fn count_client_request(request: axum::extract::Request) -> axum::extract::Request {
+    if let Some(client) = request
+        .extensions()
+        .get::<super::tls::TlsConnectInfo>()
+        .and_then(|info| info.client.as_ref())
     {
-        "degraded"
-    } else {
-        "unhealthy"
-    };
+        let transport = if request.version() == axum::http::Version::HTTP_3 { "http3" } else { "http" };
+        crate::metrics::METRICS
+            .client_requests_total
+            .with_label_values(&[&client.name, transport])
+            .inc();
+    }
+    request
+}
+
+/// GET /health - Thanos and provider health from the background checks
+///
+/// Providers without a recent result are checked on the spot.
+pub async fn health_handler(State(state): State<AppState>) -> Json<Value> {
+    let provider_health = state.router().provider_health().await;
+    let overall_status = crate::health::overall_status(provider_health.iter().map(|(_, health)| health));
 
     Json(json!({
         "status": overall_status,
         "version": crate::VERSION,
-        "providers": provider_health,
+        "uptime_secs": state.router().health_checker().uptime_secs(),
+        "providers": provider_health.into_iter().collect::<std::collections::BTreeMap<_, _>>(),
     }))
 }
 
+/// GET /livez - The process is up and serving requests
+pub async fn livez_handler() -> Json<Value> {
+    Json(json!({ "status": "ok" }))
+}
+
+/// GET /readyz - Config loaded, listeners bound and a provider available
+pub async fn readyz_handler(State(state): State<AppState>) -> impl IntoResponse {
+    let readiness = crate::health::readiness(&state.router()).await;
+    let status = if readiness.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(readiness))
+}
+
 /// GET /metrics (Prometheus format)
 pub async fn metrics_handler() -> String {
     use prometheus::Encoder;
@@ -139,7 +269,7 @@ pub async fn metrics_handler() -> String {
 /// GET /v1/providers - List enabled providers
 pub async fn providers_handler(State(state): State<AppState>) -> Json<Value> {
     let providers: Vec<Value> = state
-        .config
+        .config()
         .enabled_providers()
         .iter()
         .map(|(name, config)| {
@@ -200,18 +330,132 @@ pub async fn models_handler(State(_state): State<AppState>) -> Json<Value> {
     }))
 }
 
+/// Parse a chat request body, with the routing rule context from its headers
+fn parse_chat_request(
+    headers: &HeaderMap,
+    body: Value,
+) -> Result<(ChatRequest, RequestContext), (StatusCode, String)> {
+    let context = RequestContext::from_headers(
+        headers.iter().filter_map(|(name, value)| Some((name.as_str(), value.to_str().ok()?))),
+    );
+
+    let request = serde_json::from_value(body).map_err(|e| {
+        (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            format!("Failed to deserialize the JSON body into the target type: {}", e),
+        )
+    })?;
+
+    Ok((request, context))
+}
+
+/// POST /v1/routing/dry-run - Which rule, strategy and provider a request would get
+pub async fn routing_dry_run_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(body): Json<Value>,
+) -> Result<Json<Value>, axum::response::Response> {
+    let (payload, context) = parse_chat_request(&headers, body).map_err(IntoResponse::into_response)?;
+    let plan = state.router().plan(&payload, &context);
+
+    Ok(Json(json!({
+        "rule": plan.rule,
+        "rule_index": plan.rule_index,
+        "strategy": plan.strategy,
+        "provider": plan.provider,
+        "model": plan.model,
+        "context": {
+            "client_key": context.client_key.is_some(),
+            "tenant": context.tenant,
+            "prompt_chars": crate::rules::prompt_chars(&payload),
+        },
+    })))
+}
+
+/// POST /v1/tokenize - Token IDs and count for `text` (or `messages`) under `model`
+///
+/// Token IDs are only returned for models with an exact local vocabulary.
+pub async fn tokenize_handler(Json(body): Json<Value>) -> Result<Json<Value>, (StatusCode, String)> {
+    let model = body["model"]
+        .as_str()
+        .ok_or((StatusCode::UNPROCESSABLE_ENTITY, "missing field `model`".to_string()))?;
+    let tokenizer = crate::tokenizer::Tokenizer::for_model(model);
+
+    let (count, tokens) = match body["text"].as_str() {
+        Some(text) => (tokenizer.count(text), tokenizer.encode(text)),
+        None => {
+            let request: ChatRequest = serde_json::from_value(body.clone()).map_err(|e| {
+                (
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    format!("Expected `text` or chat `messages`: {}", e),
+                )
+            })?;
+            (tokenizer.count_request(&request), None)
+        }
+    };
+
+    Ok(Json(json!({
+        "model": model,
+        "tokenizer": tokenizer.name(),
+        "exact": tokenizer.is_exact(),
+        "count": count,
+        "tokens": tokens,
+    })))
+}
+
+/// POST /v1/count_tokens - Prompt tokens for a chat request
+///
+/// Thanos extensions: `provider` picks who counts, `exact` overrides
+/// `tokenizer.provider_count` for this request.
+pub async fn count_tokens_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(body): Json<Value>,
+) -> Result<Json<Value>, (StatusCode, String)> {
+    let provider = body["provider"].as_str().map(str::to_string);
+    let exact = body["exact"].as_bool();
+    let (payload, _) = parse_chat_request(&headers, body)?;
+
+    let count = state.router().count_tokens(&payload, provider.as_deref(), exact).await;
+
+    Ok(Json(json!({
+        "model": payload.model,
+        "input_tokens": count.tokens,
+        "tokenizer": count.tokenizer,
+        "exact": count.exact,
+    })))
+}
+
 /// POST /v1/chat/completions (OpenAI-compatible)
 pub async fn chat_completions_handler(
     State(state): State<AppState>,
-    Json(payload): Json<ChatRequest>,
-) -> Result<axum::response::Response, (StatusCode, String)> {
+    headers: HeaderMap,
+    Json(body): Json<Value>,
+) -> Result<axum::response::Response, axum::response::Response> {
+    let (payload, context) = parse_chat_request(&headers, body).map_err(IntoResponse::into_response)?;
+
     // Check if streaming is requested
     if payload.stream {
         // Return SSE stream
-        match state.router.route_chat_completion_stream(&payload).await {
+        match state.router().route_chat_completion_stream_with(&payload, &context).await {
             Ok(mut rx) => {
+                // The stream owns `rx`; when the client disconnects axum drops it,
+                // which cancels the provider's upstream request. The drain waits
+                // for it from here, not from its first poll.
+                let in_flight = crate::shutdown::SHUTDOWN.track();
                 let stream = async_stream::stream! {
-                    while let Some(result) = rx.recv().await {
+                    let _stream = in_flight;
+                    let mut cut_off = false;
+                    loop {
+                        let result = tokio::select! {
+                            result = rx.recv() => result,
+                            _ = crate::shutdown::SHUTDOWN.expired() => {
+                                cut_off = true;
+                                None
+                            }
+                        };
+                        let Some(result) = result else { break };
+
                         match result {
                             Ok(response) => {
                                 // Convert to OpenAI SSE format
@@ -232,11 +476,25 @@ pub async fn chat_completions_handler(
                             }
                             Err(e) => {
                                 error!("Stream error: {}", e);
+                                // Headers are already sent; report the failure in-band like OpenAI does
+                                let body = match crate::error::ProviderError::find(&e) {
+                                    Some(provider_error) => provider_error.to_json(),
+                                    None => json!({ "error": { "message": e.to_string(), "type": "server_error" } }),
+                                };
+                                yield Ok::<_, Infallible>(Event::default().data(body.to_string()));
                                 break;
                             }
                         }
                     }
 
+                    // Still streaming when the shutdown drain expired
+                    if cut_off {
+                        let body = json!({
+                            "error": { "message": crate::shutdown::SHUTDOWN_MESSAGE, "type": "server_shutdown" }
+                        });
+                        yield Ok::<_, Infallible>(Event::default().data(body.to_string()));
+                    }
+
                     // Send [DONE] marker
                     yield Ok::<_, Infallible>(Event::default().data("[DONE]"));
                 };
@@ -245,12 +503,12 @@ pub async fn chat_completions_handler(
             }
             Err(e) => {
                 error!("Failed to start stream: {}", e);
-                Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+                Err(crate::error::error_response(&e))
             }
         }
     } else {
         // Non-streaming response
-        match state.router.route_chat_completion(&payload).await {
+        match state.router().route_chat_completion_with(&payload, &context).await {
             Ok(response) => {
                 let openai_response = json!({
                     "id": format!("chatcmpl-{}", uuid::Uuid::new_v4()),
@@ -276,7 +534,7 @@ pub async fn chat_completions_handler(
             }
             Err(e) => {
                 error!("Chat completion error: {}", e);
-                Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+                Err(crate::error::error_response(&e))
             }
         }
     }
@@ -1,87 +1,163 @@
 /// HTTP/3 server implementation using Quinn + h3
+///
+/// Each request is run through the same axum router as the HTTP server, with
+/// its body streamed in so the same body size limit applies, and the response
+/// body is forwarded frame by frame, so SSE streams reach the client as they
+/// are produced.
 use crate::config::Config;
-use anyhow::Result;
+use anyhow::{Context, Result};
+use axum::Router;
+use axum::body::Body;
+use axum::http::{HeaderValue, Request};
+use bytes::{Buf, Bytes};
+use http_body_util::BodyExt;
+use rustls::pki_types::CertificateDer;
 use std::net::SocketAddr;
-use tracing::{info, warn};
+use std::sync::Arc;
+use tower::ServiceExt;
+use tracing::{debug, info, warn};
 
-/// Start HTTP/3 server (QUIC-based)
-pub async fn serve(config: Config) -> Result<()> {
-    warn!("HTTP/3 server is experimental and requires TLS certificates");
-
-    // For HTTP/3, we need:
-    // 1. TLS certificates (self-signed for local dev, Let's Encrypt for prod)
-    // 2. QUIC endpoint configuration
-    // 3. h3 connection handling
-
-    // Parse bind address
-    let addr: SocketAddr = config
-        .server
-        .bind
-        .parse()
-        .map_err(|e| anyhow::anyhow!("Invalid bind address: {}", e))?;
-
-    // TODO: Full HTTP/3 implementation would require:
-    // - TLS certificate loading (rustls)
-    // - Quinn endpoint setup
-    // - h3 request handling
-    // - Integration with Axum router
-
-    info!("HTTP/3 support is a stub - use HTTP/2 or gRPC for now");
-    warn!("To enable HTTP/3:");
-    warn!("  1. Generate TLS certificates:");
-    warn!("     openssl req -x509 -newkey rsa:4096 -nodes \\");
-    warn!("       -keyout key.pem -out cert.pem -days 365");
-    warn!("  2. Configure cert_path and key_path in config");
-    warn!("  3. HTTP/3 will be available on UDP port {}", addr.port());
-
-    // For now, just sleep to keep task alive
-    tokio::time::sleep(tokio::time::Duration::from_secs(u64::MAX)).await;
+type RequestStream = h3::server::RequestStream<h3_quinn::BidiStream<Bytes>, Bytes>;
 
-    Ok(())
+/// UDP address for the HTTP/3 listener
+fn bind_addr(config: &Config) -> Result<SocketAddr> {
+    let bind = config.server.http3.bind.as_ref().unwrap_or(&config.server.bind);
+    bind.parse()
+        .map_err(|e| anyhow::anyhow!("Invalid HTTP/3 bind address {}: {}", bind, e))
 }
 
-/* Full HTTP/3 implementation outline (for future reference):
+/// `Alt-Svc` value advertising HTTP/3, if it is enabled
+pub fn alt_svc(config: &Config) -> Option<HeaderValue> {
+    if !config.server.http3.enabled {
+        return None;
+    }
+    let port = bind_addr(config).ok()?.port();
+    HeaderValue::from_str(&format!("h3=\":{}\"; ma=86400", port)).ok()
+}
 
-use h3_quinn::quinn;
-use rustls::ServerConfig as TlsConfig;
+/// TLS 1.3 with the `h3` ALPN, reloaded like the TCP listeners' certificate
+fn reloadable_tls(config: &Config) -> Result<Arc<super::tls::ReloadableTls>> {
+    let tls = config
+        .server
+        .tls
+        .as_ref()
+        .context("HTTP/3 requires [server.tls] cert_path and key_path")?;
+    super::tls::ReloadableTls::new(tls, &[b"h3"], &[&rustls::version::TLS13])
+}
 
-pub async fn serve_http3(config: Config) -> Result<()> {
-    // Load TLS config
-    let tls_config = load_tls_config(&config.server.cert_path, &config.server.key_path)?;
+fn quic_config(tls: Arc<rustls::ServerConfig>) -> Result<quinn::ServerConfig> {
+    let crypto = quinn::crypto::rustls::QuicServerConfig::try_from(tls)
+        .context("TLS config is not usable for QUIC")?;
+    Ok(quinn::ServerConfig::with_crypto(Arc::new(crypto)))
+}
 
-    // Create QUIC server config
-    let mut server_config = quinn::ServerConfig::with_crypto(Arc::new(tls_config));
-    server_config.transport = Arc::new(quinn::TransportConfig::default());
+/// Bind the QUIC endpoint with the configured certificate; the TLS settings
+/// are needed again to check client certificates
+pub fn endpoint(config: &Config) -> Result<(quinn::Endpoint, Arc<super::tls::ReloadableTls>)> {
+    let tls = reloadable_tls(config)?;
+    Ok((bind(config, &tls)?, tls))
+}
 
-    // Bind UDP socket
-    let addr: SocketAddr = config.server.bind.parse()?;
-    let endpoint = quinn::Endpoint::server(server_config, addr)?;
+fn bind(config: &Config, tls: &super::tls::ReloadableTls) -> Result<quinn::Endpoint> {
+    let addr = bind_addr(config)?;
+    quinn::Endpoint::server(quic_config(tls.current())?, addr)
+        .with_context(|| format!("Failed to bind HTTP/3 listener on {}", addr))
+}
 
-    info!("HTTP/3 server listening on {}", addr);
+/// Start HTTP/3 server (QUIC-based)
+pub async fn serve(config: Config, app: Router) -> Result<()> {
+    let tls = reloadable_tls(&config)?;
+    let endpoint = bind(&config, &tls)?;
+    crate::health::STARTUP.listener_bound("http3");
+
+    // New connections get renewed certificates
+    let reloaded = endpoint.clone();
+    tls.spawn_watcher(move |tls| match quic_config(tls) {
+        Ok(server_config) => reloaded.set_server_config(Some(server_config)),
+        Err(e) => warn!("HTTP/3 keeps its old certificate: {:#}", e),
+    });
+    info!("✓ HTTP/3 server listening on {} (udp)", endpoint.local_addr()?);
+
+    serve_endpoint(endpoint, tls, app).await
+}
 
-    // Accept connections
-    while let Some(conn) = endpoint.accept().await {
+/// Accept connections on an already bound endpoint until shutdown drains it
+pub async fn serve_endpoint(
+    endpoint: quinn::Endpoint,
+    tls: Arc<super::tls::ReloadableTls>,
+    app: Router,
+) -> Result<()> {
+    loop {
+        let incoming = tokio::select! {
+            incoming = endpoint.accept() => match incoming {
+                Some(incoming) => incoming,
+                None => return Ok(()),
+            },
+            _ = crate::shutdown::SHUTDOWN.draining() => break,
+        };
+
+        let app = app.clone();
+        let tls = tls.clone();
         tokio::spawn(async move {
-            if let Err(e) = handle_connection(conn).await {
-                error!("Connection error: {}", e);
+            if let Err(e) = handle_connection(incoming, &tls, app).await {
+                debug!("HTTP/3 connection closed: {}", e);
             }
         });
     }
 
+    // Refuse new connections; open ones got GOAWAY and finish their requests
+    endpoint.set_server_config(None);
+    tokio::select! {
+        _ = endpoint.wait_idle() => {}
+        _ = crate::shutdown::SHUTDOWN.expired() => {}
+    }
+    endpoint.close(0u32.into(), b"shutdown");
     Ok(())
 }
 
-async fn handle_connection(conn: quinn::Connecting) -> Result<()> {
-    let conn = conn.await?;
-
-    // Create h3 connection
-    let mut h3_conn = h3::server::Connection::new(h3_quinn::Connection::new(conn)).await?;
+async fn handle_connection(incoming: quinn::Incoming, tls: &super::tls::ReloadableTls, app: Router) -> Result<()> {
+    let conn = incoming.await?;
+
+    // Same client checks as the TCP listeners
+    let certs = conn
+        .peer_identity()
+        .and_then(|identity| identity.downcast::<Vec<CertificateDer<'static>>>().ok());
+    let info = match tls.check_client(certs.as_deref().map(Vec::as_slice), Some(conn.remote_address())) {
+        Ok(info) => info,
+        Err(e) => {
+            conn.close(0u32.into(), b"client not allowed");
+            return Err(e);
+        }
+    };
+
+    let mut h3_conn = h3::server::builder()
+        .build::<_, Bytes>(h3_quinn::Connection::new(conn))
+        .await?;
+
+    let mut draining = false;
+    loop {
+        let accepted = tokio::select! {
+            accepted = h3_conn.accept() => accepted?,
+            _ = crate::shutdown::SHUTDOWN.draining(), if !draining => {
+                // GOAWAY: no new requests, in-flight ones continue
+                draining = true;
+                h3_conn.shutdown(0).await?;
+                continue;
+            }
+        };
+        let Some(resolver) = accepted else { break };
 
-    // Handle requests
-    while let Some((req, stream)) = h3_conn.accept().await? {
+        let app = app.clone();
+        let info = info.clone();
         tokio::spawn(async move {
-            if let Err(e) = handle_request(req, stream).await {
-                error!("Request error: {}", e);
+            match resolver.resolve_request().await {
+                Ok((mut request, stream)) => {
+                    request.extensions_mut().insert(info);
+                    if let Err(e) = handle_request(request, stream, app).await {
+                        warn!("HTTP/3 request failed: {}", e);
+                    }
+                }
+                Err(e) => debug!("HTTP/3 request not resolved: {}", e),
             }
         });
     }
@@ -89,14 +165,39 @@ async fn handle_connection(conn: quinn::Connecting) -> Result<()> {
     Ok(())
 }
 
-async fn handle_request(
-    req: http::Request<()>,
-    mut stream: h3::server::RequestStream<h3_quinn::BidiStream<Bytes>, Bytes>,
-) -> Result<()> {
-    // Route request through Axum router
-    // Transform h3 request/response to/from Axum format
-    // Send response via stream
+async fn handle_request(request: Request<()>, stream: RequestStream, app: Router) -> Result<()> {
+    // Read as the app consumes it, so oversized bodies get a 413 like over TCP
+    let (mut stream, recv) = stream.split();
+    let body = Body::from_stream(futures::stream::unfold(Some(recv), |recv| async move {
+        let mut recv = recv?;
+        match recv.recv_data().await {
+            Ok(Some(mut chunk)) => Some((Ok(chunk.copy_to_bytes(chunk.remaining())), Some(recv))),
+            Ok(None) => None,
+            Err(e) => Some((Err(e), None)),
+        }
+    }));
+
+    let (parts, ()) = request.into_parts();
+    let response = app.oneshot(Request::from_parts(parts, body)).await?;
+
+    let (parts, mut body) = response.into_parts();
+    stream
+        .send_response(axum::http::Response::from_parts(parts, ()))
+        .await?;
+
+    while let Some(frame) = body.frame().await {
+        let frame = frame.map_err(|e| anyhow::anyhow!("Response body error: {}", e))?;
+        match frame.into_data() {
+            Ok(data) => stream.send_data(data).await?,
+            Err(frame) => {
+                if let Ok(trailers) = frame.into_trailers() {
+                    stream.send_trailers(trailers).await?;
+                    return Ok(());
+                }
+            }
+        }
+    }
 
+    stream.finish().await?;
     Ok(())
 }
-*/
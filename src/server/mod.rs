@@ -1,88 +1,148 @@
+pub mod admin;
 pub mod grpc;
 pub mod http;
 pub mod http3;
+pub mod tls;
 pub mod uds;
 
-use crate::config::Config;
+use crate::config::{Config, Sources};
 use anyhow::Result;
 use std::sync::Arc;
-use tracing::info;
+use std::time::Duration;
+use tracing::{info, warn};
 
-/// Run HTTP, gRPC, and UDS servers concurrently
-pub async fn run(config: Config) -> Result<()> {
+/// Run HTTP, gRPC, UDS and HTTP/3 servers concurrently
+///
+/// `sources` are the config layers `config` was loaded from, re-read on reload.
+pub async fn run(config: Config, sources: Sources) -> Result<()> {
     let http_addr = config.server.bind.clone();
     let grpc_addr = config.server.grpc.clone();
     let uds_enabled = config.server.uds_enabled;
+    let http3_enabled = config.server.http3.enabled;
+    let drain_timeout = Duration::from_secs(config.server.drain_timeout_secs);
 
-    // Clone config for all servers
-    let http_config = config.clone();
-    let grpc_config = config.clone();
-    let uds_config = config;
+    crate::auth::token_store::configure(&config.oauth)?;
+
+    // Ready once every listener has bound (see /readyz)
+    crate::health::STARTUP.config_loaded();
+    crate::health::STARTUP.expect_listener("http");
+    crate::health::STARTUP.expect_listener("grpc");
+    if uds_enabled {
+        crate::health::STARTUP.expect_listener("uds");
+    }
+    if http3_enabled {
+        crate::health::STARTUP.expect_listener("http3");
+    }
+
+    // One router, cache, circuit breaker, rate limiter and health checker,
+    // shared by every transport
+    let state = app_state(&config, sources);
+
+    // One coordinator drains every listener on SIGTERM / Ctrl+C
+    tokio::spawn(crate::shutdown::coordinate(drain_timeout));
+
+    let mut handles = Vec::new();
 
     // Spawn HTTP server
-    let http_handle = tokio::spawn(async move {
+    let http_state = state.clone();
+    handles.push(tokio::spawn(async move {
         info!("🌐 HTTP server starting on {}", http_addr);
-        http::serve(http_config).await
-    });
+        http::serve(http_state).await
+    }));
 
     // Spawn gRPC server
-    let grpc_handle = tokio::spawn(async move {
+    let grpc_state = state.clone();
+    handles.push(tokio::spawn(async move {
         info!("⚡ gRPC server starting on {}", grpc_addr);
-        grpc::serve(grpc_config).await
-    });
+        grpc::serve(grpc_state).await
+    }));
 
     // Spawn UDS server (optional)
-    let uds_handle = if uds_enabled {
-        let socket_path = uds_config
+    if uds_enabled {
+        let socket_path = config
             .server
             .uds_path
             .clone()
             .unwrap_or_else(|| "/var/run/thanos/thanos.sock".to_string());
 
-        Some(tokio::spawn(async move {
+        let uds_config = config.clone();
+        let app = uds::build_router(state.clone());
+        handles.push(tokio::spawn(async move {
             info!("🔌 UDS server starting on {}", socket_path);
+            uds::serve(uds_config, app).await
+        }));
+    }
 
-            // Build shared app with router
-            let config_arc = Arc::new(uds_config.clone());
-            let router = Arc::new(crate::router::Router::new(config_arc.clone()));
-            let health_checker = Arc::new(crate::health::HealthChecker::new());
+    // Spawn HTTP/3 server (optional)
+    if http3_enabled {
+        let http3_config = config.clone();
+        let app = http::app(state.clone());
+        handles.push(tokio::spawn(async move {
+            info!("🚀 HTTP/3 server starting");
+            http3::serve(http3_config, app).await
+        }));
+    }
 
-            let state = http::AppState {
-                config: config_arc,
-                router,
-                health_checker,
-            };
-            let app = uds::build_router(state);
+    // Any server stopping on its own ends the process; once draining, wait
+    // for every listener to finish
+    let servers = async {
+        let mut pending = handles;
+        while !pending.is_empty() {
+            let (res, _, rest) = futures::future::select_all(pending).await;
+            pending = rest;
+            match res? {
+                Err(e) if !crate::shutdown::SHUTDOWN.is_draining() => return Err(e),
+                Ok(()) if !crate::shutdown::SHUTDOWN.is_draining() => return Ok(()),
+                Err(e) => warn!("Server stopped with an error while draining: {}", e),
+                Ok(()) => {}
+            }
+        }
+        Ok(())
+    };
 
-            uds::serve(uds_config, app).await
-        }))
-    } else {
-        None
+    // Connections that outlive the drain by a few seconds are dropped
+    let grace_expired = async {
+        crate::shutdown::SHUTDOWN.expired().await;
+        tokio::time::sleep(Duration::from_secs(5)).await;
     };
 
-    // Wait for all servers (any one crashing will terminate)
-    if let Some(uds_handle) = uds_handle {
-        tokio::select! {
-            res = http_handle => {
-                res??;
-            }
-            res = grpc_handle => {
-                res??;
-            }
-            res = uds_handle => {
-                res??;
-            }
-        }
-    } else {
-        tokio::select! {
-            res = http_handle => {
-                res??;
-            }
-            res = grpc_handle => {
-                res??;
-            }
-        }
+    tokio::select! {
+        result = servers => result?,
+        _ = grace_expired => warn!("Connections still open after the drain, exiting anyway"),
     }
 
+    info!("✓ Shutdown complete");
     Ok(())
 }
+
+/// Who a request is from, for per-client limits, as established by the
+/// connection: the verified client certificate name, else the peer's IP
+/// address, else the Unix socket peer's user
+pub fn client_key(
+    tls: Option<&tls::TlsConnectInfo>,
+    remote_addr: Option<std::net::SocketAddr>,
+    local: Option<&admin::LocalSocket>,
+) -> String {
+    if let Some(client) = tls.and_then(|info| info.client.as_ref()) {
+        return format!("client:{}", client.name);
+    }
+    if let Some(addr) = tls.and_then(|info| info.remote_addr).or(remote_addr) {
+        return format!("ip:{}", addr.ip());
+    }
+    match local.and_then(|socket| socket.uid) {
+        Some(uid) => format!("uid:{}", uid),
+        None => "unknown".to_string(),
+    }
+}
+
+/// Router, health checker and background health checks, built once for all
+/// listeners and reloaded from the config layers
+fn app_state(config: &Config, sources: Sources) -> http::AppState {
+    let router = Arc::new(crate::router::Router::new(Arc::new(config.clone())));
+    let live = crate::reload::LiveRouter::with_sources(router, sources);
+    live.spawn_watchers();
+    crate::health::spawn_monitor(live.clone());
+    crate::auth::token_manager::spawn_refresher(live.clone());
+
+    http::AppState { live }
+}
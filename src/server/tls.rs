@@ -0,0 +1,325 @@
+/// TLS for the server's listeners (rustls, ring provider)
+///
+/// Certificates are re-read when their files change, so renewed certs are
+/// picked up without a restart; connections already open keep the old one.
+/// With `client_ca_path` set, clients must present a certificate signed by
+/// that CA, and its subject becomes the connection's `ClientIdentity`.
+use crate::config::TlsConfig;
+use anyhow::{Context, Result};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::WebPkiClientVerifier;
+use serde::Serialize;
+use std::fs::File;
+use std::io::BufReader;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, RwLock};
+use std::task::{Context as TaskContext, Poll};
+use std::time::{Duration, SystemTime};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::{debug, info, warn};
+
+/// Read a PEM certificate chain
+pub fn load_certs(path: &str) -> Result<Vec<CertificateDer<'static>>> {
+    let file = File::open(path).with_context(|| format!("Failed to open certificate {}", path))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("Failed to parse certificate {}", path))?;
+
+    if certs.is_empty() {
+        anyhow::bail!("No certificates found in {}", path);
+    }
+    Ok(certs)
+}
+
+/// Read a PEM private key (PKCS#8, PKCS#1 or SEC1)
+pub fn load_key(path: &str) -> Result<PrivateKeyDer<'static>> {
+    let file = File::open(path).with_context(|| format!("Failed to open private key {}", path))?;
+    rustls_pemfile::private_key(&mut BufReader::new(file))
+        .with_context(|| format!("Failed to parse private key {}", path))?
+        .with_context(|| format!("No private key found in {}", path))
+}
+
+/// The crypto provider every listener uses
+pub fn provider() -> Arc<rustls::crypto::CryptoProvider> {
+    Arc::new(rustls::crypto::ring::default_provider())
+}
+
+/// Server config for the configured certificate (and client CA), offering
+/// the given ALPN protocols
+pub fn server_config(
+    tls: &TlsConfig,
+    alpn: &[&[u8]],
+    versions: &[&'static rustls::SupportedProtocolVersion],
+) -> Result<rustls::ServerConfig> {
+    let builder = rustls::ServerConfig::builder_with_provider(provider()).with_protocol_versions(versions)?;
+
+    let builder = match &tls.client_ca_path {
+        Some(ca_path) => {
+            let mut roots = rustls::RootCertStore::empty();
+            for cert in load_certs(ca_path)? {
+                roots.add(cert).with_context(|| format!("Invalid CA certificate in {}", ca_path))?;
+            }
+
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider());
+            let verifier = if tls.require_client_cert {
+                verifier.build()
+            } else {
+                verifier.allow_unauthenticated().build()
+            };
+            builder.with_client_cert_verifier(verifier.context("Invalid client CA bundle")?)
+        }
+        None => builder.with_no_client_auth(),
+    };
+
+    let mut config = builder
+        .with_single_cert(load_certs(&tls.cert_path)?, load_key(&tls.key_path)?)
+        .context("Invalid TLS certificate or key")?;
+
+    config.alpn_protocols = alpn.iter().map(|protocol| protocol.to_vec()).collect();
+    Ok(config)
+}
+
+/// Who is on the other end of a mutual TLS connection
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ClientIdentity {
+    /// Certificate CN, or the full subject when there is no CN
+    pub name: String,
+    pub subject: String,
+}
+
+impl ClientIdentity {
+    /// Identity from the leaf of a verified client certificate chain
+    pub fn from_certs(certs: &[CertificateDer<'_>]) -> Option<Self> {
+        let (_, cert) = x509_parser::parse_x509_certificate(certs.first()?).ok()?;
+        let subject = cert.subject().to_string();
+        let name = cert
+            .subject()
+            .iter_common_name()
+            .next()
+            .and_then(|cn| cn.as_str().ok())
+            .map(str::to_string)
+            .unwrap_or_else(|| subject.clone());
+
+        Some(Self { name, subject })
+    }
+}
+
+/// A listener's TLS settings, swapped out when the certificate files change
+pub struct ReloadableTls {
+    tls: TlsConfig,
+    alpn: Vec<&'static [u8]>,
+    versions: Vec<&'static rustls::SupportedProtocolVersion>,
+    current: RwLock<Arc<rustls::ServerConfig>>,
+    modified: Mutex<Option<SystemTime>>,
+}
+
+impl ReloadableTls {
+    pub fn new(
+        tls: &TlsConfig,
+        alpn: &[&'static [u8]],
+        versions: &[&'static rustls::SupportedProtocolVersion],
+    ) -> Result<Arc<Self>> {
+        let config = server_config(tls, alpn, versions)?;
+        Ok(Arc::new(Self {
+            tls: tls.clone(),
+            alpn: alpn.to_vec(),
+            versions: versions.to_vec(),
+            current: RwLock::new(Arc::new(config)),
+            modified: Mutex::new(files_modified(tls)),
+        }))
+    }
+
+    /// HTTP/2 and HTTP/1.1 (HTTP and gRPC listeners)
+    pub fn for_tcp(tls: &TlsConfig) -> Result<Arc<Self>> {
+        Self::new(tls, &[b"h2", b"http/1.1"], rustls::DEFAULT_VERSIONS)
+    }
+
+    pub fn current(&self) -> Arc<rustls::ServerConfig> {
+        self.current.read().unwrap().clone()
+    }
+
+    /// Rebuild the config if any certificate file changed since the last load
+    ///
+    /// Returns whether a new config is in place; on error the old one stays.
+    pub fn reload(&self) -> Result<bool> {
+        let modified = files_modified(&self.tls);
+        if modified == *self.modified.lock().unwrap() {
+            return Ok(false);
+        }
+
+        let config = server_config(&self.tls, &self.alpn, &self.versions)?;
+        *self.current.write().unwrap() = Arc::new(config);
+        *self.modified.lock().unwrap() = modified;
+        Ok(true)
+    }
+
+    /// Check for new certificates every `reload_secs`, calling `on_reload`
+    /// with each new config
+    pub fn spawn_watcher(
+        self: &Arc<Self>,
+        on_reload: impl Fn(Arc<rustls::ServerConfig>) + Send + 'static,
+    ) -> Option<tokio::task::JoinHandle<()>> {
+        if self.tls.reload_secs == 0 {
+            return None;
+        }
+
+        let tls = self.clone();
+        Some(tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(tls.tls.reload_secs));
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                match tls.reload() {
+                    Ok(true) => {
+                        info!("Reloaded TLS certificate from {}", tls.tls.cert_path);
+                        on_reload(tls.current());
+                    }
+                    Ok(false) => {}
+                    Err(e) => warn!("Keeping current TLS certificate, reload failed: {:#}", e),
+                }
+            }
+        }))
+    }
+
+    /// Handshake with a new TCP connection and check the client's identity
+    pub async fn accept(&self, tcp: TcpStream) -> Result<TlsConnection> {
+        let remote_addr = tcp.peer_addr().ok();
+        let acceptor = tokio_rustls::TlsAcceptor::from(self.current());
+        let stream = tokio::time::timeout(Duration::from_secs(10), acceptor.accept(tcp))
+            .await
+            .context("TLS handshake timed out")?
+            .context("TLS handshake failed")?;
+
+        let info = self.check_client(stream.get_ref().1.peer_certificates(), remote_addr)?;
+        Ok(TlsConnection { stream, info })
+    }
+
+    /// Identify the client of a finished handshake and check it against
+    /// `allowed_clients`; shared by the TCP and QUIC listeners
+    pub fn check_client(
+        &self,
+        certs: Option<&[CertificateDer<'_>]>,
+        remote_addr: Option<SocketAddr>,
+    ) -> Result<TlsConnectInfo> {
+        let client = certs.and_then(ClientIdentity::from_certs);
+        if !self.tls.allowed_clients.is_empty() {
+            match &client {
+                Some(identity) if self.tls.allowed_clients.contains(&identity.name) => {}
+                Some(identity) => anyhow::bail!("client '{}' is not in allowed_clients", identity.name),
+                None => anyhow::bail!("no client certificate presented"),
+            }
+        }
+
+        Ok(TlsConnectInfo { remote_addr, client })
+    }
+
+    /// Accept TLS connections on a listener; handshakes run concurrently and
+    /// failed ones are logged and dropped
+    pub fn incoming(self: &Arc<Self>, listener: TcpListener) -> ReceiverStream<std::io::Result<TlsConnection>> {
+        let (tx, rx) = tokio::sync::mpsc::channel(64);
+        let tls = self.clone();
+
+        tokio::spawn(async move {
+            loop {
+                // Closes the listener once the receiving side is gone
+                let accepted = tokio::select! {
+                    accepted = listener.accept() => accepted,
+                    _ = tx.closed() => break,
+                };
+                let tcp = match accepted {
+                    Ok((tcp, _)) => tcp,
+                    Err(e) => {
+                        warn!("Failed to accept connection: {}", e);
+                        tokio::time::sleep(Duration::from_millis(100)).await;
+                        continue;
+                    }
+                };
+
+                let tls = tls.clone();
+                let sender = tx.clone();
+                tokio::spawn(async move {
+                    match tls.accept(tcp).await {
+                        Ok(conn) => {
+                            let _ = sender.send(Ok(conn)).await;
+                        }
+                        Err(e) => debug!("Rejected TLS connection: {:#}", e),
+                    }
+                });
+            }
+        });
+
+        ReceiverStream::new(rx)
+    }
+}
+
+/// Latest modification time across the certificate, key and CA files
+fn files_modified(tls: &TlsConfig) -> Option<SystemTime> {
+    [Some(&tls.cert_path), Some(&tls.key_path), tls.client_ca_path.as_ref()]
+        .into_iter()
+        .flatten()
+        .filter_map(|path| std::fs::metadata(path).and_then(|meta| meta.modified()).ok())
+        .max()
+}
+
+/// Connection details available to request handlers (as a request extension)
+#[derive(Debug, Clone)]
+pub struct TlsConnectInfo {
+    pub remote_addr: Option<SocketAddr>,
+    /// Set when the client presented a verified certificate
+    pub client: Option<ClientIdentity>,
+}
+
+/// An accepted TLS connection and who it is from
+pub struct TlsConnection {
+    stream: tokio_rustls::server::TlsStream<TcpStream>,
+    info: TlsConnectInfo,
+}
+
+impl TlsConnection {
+    pub fn info(&self) -> &TlsConnectInfo {
+        &self.info
+    }
+}
+
+impl tonic::transport::server::Connected for TlsConnection {
+    type ConnectInfo = TlsConnectInfo;
+
+    fn connect_info(&self) -> Self::ConnectInfo {
+        self.info.clone()
+    }
+}
+
+impl AsyncRead for TlsConnection {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.stream).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for TlsConnection {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+        Pin::new(&mut self.stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.stream).poll_shutdown(cx)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+        bufs: &[std::io::IoSlice<'_>],
+    ) -> Poll<std::io::Result<usize>> {
+        Pin::new(&mut self.stream).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.stream.is_write_vectored()
+    }
+}
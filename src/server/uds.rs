@@ -10,7 +10,6 @@ use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::net::UnixListener;
-use tokio::signal;
 use tokio::time::timeout;
 use tower::Service;
 use tracing::{debug, error, info, warn};
@@ -52,48 +51,24 @@ pub async fn serve(config: Config, app: Router) -> Result<()> {
 
     // Track active connections for graceful shutdown
     let active_connections = Arc::new(AtomicUsize::new(0));
-    let active_conn_counter = active_connections.clone();
 
+    crate::health::STARTUP.listener_bound("uds");
     info!("✓ UDS server listening on {}", socket_path);
 
-    // Set up graceful shutdown handler
-    let socket_path_clone = socket_path.clone();
-    tokio::spawn(async move {
-        shutdown_signal().await;
-        info!("Received shutdown signal, cleaning up UDS socket...");
-
-        // Wait for active connections to finish (max 10 seconds)
-        let start = std::time::Instant::now();
-        while active_conn_counter.load(Ordering::Relaxed) > 0
-            && start.elapsed() < Duration::from_secs(10)
-        {
-            debug!(
-                "Waiting for {} active connections to finish...",
-                active_conn_counter.load(Ordering::Relaxed)
-            );
-            tokio::time::sleep(Duration::from_millis(100)).await;
-        }
-
-        // Clean up socket file
-        if Path::new(&socket_path_clone).exists() {
-            if let Err(e) = std::fs::remove_file(&socket_path_clone) {
-                error!("Failed to remove socket on shutdown: {}", e);
-            } else {
-                info!("✓ Cleaned up socket at {}", socket_path_clone);
-            }
-        }
-    });
-
-    // Main accept loop
+    // Main accept loop, until the shutdown coordinator starts draining
     loop {
-        let (stream, _) = match listener.accept().await {
-            Ok(conn) => {
+        let accepted = tokio::select! {
+            accepted = listener.accept() => accepted,
+            _ = crate::shutdown::SHUTDOWN.draining() => break,
+        };
+        let stream = match accepted {
+            Ok((stream, _)) => {
                 active_connections.fetch_add(1, Ordering::Relaxed);
                 debug!(
                     "New UDS connection (active: {})",
                     active_connections.load(Ordering::Relaxed)
                 );
-                conn
+                stream
             }
             Err(e) => {
                 error!("Failed to accept UDS connection: {}", e);
@@ -103,23 +78,41 @@ pub async fn serve(config: Config, app: Router) -> Result<()> {
             }
         };
 
+        let local = super::admin::LocalSocket {
+            uid: stream.peer_cred().ok().map(|cred| cred.uid()),
+        };
         let io = TokioIo::new(stream);
         let app = app.clone();
         let conn_counter = active_connections.clone();
 
         tokio::spawn(async move {
-            let service = hyper::service::service_fn(move |req: Request<Incoming>| {
+            let service = hyper::service::service_fn(move |mut req: Request<Incoming>| {
+                // Peers are limited by the socket's permissions, so admin routes trust them
+                req.extensions_mut().insert(local);
                 let mut app = app.clone();
                 async move { app.call(req).await }
             });
 
             // Create builder with graceful shutdown support
             let builder = Builder::new(hyper_util::rt::TokioExecutor::new());
+            let connection = builder.serve_connection(io, service);
+            tokio::pin!(connection);
+
+            // Finish in-flight requests, then close, once draining starts
+            let served = async {
+                tokio::select! {
+                    result = connection.as_mut() => result,
+                    _ = crate::shutdown::SHUTDOWN.draining() => {
+                        connection.as_mut().graceful_shutdown();
+                        connection.await
+                    }
+                }
+            };
 
             // Wrap connection with timeout to prevent hanging connections
             let conn_timeout = timeout(
                 Duration::from_secs(300), // 5 minute timeout per connection
-                builder.serve_connection(io, service)
+                served,
             );
 
             match conn_timeout.await {
@@ -142,59 +135,65 @@ pub async fn serve(config: Config, app: Router) -> Result<()> {
             debug!("UDS connection ended (active: {})", remaining);
         });
     }
-}
-
-/// Wait for shutdown signal (SIGTERM or SIGINT)
-async fn shutdown_signal() {
-    let ctrl_c = async {
-        signal::ctrl_c()
-            .await
-            .expect("Failed to install Ctrl+C handler");
-    };
 
-    #[cfg(unix)]
-    let terminate = async {
-        signal::unix::signal(signal::unix::SignalKind::terminate())
-            .expect("Failed to install SIGTERM handler")
-            .recv()
-            .await;
+    // Stop accepting and wait for active connections to finish, for up to
+    // the drain timeout
+    drop(listener);
+    info!("UDS server draining...");
+    let finished = async {
+        while active_connections.load(Ordering::Relaxed) > 0 {
+            debug!(
+                "Waiting for {} active connections to finish...",
+                active_connections.load(Ordering::Relaxed)
+            );
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
     };
+    let drain_timeout = Duration::from_secs(config.server.drain_timeout_secs);
+    if timeout(drain_timeout, finished).await.is_err() {
+        warn!(
+            "{} UDS connections still open after {}s, closing the socket",
+            active_connections.load(Ordering::Relaxed),
+            drain_timeout.as_secs()
+        );
+    }
 
-    #[cfg(not(unix))]
-    let terminate = std::future::pending::<()>();
-
-    tokio::select! {
-        _ = ctrl_c => {
-            info!("Received Ctrl+C");
-        }
-        _ = terminate => {
-            info!("Received SIGTERM");
+    // Clean up socket file
+    if Path::new(&socket_path).exists() {
+        if let Err(e) = std::fs::remove_file(&socket_path) {
+            error!("Failed to remove socket on shutdown: {}", e);
+        } else {
+            info!("✓ Cleaned up socket at {}", socket_path);
         }
     }
+
+    Ok(())
 }
 
 /// Build the router for UDS (same as HTTP)
 pub fn build_router(state: AppState) -> Router {
-    use super::http::{chat_completions_handler, health_handler, models_handler};
+    use super::http::{
+        chat_completions_handler, count_tokens_handler, health_handler, livez_handler, models_handler,
+        rate_limit, readyz_handler, routing_dry_run_handler, tokenize_handler,
+    };
     use axum::routing::{get, post};
     use tower_http::{compression::CompressionLayer, trace::TraceLayer};
 
     Router::new()
         .route("/health", get(health_handler))
+        .route("/livez", get(livez_handler))
+        .route("/readyz", get(readyz_handler))
         .route("/v1/models", get(models_handler))
-        .route("/v1/chat/completions", post(chat_completions_handler))
+        .route(
+            "/v1/chat/completions",
+            post(chat_completions_handler).layer(axum::middleware::from_fn_with_state(state.clone(), rate_limit)),
+        )
+        .route("/v1/routing/dry-run", post(routing_dry_run_handler))
+        .route("/v1/tokenize", post(tokenize_handler))
+        .route("/v1/count_tokens", post(count_tokens_handler))
+        .merge(super::admin::routes(state.clone()))
         .layer(TraceLayer::new_for_http())
         .layer(CompressionLayer::new())
+        .layer(axum::middleware::from_fn(super::http::track_in_flight))
         .with_state(state)
 }
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_shutdown_signal_creation() {
-        // Just verify the function compiles
-        let _signal = shutdown_signal();
-    }
-}
@@ -0,0 +1,165 @@
+/// Coordinated graceful shutdown for every listener
+///
+/// On SIGTERM or Ctrl+C the server starts draining: HTTP, gRPC, UDS and HTTP/3
+/// stop accepting connections, readiness reports "draining", and requests in
+/// flight carry on. Once every request and stream finishes, or
+/// `server.drain_timeout_secs` passes (or a second signal arrives), the drain
+/// expires and streams still open end with a terminal "server shutting down"
+/// event.
+use once_cell::sync::Lazy;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::signal;
+use tokio_util::sync::CancellationToken;
+use tracing::info;
+
+/// Message sent to streams cut off when the drain expires
+pub const SHUTDOWN_MESSAGE: &str = "server shutting down";
+
+/// Process-wide shutdown state
+pub struct Shutdown {
+    draining: CancellationToken,
+    expired: CancellationToken,
+    in_flight: AtomicUsize,
+}
+
+pub static SHUTDOWN: Lazy<Shutdown> = Lazy::new(|| Shutdown {
+    draining: CancellationToken::new(),
+    expired: CancellationToken::new(),
+    in_flight: AtomicUsize::new(0),
+});
+
+impl Shutdown {
+    /// Stop accepting connections and let in-flight work finish
+    pub fn begin(&self) {
+        self.draining.cancel();
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.draining.is_cancelled()
+    }
+
+    /// Resolves once draining has begun
+    pub async fn draining(&self) {
+        self.draining.cancelled().await
+    }
+
+    /// End the drain: streams still open are closed
+    pub fn expire(&self) {
+        self.draining.cancel();
+        self.expired.cancel();
+    }
+
+    /// Resolves once the drain has expired
+    pub async fn expired(&self) {
+        self.expired.cancelled().await
+    }
+
+    /// Count a request, or a response stream outliving its request, as in
+    /// flight until the guard is dropped
+    pub fn track(&self) -> InFlight {
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+        InFlight
+    }
+
+    /// Requests and streams the drain waits for
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Relaxed)
+    }
+}
+
+/// Holds off the end of the drain while alive
+pub struct InFlight;
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        SHUTDOWN.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Wait for shutdown signal (SIGTERM or SIGINT)
+pub async fn wait_for_signal() {
+    let ctrl_c = async {
+        signal::ctrl_c()
+            .await
+            .expect("Failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        signal::unix::signal(signal::unix::SignalKind::terminate())
+            .expect("Failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {
+            info!("Received Ctrl+C");
+        }
+        _ = terminate => {
+            info!("Received SIGTERM");
+        }
+    }
+}
+
+/// Drain on the first signal, then expire when requests and streams are
+/// done, the drain timeout passes, or a second signal arrives
+pub async fn coordinate(drain_timeout: Duration) {
+    wait_for_signal().await;
+    tokio::select! {
+        _ = drain(drain_timeout) => {}
+        _ = wait_for_signal() => {
+            info!("Second signal, closing streams now");
+            SHUTDOWN.expire();
+        }
+    }
+}
+
+/// Start draining, and expire once nothing is in flight or `drain_timeout`
+/// passes
+pub async fn drain(drain_timeout: Duration) {
+    SHUTDOWN.begin();
+    info!(
+        "Draining ({} requests in flight, up to {}s)",
+        SHUTDOWN.in_flight(),
+        drain_timeout.as_secs()
+    );
+
+    let finished = async {
+        while SHUTDOWN.in_flight() > 0 {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    };
+
+    tokio::select! {
+        _ = finished => info!("All requests finished"),
+        _ = tokio::time::sleep(drain_timeout) => {
+            info!("Drain timeout reached, closing {} requests", SHUTDOWN.in_flight());
+        }
+    }
+    SHUTDOWN.expire();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shutdown_signal_creation() {
+        // Just verify the function compiles
+        let _signal = wait_for_signal();
+    }
+
+    #[test]
+    fn test_in_flight_guard_counts() {
+        let before = SHUTDOWN.in_flight();
+        let guard = SHUTDOWN.track();
+        assert_eq!(SHUTDOWN.in_flight(), before + 1);
+        drop(guard);
+        assert_eq!(SHUTDOWN.in_flight(), before);
+    }
+}
@@ -0,0 +1,204 @@
+/// Local token counting
+///
+/// OpenAI model families are counted exactly with their BPE vocabularies
+/// (`o200k_base`, `cl100k_base`). Other families have no public vocabulary we
+/// can bundle, so their counts are approximated from `cl100k_base` with a
+/// per-family correction factor measured on typical English and code.
+use crate::types::ChatRequest;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use tiktoken_rs::CoreBPE;
+
+static O200K: Lazy<CoreBPE> = Lazy::new(|| tiktoken_rs::o200k_base().expect("bundled o200k_base vocabulary"));
+static CL100K: Lazy<CoreBPE> = Lazy::new(|| tiktoken_rs::cl100k_base().expect("bundled cl100k_base vocabulary"));
+
+/// Tokens added per chat message for role and delimiters
+pub const MESSAGE_OVERHEAD: usize = 3;
+/// Tokens that prime the assistant reply
+pub const REPLY_OVERHEAD: usize = 3;
+
+/// Tokenizer chosen for a model
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tokenizer {
+    /// Exact BPE vocabulary
+    Bpe(Vocabulary),
+    /// `cl100k_base` count scaled for a family without a bundled vocabulary
+    Approximate { family: &'static str, factor: f64 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Vocabulary {
+    O200k,
+    Cl100k,
+}
+
+/// Result of counting a text or request
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TokenCount {
+    pub tokens: usize,
+    /// Vocabulary or approximation that produced the count
+    pub tokenizer: String,
+    /// False for approximations
+    pub exact: bool,
+}
+
+impl Tokenizer {
+    /// Pick the tokenizer for a model ID (provider prefixes like `openai/` are ignored)
+    pub fn for_model(model: &str) -> Self {
+        let model = model.rsplit('/').next().unwrap_or(model).to_ascii_lowercase();
+        let starts = |prefixes: &[&str]| prefixes.iter().any(|p| model.starts_with(p));
+
+        if starts(&["gpt-4o", "gpt-4.1", "gpt-4.5", "gpt-5", "chatgpt-4o", "o1", "o3", "o4", "codex"]) {
+            Tokenizer::Bpe(Vocabulary::O200k)
+        } else if starts(&["gpt-4", "gpt-3.5", "text-embedding-3", "text-embedding-ada"]) {
+            Tokenizer::Bpe(Vocabulary::Cl100k)
+        } else if starts(&["claude"]) {
+            Tokenizer::Approximate { family: "claude", factor: 1.15 }
+        } else if starts(&["gemini", "gemma"]) {
+            Tokenizer::Approximate { family: "gemini", factor: 1.05 }
+        } else if model.contains("llama") || model.contains("codellama") {
+            Tokenizer::Approximate { family: "llama", factor: 1.1 }
+        } else if starts(&["grok"]) {
+            Tokenizer::Approximate { family: "grok", factor: 1.0 }
+        } else {
+            Tokenizer::Approximate { family: "generic", factor: 1.0 }
+        }
+    }
+
+    /// Name reported to clients (`o200k_base`, `claude~cl100k_base`, ...)
+    pub fn name(&self) -> String {
+        match self {
+            Tokenizer::Bpe(vocabulary) => vocabulary.name().to_string(),
+            Tokenizer::Approximate { family, .. } => format!("{}~cl100k_base", family),
+        }
+    }
+
+    pub fn is_exact(&self) -> bool {
+        matches!(self, Tokenizer::Bpe(_))
+    }
+
+    /// Token IDs, only available for exact vocabularies
+    pub fn encode(&self, text: &str) -> Option<Vec<usize>> {
+        match self {
+            Tokenizer::Bpe(vocabulary) => Some(vocabulary.bpe().encode_ordinary(text)),
+            Tokenizer::Approximate { .. } => None,
+        }
+    }
+
+    /// Token count for plain text
+    pub fn count(&self, text: &str) -> usize {
+        match self {
+            Tokenizer::Bpe(vocabulary) => vocabulary.bpe().encode_ordinary(text).len(),
+            Tokenizer::Approximate { factor, .. } => {
+                let base = CL100K.encode_ordinary(text).len();
+                (base as f64 * factor).ceil() as usize
+            }
+        }
+    }
+
+    /// Prompt tokens for a chat request, including per-message overhead
+    pub fn count_request(&self, request: &ChatRequest) -> usize {
+        let messages: usize = request
+            .messages
+            .iter()
+            .map(|m| self.count(&m.content) + MESSAGE_OVERHEAD)
+            .sum();
+        let system = request.system.as_deref().map_or(0, |s| self.count(s) + MESSAGE_OVERHEAD);
+
+        messages + system + REPLY_OVERHEAD
+    }
+}
+
+impl Vocabulary {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Vocabulary::O200k => "o200k_base",
+            Vocabulary::Cl100k => "cl100k_base",
+        }
+    }
+
+    fn bpe(&self) -> &'static CoreBPE {
+        match self {
+            Vocabulary::O200k => &O200K,
+            Vocabulary::Cl100k => &CL100K,
+        }
+    }
+}
+
+/// Count a text for a model
+pub fn count_text(model: &str, text: &str) -> TokenCount {
+    let tokenizer = Tokenizer::for_model(model);
+    TokenCount {
+        tokens: tokenizer.count(text),
+        tokenizer: tokenizer.name(),
+        exact: tokenizer.is_exact(),
+    }
+}
+
+/// Count a chat request's prompt for its model
+pub fn count_request(request: &ChatRequest) -> TokenCount {
+    let tokenizer = Tokenizer::for_model(&request.model);
+    TokenCount {
+        tokens: tokenizer.count_request(request),
+        tokenizer: tokenizer.name(),
+        exact: tokenizer.is_exact(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{ChatMessage, Role};
+
+    #[test]
+    fn test_model_selection() {
+        assert_eq!(Tokenizer::for_model("gpt-4o"), Tokenizer::Bpe(Vocabulary::O200k));
+        assert_eq!(Tokenizer::for_model("openai/gpt-5"), Tokenizer::Bpe(Vocabulary::O200k));
+        assert_eq!(Tokenizer::for_model("o3-mini"), Tokenizer::Bpe(Vocabulary::O200k));
+        assert_eq!(Tokenizer::for_model("gpt-4-turbo"), Tokenizer::Bpe(Vocabulary::Cl100k));
+        assert_eq!(Tokenizer::for_model("gpt-3.5-turbo"), Tokenizer::Bpe(Vocabulary::Cl100k));
+        assert_eq!(Tokenizer::for_model("claude-sonnet-4-5").name(), "claude~cl100k_base");
+        assert_eq!(Tokenizer::for_model("gemini-2.5-pro").name(), "gemini~cl100k_base");
+        assert_eq!(Tokenizer::for_model("codellama:latest").name(), "llama~cl100k_base");
+        assert!(!Tokenizer::for_model("mystery-model").is_exact());
+    }
+
+    #[test]
+    fn test_exact_counts() {
+        // Reference counts from OpenAI's tiktoken
+        assert_eq!(count_text("gpt-4", "hello world").tokens, 2);
+        assert_eq!(count_text("gpt-4o", "hello world").tokens, 2);
+        assert_eq!(Tokenizer::for_model("gpt-4").encode("hello world"), Some(vec![15339, 1917]));
+    }
+
+    #[test]
+    fn test_approximate_counts() {
+        let text = "fn main() { println!(\"Hello, world!\"); }";
+        let base = count_text("gpt-4", text).tokens;
+        let claude = count_text("claude-sonnet-4-5", text);
+
+        assert!(!claude.exact);
+        assert!(claude.tokens >= base);
+        assert_eq!(Tokenizer::for_model("claude-sonnet-4-5").encode(text), None);
+    }
+
+    #[test]
+    fn test_count_request() {
+        let request = ChatRequest {
+            model: "gpt-4".to_string(),
+            messages: vec![ChatMessage {
+                role: Role::User,
+                content: "hello world".to_string(),
+            }],
+            stream: false,
+            temperature: None,
+            max_tokens: None,
+            top_p: None,
+            system: Some("be brief".to_string()),
+            hedge: None,
+        };
+
+        let system = count_text("gpt-4", "be brief").tokens;
+        assert_eq!(count_request(&request).tokens, 2 + system + 2 * MESSAGE_OVERHEAD + REPLY_OVERHEAD);
+    }
+}
@@ -31,6 +31,9 @@ pub struct ChatRequest {
     pub top_p: Option<f32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub system: Option<String>,
+    /// Thanos extension: force (`true`) or disable (`false`) hedged routing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hedge: Option<bool>,
 }
 
 /// Chat completion response (streaming or complete)
@@ -83,6 +86,7 @@ impl Provider {
         }
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Option<Self> {
         match s.to_lowercase().as_str() {
             "anthropic" => Some(Provider::Anthropic),
@@ -180,6 +184,7 @@ mod tests {
             max_tokens: None,
             top_p: None,
             system: None,
+            hedge: None,
         };
 
         assert!(!request.stream);
@@ -0,0 +1,86 @@
+// Adaptive routing tests - measured latency steers traffic between mocked providers
+
+use std::sync::Arc;
+use std::time::Duration;
+use thanos::config::Config;
+use thanos::router::Router;
+use thanos::types::{ChatMessage, ChatRequest, Role};
+
+/// Ollama answering after half a second, Anthropic at once
+async fn upstream() -> mockito::ServerGuard {
+    let mut upstream = mockito::Server::new_async().await;
+    upstream
+        .mock("POST", "/api/chat")
+        .with_chunked_body(|w| {
+            std::thread::sleep(Duration::from_millis(500));
+            w.write_all(br#"{"message":{"role":"assistant","content":"hi"},"done":true}"#)
+        })
+        .create_async()
+        .await;
+    upstream
+        .mock("POST", "/v1/messages")
+        .with_body(r#"{"content":[{"type":"text","text":"hi"}],"usage":{"input_tokens":1,"output_tokens":1},"stop_reason":"end_turn"}"#)
+        .create_async()
+        .await;
+    upstream
+}
+
+fn router(upstream: &mockito::ServerGuard) -> Router {
+    let config: Config = toml::from_str(&format!(
+        r#"
+        [server]
+        [routing]
+        strategy = "adaptive"
+        fallback_chain = ["ollama", "anthropic"]
+        adaptive = {{ exploration = 0.0, cost_weight = 0.0 }}
+
+        [providers.ollama]
+        enabled = true
+        auth_method = "none"
+        endpoint = "{url}"
+
+        [providers.anthropic]
+        enabled = true
+        auth_method = "api_key"
+        api_key = "sk-test"
+        base_url = "{url}"
+        "#,
+        url = upstream.url(),
+    ))
+    .unwrap();
+
+    Router::new(Arc::new(config))
+}
+
+fn create_test_request() -> ChatRequest {
+    ChatRequest {
+        model: "m".to_string(),
+        messages: vec![ChatMessage {
+            role: Role::User,
+            content: "ping".to_string(),
+        }],
+        stream: false,
+        temperature: None,
+        max_tokens: None,
+        top_p: None,
+        system: None,
+        hedge: None,
+    }
+}
+
+#[tokio::test]
+async fn test_adaptive_learns_faster_provider() {
+    let upstream = upstream().await;
+    let router = router(&upstream);
+    let request = create_test_request();
+
+    // Nothing measured yet: configured order
+    let response = router.route_chat_completion(&request).await.unwrap();
+    assert_eq!(response.provider, "ollama");
+
+    // The unmeasured provider is tried next, then keeps winning on latency
+    for _ in 0..3 {
+        let response = router.route_chat_completion(&request).await.unwrap();
+        assert_eq!(response.provider, "anthropic");
+    }
+}
@@ -0,0 +1,126 @@
+// Cancellation tests - a client disconnect must reach the provider
+//
+// The provider is a shell-script plugin that never finishes a reply and records
+// the `cancel` notification Thanos sends it when the request is abandoned.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use thanos::config::Config;
+use thanos::router::Router as ThanosRouter;
+use thanos::server::http::AppState;
+
+/// Stalling plugin: streams one chunk for chat_stream, never answers chat, logs cancels
+const STALL_PLUGIN: &str = r#"
+while IFS= read -r line; do
+  id=$(printf '%s' "$line" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+  case "$line" in
+    *'"method":"cancel"'*)
+      echo cancelled >> "$CANCEL_MARKER"
+      ;;
+    *'"method":"chat_stream"'*)
+      printf '{"jsonrpc":"2.0","method":"chunk","params":{"id":%s,"response":{"provider":"stall","model":"stall-1","content":"thinking","done":false}}}\n' "$id"
+      ;;
+  esac
+done
+"#;
+
+struct Fixture {
+    url: String,
+    marker: PathBuf,
+}
+
+async fn start_server(name: &str) -> Fixture {
+    let dir = std::env::temp_dir();
+    let script = dir.join(format!("thanos-{}-{}.sh", name, std::process::id()));
+    let marker = dir.join(format!("thanos-{}-{}.cancelled", name, std::process::id()));
+    std::fs::write(&script, STALL_PLUGIN).unwrap();
+    let _ = std::fs::remove_file(&marker);
+
+    let config: Config = toml::from_str(&format!(
+        r#"
+        [server]
+        [routing]
+        strategy = "preferred"
+
+        [providers.{name}]
+        enabled = true
+        auth_method = "none"
+
+        [providers.{name}.plugin]
+        command = "sh"
+        args = ["{script}"]
+        env = {{ CANCEL_MARKER = "{marker}" }}
+        "#,
+        name = name,
+        script = script.display(),
+        marker = marker.display(),
+    ))
+    .unwrap();
+
+    let config = Arc::new(config);
+    let state = AppState::new(Arc::new(ThanosRouter::new(config)));
+    let app = thanos::server::uds::build_router(state);
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    Fixture { url, marker }
+}
+
+async fn wait_for_cancel(marker: &Path) -> bool {
+    for _ in 0..50 {
+        if marker.exists() {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    false
+}
+
+fn chat_body(stream: bool) -> serde_json::Value {
+    serde_json::json!({
+        "model": "stall-1",
+        "messages": [{ "role": "user", "content": "take your time" }],
+        "stream": stream,
+    })
+}
+
+#[tokio::test]
+async fn test_disconnect_cancels_non_streaming_request() {
+    let fixture = start_server("cancel_unary").await;
+
+    // Client gives up long before the plugin would answer
+    let result = reqwest::Client::new()
+        .post(format!("{}/v1/chat/completions", fixture.url))
+        .json(&chat_body(false))
+        .timeout(Duration::from_millis(500))
+        .send()
+        .await;
+    assert!(result.is_err());
+
+    assert!(wait_for_cancel(&fixture.marker).await, "plugin never saw the cancel");
+}
+
+#[tokio::test]
+async fn test_disconnect_cancels_stream() {
+    let fixture = start_server("cancel_stream").await;
+
+    let mut res = reqwest::Client::new()
+        .post(format!("{}/v1/chat/completions", fixture.url))
+        .json(&chat_body(true))
+        .send()
+        .await
+        .unwrap();
+    assert!(res.status().is_success());
+
+    // Read the first chunk, then hang up
+    let first = res.chunk().await.unwrap().unwrap();
+    assert!(String::from_utf8_lossy(&first).contains("thinking"));
+    drop(res);
+
+    assert!(wait_for_cancel(&fixture.marker).await, "plugin never saw the cancel");
+}
@@ -0,0 +1,195 @@
+// Circuit breaker tests - failures open a provider's circuit, admin endpoints inspect and reset it
+
+use std::sync::Arc;
+use thanos::config::Config;
+use thanos::router::Router as ThanosRouter;
+use thanos::server::http::AppState;
+
+const ADMIN_TOKEN: &str = "s3cret";
+
+/// Ollama failing every chat call with a 503
+async fn failing_upstream() -> mockito::ServerGuard {
+    let mut upstream = mockito::Server::new_async().await;
+    upstream
+        .mock("POST", "/api/chat")
+        .with_status(503)
+        .with_body(r#"{"error":"overloaded"}"#)
+        .create_async()
+        .await;
+    upstream
+}
+
+fn test_config(upstream: &mockito::ServerGuard, admin_token: Option<&str>) -> Config {
+    toml::from_str(&format!(
+        r#"
+        [server]
+        {token}
+
+        [routing]
+        strategy = "preferred"
+
+        [providers.ollama]
+        enabled = true
+        auth_method = "none"
+        endpoint = "{endpoint}"
+        retry = {{ max_attempts = 1 }}
+        circuit_breaker = {{ failure_threshold = 2, open_secs = 60 }}
+        "#,
+        token = admin_token.map(|t| format!("admin_token = \"{}\"", t)).unwrap_or_default(),
+        endpoint = upstream.url(),
+    ))
+    .unwrap()
+}
+
+async fn start_server(config: Config) -> String {
+    let state = AppState::new(Arc::new(ThanosRouter::new(Arc::new(config))));
+    let app = thanos::server::uds::build_router(state);
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    url
+}
+
+async fn chat(client: &reqwest::Client, url: &str) -> reqwest::StatusCode {
+    client
+        .post(format!("{}/v1/chat/completions", url))
+        .json(&serde_json::json!({
+            "model": "m",
+            "messages": [{ "role": "user", "content": "hello" }],
+        }))
+        .send()
+        .await
+        .unwrap()
+        .status()
+}
+
+async fn circuit(client: &reqwest::Client, url: &str, provider: &str) -> serde_json::Value {
+    client
+        .get(format!("{}/admin/circuits/{}", url, provider))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap()
+}
+
+/// Client sending the admin token on every request
+fn admin_client() -> reqwest::Client {
+    let mut headers = reqwest::header::HeaderMap::new();
+    headers.insert(
+        reqwest::header::AUTHORIZATION,
+        format!("Bearer {}", ADMIN_TOKEN).parse().unwrap(),
+    );
+    reqwest::Client::builder().default_headers(headers).build().unwrap()
+}
+
+#[tokio::test]
+async fn test_failures_open_circuit_until_reset() {
+    let upstream = failing_upstream().await;
+    let url = start_server(test_config(&upstream, Some(ADMIN_TOKEN))).await;
+    let client = admin_client();
+
+    for _ in 0..2 {
+        assert_eq!(chat(&client, &url).await, 503);
+    }
+    let state = circuit(&client, &url, "ollama").await;
+    assert_eq!(state["state"], "open");
+    assert_eq!(state["consecutive_failures"], 2);
+
+    let listed: serde_json::Value = client
+        .get(format!("{}/admin/circuits", url))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(listed["data"][0]["provider"], "ollama");
+
+    let reset = client
+        .post(format!("{}/admin/circuits/ollama/reset", url))
+        .send()
+        .await
+        .unwrap();
+    assert!(reset.status().is_success());
+    assert_eq!(circuit(&client, &url, "ollama").await["state"], "closed");
+
+    let forced: serde_json::Value = client
+        .post(format!("{}/admin/circuits/ollama/open", url))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(forced["state"], "open");
+    assert_eq!(forced["forced"], true);
+
+    let missing = client.get(format!("{}/admin/circuits/nope", url)).send().await.unwrap();
+    assert_eq!(missing.status(), 404);
+}
+
+#[tokio::test]
+async fn test_admin_token_required() {
+    let upstream = failing_upstream().await;
+    let url = start_server(test_config(&upstream, Some(ADMIN_TOKEN))).await;
+    let client = reqwest::Client::new();
+
+    let denied = client.get(format!("{}/admin/circuits", url)).send().await.unwrap();
+    assert_eq!(denied.status(), 401);
+
+    let allowed = client
+        .get(format!("{}/admin/circuits", url))
+        .bearer_auth(ADMIN_TOKEN)
+        .send()
+        .await
+        .unwrap();
+    assert!(allowed.status().is_success());
+}
+
+#[tokio::test]
+async fn test_admin_only_on_unix_socket_without_token() {
+    // Refused over TCP, even with a made-up token
+    let upstream = failing_upstream().await;
+    let url = start_server(test_config(&upstream, None)).await;
+    let client = reqwest::Client::new();
+    for token in [None, Some("guess")] {
+        let mut request = client.post(format!("{}/admin/circuits/ollama/open", url));
+        if let Some(token) = token {
+            request = request.bearer_auth(token);
+        }
+        assert_eq!(request.send().await.unwrap().status(), 403);
+    }
+    assert_eq!(chat(&client, &url).await, 503, "provider must still be routed to");
+
+    // Served on the Unix socket
+    let socket = std::env::temp_dir().join(format!("thanos-cb-{}.sock", std::process::id()));
+    let mut config = test_config(&upstream, None);
+    config.server.uds_path = Some(socket.display().to_string());
+    let state = AppState::new(Arc::new(ThanosRouter::new(Arc::new(config.clone()))));
+    tokio::spawn(thanos::server::uds::serve(config, thanos::server::uds::build_router(state)));
+
+    let mut stream = None;
+    for _ in 0..50 {
+        if let Ok(connected) = tokio::net::UnixStream::connect(&socket).await {
+            stream = Some(connected);
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    let mut stream = stream.expect("UDS server did not start");
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    stream
+        .write_all(b"GET /admin/circuits HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .await
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    assert!(response.contains("ollama"), "{}", response);
+}
@@ -0,0 +1,75 @@
+// Drain tests - a shutdown drain waits for slow non-streaming requests
+//
+// The shutdown state is process-wide, so this is apart from shutdown_tests.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use thanos::config::Config;
+use thanos::router::Router as ThanosRouter;
+use thanos::server::http::AppState;
+use thanos::shutdown::SHUTDOWN;
+
+#[tokio::test]
+async fn test_drain_waits_for_non_streaming_requests() {
+    // Ollama answers after two seconds
+    let mut upstream = mockito::Server::new_async().await;
+    upstream
+        .mock("POST", "/api/chat")
+        .with_chunked_body(|w| {
+            std::thread::sleep(Duration::from_secs(2));
+            w.write_all(br#"{"message":{"role":"assistant","content":"done"},"done":true}"#)
+        })
+        .create_async()
+        .await;
+
+    let config: Config = toml::from_str(&format!(
+        r#"
+        [server]
+        [routing]
+        strategy = "preferred"
+
+        [providers.ollama]
+        enabled = true
+        auth_method = "none"
+        endpoint = "{}"
+        "#,
+        upstream.url()
+    ))
+    .unwrap();
+
+    let state = AppState::new(Arc::new(ThanosRouter::new(Arc::new(config))));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = tokio::spawn(thanos::server::http::serve_listener(listener, thanos::server::http::app(state), None));
+
+    let request = tokio::spawn(
+        reqwest::Client::new()
+            .post(format!("http://{}/v1/chat/completions", addr))
+            .json(&serde_json::json!({
+                "model": "slow-1",
+                "messages": [{ "role": "user", "content": "hi" }],
+            }))
+            .send(),
+    );
+    while SHUTDOWN.in_flight() == 0 {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    // The drain lasts until the request is answered, well before its timeout
+    let started = Instant::now();
+    thanos::shutdown::drain(Duration::from_secs(30)).await;
+    assert!(started.elapsed() >= Duration::from_secs(1), "drain ended with the request in flight");
+    assert!(started.elapsed() < Duration::from_secs(10));
+    assert_eq!(SHUTDOWN.in_flight(), 0);
+
+    let response = request.await.unwrap().unwrap();
+    assert_eq!(response.status(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["choices"][0]["message"]["content"], "done");
+
+    tokio::time::timeout(Duration::from_secs(5), server)
+        .await
+        .expect("server did not stop after draining")
+        .unwrap()
+        .unwrap();
+}
@@ -0,0 +1,279 @@
+// Health check tests - authenticated provider probes drive /health and the circuit breaker
+
+use std::sync::Arc;
+use thanos::circuit_breaker::CircuitState;
+use thanos::config::Config;
+use thanos::health::HealthStatus;
+use thanos::router::Router as ThanosRouter;
+use thanos::server::http::AppState;
+use thanos::types::{ChatMessage, ChatRequest, Role};
+
+fn config(base_url: &str) -> Arc<Config> {
+    let config: Config = toml::from_str(&format!(
+        r#"
+        [server]
+        [routing]
+        strategy = "preferred"
+
+        [health_check]
+        unhealthy_threshold = 2
+
+        [providers.anthropic]
+        enabled = true
+        auth_method = "api_key"
+        api_key = "sk-test"
+        base_url = "{}"
+        "#,
+        base_url
+    ))
+    .unwrap();
+    Arc::new(config)
+}
+
+fn ping(model: &str) -> ChatRequest {
+    ChatRequest {
+        model: model.to_string(),
+        messages: vec![ChatMessage {
+            role: Role::User,
+            content: "ping".to_string(),
+        }],
+        stream: false,
+        temperature: None,
+        max_tokens: None,
+        top_p: None,
+        system: None,
+        hedge: None,
+    }
+}
+
+#[tokio::test]
+async fn test_failed_checks_open_circuit() {
+    let mut upstream = mockito::Server::new_async().await;
+    let rejected = upstream
+        .mock("GET", "/v1/models?limit=1")
+        .match_header("x-api-key", "sk-test")
+        .with_status(401)
+        .with_body(r#"{"error":{"type":"authentication_error","message":"invalid x-api-key"}}"#)
+        .expect(2)
+        .create_async()
+        .await;
+
+    let router = ThanosRouter::new(config(&upstream.url()));
+
+    let first = router.check_all_providers().await;
+    assert_eq!(first[0].1.status, HealthStatus::Degraded);
+    assert_eq!(router.circuit("anthropic").unwrap().state, CircuitState::Closed);
+
+    let second = router.check_all_providers().await;
+    assert_eq!(second[0].1.status, HealthStatus::Unhealthy);
+    assert!(second[0].1.error.as_deref().unwrap().contains("invalid x-api-key"));
+    assert_eq!(router.circuit("anthropic").unwrap().state, CircuitState::Open);
+    rejected.assert_async().await;
+
+    // A passing check lets routing probe the provider again
+    rejected.remove_async().await;
+    upstream
+        .mock("GET", "/v1/models?limit=1")
+        .with_status(200)
+        .with_body(r#"{"data":[]}"#)
+        .create_async()
+        .await;
+
+    let recovered = router.check_all_providers().await;
+    assert_eq!(recovered[0].1.status, HealthStatus::Healthy);
+    assert_eq!(router.circuit("anthropic").unwrap().state, CircuitState::HalfOpen);
+}
+
+#[tokio::test]
+async fn test_health_endpoint_reports_checks() {
+    let mut upstream = mockito::Server::new_async().await;
+    upstream
+        .mock("GET", "/v1/models?limit=1")
+        .with_status(200)
+        .with_body(r#"{"data":[]}"#)
+        .expect(1)
+        .create_async()
+        .await;
+
+    let config = config(&upstream.url());
+    let state = AppState::new(Arc::new(ThanosRouter::new(config)));
+    let app = thanos::server::uds::build_router(state);
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/health", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    // The second request is served from the first check's result
+    for _ in 0..2 {
+        let body: serde_json::Value = reqwest::get(&url).await.unwrap().json().await.unwrap();
+        assert_eq!(body["status"], "healthy");
+        assert_eq!(body["providers"]["anthropic"]["status"], "healthy");
+        assert!(body["uptime_secs"].is_u64());
+    }
+}
+
+#[tokio::test]
+async fn test_liveness_and_readiness() {
+    let config = config("http://127.0.0.1:9");
+    let state = AppState::new(Arc::new(ThanosRouter::new(config)));
+    let app = thanos::server::uds::build_router(state);
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    let live = reqwest::get(format!("{}/livez", url)).await.unwrap();
+    assert_eq!(live.status(), 200);
+
+    // Not ready until the config is loaded and every expected listener is bound
+    thanos::health::STARTUP.expect_listener("readyz-test");
+    let not_ready = reqwest::get(format!("{}/readyz", url)).await.unwrap();
+    assert_eq!(not_ready.status(), 503);
+    let body: serde_json::Value = not_ready.json().await.unwrap();
+    assert_eq!(body["config_loaded"], false);
+    assert_eq!(body["pending_listeners"], serde_json::json!(["readyz-test"]));
+    assert_eq!(body["available_providers"], serde_json::json!(["anthropic"]));
+
+    thanos::health::STARTUP.config_loaded();
+    thanos::health::STARTUP.listener_bound("readyz-test");
+    let ready = reqwest::get(format!("{}/readyz", url)).await.unwrap();
+    assert_eq!(ready.status(), 200);
+    let body: serde_json::Value = ready.json().await.unwrap();
+    assert_eq!(body["ready"], true);
+}
+
+#[tokio::test]
+async fn test_open_circuit_available_after_cooldown() {
+    let mut upstream = mockito::Server::new_async().await;
+    upstream
+        .mock("POST", "/v1/messages")
+        .with_status(500)
+        .with_body(r#"{"error":{"type":"api_error","message":"boom"}}"#)
+        .create_async()
+        .await;
+
+    // Without health checks nothing but a request ever probes the circuit
+    let config: Config = toml::from_str(&format!(
+        r#"
+        [server]
+        [routing]
+        strategy = "preferred"
+
+        [health_check]
+        enabled = false
+
+        [providers.anthropic]
+        enabled = true
+        auth_method = "api_key"
+        api_key = "sk-test"
+        base_url = "{}"
+        retry = {{ max_attempts = 1 }}
+        circuit_breaker = {{ failure_threshold = 1, open_secs = 1 }}
+        "#,
+        upstream.url()
+    ))
+    .unwrap();
+    let router = ThanosRouter::new(Arc::new(config));
+
+    assert!(router.route_chat_completion(&ping("claude-3-5-sonnet-20241022")).await.is_err());
+    assert_eq!(router.circuit("anthropic").unwrap().state, CircuitState::Open);
+    assert!(router.available_providers().await.is_empty());
+
+    tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+    assert_eq!(router.available_providers().await, vec!["anthropic".to_string()]);
+}
+
+#[tokio::test]
+async fn test_stale_health_ignored() {
+    let config: Config = toml::from_str(
+        r#"
+        [server]
+        [routing]
+        strategy = "preferred"
+
+        [health_check]
+        enabled = false
+        interval_secs = 1
+
+        [providers.anthropic]
+        enabled = true
+        auth_method = "api_key"
+        api_key = "sk-test"
+        base_url = "http://127.0.0.1:9"
+        "#,
+    )
+    .unwrap();
+    let checker = Arc::new(thanos::health::HealthChecker::new());
+    let router = ThanosRouter::with_health_checker(Arc::new(config), checker.clone());
+
+    let failure = Some("HTTP 503".to_string());
+    checker.record("anthropic", std::time::Duration::ZERO, failure, 1).await;
+    assert!(router.available_providers().await.is_empty());
+
+    // Two intervals without a new check and the old result no longer counts
+    tokio::time::sleep(std::time::Duration::from_millis(2100)).await;
+    assert_eq!(router.available_providers().await, vec!["anthropic".to_string()]);
+}
+
+#[tokio::test]
+async fn test_completion_probe_bypasses_circuit() {
+    let model = "claude-probe-test";
+    let mut upstream = mockito::Server::new_async().await;
+    let failing = upstream
+        .mock("POST", "/v1/messages")
+        .with_status(500)
+        .with_body(r#"{"error":{"type":"api_error","message":"boom"}}"#)
+        .create_async()
+        .await;
+
+    let config: Config = toml::from_str(&format!(
+        r#"
+        [server]
+        [routing]
+        strategy = "preferred"
+
+        [health_check]
+        probe = "completion"
+
+        [providers.anthropic]
+        enabled = true
+        auth_method = "api_key"
+        api_key = "sk-test"
+        base_url = "{}"
+        model = "{}"
+        retry = {{ max_attempts = 1 }}
+        circuit_breaker = {{ failure_threshold = 1, open_secs = 60 }}
+        "#,
+        upstream.url(),
+        model,
+    ))
+    .unwrap();
+    let router = ThanosRouter::new(Arc::new(config));
+    assert!(router.route_chat_completion(&ping(model)).await.is_err());
+    assert_eq!(router.circuit("anthropic").unwrap().state, CircuitState::Open);
+
+    failing.remove_async().await;
+    upstream
+        .mock("POST", "/v1/messages")
+        .with_status(200)
+        .with_body(r#"{"content":[{"type":"text","text":"p"}],"usage":{"input_tokens":1,"output_tokens":1},"stop_reason":"max_tokens"}"#)
+        .expect(1)
+        .create_async()
+        .await;
+
+    // The open circuit doesn't block the check, and the check isn't counted as traffic
+    let checked = router.check_all_providers().await;
+    assert_eq!(checked[0].1.status, HealthStatus::Healthy);
+    assert_eq!(router.circuit("anthropic").unwrap().state, CircuitState::HalfOpen);
+    let requests = |outcome: &str| {
+        thanos::metrics::METRICS.provider_requests_total
+            .with_label_values(&["anthropic", model, outcome])
+            .get()
+    };
+    assert_eq!(requests("success"), 0.0);
+    assert_eq!(requests("error"), 1.0);
+}
@@ -0,0 +1,165 @@
+// Hedged routing tests - race a slow and a fast plugin provider
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use thanos::config::Config;
+use thanos::router::Router;
+use thanos::types::{ChatMessage, ChatRequest, Role};
+
+/// Replies after $DELAY seconds as provider $NAME; logs cancel notifications
+const DELAYED_PLUGIN: &str = r#"
+while IFS= read -r line; do
+  id=$(printf '%s' "$line" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+  case "$line" in
+    *'"method":"cancel"'*)
+      echo cancelled >> "$CANCEL_MARKER"
+      ;;
+    *'"method":"chat_stream"'*)
+      sleep "$DELAY"
+      printf '{"jsonrpc":"2.0","method":"chunk","params":{"id":%s,"response":{"provider":"%s","model":"m","content":"hi","done":true}}}\n' "$id" "$NAME"
+      printf '{"jsonrpc":"2.0","id":%s,"result":null}\n' "$id"
+      ;;
+    *'"method":"chat"'*)
+      sleep "$DELAY"
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"provider":"%s","model":"m","content":"hi","done":true}}\n' "$id" "$NAME"
+      ;;
+  esac
+done
+"#;
+
+struct Fixture {
+    router: Router,
+    slow_marker: PathBuf,
+}
+
+/// Two plugin providers `<test>_slow` (1s) and `<test>_fast` (instant)
+fn fixture(test: &str, strategy: &str, chain: [&str; 2]) -> Fixture {
+    let dir = std::env::temp_dir();
+    let script = dir.join(format!("thanos-{}-{}.sh", test, std::process::id()));
+    std::fs::write(&script, DELAYED_PLUGIN).unwrap();
+
+    let marker = |name: &str| dir.join(format!("thanos-{}-{}-{}.cancelled", test, name, std::process::id()));
+    let slow_marker = marker("slow");
+    let _ = std::fs::remove_file(&slow_marker);
+
+    let provider = |name: &str, delay: &str| {
+        format!(
+            r#"
+            [providers.{test}_{name}]
+            enabled = true
+            auth_method = "none"
+
+            [providers.{test}_{name}.plugin]
+            command = "sh"
+            args = ["{script}"]
+            env = {{ DELAY = "{delay}", NAME = "{name}", CANCEL_MARKER = "{marker}" }}
+            "#,
+            test = test,
+            name = name,
+            script = script.display(),
+            delay = delay,
+            marker = marker(name).display(),
+        )
+    };
+
+    let config: Config = toml::from_str(&format!(
+        r#"
+        [server]
+        [routing]
+        strategy = "{strategy}"
+        fallback_chain = ["{test}_{first}", "{test}_{second}"]
+        hedge = {{ delay_ms = 100, max_hedges = 1 }}
+        {slow}
+        {fast}
+        "#,
+        strategy = strategy,
+        test = test,
+        first = chain[0],
+        second = chain[1],
+        slow = provider("slow", "1"),
+        fast = provider("fast", "0"),
+    ))
+    .unwrap();
+
+    Fixture {
+        router: Router::new(Arc::new(config)),
+        slow_marker,
+    }
+}
+
+fn create_test_request() -> ChatRequest {
+    ChatRequest {
+        model: "m".to_string(),
+        messages: vec![ChatMessage {
+            role: Role::User,
+            content: "ping".to_string(),
+        }],
+        stream: false,
+        temperature: None,
+        max_tokens: None,
+        top_p: None,
+        system: None,
+        hedge: None,
+    }
+}
+
+async fn wait_for(path: &Path) -> bool {
+    for _ in 0..40 {
+        if path.exists() {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    false
+}
+
+#[tokio::test]
+async fn test_hedge_fires_and_wins() {
+    let fixture = fixture("hedge_wins", "hedged", ["slow", "fast"]);
+
+    let start = Instant::now();
+    let response = fixture.router.route_chat_completion(&create_test_request()).await.unwrap();
+
+    assert_eq!(response.provider, "fast");
+    assert!(start.elapsed() < Duration::from_millis(900));
+    // The slow provider is told to stop once the hedge wins
+    assert!(wait_for(&fixture.slow_marker).await, "loser was not cancelled");
+}
+
+#[tokio::test]
+async fn test_no_hedge_when_first_is_fast() {
+    let fixture = fixture("hedge_unneeded", "hedged", ["fast", "slow"]);
+
+    let response = fixture.router.route_chat_completion(&create_test_request()).await.unwrap();
+
+    assert_eq!(response.provider, "fast");
+}
+
+#[tokio::test]
+async fn test_per_request_opt_in() {
+    let fixture = fixture("hedge_opt_in", "fallback", ["slow", "fast"]);
+
+    let mut request = create_test_request();
+    request.hedge = Some(true);
+    let response = fixture.router.route_chat_completion(&request).await.unwrap();
+    assert_eq!(response.provider, "fast");
+
+    // Without the opt-in, plain fallback waits for the slow provider
+    let response = fixture.router.route_chat_completion(&create_test_request()).await.unwrap();
+    assert_eq!(response.provider, "slow");
+}
+
+#[tokio::test]
+async fn test_hedged_stream() {
+    let fixture = fixture("hedge_stream", "hedged", ["slow", "fast"]);
+
+    let mut request = create_test_request();
+    request.stream = true;
+
+    let mut rx = fixture.router.route_chat_completion_stream(&request).await.unwrap();
+    let chunk = rx.recv().await.unwrap().unwrap();
+
+    assert_eq!(chunk.provider, "fast");
+    assert!(wait_for(&fixture.slow_marker).await, "loser stream was not cancelled");
+}
@@ -0,0 +1,177 @@
+// HTTP/3 tests - a quinn + h3 client against the QUIC listener with a self-signed cert
+
+use bytes::{Buf, Bytes};
+use std::sync::Arc;
+use thanos::config::Config;
+use thanos::router::Router as ThanosRouter;
+use thanos::server::http::AppState;
+
+type SendRequest = h3::client::SendRequest<h3_quinn::OpenStreams, Bytes>;
+
+/// Ollama streaming "Hel" + "lo"
+async fn streaming_upstream() -> mockito::ServerGuard {
+    let mut upstream = mockito::Server::new_async().await;
+    upstream
+        .mock("POST", "/api/chat")
+        .with_body(concat!(
+            r#"{"message":{"role":"assistant","content":"Hel"},"done":false}"#,
+            "\n",
+            r#"{"message":{"role":"assistant","content":"lo"},"done":true}"#,
+            "\n",
+        ))
+        .create_async()
+        .await;
+    upstream
+}
+
+/// Start the HTTP/3 listener on a random UDP port; returns its address and the cert
+async fn start_server(
+    name: &str,
+    upstream: &mockito::ServerGuard,
+) -> (std::net::SocketAddr, rustls::pki_types::CertificateDer<'static>) {
+    let dir = std::env::temp_dir();
+    let id = std::process::id();
+    let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+    let cert_path = dir.join(format!("thanos-{}-cert-{}.pem", name, id));
+    let key_path = dir.join(format!("thanos-{}-key-{}.pem", name, id));
+    std::fs::write(&cert_path, cert.cert.pem()).unwrap();
+    std::fs::write(&key_path, cert.key_pair.serialize_pem()).unwrap();
+
+    let mut config: Config = toml::from_str(&format!(
+        r#"
+        [server]
+        bind = "127.0.0.1:8080"
+
+        [server.http3]
+        enabled = true
+        bind = "127.0.0.1:0"
+
+        [server.tls]
+        cert_path = "{}"
+        key_path = "{}"
+
+        [routing]
+        strategy = "preferred"
+
+        [providers.ollama]
+        enabled = true
+        auth_method = "none"
+        endpoint = "{}"
+        "#,
+        cert_path.display(),
+        key_path.display(),
+        upstream.url(),
+    ))
+    .unwrap();
+
+    let (endpoint, tls) = thanos::server::http3::endpoint(&config).unwrap();
+    let addr = endpoint.local_addr().unwrap();
+
+    // Advertise the port that was actually bound
+    config.server.http3.bind = Some(addr.to_string());
+    let config = Arc::new(config);
+    let state = AppState::new(Arc::new(ThanosRouter::new(config)));
+    let app = thanos::server::http::app(state);
+    tokio::spawn(thanos::server::http3::serve_endpoint(endpoint, tls, app));
+
+    (addr, cert.cert.der().clone())
+}
+
+async fn connect(addr: std::net::SocketAddr, cert: rustls::pki_types::CertificateDer<'static>) -> SendRequest {
+    let mut roots = rustls::RootCertStore::empty();
+    roots.add(cert).unwrap();
+    let mut tls = rustls::ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_protocol_versions(&[&rustls::version::TLS13])
+        .unwrap()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    tls.alpn_protocols = vec![b"h3".to_vec()];
+
+    let crypto = quinn::crypto::rustls::QuicClientConfig::try_from(tls).unwrap();
+    let mut client = quinn::Endpoint::client("127.0.0.1:0".parse().unwrap()).unwrap();
+    client.set_default_client_config(quinn::ClientConfig::new(Arc::new(crypto)));
+
+    let conn = client.connect(addr, "localhost").unwrap().await.unwrap();
+    let (mut driver, send_request) = h3::client::new(h3_quinn::Connection::new(conn)).await.unwrap();
+    tokio::spawn(async move {
+        let _ = std::future::poll_fn(|cx| driver.poll_close(cx)).await;
+    });
+    send_request
+}
+
+/// Send a request and collect the status, headers and body
+async fn send(
+    client: &mut SendRequest,
+    request: axum::http::Request<()>,
+    body: Option<&str>,
+) -> (axum::http::StatusCode, axum::http::HeaderMap, String) {
+    let mut stream = client.send_request(request).await.unwrap();
+    if let Some(body) = body {
+        stream.send_data(Bytes::from(body.to_string())).await.unwrap();
+    }
+    stream.finish().await.unwrap();
+
+    let response = stream.recv_response().await.unwrap();
+    let mut text = Vec::new();
+    while let Some(mut chunk) = stream.recv_data().await.unwrap() {
+        text.extend_from_slice(&chunk.copy_to_bytes(chunk.remaining()));
+    }
+    (response.status(), response.headers().clone(), String::from_utf8(text).unwrap())
+}
+
+#[tokio::test]
+async fn test_http3_routes() {
+    let upstream = streaming_upstream().await;
+    let (addr, cert) = start_server("h3-routes", &upstream).await;
+    let mut client = connect(addr, cert).await;
+
+    let request = axum::http::Request::get("https://localhost/livez").body(()).unwrap();
+    let (status, headers, body) = send(&mut client, request, None).await;
+    assert_eq!(status, 200);
+    assert!(body.contains("ok"));
+    assert_eq!(headers["alt-svc"], format!("h3=\":{}\"; ma=86400", addr.port()));
+
+    let request = axum::http::Request::post("https://localhost/v1/chat/completions")
+        .header("content-type", "application/json")
+        .body(())
+        .unwrap();
+    let (status, headers, body) = send(
+        &mut client,
+        request,
+        Some(r#"{"model":"echo-1","stream":true,"messages":[{"role":"user","content":"hi"}]}"#),
+    )
+    .await;
+    assert_eq!(status, 200);
+    assert_eq!(headers["content-type"], "text/event-stream");
+    assert!(body.contains("Hel"));
+    assert!(body.contains("lo"));
+    assert!(body.contains("[DONE]"));
+}
+
+#[tokio::test]
+async fn test_http3_rejects_oversized_bodies() {
+    let upstream = streaming_upstream().await;
+    let (addr, cert) = start_server("h3-oversized", &upstream).await;
+    let mut client = connect(addr, cert).await;
+
+    let request = axum::http::Request::post("https://localhost/v1/chat/completions")
+        .header("content-type", "application/json")
+        .body(())
+        .unwrap();
+    let (mut send, mut recv) = client.send_request(request).await.unwrap().split();
+
+    // The server may stop reading once it answers, so don't wait on the upload
+    let content = "x".repeat(3 * 1024 * 1024);
+    let body = format!(r#"{{"model":"echo-1","messages":[{{"role":"user","content":"{}"}}]}}"#, content);
+    tokio::spawn(async move {
+        if send.send_data(Bytes::from(body)).await.is_ok() {
+            let _ = send.finish().await;
+        }
+    });
+
+    let response = tokio::time::timeout(std::time::Duration::from_secs(10), recv.recv_response())
+        .await
+        .expect("no response to an oversized body")
+        .unwrap();
+    assert_eq!(response.status(), 413);
+}
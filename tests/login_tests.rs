@@ -0,0 +1,158 @@
+// Remote OAuth login tests - admin HTTP and gRPC endpoints start and drive a login
+
+use std::sync::Arc;
+use std::time::Duration;
+use thanos::config::Config;
+use thanos::proto::thanos_service_client::ThanosServiceClient;
+use thanos::router::Router as ThanosRouter;
+use thanos::server::http::AppState;
+use tonic::Code;
+
+const ADMIN_TOKEN: &str = "s3cret";
+
+fn free_port() -> u16 {
+    std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port()
+}
+
+/// HTTP and gRPC servers, guarded by `admin_token` if given
+async fn start_servers(admin_token: Option<&str>) -> (String, ThanosServiceClient<tonic::transport::Channel>) {
+    let grpc_addr = format!("127.0.0.1:{}", free_port());
+    let config: Config = toml::from_str(&format!(
+        r#"
+        [server]
+        grpc = "{}"
+        {}
+
+        [routing]
+
+        [providers]
+        "#,
+        grpc_addr,
+        admin_token.map(|token| format!("admin_token = \"{}\"", token)).unwrap_or_default(),
+    ))
+    .unwrap();
+
+    let state = AppState::new(Arc::new(ThanosRouter::new(Arc::new(config))));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(thanos::server::http::serve_listener(listener, thanos::server::http::app(state.clone()), None));
+    tokio::spawn(thanos::server::grpc::serve(state));
+
+    for _ in 0..50 {
+        if let Ok(client) = ThanosServiceClient::connect(format!("http://{}", grpc_addr)).await {
+            return (url, client);
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("gRPC server did not start");
+}
+
+fn with_token<T>(message: T) -> tonic::Request<T> {
+    let mut request = tonic::Request::new(message);
+    request
+        .metadata_mut()
+        .insert("authorization", format!("Bearer {}", ADMIN_TOKEN).parse().unwrap());
+    request
+}
+
+#[tokio::test]
+async fn test_remote_login_over_http_and_grpc() {
+    let (url, mut grpc) = start_servers(Some(ADMIN_TOKEN)).await;
+    let http = reqwest::Client::new();
+
+    // HTTP: start a Claude login, then check on it, reject a bad code and cancel it
+    let denied = http.post(format!("{}/admin/auth/claude/login", url)).send().await.unwrap();
+    assert_eq!(denied.status(), 401);
+
+    let started: serde_json::Value = http
+        .post(format!("{}/admin/auth/claude/login", url))
+        .bearer_auth(ADMIN_TOKEN)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(started["provider"], "anthropic_max");
+    assert_eq!(started["flow"], "pkce");
+    assert_eq!(started["state"], "pending");
+    assert!(started["url"].as_str().unwrap().contains("code_challenge="));
+    let login = format!("{}/admin/auth/logins/{}", url, started["id"].as_str().unwrap());
+
+    let status: serde_json::Value =
+        http.get(&login).bearer_auth(ADMIN_TOKEN).send().await.unwrap().json().await.unwrap();
+    assert_eq!(status["id"], started["id"]);
+    assert_eq!(status["state"], "pending");
+
+    let rejected = http
+        .post(format!("{}/code", login))
+        .bearer_auth(ADMIN_TOKEN)
+        .json(&serde_json::json!({ "code": "abc#forged" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(rejected.status(), 400);
+
+    let unknown = http.post(format!("{}/admin/auth/nope/login", url)).bearer_auth(ADMIN_TOKEN).send().await.unwrap();
+    assert_eq!(unknown.status(), 404);
+
+    assert!(http.delete(&login).bearer_auth(ADMIN_TOKEN).send().await.unwrap().status().is_success());
+    assert_eq!(http.get(&login).bearer_auth(ADMIN_TOKEN).send().await.unwrap().status(), 404);
+
+    // gRPC: the same flow, with the admin token in metadata
+    let start = thanos::proto::StartLoginRequest { provider: "claude".to_string() };
+    let denied = grpc.start_login(start.clone()).await.unwrap_err();
+    assert_eq!(denied.code(), Code::Unauthenticated);
+
+    let started = grpc.start_login(with_token(start)).await.unwrap().into_inner();
+    assert_eq!((started.provider.as_str(), started.flow.as_str()), ("anthropic_max", "pkce"));
+
+    let status = grpc
+        .get_login(with_token(thanos::proto::LoginRequest { id: started.id.clone() }))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(status.state, "pending");
+    assert!(status.expires_in > 0);
+
+    let rejected = grpc
+        .submit_login_code(with_token(thanos::proto::SubmitLoginCodeRequest {
+            id: started.id,
+            code: "no-separator".to_string(),
+        }))
+        .await
+        .unwrap_err();
+    assert_eq!(rejected.code(), Code::InvalidArgument);
+
+    let missing = grpc
+        .get_login(with_token(thanos::proto::LoginRequest { id: "nope".to_string() }))
+        .await
+        .unwrap_err();
+    assert_eq!(missing.code(), Code::NotFound);
+}
+
+#[tokio::test]
+async fn test_login_refused_without_admin_token() {
+    let (url, mut grpc) = start_servers(None).await;
+    let http = reqwest::Client::new();
+
+    let denied = http.post(format!("{}/admin/auth/claude/login", url)).bearer_auth("guess").send().await.unwrap();
+    assert_eq!(denied.status(), 403);
+
+    let start = thanos::proto::StartLoginRequest { provider: "claude".to_string() };
+    let denied = grpc.start_login(start).await.unwrap_err();
+    assert_eq!(denied.code(), Code::PermissionDenied);
+
+    // Browsers get CORS headers for the API, but not for the admin routes
+    let preflight = |path: &str| {
+        http.request(reqwest::Method::OPTIONS, format!("{}{}", url, path))
+            .header("origin", "https://evil.example")
+            .header("access-control-request-method", "POST")
+            .header("access-control-request-headers", "authorization")
+            .send()
+    };
+    let api = preflight("/v1/chat/completions").await.unwrap();
+    assert!(api.headers().contains_key("access-control-allow-origin"));
+    let admin = preflight("/admin/auth/claude/login").await.unwrap();
+    assert!(!admin.headers().contains_key("access-control-allow-origin"));
+}
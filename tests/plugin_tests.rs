@@ -0,0 +1,106 @@
+// Plugin provider tests - drive a tiny shell-script plugin over the stdio protocol
+
+use std::collections::HashMap;
+use thanos::config::PluginConfig;
+use thanos::providers::plugin::PluginProvider;
+use thanos::providers::Provider;
+use thanos::types::{ChatMessage, ChatRequest, Role};
+
+/// Echo plugin: answers chat/chat_stream/health, optionally exiting after each chat reply
+const ECHO_PLUGIN: &str = r#"
+while IFS= read -r line; do
+  id=$(printf '%s' "$line" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+  case "$line" in
+    *'"method":"chat_stream"'*)
+      printf '{"jsonrpc":"2.0","method":"chunk","params":{"id":%s,"response":{"provider":"echo","model":"echo-1","content":"Hel","done":false}}}\n' "$id"
+      printf '{"jsonrpc":"2.0","method":"chunk","params":{"id":%s,"response":{"provider":"echo","model":"echo-1","content":"lo","done":true,"finish_reason":"stop"}}}\n' "$id"
+      printf '{"jsonrpc":"2.0","id":%s,"result":null}\n' "$id"
+      ;;
+    *'"method":"chat"'*)
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"provider":"echo","model":"echo-1","content":"pong %s","done":true,"finish_reason":"stop"}}\n' "$id" "$$"
+      if [ -n "$CRASH_AFTER_REPLY" ]; then exit 1; fi
+      ;;
+    *'"method":"health"'*)
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"healthy":true}}\n' "$id"
+      ;;
+  esac
+done
+"#;
+
+fn plugin_config(name: &str, env: &[(&str, &str)]) -> PluginConfig {
+    let path = std::env::temp_dir().join(format!("thanos-{}-{}.sh", name, std::process::id()));
+    std::fs::write(&path, ECHO_PLUGIN).unwrap();
+
+    PluginConfig {
+        command: "sh".to_string(),
+        args: vec![path.to_string_lossy().to_string()],
+        env: env
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect::<HashMap<_, _>>(),
+        working_dir: None,
+        request_timeout: 5,
+        restart_backoff_ms: 50,
+    }
+}
+
+fn create_test_request() -> ChatRequest {
+    ChatRequest {
+        model: "echo-1".to_string(),
+        messages: vec![ChatMessage {
+            role: Role::User,
+            content: "ping".to_string(),
+        }],
+        stream: false,
+        temperature: None,
+        max_tokens: None,
+        top_p: None,
+        system: None,
+        hedge: None,
+    }
+}
+
+#[tokio::test]
+async fn test_plugin_chat_and_health() {
+    let provider = PluginProvider::from_config("echo_basic", &plugin_config("echo_basic", &[])).unwrap();
+
+    assert!(provider.health().await.unwrap());
+
+    let response = provider.chat_completion(&create_test_request()).await.unwrap();
+    assert_eq!(response.provider, "echo");
+    assert!(response.content.starts_with("pong"));
+    assert_eq!(response.finish_reason.as_deref(), Some("stop"));
+}
+
+#[tokio::test]
+async fn test_plugin_streaming() {
+    let provider = PluginProvider::from_config("echo_stream", &plugin_config("echo_stream", &[])).unwrap();
+
+    let mut request = create_test_request();
+    request.stream = true;
+
+    let mut rx = provider.chat_completion_stream(&request).await.unwrap();
+    let mut content = String::new();
+    let mut chunks = 0;
+    while let Some(chunk) = rx.recv().await {
+        content.push_str(&chunk.unwrap().content);
+        chunks += 1;
+    }
+
+    assert_eq!(chunks, 2);
+    assert_eq!(content, "Hello");
+}
+
+#[tokio::test]
+async fn test_plugin_restarts_after_crash() {
+    let provider =
+        PluginProvider::from_config("echo_crash", &plugin_config("echo_crash", &[("CRASH_AFTER_REPLY", "1")])).unwrap();
+
+    let first = provider.chat_completion(&create_test_request()).await.unwrap();
+    // Requests in flight when the plugin dies fail; give the supervisor time to notice the exit
+    tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+    let second = provider.chat_completion(&create_test_request()).await.unwrap();
+
+    // Each reply comes from a fresh process (different shell PID)
+    assert_ne!(first.content, second.content);
+}
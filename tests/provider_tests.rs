@@ -16,6 +16,7 @@ fn create_test_request() -> ChatRequest {
         max_tokens: Some(100),
         top_p: None,
         system: None,
+        hedge: None,
     }
 }
 
@@ -139,12 +140,93 @@ mod chat_request_tests {
     }
 }
 
+// Upstream failures classified into typed errors (mock Ollama endpoint)
+#[cfg(test)]
+mod provider_error_tests {
+    use super::*;
+    use std::time::Duration;
+    use thanos::error::ProviderError;
+    use thanos::providers::ollama::OllamaProvider;
+
+    async fn chat_error(status: usize, headers: &[(&str, &str)], body: &str) -> ProviderError {
+        let mut server = mockito::Server::new_async().await;
+        let mut mock = server.mock("POST", "/api/chat").with_status(status).with_body(body);
+        for &(name, value) in headers {
+            mock = mock.with_header(name, value);
+        }
+        let _mock = mock.create_async().await;
+
+        let provider = OllamaProvider::new(server.url(), "llama3.2".to_string());
+        let err = provider.chat_completion(&create_test_request()).await.unwrap_err();
+        ProviderError::find(&err).expect("typed provider error").clone()
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_with_retry_after() {
+        let err = chat_error(429, &[("retry-after", "12")], r#"{"error":"too many requests"}"#).await;
+
+        assert!(matches!(err, ProviderError::RateLimited { .. }));
+        assert_eq!(err.provider(), "ollama");
+        assert_eq!(err.message(), "too many requests");
+        assert_eq!(err.retry_after(), Some(Duration::from_secs(12)));
+    }
+
+    #[tokio::test]
+    async fn test_upstream_outage() {
+        let err = chat_error(503, &[], "upstream connect error").await;
+
+        assert!(matches!(err, ProviderError::Unavailable { .. }));
+        assert!(err.is_retryable());
+        assert_eq!(err.http_status().as_u16(), 503);
+    }
+
+    #[tokio::test]
+    async fn test_model_not_found() {
+        let err = chat_error(404, &[], r#"{"error":"model 'llama3.2' not found"}"#).await;
+
+        assert!(matches!(err, ProviderError::NotFound { .. }));
+        assert_eq!(err.grpc_code(), tonic::Code::NotFound);
+    }
+
+    #[tokio::test]
+    async fn test_connection_refused() {
+        // Nothing listens on port 9 (discard) in the test environment
+        let provider = OllamaProvider::new("http://127.0.0.1:9".to_string(), "llama3.2".to_string());
+        let err = provider.chat_completion(&create_test_request()).await.unwrap_err();
+
+        assert!(matches!(ProviderError::find(&err), Some(ProviderError::Unavailable { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_gemini_key_sent_in_header() {
+        use thanos::providers::Provider;
+
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/v1beta/models")
+            .match_query(mockito::Matcher::Exact("pageSize=1".to_string()))
+            .match_header("x-goog-api-key", "test-gemini-key")
+            .with_body(r#"{"models":[]}"#)
+            .create_async()
+            .await;
+
+        let config: thanos::config::ProviderConfig = toml::from_str(&format!(
+            "enabled = true\nauth_method = \"api_key\"\napi_key = \"test-gemini-key\"\nbase_url = \"{}\"",
+            server.url()
+        ))
+        .unwrap();
+        let provider = thanos::providers::gemini::GeminiProvider::from_config(&config).unwrap();
+
+        assert!(provider.health().await.unwrap());
+        mock.assert_async().await;
+    }
+}
+
 // Live integration tests (require API keys or running Thanos server)
 #[cfg(test)]
 #[cfg(feature = "integration_tests")]
 mod live_provider_tests {
     use super::*;
-    use thanos::providers::Provider;
 
     #[tokio::test]
     async fn test_ollama_local() {
@@ -162,7 +244,7 @@ mod live_provider_tests {
         match result {
             Ok(response) => {
                 println!("✓ Ollama connection successful");
-                assert!(!response.choices.is_empty());
+                assert!(response.done);
             }
             Err(e) => {
                 println!("✗ Ollama not available: {}", e);
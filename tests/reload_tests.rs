@@ -0,0 +1,147 @@
+// Reload tests - config changes apply without a restart; requests in flight finish on the old config
+
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use thanos::config::Config;
+use thanos::reload::LiveRouter;
+use thanos::router::Router as ThanosRouter;
+use thanos::server::http::AppState;
+
+/// Config whose Ollama endpoint answers with `reply`
+fn write_config(path: &Path, upstream: &mockito::ServerGuard, reply: &str, extra: &str) {
+    // Only watch the file once the first reload is done through the admin API
+    let watch_secs = if reply == "v1" { 0 } else { 1 };
+    let config = format!(
+        r#"
+        [server]
+        admin_token = "admin-{reply}"
+        config_watch_secs = {watch_secs}
+
+        [routing]
+        strategy = "preferred"
+        {extra}
+
+        [providers.ollama]
+        enabled = true
+        auth_method = "none"
+        endpoint = "{url}/{reply}"
+        "#,
+        url = upstream.url(),
+    );
+    std::fs::write(path, config).unwrap();
+
+    // Make sure the watcher sees a new modification time
+    let later = std::time::SystemTime::now() + Duration::from_secs(1);
+    std::fs::File::options().write(true).open(path).unwrap().set_modified(later).unwrap();
+}
+
+/// Ollama `/api/chat` body
+fn ollama_reply(content: &str) -> String {
+    format!(r#"{{"message":{{"role":"assistant","content":"{}"}},"done":true}}"#, content)
+}
+
+async fn ask(client: &reqwest::Client, addr: std::net::SocketAddr, content: &str) -> String {
+    let body: serde_json::Value = client
+        .post(format!("http://{}/v1/chat/completions", addr))
+        .json(&serde_json::json!({
+            "model": "echo-1",
+            "messages": [{ "role": "user", "content": content }],
+        }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    body["choices"][0]["message"]["content"].as_str().unwrap_or_default().to_string()
+}
+
+#[tokio::test]
+async fn test_reload_without_restart() {
+    // Each config version has its own endpoint; v1 answers slowly
+    let mut upstream = mockito::Server::new_async().await;
+    upstream
+        .mock("POST", "/v1/api/chat")
+        .with_chunked_body(|w| {
+            std::thread::sleep(Duration::from_secs(1));
+            w.write_all(ollama_reply("v1").as_bytes())
+        })
+        .create_async()
+        .await;
+    for reply in ["v2", "v3", "v4"] {
+        upstream
+            .mock("POST", format!("/{}/api/chat", reply).as_str())
+            .with_body(ollama_reply(reply))
+            .create_async()
+            .await;
+    }
+
+    let dir = std::env::temp_dir().join(format!("thanos-reload-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let config_path = dir.join("config.toml");
+    write_config(&config_path, &upstream, "v1", "");
+
+    let config = Config::load_from(&config_path.to_string_lossy()).unwrap();
+    let live = LiveRouter::with_file(Arc::new(ThanosRouter::new(Arc::new(config))), &config_path);
+    live.spawn_watchers();
+    let app = thanos::server::http::app(AppState { live: live.clone() });
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    let client = reqwest::Client::new();
+    assert_eq!(ask(&client, addr, "hi").await, "v1");
+
+    // A request in flight during the reload finishes on the old config
+    let in_flight = tokio::spawn({
+        let client = client.clone();
+        async move { ask(&client, addr, "slow").await }
+    });
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    write_config(&config_path, &upstream, "v2", "");
+    let reloaded: serde_json::Value = client
+        .post(format!("http://{}/admin/reload", addr))
+        .bearer_auth("admin-v1")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(reloaded["changes"]["providers_changed"], serde_json::json!(["ollama"]));
+
+    assert_eq!(ask(&client, addr, "hi again").await, "v2");
+    assert_eq!(in_flight.await.unwrap(), "v1");
+
+    // An invalid file is rejected and the current config stays
+    write_config(&config_path, &upstream, "v3", r#"balance = "random""#);
+    let rejected = client
+        .post(format!("http://{}/admin/reload", addr))
+        .bearer_auth("admin-v2")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(rejected.status(), 422);
+    assert_eq!(ask(&client, addr, "still there?").await, "v2");
+
+    // The watcher picks up a fixed file by itself
+    write_config(&config_path, &upstream, "v4", "");
+    let mut reply = String::new();
+    for _ in 0..40 {
+        reply = ask(&client, addr, "and now?").await;
+        if reply == "v4" {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(reply, "v4");
+    assert_eq!(live.config().server.admin_token.as_ref().map(|token| token.expose()), Some("admin-v4"));
+
+    let metrics = client.get(format!("http://{}/metrics", addr)).send().await.unwrap().text().await.unwrap();
+    assert!(metrics.contains(r#"thanos_config_reloads_total{result="failure",trigger="admin"} 1"#), "{}", metrics);
+    assert!(metrics.contains(r#"thanos_config_reloads_total{result="success",trigger="file"} 1"#));
+    assert!(metrics.contains("thanos_config_last_reload_success 1"));
+}
@@ -0,0 +1,192 @@
+// Routing rules tests - rules steer requests between mocked providers over HTTP
+
+use std::sync::Arc;
+use thanos::config::Config;
+use thanos::router::Router as ThanosRouter;
+use thanos::server::http::AppState;
+
+/// Local Ollama and cloud Anthropic, each answering with its own name
+async fn start_server(upstream: &mut mockito::Server) -> String {
+    upstream
+        .mock("POST", "/api/chat")
+        .with_body(r#"{"message":{"role":"assistant","content":"local"},"done":true}"#)
+        .create_async()
+        .await;
+    upstream
+        .mock("POST", "/v1/messages")
+        .with_body(r#"{"content":[{"type":"text","text":"cloud"}],"usage":{"input_tokens":1,"output_tokens":1},"stop_reason":"end_turn"}"#)
+        .create_async()
+        .await;
+
+    let config: Config = toml::from_str(&format!(
+        r#"
+        [server]
+        [routing]
+        strategy = "fallback"
+        fallback_chain = ["anthropic"]
+
+        [[routing.rules]]
+        name = "research-team"
+        match = {{ tenant = "research", headers = {{ "x-priority" = "high" }} }}
+        provider = "ollama"
+        model = "big-model"
+
+        [[routing.rules]]
+        name = "short-code"
+        match = {{ model = "code-*", max_prompt_chars = 20 }}
+        provider = "ollama"
+
+        [providers.anthropic]
+        enabled = true
+        auth_method = "api_key"
+        api_key = "sk-test"
+        base_url = "{url}"
+
+        [providers.ollama]
+        enabled = true
+        auth_method = "none"
+        endpoint = "{url}"
+        "#,
+        url = upstream.url(),
+    ))
+    .unwrap();
+
+    let state = AppState::new(Arc::new(ThanosRouter::new(Arc::new(config))));
+    let app = thanos::server::uds::build_router(state);
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    url
+}
+
+fn chat_body(model: &str, content: &str) -> serde_json::Value {
+    serde_json::json!({
+        "model": model,
+        "messages": [{ "role": "user", "content": content }],
+    })
+}
+
+#[tokio::test]
+async fn test_dry_run_reports_matching_rule() {
+    let mut upstream = mockito::Server::new_async().await;
+    let url = start_server(&mut upstream).await;
+    let client = reqwest::Client::new();
+
+    let plan: serde_json::Value = client
+        .post(format!("{}/v1/routing/dry-run", url))
+        .header("x-thanos-tenant", "research")
+        .header("X-Priority", "high")
+        .json(&chat_body("small-model", "hello"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(plan["rule"], "research-team");
+    assert_eq!(plan["provider"], "ollama");
+    assert_eq!(plan["model"], "big-model");
+
+    // Missing one condition: no rule, default strategy
+    let plan: serde_json::Value = client
+        .post(format!("{}/v1/routing/dry-run", url))
+        .header("x-thanos-tenant", "research")
+        .json(&chat_body("small-model", "hello"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(plan["rule"].is_null());
+    assert_eq!(plan["strategy"], "fallback");
+    assert_eq!(plan["model"], "small-model");
+}
+
+#[tokio::test]
+async fn test_rules_route_requests() {
+    let mut upstream = mockito::Server::new_async().await;
+    let url = start_server(&mut upstream).await;
+    let client = reqwest::Client::new();
+
+    let response: serde_json::Value = client
+        .post(format!("{}/v1/chat/completions", url))
+        .header("x-thanos-tenant", "research")
+        .header("x-priority", "high")
+        .json(&chat_body("small-model", "hello"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(response["model"], "big-model");
+    assert_eq!(response["choices"][0]["message"]["content"], "local");
+
+    // Conditions on the model and the prompt length steer the actual request
+    let response: serde_json::Value = client
+        .post(format!("{}/v1/chat/completions", url))
+        .json(&chat_body("code-small", "hello"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(response["choices"][0]["message"]["content"], "local");
+
+    let response: serde_json::Value = client
+        .post(format!("{}/v1/chat/completions", url))
+        .json(&chat_body("code-small", "a prompt well past the twenty character limit"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(response["choices"][0]["message"]["content"], "cloud");
+
+    // Unmatched requests take the fallback chain
+    let response: serde_json::Value = client
+        .post(format!("{}/v1/chat/completions", url))
+        .json(&chat_body("small-model", "hello"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(response["model"], "small-model");
+    assert_eq!(response["choices"][0]["message"]["content"], "cloud");
+}
+
+#[test]
+fn test_content_conditions_rejected() {
+    let path = std::env::temp_dir().join(format!("thanos-rules-content-{}.toml", std::process::id()));
+    std::fs::write(
+        &path,
+        r#"
+[server]
+[routing]
+strategy = "fallback"
+
+[[routing.rules]]
+name = "vision"
+match = { has_images = true }
+provider = "ollama"
+
+[providers.ollama]
+enabled = true
+auth_method = "none"
+"#,
+    )
+    .unwrap();
+
+    // Images and tools never reach providers, so neither may steer routing
+    let (_, problems) = Config::check_file(path.to_str().unwrap()).unwrap();
+    let problem = problems.iter().find(|p| p.key == "routing.rules[0]").unwrap();
+    assert!(problem.message.contains("has_images"));
+}
@@ -0,0 +1,123 @@
+// Shared state tests - HTTP and gRPC share one router, cache and rate limiter
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use thanos::config::Config;
+use thanos::proto::thanos_service_client::ThanosServiceClient;
+use thanos::router::Router as ThanosRouter;
+use thanos::server::http::AppState;
+
+fn free_port() -> u16 {
+    std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port()
+}
+
+fn grpc_request(content: &str) -> thanos::proto::ChatRequest {
+    thanos::proto::ChatRequest {
+        model: "echo-1".to_string(),
+        messages: vec![thanos::proto::Message {
+            role: "user".to_string(),
+            content: content.to_string(),
+        }],
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_transports_share_state() {
+    // Ollama numbers its replies, so a cached answer is recognisable
+    let mut upstream = mockito::Server::new_async().await;
+    let replies = AtomicUsize::new(0);
+    upstream
+        .mock("POST", "/api/chat")
+        .with_body_from_request(move |_| {
+            let n = replies.fetch_add(1, Ordering::SeqCst) + 1;
+            format!(r#"{{"message":{{"role":"assistant","content":"pong {}"}},"done":true}}"#, n).into()
+        })
+        .create_async()
+        .await;
+
+    let grpc_addr = format!("127.0.0.1:{}", free_port());
+    let config: Config = toml::from_str(&format!(
+        r#"
+        [server]
+        grpc = "{}"
+
+        [routing]
+        strategy = "preferred"
+
+        [cache]
+        enabled = true
+
+        [rate_limiting]
+        enabled = true
+        requests_per_minute = 3
+
+        [providers.ollama]
+        enabled = true
+        auth_method = "none"
+        endpoint = "{}"
+        "#,
+        grpc_addr,
+        upstream.url()
+    ))
+    .unwrap();
+
+    let state = AppState::new(Arc::new(ThanosRouter::new(Arc::new(config))));
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let http_addr = listener.local_addr().unwrap();
+    tokio::spawn(thanos::server::http::serve_listener(listener, thanos::server::http::app(state.clone()), None));
+    tokio::spawn(thanos::server::grpc::serve(state));
+
+    let mut grpc = None;
+    for _ in 0..50 {
+        if let Ok(client) = ThanosServiceClient::connect(format!("http://{}", grpc_addr)).await {
+            grpc = Some(client);
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    let mut grpc = grpc.expect("gRPC server did not start");
+
+    // Answered over HTTP, then served from the same cache over gRPC
+    let http = reqwest::Client::new();
+    let body: serde_json::Value = http
+        .post(format!("http://{}/v1/chat/completions", http_addr))
+        .json(&serde_json::json!({
+            "model": "echo-1",
+            "messages": [{ "role": "user", "content": "hi" }],
+        }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let answer = body["choices"][0]["message"]["content"].as_str().unwrap().to_string();
+
+    let mut stream = grpc.chat_completion(grpc_request("hi")).await.unwrap().into_inner();
+    let cached = stream.message().await.unwrap().unwrap();
+    assert_eq!(cached.content, answer);
+
+    // Both transports draw from one budget of 3 requests a minute for this
+    // client's address
+    let mut stream = grpc.chat_completion(grpc_request("again")).await.unwrap().into_inner();
+    assert!(stream.message().await.unwrap().is_some());
+
+    let limited = http
+        .post(format!("http://{}/v1/chat/completions", http_addr))
+        .json(&serde_json::json!({
+            "model": "echo-1",
+            "messages": [{ "role": "user", "content": "one more" }],
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(limited.status(), 429);
+    let body: serde_json::Value = limited.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "rate_limit_exceeded");
+
+    let status = grpc.chat_completion(grpc_request("and more")).await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+}
@@ -0,0 +1,97 @@
+// Shutdown tests - draining stops new connections and ends open streams with a terminal event
+//
+// The shutdown state is process-wide, so this file holds a single test.
+
+use std::sync::Arc;
+use std::time::Duration;
+use thanos::config::Config;
+use thanos::router::Router as ThanosRouter;
+use thanos::server::http::AppState;
+use thanos::shutdown::SHUTDOWN;
+
+#[tokio::test]
+async fn test_drain_closes_streams() {
+    // Ollama streams one chunk, then stalls until Thanos hangs up
+    let mut upstream = mockito::Server::new_async().await;
+    upstream
+        .mock("POST", "/api/chat")
+        .with_chunked_body(|w| {
+            w.write_all(br#"{"message":{"role":"assistant","content":"Hel"},"done":false}"#)?;
+            loop {
+                std::thread::sleep(Duration::from_millis(100));
+                w.write_all(b"\n")?;
+            }
+        })
+        .create_async()
+        .await;
+
+    let config: Config = toml::from_str(&format!(
+        r#"
+        [server]
+        [routing]
+        strategy = "preferred"
+
+        [providers.ollama]
+        enabled = true
+        auth_method = "none"
+        endpoint = "{}"
+        "#,
+        upstream.url()
+    ))
+    .unwrap();
+
+    let config = Arc::new(config);
+    let router = Arc::new(ThanosRouter::new(config.clone()));
+    let state = AppState::new(router.clone());
+    let app = thanos::server::http::app(state);
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = tokio::spawn(thanos::server::http::serve_listener(listener, app, None));
+
+    let mut response = reqwest::Client::new()
+        .post(format!("http://{}/v1/chat/completions", addr))
+        .json(&serde_json::json!({
+            "model": "slow-1",
+            "stream": true,
+            "messages": [{ "role": "user", "content": "hi" }],
+        }))
+        .send()
+        .await
+        .unwrap();
+    let first = response.chunk().await.unwrap().unwrap();
+    assert!(String::from_utf8_lossy(&first).contains("Hel"));
+    assert_eq!(SHUTDOWN.in_flight(), 1);
+
+    // Draining: not ready, and no new connections
+    SHUTDOWN.begin();
+    assert_eq!(thanos::health::readiness(&router).await.status, "draining");
+
+    let mut refused = false;
+    for _ in 0..20 {
+        if tokio::net::TcpStream::connect(addr).await.is_err() {
+            refused = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert!(refused, "listener still accepting while draining");
+
+    // The open stream is still running until the drain expires
+    assert!(!server.is_finished());
+    SHUTDOWN.expire();
+
+    let mut rest = String::new();
+    while let Some(chunk) = response.chunk().await.unwrap() {
+        rest.push_str(&String::from_utf8_lossy(&chunk));
+    }
+    assert!(rest.contains(r#""type":"server_shutdown""#), "{}", rest);
+    assert!(rest.contains("[DONE]"));
+
+    tokio::time::timeout(Duration::from_secs(5), server)
+        .await
+        .expect("server did not stop after draining")
+        .unwrap()
+        .unwrap();
+    assert_eq!(SHUTDOWN.in_flight(), 0);
+}
@@ -0,0 +1,226 @@
+// TLS tests - HTTPS listener with mutual TLS, client identities and certificate reload
+
+use rcgen::{BasicConstraints, CertificateParams, DnType, ExtendedKeyUsagePurpose, IsCa, KeyPair};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use std::path::PathBuf;
+use std::sync::Arc;
+use thanos::config::{Config, TlsConfig};
+use thanos::router::Router as ThanosRouter;
+use thanos::server::http::AppState;
+use thanos::server::tls::ReloadableTls;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+struct Ca {
+    cert: rcgen::Certificate,
+    key: KeyPair,
+}
+
+impl Ca {
+    fn new() -> Self {
+        let key = KeyPair::generate().unwrap();
+        let mut params = CertificateParams::new(Vec::new()).unwrap();
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        params.distinguished_name.push(DnType::CommonName, "Thanos Test CA");
+        Self { cert: params.self_signed(&key).unwrap(), key }
+    }
+
+    /// Leaf certificate and key, as PEM
+    fn issue(&self, common_name: &str, usage: ExtendedKeyUsagePurpose) -> (String, String) {
+        let key = KeyPair::generate().unwrap();
+        let mut params = CertificateParams::new(vec!["localhost".to_string()]).unwrap();
+        params.distinguished_name.push(DnType::CommonName, common_name);
+        params.extended_key_usages = vec![usage];
+        let cert = params.signed_by(&key, &self.cert, &self.key).unwrap();
+        (cert.pem(), key.serialize_pem())
+    }
+}
+
+fn write(dir: &std::path::Path, name: &str, contents: &str) -> PathBuf {
+    let path = dir.join(name);
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+/// Server cert + client CA on disk; returns the TLS settings
+fn tls_files(test: &str, ca: &Ca) -> TlsConfig {
+    let dir = std::env::temp_dir().join(format!("thanos-{}-{}", test, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let (cert, key) = ca.issue("thanos", ExtendedKeyUsagePurpose::ServerAuth);
+    TlsConfig {
+        cert_path: write(&dir, "cert.pem", &cert).display().to_string(),
+        key_path: write(&dir, "key.pem", &key).display().to_string(),
+        client_ca_path: Some(write(&dir, "ca.pem", &ca.cert.pem()).display().to_string()),
+        require_client_cert: true,
+        allowed_clients: vec!["ci-runner".to_string()],
+        reload_secs: 0,
+    }
+}
+
+async fn start_server(tls: Arc<ReloadableTls>) -> std::net::SocketAddr {
+    let config: Config = toml::from_str("[server]\n[routing]\n[providers]\n").unwrap();
+    let config = Arc::new(config);
+    let state = AppState::new(Arc::new(ThanosRouter::new(config)));
+    let app = thanos::server::http::app(state);
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(thanos::server::http::serve_listener(listener, app, Some(tls)));
+    addr
+}
+
+/// GET a path over HTTP/1.1; returns the server's certificate and the raw response
+async fn get(
+    addr: std::net::SocketAddr,
+    ca: &Ca,
+    client: Option<(String, String)>,
+    path: &str,
+) -> std::io::Result<(CertificateDer<'static>, String)> {
+    let mut roots = rustls::RootCertStore::empty();
+    roots.add(ca.cert.der().clone()).unwrap();
+    let builder = rustls::ClientConfig::builder_with_provider(thanos::server::tls::provider())
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_root_certificates(roots);
+
+    let config = match client {
+        Some((cert, key)) => {
+            let certs = rustls_pemfile::certs(&mut cert.as_bytes()).collect::<Result<Vec<_>, _>>().unwrap();
+            let key: PrivateKeyDer = rustls_pemfile::private_key(&mut key.as_bytes()).unwrap().unwrap();
+            builder.with_client_auth_cert(certs, key).unwrap()
+        }
+        None => builder.with_no_client_auth(),
+    };
+
+    let connector = tokio_rustls::TlsConnector::from(Arc::new(config));
+    let tcp = tokio::net::TcpStream::connect(addr).await?;
+    let mut stream = connector.connect(ServerName::try_from("localhost").unwrap(), tcp).await?;
+    let server_cert = stream.get_ref().1.peer_certificates().unwrap()[0].clone().into_owned();
+
+    stream
+        .write_all(format!("GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n", path).as_bytes())
+        .await?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await?;
+    Ok((server_cert, response))
+}
+
+#[tokio::test]
+async fn test_mutual_tls_identities() {
+    let ca = Ca::new();
+    let tls = ReloadableTls::for_tcp(&tls_files("mtls", &ca)).unwrap();
+    let addr = start_server(tls).await;
+
+    let runner = ca.issue("ci-runner", ExtendedKeyUsagePurpose::ClientAuth);
+    let (_, response) = get(addr, &ca, Some(runner.clone()), "/livez").await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+
+    // Requests are counted per client identity
+    let (_, metrics) = get(addr, &ca, Some(runner), "/metrics").await.unwrap();
+    assert!(metrics.contains(r#"thanos_client_requests_total{client="ci-runner",transport="http"}"#));
+
+    // No certificate, or one the CA signed for a client that isn't allowed
+    let anonymous = get(addr, &ca, None, "/livez").await;
+    assert!(anonymous.is_err() || anonymous.unwrap().1.is_empty());
+
+    let intruder = ca.issue("intruder", ExtendedKeyUsagePurpose::ClientAuth);
+    let rejected = get(addr, &ca, Some(intruder), "/livez").await;
+    assert!(rejected.is_err() || rejected.unwrap().1.is_empty());
+}
+
+#[tokio::test]
+async fn test_certificate_reload() {
+    let ca = Ca::new();
+    let files = tls_files("reload", &ca);
+    let tls = ReloadableTls::for_tcp(&files).unwrap();
+    let addr = start_server(tls.clone()).await;
+    let runner = ca.issue("ci-runner", ExtendedKeyUsagePurpose::ClientAuth);
+
+    let (before, _) = get(addr, &ca, Some(runner.clone()), "/livez").await.unwrap();
+    assert!(!tls.reload().unwrap(), "unchanged files are not reloaded");
+
+    // Renew the server certificate in place
+    let (cert, key) = ca.issue("thanos", ExtendedKeyUsagePurpose::ServerAuth);
+    std::fs::write(&files.cert_path, cert).unwrap();
+    std::fs::write(&files.key_path, key).unwrap();
+    let later = std::time::SystemTime::now() + std::time::Duration::from_secs(5);
+    std::fs::File::options().write(true).open(&files.cert_path).unwrap().set_modified(later).unwrap();
+    assert!(tls.reload().unwrap());
+
+    let (after, response) = get(addr, &ca, Some(runner), "/livez").await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 200"));
+    assert_ne!(before, after);
+}
+
+/// HTTP/3 listener with the same TLS settings; returns its UDP address
+async fn start_http3_server(tls: TlsConfig) -> std::net::SocketAddr {
+    let mut config: Config =
+        toml::from_str("[server]\n[server.http3]\nenabled = true\nbind = \"127.0.0.1:0\"\n[routing]\n[providers]\n").unwrap();
+    config.server.tls = Some(tls);
+
+    let (endpoint, tls) = thanos::server::http3::endpoint(&config).unwrap();
+    let addr = endpoint.local_addr().unwrap();
+    let state = AppState::new(Arc::new(ThanosRouter::new(Arc::new(config))));
+    let app = thanos::server::http::app(state);
+    tokio::spawn(thanos::server::http3::serve_endpoint(endpoint, tls, app));
+    addr
+}
+
+/// GET a path over HTTP/3 with a client certificate; returns the status and body
+async fn get_h3(
+    addr: std::net::SocketAddr,
+    ca: &Ca,
+    (cert, key): (String, String),
+    path: &str,
+) -> Result<(u16, String), Box<dyn std::error::Error>> {
+    use bytes::Buf;
+
+    let mut roots = rustls::RootCertStore::empty();
+    roots.add(ca.cert.der().clone()).unwrap();
+    let certs = rustls_pemfile::certs(&mut cert.as_bytes()).collect::<Result<Vec<_>, _>>().unwrap();
+    let key: PrivateKeyDer = rustls_pemfile::private_key(&mut key.as_bytes()).unwrap().unwrap();
+    let mut tls = rustls::ClientConfig::builder_with_provider(thanos::server::tls::provider())
+        .with_protocol_versions(&[&rustls::version::TLS13])
+        .unwrap()
+        .with_root_certificates(roots)
+        .with_client_auth_cert(certs, key)
+        .unwrap();
+    tls.alpn_protocols = vec![b"h3".to_vec()];
+
+    let crypto = quinn::crypto::rustls::QuicClientConfig::try_from(tls).unwrap();
+    let mut client = quinn::Endpoint::client("127.0.0.1:0".parse().unwrap()).unwrap();
+    client.set_default_client_config(quinn::ClientConfig::new(Arc::new(crypto)));
+
+    let conn = client.connect(addr, "localhost")?.await?;
+    let (mut driver, mut send_request) = h3::client::new(h3_quinn::Connection::new(conn)).await?;
+    tokio::spawn(async move {
+        let _ = std::future::poll_fn(|cx| driver.poll_close(cx)).await;
+    });
+
+    let request = axum::http::Request::get(format!("https://localhost{}", path)).body(()).unwrap();
+    let mut stream = send_request.send_request(request).await?;
+    stream.finish().await?;
+    let response = stream.recv_response().await?;
+    let mut body = Vec::new();
+    while let Some(mut chunk) = stream.recv_data().await? {
+        body.extend_from_slice(&chunk.copy_to_bytes(chunk.remaining()));
+    }
+    Ok((response.status().as_u16(), String::from_utf8(body)?))
+}
+
+#[tokio::test]
+async fn test_http3_checks_client_certificates() {
+    let ca = Ca::new();
+    let addr = start_http3_server(tls_files("h3-mtls", &ca)).await;
+
+    let runner = ca.issue("ci-runner", ExtendedKeyUsagePurpose::ClientAuth);
+    let (status, _) = get_h3(addr, &ca, runner.clone(), "/livez").await.unwrap();
+    assert_eq!(status, 200);
+
+    let (_, metrics) = get_h3(addr, &ca, runner, "/metrics").await.unwrap();
+    assert!(metrics.contains(r#"thanos_client_requests_total{client="ci-runner",transport="http3"}"#));
+
+    // Signed by the trusted CA, but not in allowed_clients
+    let intruder = ca.issue("intruder", ExtendedKeyUsagePurpose::ClientAuth);
+    assert!(get_h3(addr, &ca, intruder, "/livez").await.is_err());
+}
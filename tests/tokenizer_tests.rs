@@ -0,0 +1,115 @@
+// Tokenizer endpoint tests - local counts and exact counts from a provider
+
+use std::sync::Arc;
+use thanos::config::Config;
+use thanos::router::Router as ThanosRouter;
+use thanos::server::http::AppState;
+
+async fn start_server(config: &str) -> String {
+    let config: Config = toml::from_str(config).unwrap();
+    let config = Arc::new(config);
+    let state = AppState::new(Arc::new(ThanosRouter::new(config)));
+    let app = thanos::server::uds::build_router(state);
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    url
+}
+
+async fn post(url: &str, body: serde_json::Value) -> serde_json::Value {
+    reqwest::Client::new()
+        .post(url)
+        .json(&body)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_tokenize_locally() {
+    let url = start_server("[server]\n[routing]\n[providers]\n").await;
+
+    let exact = post(
+        &format!("{}/v1/tokenize", url),
+        serde_json::json!({ "model": "gpt-4", "text": "hello world" }),
+    )
+    .await;
+    assert_eq!(exact["tokenizer"], "cl100k_base");
+    assert_eq!(exact["exact"], true);
+    assert_eq!(exact["count"], 2);
+    assert_eq!(exact["tokens"], serde_json::json!([15339, 1917]));
+
+    let approximate = post(
+        &format!("{}/v1/tokenize", url),
+        serde_json::json!({ "model": "claude-sonnet-4-5", "text": "hello world" }),
+    )
+    .await;
+    assert_eq!(approximate["exact"], false);
+    assert!(approximate["tokens"].is_null());
+
+    let counted = post(
+        &format!("{}/v1/count_tokens", url),
+        serde_json::json!({
+            "model": "gpt-4o",
+            "messages": [{ "role": "user", "content": "hello world" }],
+        }),
+    )
+    .await;
+    // 2 content tokens + 3 per message + 3 reply priming
+    assert_eq!(counted["input_tokens"], 8);
+    assert_eq!(counted["tokenizer"], "o200k_base");
+}
+
+#[tokio::test]
+async fn test_count_tokens_from_provider() {
+    let mut upstream = mockito::Server::new_async().await;
+    let mock = upstream
+        .mock("POST", "/v1/messages/count_tokens")
+        .match_header("x-api-key", "sk-test")
+        .with_status(200)
+        .with_body(r#"{"input_tokens": 42}"#)
+        .expect(1)
+        .create_async()
+        .await;
+
+    let url = start_server(&format!(
+        r#"
+        [server]
+        [routing]
+
+        [tokenizer]
+        provider_count = true
+
+        [providers.anthropic]
+        enabled = true
+        auth_method = "api_key"
+        api_key = "sk-test"
+        base_url = "{}"
+        "#,
+        upstream.url()
+    ))
+    .await;
+
+    let body = serde_json::json!({
+        "model": "claude-sonnet-4-5",
+        "messages": [{ "role": "user", "content": "hello world" }],
+    });
+    let counted = post(&format!("{}/v1/count_tokens", url), body.clone()).await;
+    assert_eq!(counted["input_tokens"], 42);
+    assert_eq!(counted["tokenizer"], "anthropic:count_tokens");
+    assert_eq!(counted["exact"], true);
+    mock.assert_async().await;
+
+    // Opting out per request uses the local approximation
+    let mut local = body;
+    local["exact"] = serde_json::json!(false);
+    let counted = post(&format!("{}/v1/count_tokens", url), local).await;
+    assert_eq!(counted["exact"], false);
+    assert_eq!(counted["tokenizer"], "claude~cl100k_base");
+}